@@ -1,54 +1,324 @@
+use crate::audit::{AuditEvent, AuditLog, EventKind};
+use crate::bus::{room_subject, session_subject, BusMessage, RoomBus};
 use crate::messages::{
-    server::{ClientMessage, Connect, Disconnect, JoinRoom, Leave},
-    session::Message,
+    server::{
+        ClientMessage, CloseRoom, CloseSession, Connect, Disconnect, GetParticipants, GetSession,
+        JoinError, JoinRoom, Leave, ListRooms, Resume, Resumed,
+    },
+    session::{Close, Message},
 };
+use crate::resumption::{new_token, Outbox, Resumption};
+use crate::rooms::admin::{admin_commands, CloseTarget};
+use crate::rooms::capacity::{Capacity, CapacityError};
+use crate::rooms::presence::{Presence, PRESENCE_REFRESH};
+use crate::rooms::{participant_packet, Participant, RoomRegistry};
+use crate::webhooks::Webhooks;
 
-use actix::{Actor, AsyncContext, Context, Handler, MessageResult, Recipient};
+use actix::fut::{self, ActorFutureExt, WrapFuture};
+use actix::{Actor, AsyncContext, Context, Handler, MessageResult, Recipient, ResponseActFuture};
 use futures::StreamExt;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tokio::task::JoinHandle;
 use tracing::{debug, error, info, trace};
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
 
-use super::chat_session::SessionId;
+use super::chat_session::{RoomId, SessionId};
+
+/// Why a session whose connection was replaced is closed.
+const RESUMED_ELSEWHERE: &str = "resumed on another connection";
+
+struct ConnectedSession {
+    addr: Recipient<Message>,
+    close: Recipient<Close>,
+}
+
+/// A session in a room, see [crate::resumption].
+struct RoomSession {
+    outbox: Arc<Outbox>,
+    token: String,
+    /// `true` from when the connection was lost until the session is resumed or leaves.
+    detached: bool,
+}
 
 pub struct ChatServer {
-    nats_connection: async_nats::client::Client,
-    sessions: HashMap<SessionId, Recipient<Message>>,
+    bus: Arc<dyn RoomBus>,
+    sessions: HashMap<SessionId, ConnectedSession>,
     active_subs: HashMap<SessionId, JoinHandle<()>>,
+    room_sessions: HashMap<SessionId, RoomSession>,
+    rooms: RoomRegistry,
+    capacity: Capacity,
+    audit: Option<AuditLog>,
+    webhooks: Option<Webhooks>,
+    presence: Option<Presence>,
+    resumption: Resumption,
 }
 
 impl ChatServer {
-    pub async fn new() -> Self {
-        let url = std::env::var("NATS_URL").expect("NATS_URL env var must be defined");
+    pub fn new(bus: Arc<dyn RoomBus>) -> Self {
         ChatServer {
-            nats_connection: async_nats::ConnectOptions::new()
-                .require_tls(false)
-                .ping_interval(std::time::Duration::from_secs(10))
-                .connect(&url)
-                .await
-                .unwrap(),
+            bus,
             active_subs: HashMap::new(),
             sessions: HashMap::new(),
+            room_sessions: HashMap::new(),
+            rooms: RoomRegistry::new(),
+            capacity: Capacity::default(),
+            audit: None,
+            webhooks: None,
+            presence: None,
+            resumption: Resumption::disabled(),
         }
     }
 
+    /// Limits how many sessions join a room and the server.
+    pub fn with_capacity(mut self, capacity: Capacity) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Records who joins and leaves rooms.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Tells the webhooks of meeting owners who joins and leaves their rooms.
+    pub fn with_webhooks(mut self, webhooks: Option<Webhooks>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    /// Writes who is in which room to the presence table shared by every server.
+    pub fn with_presence(mut self, presence: Option<Presence>) -> Self {
+        self.presence = presence;
+        self
+    }
+
+    /// Holds sessions whose connection dropped for resumption.
+    pub fn with_resumption(mut self, resumption: Resumption) -> Self {
+        self.resumption = resumption;
+        self
+    }
+
     pub fn leave_rooms(&mut self, session_id: &SessionId) {
         if let Some(task) = self.active_subs.remove(session_id) {
             task.abort();
         }
+        self.room_sessions.remove(session_id);
+        if let Some((room, participant)) = self.rooms.leave(session_id) {
+            info!("{} left room {}", participant.email, room);
+            if let Some(audit) = &self.audit {
+                audit.spawn_record(AuditEvent::new(
+                    &*room,
+                    EventKind::Left,
+                    &*participant.email,
+                ));
+            }
+            if let Some(webhooks) = &self.webhooks {
+                actix::spawn(webhooks.participant_left(&room, &participant.email));
+            }
+            self.forget_presence(participant.clone());
+            let bus = self.bus.clone();
+            let packet = participant_packet(PacketType::PARTICIPANT_LEFT, &participant.email);
+            actix::spawn(async move {
+                if let Err(e) = bus
+                    .publish_to_room(&room, &participant.session_id, packet)
+                    .await
+                {
+                    error!("error announcing that {} left: {}", participant.email, e);
+                }
+            });
+        }
+    }
+
+    /// Puts `participant` into `room` once [Presence] admitted it, if set.
+    fn join_room(
+        &mut self,
+        room: RoomId,
+        participant: Participant,
+        max_participants: Option<u32>,
+    ) -> Result<Option<String>, JoinError> {
+        let session = participant.session_id.clone();
+        let user = participant.email.clone();
+        let outbox = match self.sessions.get(&session) {
+            Some(connected) => Arc::new(Outbox::new(connected.addr.clone(), &self.resumption)),
+            None => {
+                let err = JoinError::NotConnected(session);
+                error!("{}", err);
+                return Err(err);
+            }
+        };
+        if let Err(e) = self
+            .rooms
+            .admit(&room, &session, &self.capacity, max_participants)
+        {
+            info!("refusing {} in room {}: {}", user, room, e);
+            return Err(JoinError::Full(e));
+        }
+
+        self.leave_rooms(&session);
+
+        let (subject, queue) = build_subject_and_queue(&room, &session);
+
+        self.rooms.join(room.clone(), participant);
+        let token = new_token();
+        self.room_sessions.insert(
+            session.clone(),
+            RoomSession {
+                outbox: outbox.clone(),
+                token: token.clone(),
+                detached: false,
+            },
+        );
+        if let Some(audit) = &self.audit {
+            audit.spawn_record(AuditEvent::new(&*room, EventKind::Joined, &*user));
+        }
+        if let Some(webhooks) = &self.webhooks {
+            actix::spawn(webhooks.participant_joined(&room, &user));
+        }
+
+        let bus = self.bus.clone();
+        let session_2 = session.clone();
+        let task = actix::spawn(async move {
+            match bus
+                .queue_subscribe(subject.clone(), queue.clone())
+                .await
+                .map_err(|e| handle_subscription_error(e, &subject))
+            {
+                Ok(mut sub) => {
+                    debug!("Subscribed to subject {} with queue {}", subject, queue);
+                    info!(
+                        "someone connected to room {} with session {}",
+                        room,
+                        session_2.trim(),
+                    );
+                    let joined = participant_packet(PacketType::PARTICIPANT_JOINED, &user);
+                    if let Err(e) = bus.publish_to_room(&room, &session_2, joined).await {
+                        error!("error announcing that {} joined: {}", user, e);
+                    }
+                    while let Some(msg) = sub.next().await {
+                        if let Err(e) =
+                            handle_msg(outbox.clone(), room.clone(), session_2.clone())(msg)
+                        {
+                            error!("{}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("{}", e);
+                }
+            }
+        });
+
+        self.active_subs.insert(session, task);
+
+        Ok(self.resumption.enabled().then_some(token))
+    }
+
+    /// Takes `participant` out of [Presence] without waiting for it.
+    fn forget_presence(&self, participant: Participant) {
+        if let Some(presence) = self.presence.clone() {
+            actix::spawn(async move {
+                if let Err(e) = presence.leave(&participant).await {
+                    error!(
+                        "error removing {} from room_presence: {}",
+                        participant.email, e
+                    );
+                }
+            });
+        }
+    }
+
+    /// Closes the websocket of `session` and takes it out of its room right away, so a session
+    /// that doesn't stop no longer holds its seat or subscription.
+    fn close_session(&mut self, session: &SessionId, reason: &str) -> bool {
+        let Some(connected) = self.sessions.remove(session) else {
+            return false;
+        };
+        info!("closing session {}: {}", session, reason);
+        connected.close.do_send(Close {
+            reason: reason.to_string(),
+        });
+        self.leave_rooms(session);
+        true
+    }
+
+    /// Keeps a session in its room without a connection for the grace window. Returns `false`
+    /// when the session cannot be resumed.
+    fn detach(&mut self, session: &SessionId, ctx: &mut Context<Self>) -> bool {
+        if !self.resumption.enabled() {
+            return false;
+        }
+        let Some(room_session) = self.room_sessions.get_mut(session) else {
+            return false;
+        };
+        if self.sessions.remove(session).is_none() {
+            return false;
+        }
+        room_session.detached = true;
+        room_session.outbox.detach();
+        info!(
+            "session {} lost its connection, holding it for {:?}",
+            session, self.resumption.grace
+        );
+        let (session, token) = (session.clone(), room_session.token.clone());
+        ctx.run_later(self.resumption.grace, move |act, _ctx| {
+            let expired = act
+                .room_sessions
+                .get(&session)
+                .is_some_and(|s| s.detached && s.token == token);
+            if expired {
+                info!("session {} was not resumed", session);
+                act.leave_rooms(&session);
+            }
+        });
+        true
     }
 }
 
 impl Actor for ChatServer {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let commands = admin_commands(&*self.bus);
+        let addr = ctx.address();
+        actix::spawn(async move {
+            let mut commands = match commands.await {
+                Ok(commands) => commands,
+                Err(e) => {
+                    error!("error subscribing to admin commands: {}", e);
+                    return;
+                }
+            };
+            while let Some(command) = commands.next().await {
+                let reason = command.reason;
+                match command.target {
+                    CloseTarget::Session(session) => addr.do_send(CloseSession { session, reason }),
+                    CloseTarget::Room(room) => addr.do_send(CloseRoom { room, reason }),
+                }
+            }
+        });
+        if self.presence.is_some() {
+            ctx.run_interval(PRESENCE_REFRESH, |act, _ctx| {
+                if let Some(presence) = act.presence.clone() {
+                    let sessions = act.rooms.session_ids();
+                    actix::spawn(async move {
+                        if let Err(e) = presence.refresh(&sessions).await {
+                            error!("error refreshing the sessions in room_presence: {}", e);
+                        }
+                    });
+                }
+            });
+        }
+    }
 }
 
 impl Handler<Connect> for ChatServer {
     type Result = ();
 
     fn handle(&mut self, msg: Connect, _ctx: &mut Self::Context) -> Self::Result {
-        let Connect { id, addr } = msg;
-        self.sessions.insert(id, addr);
+        let Connect { id, addr, close } = msg;
+        self.sessions.insert(id, ConnectedSession { addr, close });
     }
 }
 
@@ -57,9 +327,22 @@ impl Handler<Disconnect> for ChatServer {
 
     fn handle(
         &mut self,
-        Disconnect { session }: Disconnect,
-        _ctx: &mut Self::Context,
+        Disconnect {
+            session,
+            resumable,
+            addr,
+        }: Disconnect,
+        ctx: &mut Self::Context,
     ) -> Self::Result {
+        match self.sessions.get(&session) {
+            // Resumed on a new connection before the old one noticed it was gone.
+            Some(connected) if connected.addr != addr => return,
+            None if self.room_sessions.get(&session).is_some_and(|s| s.detached) => return,
+            _ => {}
+        }
+        if resumable && self.detach(&session, ctx) {
+            return;
+        }
         self.leave_rooms(&session);
         let _ = self.sessions.remove(&session);
     }
@@ -84,12 +367,11 @@ impl Handler<ClientMessage> for ChatServer {
             user: _,
         } = msg;
         trace!("got message in server room {} session {}", room, session);
-        let nc = self.nats_connection.clone();
-        let subject = format!("room.{}.{}", room, session);
-        let subject = subject.replace(' ', "_");
+        let bus = self.bus.clone();
+        let subject = session_subject(&room, &session);
         let b = bytes::Bytes::from(msg.data.to_vec());
         let fut = async move {
-            match nc.publish(subject.clone(), b).await {
+            match bus.publish(subject.clone(), b).await {
                 Ok(_) => trace!("published message to {}", subject),
                 Err(e) => error!("error publishing message to {}: {}", subject, e),
             }
@@ -100,64 +382,191 @@ impl Handler<ClientMessage> for ChatServer {
 }
 
 impl Handler<JoinRoom> for ChatServer {
-    type Result = MessageResult<JoinRoom>;
+    type Result = ResponseActFuture<Self, Result<Option<String>, JoinError>>;
+
     fn handle(
         &mut self,
-        JoinRoom { session, room }: JoinRoom,
+        JoinRoom {
+            session,
+            room,
+            user,
+            transport,
+            max_participants,
+        }: JoinRoom,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
-        self.leave_rooms(&session);
-
-        let (subject, queue) = build_subject_and_queue(&room, &session);
-        let session_recipient = match self.sessions.get(&session) {
-            Some(recipient) => recipient.clone(),
-            None => {
-                let err = format!("session {} is not connected", session);
-                error!("{}", err);
-                return MessageResult(Err(err));
-            }
+        let participant = Participant::new(session, user, transport);
+        let Some(presence) = self.presence.clone() else {
+            return Box::pin(fut::ready(self.join_room(
+                room,
+                participant,
+                max_participants,
+            )));
         };
-
-        let nc = self.nats_connection.clone();
-        let session_2 = session.clone();
-        let task = actix::spawn(async move {
-            match nc
-                .queue_subscribe(subject.clone(), queue.clone())
-                .await
-                .map_err(|e| handle_subscription_error(e, &subject))
-            {
-                Ok(mut sub) => {
-                    debug!("Subscribed to subject {} with queue {}", subject, queue);
+        // Checked here first so a full server doesn't ask the database.
+        if let Err(e) = self.rooms.admit(
+            &room,
+            &participant.session_id,
+            &self.capacity,
+            max_participants,
+        ) {
+            info!("refusing {} in room {}: {}", participant.email, room, e);
+            return Box::pin(fut::ready(Err(JoinError::Full(e))));
+        }
+        let limit = self.capacity.per_room;
+        let admitted = {
+            let (room, participant) = (room.clone(), participant.clone());
+            async move { presence.admit(&room, &participant, limit).await }
+        };
+        Box::pin(admitted.into_actor(self).map(move |admitted, act, _ctx| {
+            match admitted {
+                Ok(true) => {}
+                Ok(false) => {
                     info!(
-                        "someone connected to room {} with session {}",
-                        room,
-                        session_2.trim(),
+                        "refusing {} in room {}: room is full over every server",
+                        participant.email, room
                     );
-                    while let Some(msg) = sub.next().await {
-                        if let Err(e) =
-                            handle_msg(session_recipient.clone(), room.clone(), session_2.clone())(
-                                msg,
-                            )
-                        {
-                            error!("{}", e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("{}", e);
+                    return Err(JoinError::Full(CapacityError::RoomFull));
                 }
+                Err(e) => error!(
+                    "error counting room {} over every server, admitting {}: {}",
+                    room, participant.email, e
+                ),
             }
-        });
+            let joined = act.join_room(room, participant.clone(), max_participants);
+            if joined.is_err() {
+                act.forget_presence(participant);
+            }
+            joined
+        }))
+    }
+}
 
-        self.active_subs.insert(session, task);
+impl Handler<Resume> for ChatServer {
+    type Result = MessageResult<Resume>;
+
+    fn handle(
+        &mut self,
+        Resume {
+            token,
+            room,
+            user,
+            addr,
+            close,
+        }: Resume,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        if !self.resumption.enabled() {
+            return MessageResult(None);
+        }
+        let Some(session) = self
+            .room_sessions
+            .iter()
+            .find(|(_, s)| s.token == token)
+            .map(|(session, _)| session.clone())
+        else {
+            return MessageResult(None);
+        };
+        let in_room = self
+            .rooms
+            .session(&session)
+            .is_some_and(|info| info.room == room && info.participant.email == user);
+        if !in_room {
+            info!(
+                "refusing to resume session {} as {} in {}",
+                session, user, room
+            );
+            return MessageResult(None);
+        }
+        if let Some(old) = self.sessions.remove(&session) {
+            old.close.do_send(Close {
+                reason: RESUMED_ELSEWHERE.to_string(),
+            });
+        }
+        let Some(room_session) = self.room_sessions.get_mut(&session) else {
+            return MessageResult(None);
+        };
+        room_session.token = new_token();
+        room_session.detached = false;
+        let replayed = room_session.outbox.attach(addr.clone());
+        self.sessions
+            .insert(session.clone(), ConnectedSession { addr, close });
+        info!(
+            "resumed session {} in {}, replaying {} packets",
+            session, room, replayed
+        );
+        MessageResult(Some(Resumed {
+            token: room_session.token.clone(),
+            session,
+            replayed,
+        }))
+    }
+}
+
+impl Handler<GetParticipants> for ChatServer {
+    type Result = MessageResult<GetParticipants>;
 
-        MessageResult(Ok(()))
+    fn handle(
+        &mut self,
+        GetParticipants { room }: GetParticipants,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        MessageResult(self.rooms.participants(&room))
+    }
+}
+
+impl Handler<ListRooms> for ChatServer {
+    type Result = MessageResult<ListRooms>;
+
+    fn handle(&mut self, _: ListRooms, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.rooms.rooms())
+    }
+}
+
+impl Handler<GetSession> for ChatServer {
+    type Result = MessageResult<GetSession>;
+
+    fn handle(
+        &mut self,
+        GetSession { session }: GetSession,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        MessageResult(self.rooms.session(&session))
+    }
+}
+
+impl Handler<CloseSession> for ChatServer {
+    type Result = bool;
+
+    fn handle(
+        &mut self,
+        CloseSession { session, reason }: CloseSession,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.close_session(&session, &reason)
+    }
+}
+
+impl Handler<CloseRoom> for ChatServer {
+    type Result = usize;
+
+    fn handle(
+        &mut self,
+        CloseRoom { room, reason }: CloseRoom,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let sessions = self.rooms.participants(&room);
+        info!("closing room {} with {} sessions", room, sessions.len());
+        sessions
+            .iter()
+            .filter(|p| self.close_session(&p.session_id, &reason))
+            .count()
     }
 }
 
 fn build_subject_and_queue(room: &str, session: &str) -> (String, String) {
     (
-        format!("room.{}.*", room).replace(' ', "_"),
+        room_subject(room),
         format!("{}-{}", session, room).replace(' ', "_"),
     )
 }
@@ -169,22 +578,18 @@ fn handle_subscription_error(e: impl std::fmt::Display, subject: &str) -> String
 }
 
 fn handle_msg(
-    session_recipient: Recipient<Message>, // Assuming Recipient is a type
+    outbox: Arc<Outbox>,
     room: String,
     session: SessionId,
-) -> impl Fn(async_nats::Message) -> Result<(), std::io::Error> {
+) -> impl Fn(BusMessage) -> Result<(), std::io::Error> {
     move |msg| {
-        if msg.subject == format!("room.{}.{}", room, session).replace(' ', "_") {
+        if msg.subject == session_subject(&room, &session) {
             return Ok(());
         }
 
-        let message = Message {
-            msg: msg.payload.to_vec(),
-        };
-
-        session_recipient.try_send(message).map_err(|e| {
+        outbox.send(msg.payload.to_vec()).map_err(|e| {
             error!("error sending message to session {}: {}", session, e);
-            std::io::Error::new(std::io::ErrorKind::Other, e)
+            std::io::Error::other(e)
         })
     }
 }
@@ -1,20 +1,32 @@
+use crate::audit::AuditLog;
+use crate::bus::BusMessage;
+use crate::chat::{Chat, ChatHistory};
+use crate::lobby::{lobby_packet, Decision, Lobby};
+use crate::meetings::{Admission, MeetingDirectory};
 use crate::messages::server::{ClientMessage, Packet};
-use crate::messages::session::Message;
+use crate::messages::session::{Close, Message};
+use crate::moderation::{host_packet, HostControls, Observed};
+use crate::recording::Recorder;
+use crate::resumption::resumption_packet;
+use crate::rooms::Transport;
+use crate::sender::{SenderGuard, Verdict};
 use crate::{actors::chat_server::ChatServer, constants::CLIENT_TIMEOUT};
 use std::sync::Arc;
 
 use crate::{
     constants::HEARTBEAT_INTERVAL,
-    messages::server::{Connect, Disconnect, JoinRoom},
+    messages::server::{Connect, Disconnect, JoinError, JoinRoom, Resume},
 };
 use actix::ActorFutureExt;
 use actix::{
-    clock::Instant, fut, ActorContext, ContextFutureSpawner, Handler, Running, StreamHandler,
-    WrapFuture,
+    clock::Instant, fut, ActorContext, ActorState, ContextFutureSpawner, Handler, Running,
+    StreamHandler, WrapFuture,
 };
 use actix::{Actor, Addr, AsyncContext};
-use actix_web_actors::ws::{self, WebsocketContext};
-use tracing::{error, info, trace};
+use actix_web_actors::ws::{self, CloseCode, CloseReason, WebsocketContext};
+use tracing::{error, info, trace, warn};
+use types::protos::lobby_packet::lobby_packet::Status;
+use types::protos::packet_wrapper::PacketWrapper;
 use uuid::Uuid;
 
 pub type RoomId = String;
@@ -27,32 +39,134 @@ pub struct WsChatSession {
     pub addr: Addr<ChatServer>,
     pub heartbeat: Instant,
     pub email: Email,
+    pub sender: SenderGuard,
+    pub admission: Admission,
+    pub host: Arc<HostControls>,
+    /// Set in meetings with a waiting room.
+    pub lobby: Option<Lobby>,
+    /// `true` while the session waits in the lobby, when it is not in the room yet.
+    pub waiting: bool,
+    pub chat: Chat,
+    /// Token of the session the client asks to resume, taken when the session starts.
+    pub resume: Option<String>,
+    /// Set when the connection is lost rather than closed, see [crate::resumption].
+    pub resumable: bool,
 }
 
 impl WsChatSession {
-    pub fn new(addr: Addr<ChatServer>, room: String, email: String) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        addr: Addr<ChatServer>,
+        room: String,
+        email: String,
+        admission: Admission,
+        meetings: Option<MeetingDirectory>,
+        lobby: Option<Lobby>,
+        recorder: Option<Recorder>,
+        chat_history: &ChatHistory,
+        audit: &AuditLog,
+    ) -> Self {
         info!("new session with room {} and email {}", room, email);
 
         WsChatSession {
             id: Uuid::new_v4().to_string(),
             heartbeat: Instant::now(),
+            sender: SenderGuard::from_env(email.clone())
+                .with_publishing(admission.settings.may_publish(&email)),
+            host: Arc::new(
+                HostControls::new(
+                    room.clone(),
+                    email.clone(),
+                    admission.host.as_deref(),
+                    meetings,
+                )
+                .with_lobby(lobby.clone())
+                .with_recorder(recorder)
+                .with_audit(Some(audit.clone())),
+            ),
+            waiting: false,
+            resume: None,
+            resumable: false,
+            chat: chat_history.chat(room.clone(), email.clone()),
+            lobby,
             room,
+            admission,
             email,
             addr,
         }
     }
 
+    /// Resumes the session of `token` instead of starting a new one, if it is still held.
+    pub fn with_resume_token(mut self, token: Option<String>) -> Self {
+        self.resume = token;
+        self
+    }
+
+    /// Runs a host command from the client and publishes the result to the room.
+    fn command(&self, packet: PacketWrapper, ctx: &mut WebsocketContext<Self>) {
+        let host = self.host.clone();
+        async move { host.command(&packet).await }
+            .into_actor(self)
+            .map(|result, act, ctx| match result {
+                Ok(None) => {}
+                Ok(Some(control)) => {
+                    ctx.binary(control.clone());
+                    act.addr.do_send(ClientMessage {
+                        session: act.id.clone(),
+                        user: act.email.clone(),
+                        room: act.room.clone(),
+                        msg: Packet {
+                            data: Arc::new(control.to_vec()),
+                        },
+                    });
+                }
+                Err(e) => warn!("refusing command from session {}: {}", act.id, e),
+            })
+            .spawn(ctx);
+    }
+
+    /// Stores a chat message from the client and publishes it to the room.
+    fn post_chat(&self, packet: PacketWrapper, ctx: &mut WebsocketContext<Self>) {
+        let chat = self.chat.clone();
+        async move { chat.post(&packet).await }
+            .into_actor(self)
+            .map(|result, act, ctx| match result {
+                Ok(message) => {
+                    ctx.binary(message.clone());
+                    act.addr.do_send(ClientMessage {
+                        session: act.id.clone(),
+                        user: act.email.clone(),
+                        room: act.room.clone(),
+                        msg: Packet {
+                            data: Arc::new(message.to_vec()),
+                        },
+                    });
+                }
+                Err(e) => warn!("refusing chat message from session {}: {}", act.id, e),
+            })
+            .spawn(ctx);
+    }
+
+    /// Records the participant and sends the room's recent chat messages to the client.
+    fn replay_chat(&self, ctx: &mut WebsocketContext<Self>) {
+        let chat = self.chat.clone();
+        async move { chat.join().await }
+            .into_actor(self)
+            .map(|result, act, ctx| match result {
+                Ok(messages) => messages.into_iter().for_each(|message| ctx.binary(message)),
+                Err(e) => error!("error replaying the chat of {}: {}", act.room, e),
+            })
+            .wait(ctx);
+    }
+
     fn heartbeat(&self, ctx: &mut WebsocketContext<Self>) {
         ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
             if Instant::now().duration_since(act.heartbeat) > CLIENT_TIMEOUT {
                 // heartbeat timed out
                 println!("Websocket Client heartbeat failed, disconnecting!");
-                // notify chat server
-                act.addr.do_send(Disconnect {
-                    session: act.id.clone(),
-                });
-                // stop actor
+                // stop actor, which notifies the chat server
                 error!("hearbeat timeout");
+                act.resumable = true;
                 ctx.stop();
                 // don't try to send a ping
                 return;
@@ -67,11 +181,32 @@ impl Actor for WsChatSession {
 
     fn started(&mut self, ctx: &mut Self::Context) {
         self.heartbeat(ctx);
+        match self.resume.take() {
+            Some(token) => self.resume(token, ctx),
+            None => self.connect(ctx),
+        }
+    }
+
+    fn stopping(&mut self, ctx: &mut Self::Context) -> Running {
+        // notify chat server
+        self.addr.do_send(Disconnect {
+            session: self.id.clone(),
+            resumable: self.resumable,
+            addr: ctx.address().recipient(),
+        });
+        Running::Stop
+    }
+}
+
+impl WsChatSession {
+    /// Registers a new session with the chat server and joins the room, or the lobby first.
+    fn connect(&mut self, ctx: &mut WebsocketContext<Self>) {
         let addr = ctx.address();
         self.addr
             .send(Connect {
                 id: self.id.clone(),
-                addr: addr.recipient(),
+                addr: addr.clone().recipient(),
+                close: addr.recipient(),
             })
             .into_actor(self)
             .then(|res, _act, ctx| {
@@ -82,15 +217,52 @@ impl Actor for WsChatSession {
                 fut::ready(())
             })
             .wait(ctx);
-        self.join(self.room.clone(), ctx);
+        match &self.lobby {
+            Some(lobby) if self.admission.must_wait(&self.email) => {
+                self.wait_in_lobby(lobby.clone(), ctx)
+            }
+            _ => self.join(self.room.clone(), ctx),
+        }
     }
 
-    fn stopping(&mut self, _: &mut Self::Context) -> Running {
-        // notify chat server
-        self.addr.do_send(Disconnect {
-            session: self.id.clone(),
-        });
-        Running::Stop
+    /// Takes over the session of `token`, falling back to a new session when it is gone.
+    fn resume(&mut self, token: String, ctx: &mut WebsocketContext<Self>) {
+        let addr = ctx.address();
+        self.addr
+            .send(Resume {
+                token,
+                room: self.room.clone(),
+                user: self.email.clone(),
+                addr: addr.clone().recipient(),
+                close: addr.recipient(),
+            })
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(Some(resumed)) => {
+                        info!("session {} resumed as {}", act.id, resumed.session);
+                        act.id = resumed.session;
+                        ctx.binary(resumption_packet(&act.email, &resumed.token));
+                        ctx.binary(act.admission.settings.to_packet());
+                        if let Some(host) = &act.admission.host {
+                            ctx.binary(host_packet(host));
+                        }
+                        if let Some(lobby) = &act.lobby {
+                            act.watch_lobby(lobby.clone(), ctx);
+                        }
+                    }
+                    Ok(None) => {
+                        info!("session {} could not be resumed, joining anew", act.id);
+                        act.connect(ctx);
+                    }
+                    Err(err) => {
+                        error!("error {:?}", err);
+                        ctx.stop();
+                    }
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
     }
 }
 
@@ -98,14 +270,66 @@ impl Handler<Message> for WsChatSession {
     type Result = ();
 
     fn handle(&mut self, msg: Message, ctx: &mut Self::Context) -> Self::Result {
+        let observed = self.host.observe(&msg.msg);
+        if observed == Observed::Ignore {
+            return;
+        }
         ctx.binary(msg.msg);
+        if observed == Observed::Removed {
+            info!("session {} was removed by the host", self.id);
+            ctx.close(Some(CloseReason {
+                code: CloseCode::Policy,
+                description: Some("removed by the host".to_string()),
+            }));
+            ctx.stop();
+        }
+    }
+}
+
+impl Handler<Close> for WsChatSession {
+    type Result = ();
+
+    fn handle(&mut self, Close { reason }: Close, ctx: &mut Self::Context) -> Self::Result {
+        info!("session {} was closed: {}", self.id, reason);
+        ctx.close(Some(CloseReason {
+            code: CloseCode::Policy,
+            description: Some(reason),
+        }));
+        ctx.stop();
     }
 }
 
 impl Handler<Packet> for WsChatSession {
     type Result = ();
 
-    fn handle(&mut self, msg: Packet, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: Packet, ctx: &mut Self::Context) -> Self::Result {
+        if self.waiting {
+            return;
+        }
+        match self.sender.admit(&msg.data) {
+            Verdict::Forward => {}
+            Verdict::Control(packet) => {
+                self.command(packet, ctx);
+                return;
+            }
+            Verdict::Chat(packet) => {
+                self.post_chat(packet, ctx);
+                return;
+            }
+            Verdict::Drop(violation) => {
+                warn!("dropping packet from session {}: {}", self.id, violation);
+                return;
+            }
+            Verdict::Disconnect(violation) => {
+                warn!("disconnecting session {}: {}", self.id, violation);
+                ctx.close(Some(CloseReason {
+                    code: CloseCode::Policy,
+                    description: Some("too many invalid packets".to_string()),
+                }));
+                ctx.stop();
+                return;
+            }
+        }
         let room_id = self.room.clone();
         trace!(
             "got message and sending to chat session {} email {} room {}",
@@ -122,6 +346,17 @@ impl Handler<Packet> for WsChatSession {
     }
 }
 
+/// Packets from the lobby, which the session watches once it is in a room with a waiting room.
+impl StreamHandler<BusMessage> for WsChatSession {
+    fn handle(&mut self, msg: BusMessage, ctx: &mut Self::Context) {
+        ctx.notify(Message {
+            msg: msg.payload.to_vec(),
+        });
+    }
+
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
+
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsChatSession {
     fn handle(&mut self, item: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
         let msg = match item {
@@ -129,6 +364,7 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsChatSession {
             Err(err) => {
                 error!("protocol error 2 {:?}", err);
                 // ctx.text(WsMessage::err(err.to_string()));
+                self.resumable = true;
                 ctx.stop();
                 return;
             }
@@ -159,6 +395,10 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsChatSession {
     fn started(&mut self, _ctx: &mut Self::Context) {}
 
     fn finished(&mut self, ctx: &mut Self::Context) {
+        if ctx.state() == ActorState::Running {
+            // The stream ended without a close frame.
+            self.resumable = true;
+        }
         ctx.stop()
     }
 }
@@ -168,13 +408,35 @@ impl WsChatSession {
         let join_room = self.addr.send(JoinRoom {
             room: room_id.clone(),
             session: self.id.clone(),
+            user: self.email.clone(),
+            transport: Transport::WebSocket,
+            max_participants: self.admission.settings.max_participants,
         });
         let join_room = join_room.into_actor(self);
         join_room
             .then(move |response, act, ctx| {
                 match response {
-                    Ok(res) if res.is_ok() => {
+                    Ok(Ok(token)) => {
                         act.room = room_id;
+                        if let Some(token) = token {
+                            ctx.binary(resumption_packet(&act.email, &token));
+                        }
+                        ctx.binary(act.admission.settings.to_packet());
+                        if let Some(host) = &act.admission.host {
+                            ctx.binary(host_packet(host));
+                        }
+                        act.replay_chat(ctx);
+                        if let Some(lobby) = &act.lobby {
+                            act.watch_lobby(lobby.clone(), ctx);
+                        }
+                    }
+                    Ok(Err(JoinError::Full(e))) => {
+                        ctx.binary(e.to_packet());
+                        ctx.close(Some(CloseReason {
+                            code: CloseCode::Again,
+                            description: Some(e.to_string()),
+                        }));
+                        ctx.stop();
                     }
                     Ok(res) => {
                         error!("error {:?}", res);
@@ -188,4 +450,50 @@ impl WsChatSession {
             })
             .wait(ctx);
     }
+
+    /// Keeps the session out of the room until the host decides about it.
+    fn wait_in_lobby(&mut self, lobby: Lobby, ctx: &mut WebsocketContext<Self>) {
+        info!("session {} waits in the lobby of {}", self.id, self.room);
+        self.waiting = true;
+        ctx.binary(lobby_packet(Status::WAITING, &self.email));
+        let email = self.email.clone();
+        let queue = self.id.clone();
+        async move { lobby.wait(&email, &queue).await }
+            .into_actor(self)
+            .map(|decision, act, ctx| match decision {
+                Ok(Decision::Admitted) => {
+                    act.waiting = false;
+                    ctx.binary(lobby_packet(Status::ADMITTED, &act.email));
+                    act.join(act.room.clone(), ctx);
+                }
+                Ok(Decision::Denied) => {
+                    info!("session {} was denied by the host", act.id);
+                    ctx.binary(lobby_packet(Status::DENIED, &act.email));
+                    ctx.close(Some(CloseReason {
+                        code: CloseCode::Policy,
+                        description: Some("denied by the host".to_string()),
+                    }));
+                    ctx.stop();
+                }
+                Err(e) => {
+                    error!("error waiting in the lobby: {}", e);
+                    ctx.stop();
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Passes the lobby on to the client while it is the host.
+    fn watch_lobby(&self, lobby: Lobby, ctx: &mut WebsocketContext<Self>) {
+        let queue = self.id.clone();
+        async move { lobby.watch(&queue).await }
+            .into_actor(self)
+            .map(|sub, _act, ctx| match sub {
+                Ok(sub) => {
+                    ctx.add_stream(sub);
+                }
+                Err(e) => error!("error watching the lobby: {}", e),
+            })
+            .spawn(ctx);
+    }
 }
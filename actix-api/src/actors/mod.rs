@@ -1,2 +1,3 @@
 pub mod chat_server;
 pub mod chat_session;
+pub mod rejected_session;
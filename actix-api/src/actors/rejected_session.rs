@@ -0,0 +1,48 @@
+use actix::{Actor, ActorContext, StreamHandler};
+use actix_web_actors::ws::{self, CloseCode, CloseReason, WebsocketContext};
+use bytes::Bytes;
+
+/// Websocket actor that closes the connection as soon as it starts.
+///
+/// Used to refuse a handshake with a close code the browser can observe, which a plain HTTP
+/// error response does not give it.
+pub struct WsRejectedSession {
+    pub code: CloseCode,
+    pub reason: String,
+    /// Sent before closing, for clients that cannot read the close reason.
+    pub packet: Option<Bytes>,
+}
+
+impl WsRejectedSession {
+    pub fn unauthorized(reason: impl Into<String>) -> Self {
+        WsRejectedSession {
+            code: CloseCode::Policy,
+            reason: reason.into(),
+            packet: None,
+        }
+    }
+
+    pub fn with_packet(mut self, packet: Bytes) -> Self {
+        self.packet = Some(packet);
+        self
+    }
+}
+
+impl Actor for WsRejectedSession {
+    type Context = WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(packet) = self.packet.take() {
+            ctx.binary(packet);
+        }
+        ctx.close(Some(CloseReason {
+            code: self.code,
+            description: Some(self.reason.clone()),
+        }));
+        ctx.stop();
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsRejectedSession {
+    fn handle(&mut self, _msg: Result<ws::Message, ws::ProtocolError>, _ctx: &mut Self::Context) {}
+}
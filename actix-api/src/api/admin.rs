@@ -0,0 +1,188 @@
+//! Admin endpoints for operators, acting on rooms and sessions. Disabled unless `ADMIN_API_KEY`
+//! is set, requests must carry it as `Authorization: Bearer <key>`.
+//!
+//! With the database enabled, the rooms and sessions of every server are read from
+//! [Presence](crate::rooms::presence::Presence), websocket and WebTransport alike. Without it
+//! only the websocket sessions of this server are seen. Sessions are closed by publishing an
+//! [AdminCommand] on the bus, which every server runs against its own sessions, so they are
+//! closed shortly after the request returns.
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{error, get, post, web, Error, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::audit::{AuditEvent, EventKind};
+use crate::messages::server::{GetParticipants, GetSession, ListRooms};
+use crate::models::AppState;
+use crate::rooms::admin::{AdminCommand, CloseTarget, ADMIN_ACTOR};
+use crate::rooms::{Participant, RoomSummary, SessionInfo};
+
+const DEFAULT_CLOSE_REASON: &str = "closed by an administrator";
+/// Close frame reasons must fit into a control frame.
+const MAX_REASON_LEN: usize = 120;
+
+#[derive(Debug, Deserialize)]
+pub struct CloseQuery {
+    /// Sent to the closed sessions in the close frame.
+    pub reason: Option<String>,
+}
+
+impl CloseQuery {
+    fn reason(&self) -> Result<String, Error> {
+        match self.reason.as_deref().map(str::trim) {
+            None | Some("") => Ok(DEFAULT_CLOSE_REASON.to_string()),
+            Some(reason) if reason.len() > MAX_REASON_LEN => Err(error::ErrorBadRequest(format!(
+                "reason must be at most {} bytes",
+                MAX_REASON_LEN
+            ))),
+            Some(reason) => Ok(reason.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Closed {
+    closed: usize,
+}
+
+/// Lets the request through if it carries the admin key.
+fn authorize_admin(req: &HttpRequest, state: &AppState) -> Result<(), Error> {
+    let Some(key) = &state.admin_key else {
+        return Err(error::ErrorForbidden("the admin API is disabled"));
+    };
+    let authorization = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !key.verify_header(authorization) {
+        warn!("refusing admin request to {}", req.path());
+        return Err(error::ErrorUnauthorized("invalid admin key"));
+    }
+    Ok(())
+}
+
+fn internal_error(e: impl std::fmt::Debug + std::fmt::Display + 'static) -> Error {
+    error!("{:?}", e);
+    error::ErrorInternalServerError(e)
+}
+
+async fn rooms(state: &AppState) -> Result<Vec<RoomSummary>, Error> {
+    match &state.presence {
+        Some(presence) => presence.rooms().await.map_err(internal_error),
+        None => state.chat.send(ListRooms).await.map_err(internal_error),
+    }
+}
+
+async fn session_info(state: &AppState, session: String) -> Result<Option<SessionInfo>, Error> {
+    match &state.presence {
+        Some(presence) => presence.session(&session).await.map_err(internal_error),
+        None => state
+            .chat
+            .send(GetSession { session })
+            .await
+            .map_err(internal_error),
+    }
+}
+
+async fn participants(state: &AppState, room: String) -> Result<Vec<Participant>, Error> {
+    match &state.presence {
+        Some(presence) => presence.participants(&room).await.map_err(internal_error),
+        None => state
+            .chat
+            .send(GetParticipants { room })
+            .await
+            .map_err(internal_error),
+    }
+}
+
+/// Sends `command` to every server and records who it removes.
+async fn close(
+    state: &AppState,
+    command: AdminCommand,
+    room: &str,
+    removed: &[Participant],
+) -> Result<(), Error> {
+    command.publish(&*state.bus).await.map_err(internal_error)?;
+    for participant in removed {
+        let event = AuditEvent::new(room, EventKind::Removed, ADMIN_ACTOR)
+            .with_target(&*participant.email)
+            .with_detail(&*command.reason);
+        state.audit.record(event).await;
+    }
+    Ok(())
+}
+
+/// Lists the rooms with sessions in them, with how many and over which transports.
+#[get("/admin/rooms")]
+pub async fn list_rooms(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    authorize_admin(&req, &state)?;
+    Ok(HttpResponse::Ok().json(rooms(&state).await?))
+}
+
+/// Shows which room a session is in, who it is and since when.
+#[get("/admin/sessions/{session}")]
+pub async fn get_session(
+    req: HttpRequest,
+    session: web::Path<String>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    authorize_admin(&req, &state)?;
+    let info = session_info(&state, session.into_inner()).await?;
+    let info = info.ok_or_else(|| error::ErrorNotFound("session not found"))?;
+    Ok(HttpResponse::Ok().json(info))
+}
+
+/// Disconnects a session, whichever server and transport it is on.
+#[post("/admin/sessions/{session}/disconnect")]
+pub async fn disconnect_session(
+    req: HttpRequest,
+    session: web::Path<String>,
+    query: web::Query<CloseQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    authorize_admin(&req, &state)?;
+    let reason = query.reason()?;
+    let session = session.into_inner();
+    let info = session_info(&state, session.clone()).await?;
+    let info = info.ok_or_else(|| error::ErrorNotFound("session not found"))?;
+    let command = AdminCommand {
+        target: CloseTarget::Session(session),
+        reason,
+    };
+    close(&state, command, &info.room, &[info.participant]).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Disconnects every session in a room.
+#[post("/admin/rooms/{room}/close")]
+pub async fn close_room(
+    req: HttpRequest,
+    room: web::Path<String>,
+    query: web::Query<CloseQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    authorize_admin(&req, &state)?;
+    let reason = query.reason()?;
+    let room = room.into_inner();
+    let removed = participants(&state, room.clone()).await?;
+    let command = AdminCommand {
+        target: CloseTarget::Room(room.clone()),
+        reason,
+    };
+    close(&state, command, &room, &removed).await?;
+    Ok(HttpResponse::Ok().json(Closed {
+        closed: removed.len(),
+    }))
+}
+
+/// Adds the admin endpoints to an app.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_rooms)
+        .service(get_session)
+        .service(disconnect_session)
+        .service(close_room);
+}
@@ -0,0 +1,204 @@
+use actix_web::{delete, error, get, patch, post, web, Error, HttpRequest, HttpResponse};
+use tracing::error;
+
+use crate::api::session::authenticated_session;
+use crate::auth::session::{Session, SessionSettings};
+use crate::db::PostgresPool;
+use crate::meetings::{
+    create_invite, create_meeting, delete_meeting, get_meeting, list_meetings, update_meeting,
+    InviteKey, Meeting, MeetingUpdate, NewInvite, NewMeeting,
+};
+use crate::models::AppState;
+
+/// Registers a meeting owned by the logged in user.
+#[post("/meetings")]
+pub async fn create(
+    req: HttpRequest,
+    body: web::Json<NewMeeting>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    body.validate().map_err(error::ErrorBadRequest)?;
+    let meeting = create_meeting(&pool, &session.email, &body)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    Ok(HttpResponse::Created().json(meeting))
+}
+
+/// Lists the meetings owned by the logged in user.
+#[get("/meetings")]
+pub async fn list(
+    req: HttpRequest,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    let meetings = list_meetings(&pool, &session.email).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorInternalServerError(e)
+    })?;
+    Ok(HttpResponse::Ok().json(meetings))
+}
+
+/// Gets a meeting. Only the owner and the host may do this, to everyone else it doesn't exist.
+#[get("/meetings/{id}")]
+pub async fn get(
+    req: HttpRequest,
+    id: web::Path<String>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    let meeting = get_meeting(&pool, &id).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorInternalServerError(e)
+    })?;
+    let meeting = meeting
+        .filter(|m| m.owner_email == session.email || m.host_email == session.email)
+        .ok_or_else(|| error::ErrorNotFound("meeting not found"))?;
+    Ok(HttpResponse::Ok().json(meeting))
+}
+
+/// Changes the title, settings or passcode of a meeting. Only the owner may do this.
+#[patch("/meetings/{id}")]
+pub async fn update(
+    req: HttpRequest,
+    id: web::Path<String>,
+    body: web::Json<MeetingUpdate>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    body.validate().map_err(error::ErrorBadRequest)?;
+    let meeting = update_meeting(&pool, &id, &session.email, &body)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    let meeting = meeting.ok_or_else(|| error::ErrorNotFound("meeting not found"))?;
+    Ok(HttpResponse::Ok().json(meeting))
+}
+
+/// Deletes a meeting. Only the owner may do this.
+#[delete("/meetings/{id}")]
+pub async fn remove(
+    req: HttpRequest,
+    id: web::Path<String>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    let deleted = delete_meeting(&pool, &id, &session.email)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    if !deleted {
+        return Err(error::ErrorNotFound("meeting not found"));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Creates an invite link token for a meeting. Only the owner and the host may do this.
+#[post("/meetings/{id}/invites")]
+pub async fn invite(
+    req: HttpRequest,
+    id: web::Path<String>,
+    body: web::Json<NewInvite>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+    key: web::Data<InviteKey>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    body.validate().map_err(error::ErrorBadRequest)?;
+    let meeting = get_meeting(&pool, &id).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorInternalServerError(e)
+    })?;
+    let meeting = meeting
+        .filter(|m| m.owner_email == session.email || m.host_email == session.email)
+        .ok_or_else(|| error::ErrorNotFound("meeting not found"))?;
+    let invite = create_invite(&pool, &key, &meeting.id, &session.email, &body)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    Ok(HttpResponse::Created().json(invite))
+}
+
+/// Finds a meeting the logged in user owns or hosts and that may be recorded.
+async fn recordable_meeting(
+    req: &HttpRequest,
+    id: &str,
+    pool: &PostgresPool,
+    settings: &SessionSettings,
+) -> Result<(Meeting, Session), Error> {
+    let session = authenticated_session(req, pool, settings).await?;
+    let meeting = get_meeting(pool, id).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorInternalServerError(e)
+    })?;
+    let meeting = meeting
+        .filter(|m| m.owner_email == session.email || m.host_email == session.email)
+        .ok_or_else(|| error::ErrorNotFound("meeting not found"))?;
+    if !meeting.settings.recording_allowed {
+        return Err(error::ErrorForbidden(
+            "recording is not allowed in this meeting",
+        ));
+    }
+    Ok((meeting, session))
+}
+
+/// Starts recording a meeting on this server. Only the owner and the host may do this.
+#[post("/meetings/{id}/recording")]
+pub async fn start_recording(
+    req: HttpRequest,
+    id: web::Path<String>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let (meeting, session) = recordable_meeting(&req, &id, &pool, &settings).await?;
+    if state.recorder.recording(&meeting.id).is_some() {
+        return Err(error::ErrorConflict(
+            "the meeting is already being recorded",
+        ));
+    }
+    let recording = state
+        .recorder
+        .start(&meeting.id, &session.email)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    Ok(HttpResponse::Created().json(recording))
+}
+
+/// Stops the recording of a meeting, wherever it runs. Only the owner and the host may do this.
+#[delete("/meetings/{id}/recording")]
+pub async fn stop_recording(
+    req: HttpRequest,
+    id: web::Path<String>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let (meeting, session) = recordable_meeting(&req, &id, &pool, &settings).await?;
+    state
+        .recorder
+        .stop(&meeting.id, &session.email)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    Ok(HttpResponse::Accepted().finish())
+}
@@ -0,0 +1,6 @@
+//! REST endpoints served by the websocket server next to the lobby.
+pub mod admin;
+pub mod meetings;
+pub mod rooms;
+pub mod session;
+pub mod webhooks;
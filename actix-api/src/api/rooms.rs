@@ -0,0 +1,249 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{error, get, web, Error, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::api::session::authenticated_session;
+use crate::audit::AuditEvent;
+use crate::auth::session::SessionSettings;
+use crate::chat::{transcript, ChatHistory, ChatMessage};
+use crate::db::PostgresPool;
+use crate::meetings::get_meeting;
+use crate::messages::server::GetParticipants;
+use crate::models::AppState;
+
+const DEFAULT_MESSAGES_PAGE_LEN: usize = 50;
+const MAX_MESSAGES_PAGE_LEN: usize = 200;
+/// Page size used to walk the history when exporting it.
+const EXPORT_PAGE_LEN: usize = 500;
+const DEFAULT_EVENTS_PAGE_LEN: usize = 100;
+const MAX_EVENTS_PAGE_LEN: usize = 1000;
+
+/// Lists the sessions currently in `room`, to those who may read its history.
+///
+/// Read from [Presence](crate::rooms::presence::Presence) when the server has one, which sees
+/// the sessions of every server and transport, or else from the websocket sessions of this one.
+#[get("/rooms/{room}/participants")]
+pub async fn participants(
+    req: HttpRequest,
+    room: web::Path<String>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    authorize_room_reader(&req, &room, &pool, &settings, &state.chat_history).await?;
+    let room = room.into_inner();
+    let participants = match &state.presence {
+        Some(presence) => presence.participants(&room).await.map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?,
+        None => state
+            .chat
+            .send(GetParticipants { room })
+            .await
+            .map_err(|e| {
+                error!("{:?}", e);
+                error::ErrorInternalServerError(e)
+            })?,
+    };
+    Ok(HttpResponse::Ok().json(participants))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessagesQuery {
+    /// Id of the oldest message of the previous page.
+    pub before: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct MessagesPage {
+    /// Oldest first.
+    messages: Vec<ChatMessage>,
+    /// The `before` of the next, older, page. `None` on the last page.
+    next_before: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Text,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Lets the logged in user through if they ever joined `room` or own its meeting.
+async fn authorize_room_reader(
+    req: &HttpRequest,
+    room: &str,
+    pool: &PostgresPool,
+    settings: &SessionSettings,
+    history: &ChatHistory,
+) -> Result<(), Error> {
+    let session = authenticated_session(req, pool, settings).await?;
+    let participant = history
+        .store()
+        .is_participant(room, &session.email)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    if participant {
+        return Ok(());
+    }
+    let meeting = get_meeting(pool, room).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorInternalServerError(e)
+    })?;
+    match meeting {
+        Some(meeting) if meeting.owner_email == session.email => Ok(()),
+        _ => Err(error::ErrorForbidden("not a participant of this room")),
+    }
+}
+
+/// Pages through the chat history of `room`, newest page first.
+#[get("/rooms/{room}/messages")]
+pub async fn list_messages(
+    req: HttpRequest,
+    room: web::Path<String>,
+    query: web::Query<MessagesQuery>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let history = &state.chat_history;
+    authorize_room_reader(&req, &room, &pool, &settings, history).await?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_MESSAGES_PAGE_LEN)
+        .clamp(1, MAX_MESSAGES_PAGE_LEN);
+    let messages = history
+        .store()
+        .recent(&room, query.before.as_deref(), limit)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    let next_before = messages
+        .first()
+        .filter(|_| messages.len() == limit)
+        .map(|m| m.id.clone());
+    Ok(HttpResponse::Ok().json(MessagesPage {
+        messages,
+        next_before,
+    }))
+}
+
+/// Downloads the whole chat history of `room` as JSON or as a plain text transcript.
+#[get("/rooms/{room}/messages/export")]
+pub async fn export_messages(
+    req: HttpRequest,
+    room: web::Path<String>,
+    query: web::Query<ExportQuery>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let history = &state.chat_history;
+    authorize_room_reader(&req, &room, &pool, &settings, history).await?;
+    // Pages come newest first, each oldest first.
+    let mut pages = Vec::new();
+    let mut before = None;
+    loop {
+        let page = history
+            .store()
+            .recent(&room, before.as_deref(), EXPORT_PAGE_LEN)
+            .await
+            .map_err(|e| {
+                error!("{:?}", e);
+                error::ErrorInternalServerError(e)
+            })?;
+        let last_page = page.len() < EXPORT_PAGE_LEN;
+        before = page.first().map(|m| m.id.clone());
+        pages.push(page);
+        if last_page {
+            break;
+        }
+    }
+    let messages: Vec<ChatMessage> = pages.into_iter().rev().flatten().collect();
+    let extension = match query.format {
+        ExportFormat::Json => "json",
+        ExportFormat::Text => "txt",
+    };
+    let mut response = HttpResponse::Ok();
+    response.insert_header(ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(format!(
+            "{}-chat.{}",
+            room, extension
+        ))],
+    });
+    Ok(match query.format {
+        ExportFormat::Json => response.json(&messages),
+        ExportFormat::Text => response
+            .content_type("text/plain; charset=utf-8")
+            .body(transcript(&messages)),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Id of the newest event of the previous page.
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct EventsPage {
+    /// Oldest first.
+    events: Vec<AuditEvent>,
+    /// The `after` of the next, newer, page. `None` on the last page.
+    next_after: Option<String>,
+}
+
+/// Pages through the audit log of `room`, oldest page first. Only the meeting owner may read it.
+#[get("/rooms/{room}/events")]
+pub async fn list_events(
+    req: HttpRequest,
+    room: web::Path<String>,
+    query: web::Query<EventsQuery>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    let meeting = get_meeting(&pool, &room).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorInternalServerError(e)
+    })?;
+    if meeting.is_none_or(|meeting| meeting.owner_email != session.email) {
+        return Err(error::ErrorForbidden("not the owner of this meeting"));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_EVENTS_PAGE_LEN)
+        .clamp(1, MAX_EVENTS_PAGE_LEN);
+    let events = state
+        .audit
+        .store()
+        .events(&room, query.after.as_deref(), limit)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    let next_after = events
+        .last()
+        .filter(|_| events.len() == limit)
+        .map(|e| e.id.clone());
+    Ok(HttpResponse::Ok().json(EventsPage { events, next_after }))
+}
@@ -0,0 +1,133 @@
+use actix_web::cookie::Cookie;
+use actix_web::{error, get, post, web, Error, HttpRequest, HttpResponse};
+use serde::Serialize;
+use tracing::error;
+
+use crate::auth::session::{
+    fetch_active_session, revoke_session, rotate_session, Session, SessionSettings, SESSION_COOKIE,
+};
+use crate::auth::token::CONNECT_TOKEN_COOKIE;
+use crate::auth::{fetch_refresh_token, refresh_access_token, update_user_tokens};
+use crate::db::PostgresPool;
+use crate::models::{AppConfig, AppState};
+
+fn session_id(req: &HttpRequest, settings: &SessionSettings) -> Option<String> {
+    settings.verify(req.cookie(SESSION_COOKIE)?.value())
+}
+
+/// Resolves the caller's active session, for endpoints that need a logged in user.
+pub(crate) async fn authenticated_session(
+    req: &HttpRequest,
+    pool: &PostgresPool,
+    settings: &SessionSettings,
+) -> Result<Session, Error> {
+    let session_id = session_id(req, settings)
+        .ok_or_else(|| error::ErrorUnauthorized("missing or invalid session"))?;
+    fetch_active_session(pool, &session_id).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorUnauthorized("session expired")
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct SessionUser {
+    email: String,
+}
+
+/// Tells the UI who is logged in. Connections must use this email, which connect tokens are
+/// issued for.
+#[get("/session")]
+pub async fn current(
+    req: HttpRequest,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    Ok(HttpResponse::Ok().json(SessionUser {
+        email: session.email,
+    }))
+}
+
+fn removal_cookie(name: &'static str) -> Cookie<'static> {
+    let mut cookie = Cookie::named(name);
+    cookie.set_path("/");
+    cookie.make_removal();
+    cookie
+}
+
+/// Refreshes the user's OAuth access token with the stored `refresh_token` and rotates the
+/// session, issuing cookies for the new session and a connect token bound to it. The old session
+/// and its connect tokens stop working.
+#[post("/session/refresh")]
+pub async fn refresh(
+    req: HttpRequest,
+    pool: web::Data<PostgresPool>,
+    cfg: web::Data<AppConfig>,
+    state: web::Data<AppState>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+
+    let refresh_token = fetch_refresh_token(&pool, &session.email)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorUnauthorized("no refresh token, please log in again")
+        })?;
+
+    let refresh_response = refresh_access_token(
+        &cfg.oauth_client_id,
+        &cfg.oauth_secret,
+        &cfg.oauth_token_url,
+        &refresh_token,
+    )
+    .await
+    .map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorUnauthorized("provider refused to refresh, please log in again")
+    })?;
+
+    update_user_tokens(&pool, &session.email, &refresh_response)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    let session = rotate_session(&pool, &session, settings.ttl)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorUnauthorized("session expired")
+        })?;
+
+    let mut response = HttpResponse::NoContent();
+    response.cookie(settings.cookie(&session));
+    if let Some(key) = state.connect_auth.key() {
+        let cookie = key.cookie(&session).map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+        response.cookie(cookie);
+    }
+    Ok(response.finish())
+}
+
+/// Revokes the current session, and with it the connect tokens issued for it, and clears the
+/// login cookies.
+#[post("/logout")]
+pub async fn logout(
+    req: HttpRequest,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    if let Some(session_id) = session_id(&req, &settings) {
+        revoke_session(&pool, &session_id).await.map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    }
+    Ok(HttpResponse::NoContent()
+        .cookie(removal_cookie(SESSION_COOKIE))
+        .cookie(removal_cookie(CONNECT_TOKEN_COOKIE))
+        .finish())
+}
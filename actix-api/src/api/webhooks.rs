@@ -0,0 +1,112 @@
+use actix_web::{delete, error, get, post, web, Error, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::api::session::authenticated_session;
+use crate::auth::session::SessionSettings;
+use crate::db::PostgresPool;
+use crate::webhooks::{
+    create_webhook, delete_webhook, get_webhook, list_deliveries, list_webhooks, NewWebhook,
+    Webhook,
+};
+
+const DEFAULT_DELIVERIES_PAGE_LEN: usize = 50;
+const MAX_DELIVERIES_PAGE_LEN: usize = 500;
+
+#[derive(Debug, Serialize)]
+struct CreatedWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    /// Only returned here, receivers need it to check signatures.
+    secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveriesQuery {
+    pub limit: Option<usize>,
+}
+
+/// Registers a webhook for events of the meetings owned by the logged in user.
+#[post("/webhooks")]
+pub async fn create(
+    req: HttpRequest,
+    body: web::Json<NewWebhook>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    body.validate().map_err(error::ErrorBadRequest)?;
+    let webhook = create_webhook(&pool, &session.email, &body)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    let secret = webhook.secret.clone();
+    Ok(HttpResponse::Created().json(CreatedWebhook { webhook, secret }))
+}
+
+/// Lists the webhooks of the logged in user.
+#[get("/webhooks")]
+pub async fn list(
+    req: HttpRequest,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    let webhooks = list_webhooks(&pool, &session.email).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorInternalServerError(e)
+    })?;
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+/// Deletes a webhook along with its pending deliveries. Only the owner may do this.
+#[delete("/webhooks/{id}")]
+pub async fn remove(
+    req: HttpRequest,
+    id: web::Path<String>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    let deleted = delete_webhook(&pool, &id, &session.email)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
+    if !deleted {
+        return Err(error::ErrorNotFound("webhook not found"));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The delivery log of a webhook, newest first. Only the owner may read it.
+#[get("/webhooks/{id}/deliveries")]
+pub async fn deliveries(
+    req: HttpRequest,
+    id: web::Path<String>,
+    query: web::Query<DeliveriesQuery>,
+    pool: web::Data<PostgresPool>,
+    settings: web::Data<SessionSettings>,
+) -> Result<HttpResponse, Error> {
+    let session = authenticated_session(&req, &pool, &settings).await?;
+    let webhook = get_webhook(&pool, &id).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorInternalServerError(e)
+    })?;
+    match webhook {
+        Some(webhook) if webhook.owner_email == session.email => {}
+        _ => return Err(error::ErrorNotFound("webhook not found")),
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_DELIVERIES_PAGE_LEN)
+        .clamp(1, MAX_DELIVERIES_PAGE_LEN);
+    let deliveries = list_deliveries(&pool, &id, limit).await.map_err(|e| {
+        error!("{:?}", e);
+        error::ErrorInternalServerError(e)
+    })?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
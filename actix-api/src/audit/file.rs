@@ -0,0 +1,101 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result as Anysult;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+
+use super::{AuditEvent, AuditStore};
+
+/// [AuditStore] appending events to a JSON lines file, one event per line.
+///
+/// Reading scans the whole file, which is fine for a single node without a database but not
+/// for years of meetings.
+#[derive(Clone, Debug)]
+pub struct FileAuditStore {
+    path: PathBuf,
+    /// Keeps the lines of concurrent appends apart.
+    writing: Arc<Mutex<()>>,
+}
+
+impl FileAuditStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            writing: Arc::default(),
+        }
+    }
+}
+
+impl AuditStore for FileAuditStore {
+    fn append(&self, event: AuditEvent) -> BoxFuture<'static, Anysult<()>> {
+        let path = self.path.clone();
+        let writing = self.writing.clone();
+        async move {
+            let mut line = serde_json::to_vec(&event)?;
+            line.push(b'\n');
+            tokio::task::spawn_blocking(move || -> Anysult<()> {
+                let _writing = writing.lock().unwrap();
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .read(true)
+                    .append(true)
+                    .open(&path)?;
+                // Ends a line cut short by a crash so it doesn't swallow this one.
+                if file.seek(SeekFrom::End(0))? > 0 {
+                    let mut last = [0];
+                    file.seek(SeekFrom::End(-1))?;
+                    file.read_exact(&mut last)?;
+                    if last[0] != b'\n' {
+                        line.insert(0, b'\n');
+                    }
+                }
+                file.write_all(&line)?;
+                Ok(())
+            })
+            .await?
+        }
+        .boxed()
+    }
+
+    fn events(
+        &self,
+        room: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> BoxFuture<'static, Anysult<Vec<AuditEvent>>> {
+        let path = self.path.clone();
+        let room = room.to_string();
+        let after = after.map(str::to_string);
+        async move {
+            tokio::task::spawn_blocking(move || -> Anysult<Vec<AuditEvent>> {
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+                    Err(e) => return Err(e.into()),
+                };
+                let mut events = Vec::new();
+                for line in BufReader::new(file).lines() {
+                    // A line cut short by a crash is skipped.
+                    match serde_json::from_str::<AuditEvent>(&line?) {
+                        Ok(event) if event.room == room => events.push(event),
+                        _ => {}
+                    }
+                }
+                events.sort_by(|a, b| (a.at, &a.id).cmp(&(b.at, &b.id)));
+                let start = match after {
+                    Some(id) => match events.iter().position(|e| e.id == id) {
+                        Some(i) => i + 1,
+                        None => return Ok(Vec::new()),
+                    },
+                    None => 0,
+                };
+                Ok(events.into_iter().skip(start).take(limit).collect())
+            })
+            .await?
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,249 @@
+//! Audit log of what happened in meetings: who joined and left a room and when, who the host
+//! removed or handed the host role to, when recordings started and stopped, and refused
+//! connections.
+//!
+//! Events go to a [PostgresAuditStore] when the database is enabled, otherwise they are appended
+//! to a JSON lines file by a [FileAuditStore]. Meeting owners read the events of their rooms over
+//! REST, see [crate::api::rooms]. Writing an event never fails the action it records, errors are
+//! only logged.
+//!
+//! Refused connections are aggregated so a client retrying in a loop can't flood the log: one is
+//! recorded per room and claimed identity every [AUTH_FAILURE_WINDOW], and the next one recorded
+//! says how many were left out in between. The counts are kept per server process.
+mod file;
+mod postgres;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result as Anysult};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+pub use file::FileAuditStore;
+pub use postgres::PostgresAuditStore;
+
+use crate::actors::chat_session::{Email, RoomId};
+use crate::db::PostgresPool;
+
+const DEFAULT_AUDIT_LOG_FILE: &str = "audit.jsonl";
+/// How often a refused connection is recorded for the same room and claimed identity.
+pub const AUTH_FAILURE_WINDOW: Duration = Duration::from_secs(60);
+/// Most rooms and claimed identities refused connections are counted for, beyond it they are
+/// only logged.
+const MAX_AUTH_FAILURE_KEYS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Joined,
+    Left,
+    /// The host removed the target from the meeting.
+    Removed,
+    /// The host handed the host role to the target.
+    HostChanged,
+    RecordingStarted,
+    RecordingStopped,
+    /// A connection to the room was refused, the detail says why.
+    AuthFailed,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Joined => "joined",
+            EventKind::Left => "left",
+            EventKind::Removed => "removed",
+            EventKind::HostChanged => "host_changed",
+            EventKind::RecordingStarted => "recording_started",
+            EventKind::RecordingStopped => "recording_stopped",
+            EventKind::AuthFailed => "auth_failed",
+        }
+    }
+}
+
+impl std::str::FromStr for EventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Anysult<Self> {
+        Ok(match s {
+            "joined" => EventKind::Joined,
+            "left" => EventKind::Left,
+            "removed" => EventKind::Removed,
+            "host_changed" => EventKind::HostChanged,
+            "recording_started" => EventKind::RecordingStarted,
+            "recording_stopped" => EventKind::RecordingStopped,
+            "auth_failed" => EventKind::AuthFailed,
+            _ => return Err(anyhow!("unknown event kind {}", s)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: String,
+    pub room: RoomId,
+    pub kind: EventKind,
+    /// Who joined, left or acted. For refused connections, who they claimed to be.
+    pub actor: Email,
+    /// Who was removed or made host.
+    pub target: Option<Email>,
+    pub detail: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+impl AuditEvent {
+    pub fn new(room: impl Into<RoomId>, kind: EventKind, actor: impl Into<Email>) -> Self {
+        AuditEvent {
+            id: uuid::Uuid::new_v4().to_simple().to_string(),
+            room: room.into(),
+            kind,
+            actor: actor.into(),
+            target: None,
+            detail: None,
+            at: Utc::now(),
+        }
+    }
+
+    pub fn with_target(mut self, target: impl Into<Email>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Where audit events are kept.
+pub trait AuditStore: Send + Sync {
+    fn append(&self, event: AuditEvent) -> BoxFuture<'static, Anysult<()>>;
+
+    /// The first `limit` events of `room` after the event with id `after`, or its first ones
+    /// when `after` is `None`, oldest first. Empty when `after` is not an event of `room`.
+    fn events(
+        &self,
+        room: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> BoxFuture<'static, Anysult<Vec<AuditEvent>>>;
+}
+
+/// Refused connections of one room and claimed identity in the current window.
+struct AuthFailures {
+    since: Instant,
+    /// How many were not recorded.
+    left_out: u64,
+}
+
+/// Records audit events.
+#[derive(Clone)]
+pub struct AuditLog {
+    store: Arc<dyn AuditStore>,
+    auth_failure_window: Duration,
+    auth_failures: Arc<Mutex<HashMap<(RoomId, Email), AuthFailures>>>,
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog").finish_non_exhaustive()
+    }
+}
+
+impl AuditLog {
+    pub fn new(store: Arc<dyn AuditStore>) -> Self {
+        Self {
+            store,
+            auth_failure_window: AUTH_FAILURE_WINDOW,
+            auth_failures: Arc::default(),
+        }
+    }
+
+    pub fn with_auth_failure_window(mut self, window: Duration) -> Self {
+        self.auth_failure_window = window;
+        self
+    }
+
+    /// Keeps the events in Postgres when there is a `pool`, otherwise in `AUDIT_LOG_FILE`
+    /// (default `audit.jsonl`).
+    pub fn from_env(pool: Option<PostgresPool>) -> Self {
+        let store: Arc<dyn AuditStore> = match pool {
+            Some(pool) => Arc::new(PostgresAuditStore::new(pool)),
+            None => {
+                let path = std::env::var("AUDIT_LOG_FILE")
+                    .unwrap_or_else(|_| String::from(DEFAULT_AUDIT_LOG_FILE));
+                Arc::new(FileAuditStore::new(path))
+            }
+        };
+        Self::new(store)
+    }
+
+    pub fn store(&self) -> &Arc<dyn AuditStore> {
+        &self.store
+    }
+
+    /// Stores `event`, logging the error when that fails. Refused connections are only stored
+    /// once per window, see the module docs.
+    pub async fn record(&self, event: AuditEvent) {
+        let event = match event.kind {
+            EventKind::AuthFailed => match self.aggregate_auth_failure(event) {
+                Some(event) => event,
+                None => return,
+            },
+            _ => event,
+        };
+        let (room, kind) = (event.room.clone(), event.kind);
+        if let Err(e) = self.store.append(event).await {
+            error!("error recording {} in {}: {}", kind.as_str(), room, e);
+        }
+    }
+
+    /// The refused connection to record, `None` when one was recorded for the same room and
+    /// claimed identity in this window already.
+    fn aggregate_auth_failure(&self, mut event: AuditEvent) -> Option<AuditEvent> {
+        let now = Instant::now();
+        let window = self.auth_failure_window;
+        let mut failures = self.auth_failures.lock().unwrap();
+        let key = (event.room.clone(), event.actor.clone());
+        if let Some(current) = failures.get_mut(&key) {
+            if now.duration_since(current.since) < window {
+                current.left_out += 1;
+                return None;
+            }
+        } else if failures.len() >= MAX_AUTH_FAILURE_KEYS {
+            failures.retain(|_, failures| now.duration_since(failures.since) < window);
+            if failures.len() >= MAX_AUTH_FAILURE_KEYS {
+                warn!(
+                    "not recording the refused connection of {} to {}: too many refused",
+                    event.actor, event.room
+                );
+                return None;
+            }
+        }
+        let previous = failures.insert(
+            key,
+            AuthFailures {
+                since: now,
+                left_out: 0,
+            },
+        );
+        if let Some(left_out) = previous.map(|p| p.left_out).filter(|n| *n > 0) {
+            let detail = event.detail.take().unwrap_or_default();
+            event.detail = Some(format!(
+                "{} ({} more refused since the last one recorded)",
+                detail, left_out
+            ));
+        }
+        Some(event)
+    }
+
+    /// Stores `event` in the background, for callers that can't wait.
+    pub fn spawn_record(&self, event: AuditEvent) {
+        let audit = self.clone();
+        tokio::spawn(async move { audit.record(event).await });
+    }
+}
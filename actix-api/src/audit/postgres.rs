@@ -0,0 +1,87 @@
+use anyhow::Result as Anysult;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio_postgres::Row;
+
+use super::{AuditEvent, AuditStore};
+use crate::db::PostgresPool;
+
+/// [AuditStore] backed by the `audit_events` table.
+#[derive(Clone, Debug)]
+pub struct PostgresAuditStore {
+    pool: PostgresPool,
+}
+
+impl PostgresAuditStore {
+    pub fn new(pool: PostgresPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl TryFrom<Row> for AuditEvent {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> Anysult<Self> {
+        Ok(AuditEvent {
+            id: row.get("id"),
+            room: row.get("room"),
+            kind: row.get::<_, &str>("kind").parse()?,
+            actor: row.get("actor"),
+            target: row.get("target"),
+            detail: row.get("detail"),
+            at: row.get("at"),
+        })
+    }
+}
+
+impl AuditStore for PostgresAuditStore {
+    fn append(&self, event: AuditEvent) -> BoxFuture<'static, Anysult<()>> {
+        let pool = self.pool.clone();
+        async move {
+            let connection = pool.get().await?;
+            connection
+                .execute(
+                    "INSERT INTO audit_events (id, room, kind, actor, target, detail, at)
+                        VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                    &[
+                        &event.id,
+                        &event.room,
+                        &event.kind.as_str(),
+                        &event.actor,
+                        &event.target,
+                        &event.detail,
+                        &event.at,
+                    ],
+                )
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn events(
+        &self,
+        room: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> BoxFuture<'static, Anysult<Vec<AuditEvent>>> {
+        let pool = self.pool.clone();
+        let room = room.to_string();
+        let after = after.map(str::to_string);
+        async move {
+            let connection = pool.get().await?;
+            let rows = connection
+                .query(
+                    "SELECT id, room, kind, actor, target, detail, at FROM audit_events
+                        WHERE room=$1 AND ($3::TEXT IS NULL OR (at, id) > (
+                            SELECT at, id FROM audit_events WHERE room=$1 AND id=$3
+                        ))
+                        ORDER BY at, id LIMIT $2",
+                    &[&room, &(limit as i64), &after],
+                )
+                .await?;
+            rows.into_iter().map(AuditEvent::try_from).collect()
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,49 @@
+use sha2::{Digest, Sha256};
+
+/// Key operators send as `Authorization: Bearer <key>` to use the admin API.
+#[derive(Clone)]
+pub struct AdminKey {
+    /// Keys are compared by digest without stopping at the first difference, so the time a
+    /// guess takes tells nothing about the key.
+    digest: [u8; 32],
+}
+
+impl std::fmt::Debug for AdminKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdminKey").finish_non_exhaustive()
+    }
+}
+
+impl AdminKey {
+    pub fn new(key: &str) -> Self {
+        Self {
+            digest: Sha256::digest(key.as_bytes()).into(),
+        }
+    }
+
+    /// Reads `ADMIN_API_KEY`.
+    ///
+    /// Returns `None` when no key is configured, in which case the admin API is disabled.
+    pub fn from_env() -> Option<Self> {
+        let key = std::env::var("ADMIN_API_KEY")
+            .ok()
+            .filter(|s| !s.is_empty())?;
+        Some(Self::new(&key))
+    }
+
+    pub fn verify(&self, key: &str) -> bool {
+        let digest: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        digest
+            .iter()
+            .zip(self.digest)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+
+    /// Checks the value of an `Authorization` header.
+    pub fn verify_header(&self, authorization: &str) -> bool {
+        authorization
+            .strip_prefix("Bearer ")
+            .is_some_and(|key| self.verify(key.trim()))
+    }
+}
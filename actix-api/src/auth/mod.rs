@@ -1,38 +1,25 @@
-use actix_web::web;
 use anyhow::{anyhow, Result as Anysult};
 use oauth2::{CsrfToken, PkceCodeChallenge};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 use crate::db::PostgresPool;
+use oidc::OidcProvider;
 
-pub(crate) struct DecodedJwtPartClaims {
-    b64_decoded: Vec<u8>,
-}
-
-pub(crate) fn b64_decode<T: AsRef<[u8]>>(input: T) -> Anysult<Vec<u8>> {
-    base64::decode_config(input, base64::URL_SAFE_NO_PAD).map_err(|e| e.into())
-}
-
-impl DecodedJwtPartClaims {
-    pub fn from_jwt_part_claims(encoded_jwt_part_claims: impl AsRef<[u8]>) -> Anysult<Self> {
-        Ok(Self {
-            b64_decoded: b64_decode(encoded_jwt_part_claims)?,
-        })
-    }
-
-    pub fn deserialize<'a, T: Deserialize<'a>>(&'a self) -> Anysult<T> {
-        Ok(serde_json::from_slice(&self.b64_decoded)?)
-    }
-}
+pub mod admin;
+pub mod oidc;
+pub mod session;
+pub mod token;
 
 #[derive(Debug, Deserialize)]
 pub struct AuthRequest {
     pub state: String,
     pub code: String,
-    pub scope: String,
-    pub authuser: String,
-    pub prompt: String,
+    pub scope: Option<String>,
+    // Google specific, other providers don't send these.
+    pub authuser: Option<String>,
+    pub prompt: Option<String>,
 }
 
 pub struct OAuthRequest {
@@ -45,75 +32,110 @@ pub struct OAuthRequest {
 pub struct OAuthResponse {
     pub access_token: String,
     pub token_type: String,
-    pub scope: String,
+    pub scope: Option<String>,
     pub id_token: String,
     pub refresh_token: Option<String>,
 }
 
+/// Token endpoint response to a `refresh_token` grant.
+#[derive(Deserialize)]
+pub struct RefreshResponse {
+    pub access_token: String,
+    /// Only present when the provider rotates refresh tokens.
+    pub refresh_token: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub email: String,
     pub name: String,
 }
 
-pub fn generate_and_store_oauth_request(
-    pool: web::Data<PostgresPool>,
+pub async fn generate_and_store_oauth_request(
+    pool: &PostgresPool,
 ) -> Anysult<(CsrfToken, PkceCodeChallenge)> {
-    let mut connection = pool.get()?;
+    let connection = pool.get().await?;
     let csrf_state = CsrfToken::new_random();
     let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
-    connection.query(
-        "INSERT INTO oauth_requests (pkce_challenge, pkce_verifier, csrf_state)
-                       VALUES ($1, $2, $3)
+    connection
+        .query(
+            "INSERT INTO oauth_requests (pkce_challenge, pkce_verifier, csrf_state, created_at)
+                       VALUES ($1, $2, $3, now())
             ",
-        &[
-            &pkce_challenge.as_str(),
-            &pkce_verifier.secret().as_str(),
-            &csrf_state.secret().clone(),
-        ],
-    )?;
+            &[
+                &pkce_challenge.as_str(),
+                &pkce_verifier.secret().as_str(),
+                &csrf_state.secret().clone(),
+            ],
+        )
+        .await?;
     Ok((csrf_state, pkce_challenge))
 }
 
-pub fn fetch_oauth_request(pool: web::Data<PostgresPool>, state: String) -> Anysult<OAuthRequest> {
-    let mut connection = pool.get()?;
-    let result = connection.query(
-        "SELECT * FROM oauth_requests WHERE csrf_state=$1",
-        &[&state],
-    )?;
-    #[allow(clippy::manual_try_fold)]
-    result
-        .iter()
-        .fold(Err(anyhow!("Unable to find request")), |_acc, row| {
-            Ok(OAuthRequest {
-                csrf_state: row.get("csrf_state"),
-                pkce_challenge: row.get("pkce_challenge"),
-                pkce_verifier: row.get("pkce_verifier"),
-            })
-        })
+/// Deletes and returns the request for `state`, so a state can only be used once and only
+/// within `ttl` of the login that created it.
+pub async fn consume_oauth_request(
+    pool: &PostgresPool,
+    state: String,
+    ttl: Duration,
+) -> Anysult<OAuthRequest> {
+    let connection = pool.get().await?;
+    let row = connection
+        .query_opt(
+            "DELETE FROM oauth_requests
+                WHERE csrf_state=$1 AND created_at > now() - make_interval(secs => $2)
+                RETURNING csrf_state, pkce_challenge, pkce_verifier",
+            &[&state, &ttl.as_secs_f64()],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Unable to find request"))?;
+    Ok(OAuthRequest {
+        csrf_state: row.get("csrf_state"),
+        pkce_challenge: row.get("pkce_challenge"),
+        pkce_verifier: row.get("pkce_verifier"),
+    })
 }
 
-pub fn upsert_user(
-    pool: web::Data<PostgresPool>,
+/// Removes requests older than `ttl` that were never completed. Returns how many were removed.
+pub async fn sweep_oauth_requests(pool: &PostgresPool, ttl: Duration) -> Anysult<u64> {
+    let connection = pool.get().await?;
+    Ok(connection
+        .execute(
+            "DELETE FROM oauth_requests WHERE created_at <= now() - make_interval(secs => $1)",
+            &[&ttl.as_secs_f64()],
+        )
+        .await?)
+}
+
+pub async fn upsert_user(
+    pool: &PostgresPool,
     claims: &Claims,
     oauth_response: &OAuthResponse,
 ) -> Anysult<()> {
-    let mut connection = pool.get()?;
-    connection.query(
-        "INSERT INTO users (email, access_token, refresh_token) VALUES ($1, $2, $3)
+    let connection = pool.get().await?;
+    connection
+        .query(
+            "INSERT INTO users (email, access_token, refresh_token) VALUES ($1, $2, $3)
                 ON CONFLICT (email)
                     DO UPDATE
-                        SET access_token = $2, refresh_token = $3",
-        &[
-            &claims.email,
-            &oauth_response.access_token,
-            &oauth_response.refresh_token,
-        ],
-    )?;
+                        SET access_token = $2,
+                            refresh_token = COALESCE($3, users.refresh_token)",
+            &[
+                &claims.email,
+                &oauth_response.access_token,
+                &oauth_response.refresh_token,
+            ],
+        )
+        .await?;
     Ok(())
 }
 
+/// Exchanges the authorization code and verifies the returned `id_token` with `oidc`.
+///
+/// `redirect_url` has to be the one the login sent the user to the provider with, providers
+/// refuse codes exchanged for any other.
 pub async fn request_token(
+    oidc: &OidcProvider,
     redirect_url: &str,
     client_id: &str,
     client_secret: &str,
@@ -131,11 +153,54 @@ pub async fn request_token(
         ("pkce_verifier", pkce_verifier),
     ];
     let response = client.post(oauth_token_url).form(&params).send().await?;
-    let oauth_response: OAuthResponse = response.json().await?;
-    let claims: Vec<&str> = oauth_response.id_token.split('.').collect();
-    let claims_chunk = claims
-        .get(1)
-        .ok_or_else(|| anyhow!("Unable to parse jwt token"))?;
-    let decoded_claims = DecodedJwtPartClaims::from_jwt_part_claims(claims_chunk)?;
-    Ok((oauth_response, decoded_claims.deserialize()?))
+    let oauth_response: OAuthResponse = response.error_for_status()?.json().await?;
+    let claims = oidc.verify_id_token(&oauth_response.id_token).await?;
+    Ok((oauth_response, claims))
+}
+
+pub async fn fetch_refresh_token(pool: &PostgresPool, email: &str) -> Anysult<String> {
+    let connection = pool.get().await?;
+    let row = connection
+        .query_opt("SELECT refresh_token FROM users WHERE email=$1", &[&email])
+        .await?
+        .ok_or_else(|| anyhow!("Unable to find user"))?;
+    row.get::<_, Option<String>>("refresh_token")
+        .ok_or_else(|| anyhow!("User has no refresh token"))
+}
+
+pub async fn update_user_tokens(
+    pool: &PostgresPool,
+    email: &str,
+    refresh_response: &RefreshResponse,
+) -> Anysult<()> {
+    let connection = pool.get().await?;
+    connection
+        .execute(
+            "UPDATE users SET access_token = $2, refresh_token = COALESCE($3, refresh_token)
+            WHERE email = $1",
+            &[
+                &email,
+                &refresh_response.access_token,
+                &refresh_response.refresh_token,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn refresh_access_token(
+    client_id: &str,
+    client_secret: &str,
+    oauth_token_url: &str,
+    refresh_token: &str,
+) -> Anysult<RefreshResponse> {
+    let client = Client::new();
+    let params = [
+        ("grant_type", "refresh_token"),
+        ("client_id", client_id),
+        ("client_secret", client_secret),
+        ("refresh_token", refresh_token),
+    ];
+    let response = client.post(oauth_token_url).form(&params).send().await?;
+    Ok(response.error_for_status()?.json().await?)
 }
@@ -0,0 +1,150 @@
+use anyhow::{anyhow, Context, Result as Anysult};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use reqwest::Client;
+use serde::Deserialize;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use super::Claims;
+
+/// Issuer used when `OAUTH_ISSUER` is not set.
+pub const GOOGLE_ISSUER: &str = "https://accounts.google.com";
+
+/// How long fetched signing keys are trusted before the JWKS is downloaded again.
+const JWKS_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Subset of the provider's `.well-known/openid-configuration` used by the login flow.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    email: String,
+    #[serde(default)]
+    email_verified: Option<bool>,
+    #[serde(default)]
+    name: String,
+}
+
+struct CachedJwks {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+/// An OpenID Connect provider discovered from its issuer URL.
+///
+/// Verifies `id_token` signatures against the provider's published JWKS, which is cached and
+/// refreshed when it expires or when a token is signed with a key id we have not seen yet.
+pub struct OidcProvider {
+    metadata: ProviderMetadata,
+    client_id: String,
+    http: Client,
+    jwks: RwLock<Option<CachedJwks>>,
+}
+
+impl OidcProvider {
+    /// Fetches `{issuer}/.well-known/openid-configuration`.
+    pub async fn discover(issuer: &str, client_id: &str) -> Anysult<Self> {
+        let http = Client::new();
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = http
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("invalid discovery document at {}", url))?;
+        if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(anyhow!(
+                "discovery document issuer {} does not match {}",
+                metadata.issuer,
+                issuer
+            ));
+        }
+        Ok(Self {
+            metadata,
+            client_id: client_id.to_string(),
+            http,
+            jwks: RwLock::new(None),
+        })
+    }
+
+    pub fn metadata(&self) -> &ProviderMetadata {
+        &self.metadata
+    }
+
+    /// Validates the signature, issuer, audience and expiry of `id_token` and returns its claims.
+    pub async fn verify_id_token(&self, id_token: &str) -> Anysult<Claims> {
+        let header = decode_header(id_token)?;
+        if !is_asymmetric(header.alg) {
+            return Err(anyhow!("id_token signed with unsupported {:?}", header.alg));
+        }
+        let kid = header
+            .kid
+            .ok_or_else(|| anyhow!("id_token header has no kid"))?;
+        let key = match self.cached_key(&kid) {
+            Some(key) => key,
+            None => {
+                self.refresh_jwks().await?;
+                self.cached_key(&kid)
+                    .ok_or_else(|| anyhow!("no signing key with kid {}", kid))?
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.metadata.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)?.claims;
+        if claims.email_verified == Some(false) {
+            return Err(anyhow!("email {} is not verified", claims.email));
+        }
+        Ok(Claims {
+            email: claims.email,
+            name: claims.name,
+        })
+    }
+
+    fn cached_key(&self, kid: &str) -> Option<DecodingKey> {
+        let jwks = self.jwks.read().ok()?;
+        let jwks = jwks.as_ref()?;
+        if jwks.fetched_at.elapsed() > JWKS_CACHE_TTL {
+            return None;
+        }
+        DecodingKey::from_jwk(jwks.keys.find(kid)?).ok()
+    }
+
+    async fn refresh_jwks(&self) -> Anysult<()> {
+        let keys: JwkSet = self
+            .http
+            .get(&self.metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("invalid JWKS document")?;
+        let mut jwks = self
+            .jwks
+            .write()
+            .map_err(|_| anyhow!("JWKS cache lock poisoned"))?;
+        *jwks = Some(CachedJwks {
+            keys,
+            fetched_at: Instant::now(),
+        });
+        Ok(())
+    }
+}
+
+fn is_asymmetric(alg: Algorithm) -> bool {
+    !matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+}
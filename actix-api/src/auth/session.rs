@@ -0,0 +1,178 @@
+//! Login sessions backed by the `sessions` table.
+//!
+//! The browser only holds `{session_id}.{signature}` in an HttpOnly cookie; whether the session is
+//! still valid is always decided by the row, so `/logout` can revoke it server-side.
+use actix_web::cookie::{time::OffsetDateTime, Cookie, SameSite};
+use anyhow::{anyhow, Result as Anysult};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::time::{Duration, SystemTime};
+
+use crate::db::PostgresPool;
+
+pub const SESSION_COOKIE: &str = "session";
+
+const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Secret and lifetime used for session cookies.
+#[derive(Clone)]
+pub struct SessionSettings {
+    secret: Vec<u8>,
+    pub ttl: Duration,
+}
+
+impl SessionSettings {
+    pub fn new(secret: impl Into<Vec<u8>>, ttl: Duration) -> Self {
+        Self {
+            secret: secret.into(),
+            ttl,
+        }
+    }
+
+    /// Reads `SESSION_SECRET` and `SESSION_TTL_SECS`.
+    ///
+    /// Without a secret a random one is generated, which logs everybody out on restart.
+    pub fn from_env() -> Self {
+        let secret = std::env::var("SESSION_SECRET")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(String::into_bytes)
+            .unwrap_or_else(|| {
+                tracing::warn!("SESSION_SECRET is not set, sessions will not survive a restart");
+                let mut secret = vec![0u8; 32];
+                rand::thread_rng().fill_bytes(&mut secret);
+                secret
+            });
+        let ttl = std::env::var("SESSION_TTL_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_SESSION_TTL);
+        Self::new(secret, ttl)
+    }
+
+    fn mac(&self, session_id: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("hmac accepts keys of any size");
+        mac.update(session_id.as_bytes());
+        mac
+    }
+
+    /// Returns the cookie value for `session_id`.
+    pub fn sign(&self, session_id: &str) -> String {
+        let signature = self.mac(session_id).finalize().into_bytes();
+        format!("{}.{}", session_id, hex::encode(signature))
+    }
+
+    /// Returns the session id if `value` carries a valid signature.
+    pub fn verify(&self, value: &str) -> Option<String> {
+        let (session_id, signature) = value.rsplit_once('.')?;
+        let signature = hex::decode(signature).ok()?;
+        self.mac(session_id).verify_slice(&signature).ok()?;
+        Some(session_id.to_string())
+    }
+
+    pub fn cookie(&self, session: &Session) -> Cookie<'static> {
+        Cookie::build(SESSION_COOKIE, self.sign(&session.id))
+            .path("/")
+            .http_only(true)
+            .secure(true)
+            .same_site(SameSite::Lax)
+            .expires(OffsetDateTime::from(session.expires_at))
+            .finish()
+    }
+}
+
+pub struct Session {
+    pub id: String,
+    pub email: String,
+    pub expires_at: SystemTime,
+}
+
+fn new_session_id() -> String {
+    let mut id = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut id);
+    hex::encode(id)
+}
+
+pub async fn create_session(pool: &PostgresPool, email: &str, ttl: Duration) -> Anysult<Session> {
+    let connection = pool.get().await?;
+    let session = Session {
+        id: new_session_id(),
+        email: email.to_string(),
+        expires_at: SystemTime::now() + ttl,
+    };
+    connection
+        .execute(
+            "INSERT INTO sessions (id, email, expires_at) VALUES ($1, $2, $3)",
+            &[&session.id, &session.email, &session.expires_at],
+        )
+        .await?;
+    Ok(session)
+}
+
+/// Looks up a session that has neither expired nor been revoked.
+pub async fn fetch_active_session(pool: &PostgresPool, id: &str) -> Anysult<Session> {
+    let connection = pool.get().await?;
+    let row = connection
+        .query_opt(
+            "SELECT id, email, expires_at FROM sessions
+                WHERE id=$1 AND revoked_at IS NULL AND expires_at > now()",
+            &[&id],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Unable to find session"))?;
+    Ok(Session {
+        id: row.get("id"),
+        email: row.get("email"),
+        expires_at: row.get("expires_at"),
+    })
+}
+
+/// Replaces `session` with a new one for the same user lasting `ttl`. The old session is
+/// revoked along with the connect tokens issued for it, and can't be rotated twice.
+pub async fn rotate_session(
+    pool: &PostgresPool,
+    session: &Session,
+    ttl: Duration,
+) -> Anysult<Session> {
+    let mut connection = pool.get().await?;
+    let transaction = connection.transaction().await?;
+    let revoked = transaction
+        .execute(
+            "UPDATE sessions SET revoked_at=now()
+                WHERE id=$1 AND revoked_at IS NULL AND expires_at > now()",
+            &[&session.id],
+        )
+        .await?;
+    if revoked == 0 {
+        return Err(anyhow!("session was revoked or expired"));
+    }
+    let rotated = Session {
+        id: new_session_id(),
+        email: session.email.clone(),
+        expires_at: SystemTime::now() + ttl,
+    };
+    transaction
+        .execute(
+            "INSERT INTO sessions (id, email, expires_at) VALUES ($1, $2, $3)",
+            &[&rotated.id, &rotated.email, &rotated.expires_at],
+        )
+        .await?;
+    transaction.commit().await?;
+    Ok(rotated)
+}
+
+pub async fn revoke_session(pool: &PostgresPool, id: &str) -> Anysult<()> {
+    let connection = pool.get().await?;
+    connection
+        .execute(
+            "UPDATE sessions SET revoked_at=now() WHERE id=$1 AND revoked_at IS NULL",
+            &[&id],
+        )
+        .await?;
+    Ok(())
+}
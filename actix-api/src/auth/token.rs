@@ -0,0 +1,191 @@
+use actix_web::cookie::{time::OffsetDateTime, Cookie, SameSite};
+use anyhow::{anyhow, Result as Anysult};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::auth::session::{fetch_active_session, Session};
+use crate::db::PostgresPool;
+
+/// Name of the cookie carrying the connect token for browsers that can send it.
+pub const CONNECT_TOKEN_COOKIE: &str = "connect_token";
+
+const DEFAULT_CONNECT_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Claims of the token that binds a websocket/webtransport connection to a user.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConnectClaims {
+    pub sub: String,
+    /// Login session the token was issued for, logging out revokes the token with it.
+    pub sid: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+/// Query string accepted by the connect endpoints, e.g. `/lobby/{email}/{room}?token=...`
+#[derive(Debug, Deserialize)]
+pub struct ConnectParams {
+    pub token: Option<String>,
+    /// Passcode of the meeting, if it has one.
+    pub passcode: Option<String>,
+    /// Invite token letting the user in without the passcode.
+    pub invite: Option<String>,
+    /// Resumption token of a session whose connection dropped, see [crate::resumption].
+    pub resume: Option<String>,
+}
+
+/// HS256 key used to issue and verify connect tokens.
+#[derive(Clone)]
+pub struct ConnectTokenKey {
+    secret: Vec<u8>,
+    ttl: Duration,
+    /// Where the login sessions of the tokens are looked up, see [ConnectTokenKey::with_sessions].
+    sessions: Option<PostgresPool>,
+}
+
+impl ConnectTokenKey {
+    pub fn new(secret: impl Into<Vec<u8>>, ttl: Duration) -> Self {
+        Self {
+            secret: secret.into(),
+            ttl,
+            sessions: None,
+        }
+    }
+
+    /// Refuses tokens whose login session in `pool` was revoked or expired. Without it a token
+    /// stays valid for its whole ttl after logging out.
+    pub fn with_sessions(mut self, pool: PostgresPool) -> Self {
+        self.sessions = Some(pool);
+        self
+    }
+
+    /// Reads `JWT_SECRET` and `CONNECT_TOKEN_TTL_SECS`. Returns `None` when no secret is
+    /// configured.
+    pub fn from_env() -> Option<Self> {
+        let secret = std::env::var("JWT_SECRET").ok().filter(|s| !s.is_empty())?;
+        let ttl = std::env::var("CONNECT_TOKEN_TTL_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_CONNECT_TOKEN_TTL);
+        Some(Self::new(secret, ttl))
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Issues a token for `email`, valid as long as the login session `sid` is.
+    pub fn issue(&self, email: &str, sid: &str) -> Anysult<String> {
+        let iat = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let claims = ConnectClaims {
+            sub: email.to_string(),
+            sid: sid.to_string(),
+            iat,
+            exp: iat + self.ttl.as_secs(),
+        };
+        Ok(encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(&self.secret),
+        )?)
+    }
+
+    /// Issues a token for the user of `session` wrapped in a cookie the UI can read and append to
+    /// the lobby urls, since WebTransport does not send cookies.
+    pub fn cookie(&self, session: &Session) -> Anysult<Cookie<'static>> {
+        let token = self.issue(&session.email, &session.id)?;
+        Ok(Cookie::build(CONNECT_TOKEN_COOKIE, token)
+            .path("/")
+            .same_site(SameSite::Lax)
+            .expires(OffsetDateTime::now_utc() + self.ttl)
+            .finish())
+    }
+
+    pub fn verify(&self, token: &str) -> Anysult<ConnectClaims> {
+        let data = decode::<ConnectClaims>(
+            token,
+            &DecodingKey::from_secret(&self.secret),
+            &Validation::new(Algorithm::HS256),
+        )?;
+        Ok(data.claims)
+    }
+}
+
+impl std::fmt::Debug for ConnectTokenKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectTokenKey")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+/// How websocket and webtransport connections are authenticated.
+#[derive(Clone, Debug)]
+pub enum ConnectAuth {
+    /// Connections need a connect token issued with the key.
+    Tokens(ConnectTokenKey),
+    /// Anyone may connect as anyone, only ever set explicitly with `INSECURE_NO_AUTH`.
+    Insecure,
+    /// No key is configured, every connection is refused.
+    Refused,
+}
+
+impl ConnectAuth {
+    /// Uses the [ConnectTokenKey::from_env] if there is one. Without it connections are refused
+    /// unless `INSECURE_NO_AUTH` is set.
+    pub fn from_env() -> Self {
+        match ConnectTokenKey::from_env() {
+            Some(key) => ConnectAuth::Tokens(key),
+            None if types::truthy(std::env::var("INSECURE_NO_AUTH").ok().as_deref()) => {
+                ConnectAuth::Insecure
+            }
+            None => ConnectAuth::Refused,
+        }
+    }
+
+    /// See [ConnectTokenKey::with_sessions].
+    pub fn with_sessions(self, pool: PostgresPool) -> Self {
+        match self {
+            ConnectAuth::Tokens(key) => ConnectAuth::Tokens(key.with_sessions(pool)),
+            auth => auth,
+        }
+    }
+
+    /// The key connect tokens are issued with, if any.
+    pub fn key(&self) -> Option<&ConnectTokenKey> {
+        match self {
+            ConnectAuth::Tokens(key) => Some(key),
+            ConnectAuth::Insecure | ConnectAuth::Refused => None,
+        }
+    }
+}
+
+/// Checks that `token` was issued for `email`, and that its login session is still active when
+/// the key knows where sessions are.
+pub async fn authorize_connection(
+    auth: &ConnectAuth,
+    email: &str,
+    token: Option<&str>,
+) -> Anysult<()> {
+    let key = match auth {
+        ConnectAuth::Tokens(key) => key,
+        ConnectAuth::Insecure => return Ok(()),
+        ConnectAuth::Refused => {
+            return Err(anyhow!(
+                "connections are refused, neither JWT_SECRET nor INSECURE_NO_AUTH is set"
+            ))
+        }
+    };
+    let token = token.ok_or_else(|| anyhow!("missing connect token"))?;
+    let claims = key.verify(token)?;
+    if claims.sub != email {
+        return Err(anyhow!("connect token was issued for a different user"));
+    }
+    if let Some(pool) = &key.sessions {
+        fetch_active_session(pool, &claims.sid)
+            .await
+            .map_err(|e| e.context("the login session of the connect token ended"))?;
+    }
+    Ok(())
+}
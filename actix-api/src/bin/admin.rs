@@ -0,0 +1,204 @@
+//! Operates the live rooms of a websocket server through its admin API, at `ADMIN_API_URL`
+//! (default `http://localhost:8080`) with the key in `ADMIN_API_KEY`.
+//!
+//! ```text
+//! admin rooms
+//! admin session <session>
+//! admin disconnect <session> [--reason <text>]
+//! admin close <room> [--reason <text>]
+//! ```
+//!
+//! With the database enabled, the rooms and sessions of every server are seen, websocket and
+//! WebTransport alike. Without it only the websocket sessions of that server are seen.
+use std::process::exit;
+
+use anyhow::{anyhow, bail, Context, Result as Anysult};
+use dotenv::dotenv;
+use reqwest::{Method, RequestBuilder, Response};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+const USAGE: &str = "usage: admin rooms
+       admin session <session>
+       admin disconnect <session> [--reason <text>]
+       admin close <room> [--reason <text>]";
+
+const DEFAULT_ADMIN_API_URL: &str = "http://localhost:8080";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Rooms,
+    Session(String),
+    Disconnect {
+        session: String,
+        reason: Option<String>,
+    },
+    Close {
+        room: String,
+        reason: Option<String>,
+    },
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Anysult<Command> {
+    let mut positional = Vec::new();
+    let mut reason = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--reason" => {
+                reason = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--reason needs a value"))?,
+                );
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let mut positional = positional.into_iter();
+    let command = positional
+        .next()
+        .ok_or_else(|| anyhow!("expected a command"))?;
+    let mut target = |what: &str| {
+        positional
+            .next()
+            .ok_or_else(|| anyhow!("{} needs a {}", command, what))
+    };
+    let parsed = match command.as_str() {
+        "rooms" => Command::Rooms,
+        "session" => Command::Session(target("session")?),
+        "disconnect" => Command::Disconnect {
+            session: target("session")?,
+            reason: reason.take(),
+        },
+        "close" => Command::Close {
+            room: target("room")?,
+            reason: reason.take(),
+        },
+        _ => bail!("unknown command {}", command),
+    };
+    if positional.next().is_some() {
+        bail!("too many arguments for {}", command);
+    }
+    if reason.is_some() {
+        bail!("--reason only applies to disconnect and close");
+    }
+    Ok(parsed)
+}
+
+#[derive(Debug, Deserialize)]
+struct RoomSummary {
+    room: String,
+    participants: usize,
+    transports: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Closed {
+    closed: usize,
+}
+
+struct AdminClient {
+    url: String,
+    key: String,
+    http: reqwest::Client,
+}
+
+impl AdminClient {
+    fn from_env() -> Anysult<Self> {
+        let key = std::env::var("ADMIN_API_KEY").context("ADMIN_API_KEY must be set")?;
+        let url =
+            std::env::var("ADMIN_API_URL").unwrap_or_else(|_| String::from(DEFAULT_ADMIN_API_URL));
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+            key,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, format!("{}/admin/{}", self.url, path))
+            .bearer_auth(&self.key)
+    }
+
+    /// Sends the request and turns error responses into errors.
+    async fn send(request: RequestBuilder) -> Anysult<Response> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("{}: {}", status, body);
+        }
+        Ok(response)
+    }
+}
+
+fn reason_query(reason: &Option<String>) -> Vec<(&str, &str)> {
+    reason.iter().map(|r| ("reason", r.as_str())).collect()
+}
+
+async fn run(command: Command) -> Anysult<()> {
+    let client = AdminClient::from_env()?;
+    match command {
+        Command::Rooms => {
+            let rooms: Vec<RoomSummary> = AdminClient::send(client.request(Method::GET, "rooms"))
+                .await?
+                .json()
+                .await?;
+            println!("{:<40} {:>12}  TRANSPORTS", "ROOM", "PARTICIPANTS");
+            for room in rooms {
+                let transports: Vec<String> = room
+                    .transports
+                    .iter()
+                    .map(|(transport, count)| format!("{}={}", transport, count))
+                    .collect();
+                println!(
+                    "{:<40} {:>12}  {}",
+                    room.room,
+                    room.participants,
+                    transports.join(" ")
+                );
+            }
+        }
+        Command::Session(session) => {
+            let path = format!("sessions/{}", urlencoding::encode(&session));
+            let info: serde_json::Value = AdminClient::send(client.request(Method::GET, &path))
+                .await?
+                .json()
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&info)?);
+        }
+        Command::Disconnect { session, reason } => {
+            let path = format!("sessions/{}/disconnect", urlencoding::encode(&session));
+            let request = client
+                .request(Method::POST, &path)
+                .query(&reason_query(&reason));
+            AdminClient::send(request).await?;
+            println!("disconnected session {}", session);
+        }
+        Command::Close { room, reason } => {
+            let path = format!("rooms/{}/close", urlencoding::encode(&room));
+            let request = client
+                .request(Method::POST, &path)
+                .query(&reason_query(&reason));
+            let closed: Closed = AdminClient::send(request).await?.json().await?;
+            println!("closed {} sessions in room {}", closed.closed, room);
+        }
+    }
+    Ok(())
+}
+
+#[actix_rt::main]
+async fn main() {
+    dotenv().ok();
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+    if let Err(e) = run(command).await {
+        eprintln!("error: {:#}", e);
+        exit(1);
+    }
+}
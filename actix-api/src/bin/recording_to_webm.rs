@@ -0,0 +1,83 @@
+//! Writes the media of a recorded room to WebM files, one per participant and media type.
+//!
+//! ```text
+//! recording_to_webm <recording.vcpl> [out_dir] [--key <email>=<key>:<iv>]...
+//! ```
+//!
+//! Keys and IVs are hex and are only needed for participants who encrypted their media.
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::exit;
+
+use anyhow::{anyhow, Result as Anysult};
+use sec_api::recording::mux::{mux_to_webm, MediaKey};
+
+const USAGE: &str =
+    "usage: recording_to_webm <recording.vcpl> [out_dir] [--key <email>=<key>:<iv>]...";
+
+struct Args {
+    log: PathBuf,
+    out_dir: PathBuf,
+    keys: HashMap<String, MediaKey>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Anysult<Args> {
+    let mut paths = Vec::new();
+    let mut keys = HashMap::new();
+    while let Some(arg) = args.next() {
+        if arg == "--key" {
+            let key = args.next().ok_or_else(|| anyhow!("--key needs a value"))?;
+            let (email, key) = key
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected <email>=<key>:<iv>, got {}", key))?;
+            keys.insert(email.to_string(), MediaKey::parse(key)?);
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    let mut paths = paths.into_iter();
+    let log = paths.next().ok_or_else(|| anyhow!("missing recording"))?;
+    let out_dir = paths.next().unwrap_or_else(|| PathBuf::from("."));
+    if paths.next().is_some() {
+        return Err(anyhow!("too many arguments"));
+    }
+    Ok(Args { log, out_dir, keys })
+}
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+    match mux_to_webm(&args.log, &args.out_dir, &args.keys) {
+        Ok(report) => {
+            for file in &report.files {
+                println!(
+                    "{}\t{}\t{} frames\t{} dropped",
+                    file.path.display(),
+                    file.email,
+                    file.frames,
+                    file.dropped
+                );
+            }
+            if report.unreadable > 0 {
+                eprintln!(
+                    "skipped {} packets that could not be read, is a --key missing?",
+                    report.unreadable
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            exit(1);
+        }
+    }
+}
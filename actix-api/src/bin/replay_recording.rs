@@ -0,0 +1,96 @@
+//! Replays a recorded room into a room of the servers sharing the NATS server at `NATS_URL`.
+//!
+//! ```text
+//! replay_recording <recording.vcpl> <room> [--speed <factor>] [--as <email>=<email>]...
+//! ```
+//!
+//! `--speed 2` plays the recording twice as fast, `--as alice=demo-alice` replays what alice sent
+//! as coming from demo-alice.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::exit;
+
+use anyhow::{anyhow, Context, Result as Anysult};
+use dotenv::dotenv;
+use sec_api::bus::NatsBus;
+use sec_api::recording::packet_log::PacketLogReader;
+use sec_api::recording::replay::Replay;
+use tracing::info;
+
+const USAGE: &str =
+    "usage: replay_recording <recording.vcpl> <room> [--speed <factor>] [--as <email>=<email>]...";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Anysult<(PathBuf, Replay)> {
+    let mut positional = Vec::new();
+    let mut speed = 1.0;
+    let mut identities = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--speed" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--speed needs a value"))?;
+                speed = value.parse().context("invalid --speed")?;
+            }
+            "--as" => {
+                let value = args.next().ok_or_else(|| anyhow!("--as needs a value"))?;
+                let (from, to) = value
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("expected <email>=<email>, got {}", value))?;
+                identities.push((from.to_string(), to.to_string()));
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let [log, room]: [String; 2] = positional
+        .try_into()
+        .map_err(|_| anyhow!("expected a recording and a room"))?;
+    let replay = identities
+        .into_iter()
+        .fold(Replay::new(room), |replay, (from, to)| {
+            replay.with_identity(from, to)
+        })
+        .with_speed(speed)?;
+    Ok((PathBuf::from(log), replay))
+}
+
+#[actix_rt::main]
+async fn main() {
+    dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let (log, replay) = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+    if let Err(e) = run(log, replay).await {
+        eprintln!("error: {:#}", e);
+        exit(1);
+    }
+}
+
+async fn run(log: PathBuf, replay: Replay) -> Anysult<()> {
+    // The in-process bus would reach no one outside this process.
+    let url = std::env::var("NATS_URL").context("NATS_URL must be set")?;
+    let bus = NatsBus::connect(&url).await?;
+    let file = File::open(&log).with_context(|| format!("failed to open {}", log.display()))?;
+    let reader = PacketLogReader::new(BufReader::new(file))?;
+    info!(
+        "replaying {} recorded in {}",
+        log.display(),
+        reader.header().room
+    );
+    let report = replay.run(&bus, reader).await?;
+    info!(
+        "published {} packets, skipped {}",
+        report.published, report.skipped
+    );
+    Ok(())
+}
@@ -5,10 +5,6 @@ use actix_http::{
     ws::{Codec, Message, ProtocolError},
 };
 use actix_web::{
-    cookie::{
-        time::{Duration, OffsetDateTime},
-        Cookie, SameSite,
-    },
     error, get,
     web::{self, Bytes},
     App, Error, HttpRequest, HttpResponse, HttpServer, Responder,
@@ -16,18 +12,41 @@ use actix_web::{
 use actix_web_actors::ws::{handshake, WebsocketContext};
 use reqwest::header::LOCATION;
 use sec_api::{
-    actors::{chat_server::ChatServer, chat_session::WsChatSession},
+    actors::{
+        chat_server::ChatServer, chat_session::WsChatSession, rejected_session::WsRejectedSession,
+    },
+    api,
+    audit::{AuditEvent, AuditLog, EventKind},
     auth::{
-        fetch_oauth_request, generate_and_store_oauth_request, request_token, upsert_user,
-        AuthRequest,
+        admin::AdminKey,
+        consume_oauth_request, generate_and_store_oauth_request,
+        oidc::{OidcProvider, GOOGLE_ISSUER},
+        request_token,
+        session::{create_session, SessionSettings},
+        sweep_oauth_requests,
+        token::{authorize_connection, ConnectAuth, ConnectParams, CONNECT_TOKEN_COOKIE},
+        upsert_user, AuthRequest,
+    },
+    bus::{self, validate_room_id},
+    chat::ChatHistory,
+    db::{self, get_pool, PostgresPool},
+    lobby::Lobby,
+    meetings::{
+        authorize_join, require_registered_meeting, InviteKey, JoinCredentials, JoinRefused,
+        MeetingDirectory,
     },
-    db::{get_pool, PostgresPool},
     models::{AppConfig, AppState},
+    recording::Recorder,
+    resumption::Resumption,
+    rooms::{capacity::Capacity, presence::Presence},
+    webhooks::{AllowedHosts, Webhooks},
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use types::truthy;
 
 const SCOPE: &str = "email%20profile%20openid";
+const DEFAULT_OAUTH_STATE_TTL_SECS: u64 = 600;
+const OAUTH_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
 /**
  * Function used by the Web Application to initiate OAuth.
  *
@@ -41,17 +60,13 @@ async fn login(
     cfg: web::Data<AppConfig>,
 ) -> Result<HttpResponse, Error> {
     // TODO: verify if user exists in the db by looking at the session cookie, (if the client provides one.)
-    let pool2 = pool.clone();
 
     // 2. Generate and Store OAuth Request.
-    let (csrf_token, pkce_challenge) = {
-        let pool = pool2.clone();
-        web::block(move || generate_and_store_oauth_request(pool)).await?
-    }
-    .map_err(|e| {
-        error!("{:?}", e);
-        error::ErrorInternalServerError(e)
-    })?;
+    let (csrf_token, pkce_challenge) =
+        generate_and_store_oauth_request(&pool).await.map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorInternalServerError(e)
+        })?;
 
     // 3. Craft OAuth Login URL
     let oauth_login_url = format!("{oauth_url}?client_id={client_id}&redirect_uri={redirect_url}&response_type=code&scope={scope}&prompt=select_account&pkce_challenge={pkce_challenge}&state={state}&access_type=offline",
@@ -82,22 +97,24 @@ async fn handle_google_oauth_callback(
     pool: web::Data<PostgresPool>,
     info: web::Query<AuthRequest>,
     cfg: web::Data<AppConfig>,
+    app_state: web::Data<AppState>,
+    oidc: web::Data<OidcProvider>,
+    session_settings: web::Data<SessionSettings>,
 ) -> Result<HttpResponse, Error> {
     let state = info.state.clone();
 
-    // 1. Fetch OAuth request, if this fails, probably a hacker is trying to p*wn us.
-    let oauth_request = {
-        let pool = pool.clone();
-        web::block(move || fetch_oauth_request(pool, state)).await?
-    }
-    .map_err(|e| {
-        error!("{:?}", e);
-        error::ErrorBadRequest("couldn't find a request, are you a hacker?")
-    })?;
+    // 1. Consume OAuth request, if this fails, probably a hacker is trying to p*wn us.
+    let oauth_request = consume_oauth_request(&pool, state, cfg.oauth_state_ttl)
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            error::ErrorBadRequest("couldn't find a request, are you a hacker?")
+        })?;
 
     // 2. Request token from OAuth provider.
     let (oauth_response, claims) = request_token(
-        &cfg.oauth_auth_url,
+        &oidc,
+        &cfg.oauth_redirect_url,
         &cfg.oauth_client_id,
         &cfg.oauth_secret,
         &oauth_request.pkce_verifier,
@@ -111,30 +128,64 @@ async fn handle_google_oauth_callback(
     })?;
 
     // 3. Store tokens and create user.
-    {
-        let claims = claims.clone();
-        web::block(move || upsert_user(pool, &claims, &oauth_response)).await?
-    }
-    .map_err(|err| {
-        error!("{:?}", err);
-        error::ErrorInternalServerError(err)
-    })?;
+    upsert_user(&pool, &claims, &oauth_response)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            error::ErrorInternalServerError(err)
+        })?;
+
+    // 4. Create a server-side session and hand the browser a signed cookie for it.
+    let session = create_session(&pool, &claims.email, session_settings.ttl)
+        .await
+        .map_err(|err| {
+            error!("{:?}", err);
+            error::ErrorInternalServerError(err)
+        })?;
 
-    // 4. Create session cookie with email.
-    let cookie = Cookie::build("email", claims.email)
-        .path("/")
-        .same_site(SameSite::Lax)
-        // Session lasts only 360 secs to test cookie expiration.
-        .expires(OffsetDateTime::now_utc().checked_add(Duration::seconds(360)))
-        .finish();
+    // 5. Sign a connect token so the websocket and webtransport servers can trust the email, for
+    // as long as the session lasts.
+    let token_cookie = app_state
+        .connect_auth
+        .key()
+        .map(|key| key.cookie(&session))
+        .transpose()
+        .map_err(|err| {
+            error!("{:?}", err);
+            error::ErrorInternalServerError(err)
+        })?;
 
-    // 5. Send cookie and redirect browser to AFTER_LOGIN_URL
+    // 6. Send cookies and redirect browser to AFTER_LOGIN_URL
     let mut response = HttpResponse::Found();
     response.append_header((LOCATION, cfg.after_login_url.clone()));
-    response.cookie(cookie);
+    response.cookie(session_settings.cookie(&session));
+    if let Some(token_cookie) = token_cookie {
+        response.cookie(token_cookie);
+    }
     Ok(response.finish())
 }
 
+/// Deletes abandoned logins so `oauth_requests` does not grow without bound.
+async fn sweep_oauth_requests_periodically(pool: PostgresPool, ttl: std::time::Duration) {
+    let mut interval = actix_rt::time::interval(OAUTH_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        match sweep_oauth_requests(&pool, ttl).await {
+            Ok(0) => {}
+            Ok(swept) => debug!("swept {} expired oauth requests", swept),
+            Err(e) => error!("failed to sweep oauth requests: {:?}", e),
+        }
+    }
+}
+
+async fn run_migrations(pool: &PostgresPool) -> std::io::Result<()> {
+    let applied = db::migrate(pool)
+        .await
+        .map_err(|e| std::io::Error::other(format!("failed to run migrations: {:?}", e)))?;
+    info!("applied {} migrations", applied.len());
+    Ok(())
+}
+
 fn start_with_codec<A, S>(
     actor: A,
     req: &HttpRequest,
@@ -152,15 +203,74 @@ where
 #[get("/lobby/{email}/{room}")]
 pub async fn ws_connect(
     session: web::Path<(String, String)>,
+    params: web::Query<ConnectParams>,
     req: HttpRequest,
     stream: web::Payload,
     state: web::Data<AppState>,
 ) -> impl Responder {
     let (email, room) = session.into_inner();
     debug!("socket connected");
-    let chat = state.chat.clone();
-    let actor = WsChatSession::new(chat, room, email);
     let codec = Codec::new().max_size(1_000_000);
+    let params = params.into_inner();
+    let token = params.token.or_else(|| {
+        req.cookie(CONNECT_TOKEN_COOKIE)
+            .map(|c| c.value().to_string())
+    });
+    if let Err(e) = validate_room_id(&room) {
+        warn!("rejecting connection for {}: {}", email, e);
+        let actor = WsRejectedSession::unauthorized("invalid meeting id");
+        return start_with_codec(actor, &req, stream, codec);
+    }
+    let credentials = JoinCredentials::new(params.passcode, params.invite)
+        .with_peer(req.peer_addr().map(|addr| addr.ip()));
+    if let Err(e) = authorize_connection(&state.connect_auth, &email, token.as_deref()).await {
+        warn!("rejecting connection for {}: {}", email, e);
+        let event = AuditEvent::new(&*room, EventKind::AuthFailed, &*email)
+            .with_detail("invalid connect token");
+        state.audit.record(event).await;
+        let actor = WsRejectedSession::unauthorized("invalid connect token");
+        return start_with_codec(actor, &req, stream, codec);
+    }
+    let admission = match authorize_join(state.meetings.as_ref(), &room, &email, &credentials).await
+    {
+        Ok(admission) => admission,
+        Err(e) => {
+            warn!("rejecting connection for {}: {}", email, e);
+            let reason = match &e {
+                JoinRefused::Failed(_) => "failed to look up the meeting".to_string(),
+                _ => e.to_string(),
+            };
+            let event =
+                AuditEvent::new(&*room, EventKind::AuthFailed, &*email).with_detail(&*reason);
+            state.audit.record(event).await;
+            let mut actor = WsRejectedSession::unauthorized(reason);
+            if let Some(packet) = e.to_packet() {
+                actor = actor.with_packet(packet);
+            }
+            return start_with_codec(actor, &req, stream, codec);
+        }
+    };
+    let chat = state.chat.clone();
+    let lobby = admission
+        .settings
+        .waiting_room
+        .then(|| Lobby::new(state.bus.clone(), room.clone()));
+    let recorder = admission
+        .settings
+        .recording_allowed
+        .then(|| state.recorder.clone());
+    let actor = WsChatSession::new(
+        chat,
+        room,
+        email,
+        admission,
+        state.meetings.clone(),
+        lobby,
+        recorder,
+        &state.chat_history,
+        &state.audit,
+    )
+    .with_resume_token(params.resume);
     start_with_codec(actor, &req, stream, codec)
 }
 
@@ -171,36 +281,143 @@ async fn main() -> std::io::Result<()> {
         .with_span_events(tracing_subscriber::fmt::format::FmtSpan::FULL)
         .with_writer(std::io::stderr)
         .init();
+    let db_enabled: bool = truthy(Some(
+        &std::env::var("DATABASE_ENABLED").unwrap_or_else(|_| String::from("false")),
+    ));
+    if std::env::args().nth(1).as_deref() == Some("migrate") {
+        return run_migrations(&get_pool()).await;
+    }
     info!("start");
-    let chat = ChatServer::new().await.start();
+    let pool = if db_enabled {
+        let pool = get_pool();
+        run_migrations(&pool).await?;
+        Some(pool)
+    } else {
+        None
+    };
+    let bus = bus::connect_from_env()
+        .await
+        .expect("failed to connect to the room bus");
+    let audit = AuditLog::from_env(pool.clone());
+    let webhooks = pool
+        .clone()
+        .map(|pool| Webhooks::new(pool).with_allowed_hosts(AllowedHosts::from_env()));
+    if let Some(webhooks) = &webhooks {
+        actix_rt::spawn(webhooks.clone().dispatch());
+    }
+    let presence = pool.clone().map(Presence::new);
+    let chat = ChatServer::new(bus.clone())
+        .with_capacity(Capacity::from_env())
+        .with_audit(audit.clone())
+        .with_webhooks(webhooks.clone())
+        .with_presence(presence.clone())
+        .with_resumption(Resumption::from_env())
+        .start();
+    let connect_auth = match &pool {
+        Some(pool) => ConnectAuth::from_env().with_sessions(pool.clone()),
+        None => ConnectAuth::from_env(),
+    };
+    let admin_key = AdminKey::from_env();
+    let require_registered = require_registered_meeting();
+    if require_registered && pool.is_none() {
+        panic!("REQUIRE_REGISTERED_MEETING needs DATABASE_ENABLED");
+    }
+    let invite_key = InviteKey::from_env();
+    let chat_history = ChatHistory::from_env(pool.clone());
+    let recorder = Recorder::from_env(bus.clone())
+        .with_audit(audit.clone())
+        .with_webhooks(webhooks);
+    let meetings = pool.clone().map(|pool| {
+        MeetingDirectory::new(pool, require_registered).with_invite_key(invite_key.clone())
+    });
     let oauth_client_id: String =
         std::env::var("OAUTH_CLIENT_ID").unwrap_or_else(|_| String::from(""));
-    let oauth_auth_url: String =
-        std::env::var("OAUTH_AUTH_URL").unwrap_or_else(|_| String::from(""));
-    let oauth_token_url: String =
-        std::env::var("OAUTH_TOKEN_URL").unwrap_or_else(|_| String::from(""));
+    let oauth_issuer: String =
+        std::env::var("OAUTH_ISSUER").unwrap_or_else(|_| String::from(GOOGLE_ISSUER));
     let oauth_secret: String =
         std::env::var("OAUTH_CLIENT_SECRET").unwrap_or_else(|_| String::from(""));
     let oauth_redirect_url: String =
         std::env::var("OAUTH_REDIRECT_URL").unwrap_or_else(|_| String::from(""));
     let after_login_url: String = std::env::var("UI_ENDPOINT").unwrap_or_else(|_| String::from(""));
-    let db_enabled: bool = truthy(Some(
-        &std::env::var("DATABASE_ENABLED").unwrap_or_else(|_| String::from("false")),
-    ));
+    match connect_auth {
+        ConnectAuth::Tokens(_) => {}
+        // Logins would be pointless if anyone could connect as anyone.
+        _ if !oauth_client_id.is_empty() => panic!("OAUTH_CLIENT_ID needs JWT_SECRET"),
+        ConnectAuth::Insecure => {
+            warn!("INSECURE_NO_AUTH is set, websocket connections are not authenticated")
+        }
+        ConnectAuth::Refused => {
+            warn!("JWT_SECRET is not set, every websocket connection will be refused")
+        }
+    }
+    let oidc = if oauth_client_id.is_empty() {
+        None
+    } else {
+        let provider = OidcProvider::discover(&oauth_issuer, &oauth_client_id)
+            .await
+            .expect("failed to discover the OpenID provider");
+        Some(web::Data::new(provider))
+    };
+    let session_settings = web::Data::new(SessionSettings::from_env());
+    let oauth_state_ttl = std::time::Duration::from_secs(
+        std::env::var("OAUTH_STATE_TTL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_OAUTH_STATE_TTL_SECS),
+    );
+    if let (Some(pool), Some(_)) = (&pool, &oidc) {
+        actix_rt::spawn(sweep_oauth_requests_periodically(
+            pool.clone(),
+            oauth_state_ttl,
+        ));
+    }
+    // Endpoints come from discovery unless explicitly overridden.
+    let oauth_auth_url: String = std::env::var("OAUTH_AUTH_URL").unwrap_or_else(|_| {
+        oidc.as_ref()
+            .map(|oidc| oidc.metadata().authorization_endpoint.clone())
+            .unwrap_or_default()
+    });
+    let oauth_token_url: String = std::env::var("OAUTH_TOKEN_URL").unwrap_or_else(|_| {
+        oidc.as_ref()
+            .map(|oidc| oidc.metadata().token_endpoint.clone())
+            .unwrap_or_default()
+    });
 
     HttpServer::new(move || {
         let cors = Cors::permissive();
 
-        if oauth_client_id.is_empty() {
-            App::new()
+        match oidc.clone() {
+            None => App::new()
                 .wrap(cors)
-                .app_data(web::Data::new(AppState { chat: chat.clone() }))
+                .app_data(web::Data::new(AppState {
+                    chat: chat.clone(),
+                    bus: bus.clone(),
+                    connect_auth: connect_auth.clone(),
+                    meetings: meetings.clone(),
+                    chat_history: chat_history.clone(),
+                    recorder: recorder.clone(),
+                    audit: audit.clone(),
+                    admin_key: admin_key.clone(),
+                    presence: presence.clone(),
+                }))
                 .service(ws_connect)
-        } else {
-            let pool = if db_enabled { Some(get_pool()) } else { None };
-            App::new()
-                .app_data(web::Data::new(pool))
-                .app_data(web::Data::new(AppState { chat: chat.clone() }))
+                .configure(api::admin::configure),
+            Some(oidc) => {
+                let mut app = App::new();
+                if let Some(pool) = &pool {
+                    app = app.app_data(web::Data::new(pool.clone()));
+                }
+                app.app_data(web::Data::new(AppState {
+                    chat: chat.clone(),
+                    bus: bus.clone(),
+                    connect_auth: connect_auth.clone(),
+                    meetings: meetings.clone(),
+                    chat_history: chat_history.clone(),
+                    recorder: recorder.clone(),
+                    audit: audit.clone(),
+                    admin_key: admin_key.clone(),
+                    presence: presence.clone(),
+                }))
                 .app_data(web::Data::new(AppConfig {
                     oauth_client_id: oauth_client_id.clone(),
                     oauth_auth_url: oauth_auth_url.clone(),
@@ -208,11 +425,36 @@ async fn main() -> std::io::Result<()> {
                     oauth_secret: oauth_secret.clone(),
                     oauth_redirect_url: oauth_redirect_url.clone(),
                     after_login_url: after_login_url.clone(),
+                    oauth_state_ttl,
                 }))
+                .app_data(oidc)
+                .app_data(session_settings.clone())
+                .app_data(web::Data::new(invite_key.clone()))
                 .wrap(cors)
                 .service(handle_google_oauth_callback)
                 .service(login)
+                .service(api::session::current)
+                .service(api::session::refresh)
+                .service(api::session::logout)
+                .service(api::meetings::create)
+                .service(api::meetings::list)
+                .service(api::meetings::get)
+                .service(api::meetings::invite)
+                .service(api::meetings::start_recording)
+                .service(api::meetings::stop_recording)
+                .service(api::meetings::update)
+                .service(api::meetings::remove)
                 .service(ws_connect)
+                .service(api::rooms::participants)
+                .service(api::rooms::export_messages)
+                .service(api::rooms::list_messages)
+                .service(api::rooms::list_events)
+                .service(api::webhooks::create)
+                .service(api::webhooks::list)
+                .service(api::webhooks::remove)
+                .service(api::webhooks::deliveries)
+                .configure(api::admin::configure)
+            }
         }
     })
     .bind((
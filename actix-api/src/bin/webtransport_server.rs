@@ -2,9 +2,20 @@ use std::net::ToSocketAddrs;
 
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 use dotenv::dotenv;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use types::truthy;
 
-use sec_api::webtransport::{self, Certs};
+use sec_api::{
+    audit::AuditLog,
+    auth::token::ConnectAuth,
+    bus,
+    chat::ChatHistory,
+    db::get_pool,
+    meetings::{require_registered_meeting, InviteKey, MeetingDirectory},
+    rooms::{capacity::Capacity, presence::Presence},
+    webhooks::{AllowedHosts, Webhooks},
+    webtransport::{self, Certs},
+};
 
 async fn health_responder() -> impl Responder {
     HttpResponse::Ok().body("Ok")
@@ -27,7 +38,7 @@ async fn main() {
         .next()
         .expect("expected HEALTH_LISTEN_URL to be a valid socket address");
 
-    let opt = webtransport::WebTransportOpt {
+    let mut opt = webtransport::WebTransportOpt {
         listen: std::env::var("LISTEN_URL")
             .expect("expected LISTEN_URL to be set")
             .to_socket_addrs()
@@ -42,7 +53,49 @@ async fn main() {
                 .expect("expected CERT_PATH to be set")
                 .into(),
         },
+        connect_auth: ConnectAuth::from_env(),
+        meetings: None,
+        capacity: Capacity::from_env(),
+        chat_history: ChatHistory::from_env(None),
+        audit: AuditLog::from_env(None),
+        webhooks: None,
+        presence: None,
     };
+    let db_enabled = truthy(std::env::var("DATABASE_ENABLED").ok().as_deref());
+    let require_registered = require_registered_meeting();
+    if require_registered && !db_enabled {
+        panic!("REQUIRE_REGISTERED_MEETING needs DATABASE_ENABLED");
+    }
+    if db_enabled {
+        let pool = get_pool();
+        opt.meetings = Some(
+            MeetingDirectory::new(pool.clone(), require_registered)
+                .with_invite_key(InviteKey::from_env()),
+        );
+        opt.connect_auth = opt.connect_auth.with_sessions(pool.clone());
+        opt.chat_history = ChatHistory::from_env(Some(pool.clone()));
+        opt.audit = AuditLog::from_env(Some(pool.clone()));
+        opt.presence = Some(Presence::new(pool.clone()));
+        let webhooks = Webhooks::new(pool).with_allowed_hosts(AllowedHosts::from_env());
+        actix_rt::spawn(webhooks.clone().dispatch());
+        opt.webhooks = Some(webhooks);
+    }
+    match opt.connect_auth {
+        ConnectAuth::Tokens(_) if !db_enabled => {
+            warn!("DATABASE_ENABLED is not set, connect tokens stay valid after logging out")
+        }
+        ConnectAuth::Tokens(_) => {}
+        ConnectAuth::Insecure => {
+            warn!("INSECURE_NO_AUTH is set, webtransport connections are not authenticated")
+        }
+        ConnectAuth::Refused => {
+            warn!("JWT_SECRET is not set, every webtransport connection will be refused")
+        }
+    }
+
+    let bus = bus::connect_from_env()
+        .await
+        .expect("failed to connect to the room bus");
 
     let listen = opt.listen;
     actix_rt::spawn(async move {
@@ -57,7 +110,7 @@ async fn main() {
     });
 
     let _ = actix_rt::spawn(async move {
-        webtransport::start(opt).await.unwrap();
+        webtransport::start(opt, bus).await.unwrap();
     })
     .await;
 }
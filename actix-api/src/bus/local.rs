@@ -0,0 +1,84 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use super::{BusMessage, BusSubscription, RoomBus};
+
+/// How many messages a slow subscriber may fall behind before it starts dropping them.
+const LOCAL_BUS_CAPACITY: usize = 4096;
+
+/// In-process [`RoomBus`] built on a tokio broadcast channel.
+///
+/// Every subscription sees every message and keeps the ones matching its subject, so queue groups
+/// are not load balanced: each subscriber receives its own copy.  The servers use one queue per
+/// session, so this makes no difference to them.  Subscribers that lag behind lose messages, the
+/// same way NATS drops messages for slow consumers.
+#[derive(Clone, Debug)]
+pub struct LocalBus {
+    sender: broadcast::Sender<BusMessage>,
+}
+
+impl LocalBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(LOCAL_BUS_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl Default for LocalBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RoomBus for LocalBus {
+    fn publish(&self, subject: String, payload: Bytes) -> BoxFuture<'static, Result<()>> {
+        // Publishing with no subscribers is not an error, NATS drops the message as well.
+        let _ = self.sender.send(BusMessage { subject, payload });
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    fn queue_subscribe(
+        &self,
+        subject: String,
+        _queue: String,
+    ) -> BoxFuture<'static, Result<BusSubscription>> {
+        let receiver = self.sender.subscribe();
+        let sub = futures::stream::unfold(receiver, move |mut receiver| {
+            let subject = subject.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(msg) if subject_matches(&subject, &msg.subject) => {
+                            return Some((msg, receiver))
+                        }
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("subscriber to {} skipped {} messages", subject, skipped);
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        futures::future::ready(Ok(sub.boxed())).boxed()
+    }
+}
+
+/// NATS style subject matching: `*` matches a single token and `>` matches one or more trailing
+/// tokens.
+fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut subject_tokens = subject.split('.');
+    for pattern_token in pattern.split('.') {
+        match (pattern_token, subject_tokens.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(_)) => continue,
+            (token, Some(subject_token)) if token == subject_token => continue,
+            _ => return false,
+        }
+    }
+    subject_tokens.next().is_none()
+}
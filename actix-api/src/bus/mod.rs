@@ -0,0 +1,121 @@
+//! Message bus used to fan packets out to every session of a room.
+//!
+//! Subjects follow the NATS convention used throughout the servers: a session publishes on
+//! `room.{room}.{session}` and every session of the room queue-subscribes to `room.{room}.*`.
+//! [`NatsBus`] talks to a NATS server and is what multi-node deployments should use, while
+//! [`LocalBus`] keeps everything in-process so a single node (or a test) can run without NATS.
+mod local;
+mod nats;
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use tracing::info;
+
+pub use local::LocalBus;
+pub use nats::NatsBus;
+
+/// A message delivered by a [`RoomBus`] subscription.
+#[derive(Clone, Debug)]
+pub struct BusMessage {
+    pub subject: String,
+    pub payload: Bytes,
+}
+
+pub type BusSubscription = BoxStream<'static, BusMessage>;
+
+pub trait RoomBus: Send + Sync {
+    /// Publishes `payload` on `subject`.
+    fn publish(&self, subject: String, payload: Bytes) -> BoxFuture<'static, Result<()>>;
+
+    /// Subscribes to `subject`, which may contain `*` and `>` wildcards.  Subscribers sharing a
+    /// `queue` name split the messages between them instead of each receiving a copy.
+    fn queue_subscribe(
+        &self,
+        subject: String,
+        queue: String,
+    ) -> BoxFuture<'static, Result<BusSubscription>>;
+
+    /// Publishes `payload` into `room` on behalf of `session`.
+    fn publish_to_room(
+        &self,
+        room: &str,
+        session: &str,
+        payload: Bytes,
+    ) -> BoxFuture<'static, Result<()>> {
+        self.publish(session_subject(room, session), payload)
+    }
+
+    /// Subscribes to everything published into `room`.
+    fn subscribe_to_room(
+        &self,
+        room: &str,
+        queue: &str,
+    ) -> BoxFuture<'static, Result<BusSubscription>> {
+        self.queue_subscribe(room_subject(room), queue.replace(' ', "_"))
+    }
+}
+
+/// Longest room id [validate_room_id] accepts.
+pub const MAX_ROOM_ID_LEN: usize = 64;
+
+/// Checks that `room` is 1 to [MAX_ROOM_ID_LEN] ASCII letters, digits, `_` or `-`, so it can't
+/// smuggle `*`, `>` or extra `.` tokens into the subjects below. Every transport checks it before
+/// subscribing.
+pub fn validate_room_id(room: &str) -> Result<()> {
+    let valid = !room.is_empty()
+        && room.len() <= MAX_ROOM_ID_LEN
+        && room
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!("invalid room id {:?}", room))
+    }
+}
+
+/// Subject matching every message published into `room`.
+pub fn room_subject(room: &str) -> String {
+    format!("room.{}.*", room).replace(' ', "_")
+}
+
+/// Subject on which `session` publishes into `room`.
+pub fn session_subject(room: &str, session: &str) -> String {
+    format!("room.{}.{}", room, session).replace(' ', "_")
+}
+
+/// Subject of the `LOBBY` packets of `room`'s waiting room, see [crate::lobby].
+pub fn lobby_subject(room: &str) -> String {
+    format!("lobby.{}", room).replace(' ', "_")
+}
+
+/// Builds the bus selected by the `ROOM_BUS` env var (`nats` or `local`).
+///
+/// When `ROOM_BUS` is unset the NATS bus is used if `NATS_URL` is defined, otherwise the
+/// in-process bus.
+pub async fn connect_from_env() -> Result<Arc<dyn RoomBus>> {
+    let nats_url = std::env::var("NATS_URL").ok();
+    let backend = std::env::var("ROOM_BUS").unwrap_or_else(|_| {
+        if nats_url.is_some() {
+            String::from("nats")
+        } else {
+            String::from("local")
+        }
+    });
+    match backend.to_lowercase().as_str() {
+        "nats" => {
+            let url = nats_url.ok_or_else(|| anyhow!("ROOM_BUS=nats requires NATS_URL"))?;
+            info!("using nats room bus at {}", url);
+            Ok(Arc::new(NatsBus::connect(&url).await?))
+        }
+        "local" => {
+            info!("using in-process room bus");
+            Ok(Arc::new(LocalBus::new()))
+        }
+        other => Err(anyhow!("unknown ROOM_BUS backend: {}", other)),
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+
+use super::{BusMessage, BusSubscription, RoomBus};
+
+/// [`RoomBus`] backed by a NATS server.
+#[derive(Clone, Debug)]
+pub struct NatsBus {
+    client: async_nats::Client,
+}
+
+impl NatsBus {
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = async_nats::ConnectOptions::new()
+            .require_tls(false)
+            .ping_interval(std::time::Duration::from_secs(10))
+            .connect(url)
+            .await?;
+        Ok(Self { client })
+    }
+}
+
+impl RoomBus for NatsBus {
+    fn publish(&self, subject: String, payload: Bytes) -> BoxFuture<'static, Result<()>> {
+        let client = self.client.clone();
+        async move {
+            client.publish(subject, payload).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn queue_subscribe(
+        &self,
+        subject: String,
+        queue: String,
+    ) -> BoxFuture<'static, Result<BusSubscription>> {
+        let client = self.client.clone();
+        async move {
+            let sub = client.queue_subscribe(subject, queue).await?;
+            let sub = sub.map(|msg| BusMessage {
+                subject: msg.subject.to_string(),
+                payload: msg.payload,
+            });
+            Ok(sub.boxed())
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result as Anysult;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+
+use super::{ChatMessage, ChatStore};
+use crate::actors::chat_session::{Email, RoomId};
+
+/// How many messages of each room the in-memory history keeps.
+const DEFAULT_MEMORY_CHAT_LEN: usize = 1000;
+
+/// How many rooms the in-memory history keeps.
+const DEFAULT_MEMORY_CHAT_ROOMS: usize = 1000;
+
+#[derive(Debug, Default)]
+struct Room {
+    history: VecDeque<ChatMessage>,
+    participants: HashSet<Email>,
+}
+
+#[derive(Debug, Default)]
+struct Rooms {
+    rooms: HashMap<RoomId, Room>,
+    /// Room ids, least recently written first.
+    order: VecDeque<RoomId>,
+}
+
+impl Rooms {
+    /// The room `id`, marked as the most recently written one. Forgets the least recently
+    /// written rooms beyond `max_rooms`.
+    fn touch(&mut self, id: &str, max_rooms: usize) -> &mut Room {
+        if let Some(position) = self.order.iter().position(|room| room == id) {
+            self.order.remove(position);
+        } else {
+            while self.order.len() >= max_rooms.max(1) {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.rooms.remove(&oldest);
+                }
+            }
+        }
+        self.order.push_back(id.to_string());
+        self.rooms.entry(id.to_string()).or_default()
+    }
+}
+
+/// In-process [ChatStore] that keeps the last messages of the most recently written rooms.
+#[derive(Clone, Debug)]
+pub struct MemoryChatStore {
+    rooms: Arc<Mutex<Rooms>>,
+    capacity: usize,
+    max_rooms: usize,
+}
+
+impl MemoryChatStore {
+    /// Keeps up to `capacity` messages per room, dropping the oldest ones.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            rooms: Arc::default(),
+            capacity,
+            max_rooms: DEFAULT_MEMORY_CHAT_ROOMS,
+        }
+    }
+
+    /// Keeps up to `max_rooms` rooms, forgetting the history and participants of the least
+    /// recently written ones.
+    pub fn with_max_rooms(mut self, max_rooms: usize) -> Self {
+        self.max_rooms = max_rooms;
+        self
+    }
+}
+
+impl Default for MemoryChatStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_CHAT_LEN)
+    }
+}
+
+impl ChatStore for MemoryChatStore {
+    fn append(&self, message: ChatMessage) -> BoxFuture<'static, Anysult<()>> {
+        let mut rooms = self.rooms.lock().unwrap();
+        let history = &mut rooms.touch(&message.room, self.max_rooms).history;
+        if history.len() >= self.capacity {
+            history.pop_front();
+        }
+        history.push_back(message);
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    fn recent(
+        &self,
+        room: &str,
+        before: Option<&str>,
+        limit: usize,
+    ) -> BoxFuture<'static, Anysult<Vec<ChatMessage>>> {
+        let rooms = self.rooms.lock().unwrap();
+        let messages = rooms.rooms.get(room).map_or_else(Vec::new, |room| {
+            let history = &room.history;
+            let end = match before {
+                Some(id) => history.iter().position(|m| m.id == id).unwrap_or(0),
+                None => history.len(),
+            };
+            let start = end.saturating_sub(limit);
+            history.range(start..end).cloned().collect()
+        });
+        futures::future::ready(Ok(messages)).boxed()
+    }
+
+    fn add_participant(&self, room: &str, email: &str) -> BoxFuture<'static, Anysult<()>> {
+        self.rooms
+            .lock()
+            .unwrap()
+            .touch(room, self.max_rooms)
+            .participants
+            .insert(email.to_string());
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    fn is_participant(&self, room: &str, email: &str) -> BoxFuture<'static, Anysult<bool>> {
+        let participant = self
+            .rooms
+            .lock()
+            .unwrap()
+            .rooms
+            .get(room)
+            .is_some_and(|room| room.participants.contains(email));
+        futures::future::ready(Ok(participant)).boxed()
+    }
+}
@@ -0,0 +1,227 @@
+//! In-meeting text chat.
+//!
+//! Clients send `CHAT` packets with only the text and what it replies to. The session rebuilds
+//! each one with a server assigned id, its participant as the sender and the current time,
+//! appends it to the room's history in a [ChatStore] and publishes it to the room. Sessions
+//! joining a room are recorded as its participants and first get its last
+//! [ChatHistory::replay_len] messages. Participants and the meeting owner can page through the
+//! whole history over REST, see [crate::api::rooms].
+//!
+//! [PostgresChatStore] keeps the history when the database is enabled. [MemoryChatStore] keeps
+//! it in the process for its most recently written rooms only, so on a multi-node deployment
+//! each node only knows its own sessions' messages.
+mod memory;
+mod postgres;
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result as Anysult};
+use bytes::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use futures::future::BoxFuture;
+use protobuf::Message;
+use serde::Serialize;
+use types::protos::chat_packet::ChatPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+pub use memory::MemoryChatStore;
+pub use postgres::PostgresChatStore;
+
+use crate::actors::chat_session::{Email, RoomId};
+use crate::db::PostgresPool;
+
+/// Longest chat message accepted, in characters.
+pub const MAX_CHAT_MESSAGE_LEN: usize = 4000;
+
+const DEFAULT_CHAT_REPLAY_LEN: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChatMessage {
+    pub id: String,
+    pub room: RoomId,
+    pub sender: Email,
+    pub text: String,
+    pub sent_at: DateTime<Utc>,
+    pub reply_to: Option<String>,
+}
+
+impl ChatMessage {
+    /// Builds the server generated `CHAT` packet carrying the message.
+    pub fn to_packet(&self) -> Bytes {
+        let chat = ChatPacket {
+            id: self.id.clone(),
+            sender: self.sender.clone(),
+            text: self.text.clone(),
+            timestamp_ms: self.sent_at.timestamp_millis(),
+            reply_to: self.reply_to.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+        let packet = PacketWrapper {
+            packet_type: PacketType::CHAT.into(),
+            email: self.sender.clone(),
+            data: chat.write_to_bytes().unwrap_or_default(),
+            ..Default::default()
+        };
+        Bytes::from(packet.write_to_bytes().unwrap_or_default())
+    }
+
+    /// Reads a `CHAT` packet of `room`, `None` for any other packet.
+    pub fn parse(room: &str, data: &[u8]) -> Option<Self> {
+        let packet = PacketWrapper::parse_from_bytes(data).ok()?;
+        if packet.packet_type.enum_value() != Ok(PacketType::CHAT) {
+            return None;
+        }
+        let chat = ChatPacket::parse_from_bytes(&packet.data).ok()?;
+        Some(ChatMessage {
+            id: chat.id,
+            room: room.to_string(),
+            sender: chat.sender,
+            text: chat.text,
+            sent_at: Utc.timestamp_millis_opt(chat.timestamp_ms).single()?,
+            reply_to: Some(chat.reply_to).filter(|id| !id.is_empty()),
+        })
+    }
+}
+
+/// Where the chat history of every room is kept.
+pub trait ChatStore: Send + Sync {
+    fn append(&self, message: ChatMessage) -> BoxFuture<'static, Anysult<()>>;
+
+    /// The last `limit` messages of `room` sent before the message with id `before`, or the
+    /// last ones when `before` is `None`, oldest first. Empty when `before` is not a message of
+    /// `room`.
+    fn recent(
+        &self,
+        room: &str,
+        before: Option<&str>,
+        limit: usize,
+    ) -> BoxFuture<'static, Anysult<Vec<ChatMessage>>>;
+
+    fn add_participant(&self, room: &str, email: &str) -> BoxFuture<'static, Anysult<()>>;
+
+    /// Whether `email` has ever joined `room`.
+    fn is_participant(&self, room: &str, email: &str) -> BoxFuture<'static, Anysult<bool>>;
+}
+
+/// Renders messages as a plain text transcript, one line per message with continuation lines
+/// indented.
+pub fn transcript(messages: &[ChatMessage]) -> String {
+    let mut transcript = String::new();
+    for message in messages {
+        let _ = write!(
+            transcript,
+            "[{}] {}:",
+            message.sent_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            message.sender
+        );
+        for (i, line) in message.text.lines().enumerate() {
+            let _ = writeln!(transcript, "{}{}", if i == 0 { " " } else { "    " }, line);
+        }
+    }
+    transcript
+}
+
+/// The chat history and how much of it late joiners get.
+#[derive(Clone)]
+pub struct ChatHistory {
+    store: Arc<dyn ChatStore>,
+    replay_len: usize,
+}
+
+impl std::fmt::Debug for ChatHistory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatHistory")
+            .field("replay_len", &self.replay_len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ChatHistory {
+    pub fn new(store: Arc<dyn ChatStore>, replay_len: usize) -> Self {
+        Self { store, replay_len }
+    }
+
+    /// Keeps the history in Postgres when there is a `pool`, otherwise in memory. Reads
+    /// `CHAT_REPLAY_LEN` (default 50).
+    pub fn from_env(pool: Option<PostgresPool>) -> Self {
+        let store: Arc<dyn ChatStore> = match pool {
+            Some(pool) => Arc::new(PostgresChatStore::new(pool)),
+            None => Arc::new(MemoryChatStore::default()),
+        };
+        let replay_len = std::env::var("CHAT_REPLAY_LEN")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_CHAT_REPLAY_LEN);
+        Self::new(store, replay_len)
+    }
+
+    pub fn replay_len(&self) -> usize {
+        self.replay_len
+    }
+
+    pub fn store(&self) -> &Arc<dyn ChatStore> {
+        &self.store
+    }
+
+    /// The chat of `email` in `room`.
+    pub fn chat(&self, room: impl Into<RoomId>, email: impl Into<Email>) -> Chat {
+        Chat {
+            history: self.clone(),
+            room: room.into(),
+            email: email.into(),
+        }
+    }
+}
+
+/// Chat of one session.
+#[derive(Clone, Debug)]
+pub struct Chat {
+    history: ChatHistory,
+    room: RoomId,
+    email: Email,
+}
+
+impl Chat {
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    /// Stores a `CHAT` packet the session's own client sent. Returns the packet to publish to
+    /// the room, which the client should get as well.
+    pub async fn post(&self, packet: &PacketWrapper) -> Anysult<Bytes> {
+        let chat = ChatPacket::parse_from_bytes(&packet.data)?;
+        if chat.text.trim().is_empty() {
+            return Err(anyhow!("empty chat message"));
+        }
+        if chat.text.chars().count() > MAX_CHAT_MESSAGE_LEN {
+            return Err(anyhow!(
+                "chat message longer than {} characters",
+                MAX_CHAT_MESSAGE_LEN
+            ));
+        }
+        let message = ChatMessage {
+            id: uuid::Uuid::new_v4().to_simple().to_string(),
+            room: self.room.clone(),
+            sender: self.email.clone(),
+            text: chat.text,
+            sent_at: Utc::now(),
+            reply_to: Some(chat.reply_to).filter(|id| !id.is_empty()),
+        };
+        let packet = message.to_packet();
+        self.history.store.append(message).await?;
+        Ok(packet)
+    }
+
+    /// Records the session as a participant of the room. Returns the `CHAT` packets replaying
+    /// the room's recent history to the client that just joined.
+    pub async fn join(&self) -> Anysult<Vec<Bytes>> {
+        let store = &self.history.store;
+        store.add_participant(&self.room, &self.email).await?;
+        let messages = store
+            .recent(&self.room, None, self.history.replay_len)
+            .await?;
+        Ok(messages.iter().map(ChatMessage::to_packet).collect())
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::Result as Anysult;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio_postgres::Row;
+
+use super::{ChatMessage, ChatStore};
+use crate::db::PostgresPool;
+
+/// [ChatStore] backed by the `chat_messages` table.
+#[derive(Clone, Debug)]
+pub struct PostgresChatStore {
+    pool: PostgresPool,
+}
+
+impl PostgresChatStore {
+    pub fn new(pool: PostgresPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl From<Row> for ChatMessage {
+    fn from(row: Row) -> Self {
+        ChatMessage {
+            id: row.get("id"),
+            room: row.get("room"),
+            sender: row.get("sender"),
+            text: row.get("text"),
+            sent_at: row.get("sent_at"),
+            reply_to: row.get("reply_to"),
+        }
+    }
+}
+
+impl ChatStore for PostgresChatStore {
+    fn append(&self, message: ChatMessage) -> BoxFuture<'static, Anysult<()>> {
+        let pool = self.pool.clone();
+        async move {
+            let connection = pool.get().await?;
+            connection
+                .execute(
+                    "INSERT INTO chat_messages (id, room, sender, text, sent_at, reply_to)
+                        VALUES ($1, $2, $3, $4, $5, $6)",
+                    &[
+                        &message.id,
+                        &message.room,
+                        &message.sender,
+                        &message.text,
+                        &message.sent_at,
+                        &message.reply_to,
+                    ],
+                )
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn recent(
+        &self,
+        room: &str,
+        before: Option<&str>,
+        limit: usize,
+    ) -> BoxFuture<'static, Anysult<Vec<ChatMessage>>> {
+        let pool = self.pool.clone();
+        let room = room.to_string();
+        let before = before.map(str::to_string);
+        async move {
+            let connection = pool.get().await?;
+            let rows = connection
+                .query(
+                    "SELECT * FROM (
+                        SELECT id, room, sender, text, sent_at, reply_to FROM chat_messages
+                            WHERE room=$1 AND ($3::TEXT IS NULL OR (sent_at, id) < (
+                                SELECT sent_at, id FROM chat_messages WHERE room=$1 AND id=$3
+                            ))
+                            ORDER BY sent_at DESC, id DESC LIMIT $2
+                    ) AS recent ORDER BY sent_at, id",
+                    &[&room, &(limit as i64), &before],
+                )
+                .await?;
+            Ok(rows.into_iter().map(ChatMessage::from).collect())
+        }
+        .boxed()
+    }
+
+    fn add_participant(&self, room: &str, email: &str) -> BoxFuture<'static, Anysult<()>> {
+        let pool = self.pool.clone();
+        let room = room.to_string();
+        let email = email.to_string();
+        async move {
+            let connection = pool.get().await?;
+            connection
+                .execute(
+                    "INSERT INTO room_participants (room, email) VALUES ($1, $2)
+                        ON CONFLICT DO NOTHING",
+                    &[&room, &email],
+                )
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn is_participant(&self, room: &str, email: &str) -> BoxFuture<'static, Anysult<bool>> {
+        let pool = self.pool.clone();
+        let room = room.to_string();
+        let email = email.to_string();
+        async move {
+            let connection = pool.get().await?;
+            let row = connection
+                .query_opt(
+                    "SELECT 1 FROM room_participants WHERE room=$1 AND email=$2",
+                    &[&room, &email],
+                )
+                .await?;
+            Ok(row.is_some())
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS users (
+    email TEXT PRIMARY KEY,
+    access_token TEXT,
+    refresh_token TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS oauth_requests (
+    csrf_state TEXT PRIMARY KEY,
+    pkce_challenge TEXT NOT NULL,
+    pkce_verifier TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Tables created before requests expired have no timestamp.
+ALTER TABLE oauth_requests ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+CREATE INDEX IF NOT EXISTS oauth_requests_created_at_idx ON oauth_requests (created_at);
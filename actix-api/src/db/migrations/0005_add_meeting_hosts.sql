@@ -0,0 +1,9 @@
+-- NULL means the owner is the host.
+ALTER TABLE meetings ADD COLUMN IF NOT EXISTS host_email TEXT;
+
+CREATE TABLE IF NOT EXISTS meeting_bans (
+    meeting_id TEXT NOT NULL REFERENCES meetings (id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (meeting_id, email)
+);
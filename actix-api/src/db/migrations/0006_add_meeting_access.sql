@@ -0,0 +1,19 @@
+-- Argon2 hash of the passcode as a PHC string, NULL when the meeting has none.
+ALTER TABLE meetings ADD COLUMN IF NOT EXISTS passcode_hash TEXT;
+
+CREATE TABLE IF NOT EXISTS meeting_invites (
+    id TEXT PRIMARY KEY,
+    meeting_id TEXT NOT NULL REFERENCES meetings (id) ON DELETE CASCADE,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    -- NULL means any number of people may use the invite.
+    max_uses INTEGER,
+    uses INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS meeting_invite_uses (
+    invite_id TEXT NOT NULL REFERENCES meeting_invites (id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    PRIMARY KEY (invite_id, email)
+);
@@ -0,0 +1,11 @@
+-- Rooms may be ad-hoc, so messages are not tied to a row in meetings.
+CREATE TABLE IF NOT EXISTS chat_messages (
+    id TEXT PRIMARY KEY,
+    room TEXT NOT NULL,
+    sender TEXT NOT NULL,
+    text TEXT NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    reply_to TEXT
+);
+
+CREATE INDEX IF NOT EXISTS chat_messages_room_sent_at ON chat_messages (room, sent_at, id);
@@ -0,0 +1,7 @@
+-- Everyone who has joined a room, which lets them read its chat history afterwards.
+CREATE TABLE IF NOT EXISTS room_participants (
+    room TEXT NOT NULL,
+    email TEXT NOT NULL,
+    first_joined_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (room, email)
+);
@@ -0,0 +1,13 @@
+-- Meeting events kept for compliance. Rooms may be ad-hoc, so events are not tied to a row in
+-- meetings, and nothing deletes them.
+CREATE TABLE IF NOT EXISTS audit_events (
+    id TEXT PRIMARY KEY,
+    room TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    target TEXT,
+    detail TEXT,
+    at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_room_at ON audit_events (room, at, id);
@@ -0,0 +1,54 @@
+-- HTTP endpoints told about what happens in the meetings of their owner.
+CREATE TABLE IF NOT EXISTS webhooks (
+    id TEXT PRIMARY KEY,
+    owner_email TEXT NOT NULL REFERENCES users (email) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    events TEXT[] NOT NULL,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS webhooks_owner_email_idx ON webhooks (owner_email);
+
+-- The delivery queue, which doubles as the delivery log. The body is kept as sent so every
+-- attempt is signed over the same bytes.
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id TEXT PRIMARY KEY,
+    webhook_id TEXT NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    body TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_status_code INT,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS webhook_deliveries_due ON webhook_deliveries (next_attempt_at)
+    WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS webhook_deliveries_webhook_created_at
+    ON webhook_deliveries (webhook_id, created_at);
+
+-- How many sessions are in each room across all nodes, to tell when a meeting starts and ends.
+-- Rooms may be ad-hoc, so they are not tied to a row in meetings.
+CREATE TABLE IF NOT EXISTS active_rooms (
+    room TEXT PRIMARY KEY,
+    participants INT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Sessions in each room per node, so the sessions of a node that stopped can be counted out.
+CREATE TABLE IF NOT EXISTS active_room_nodes (
+    room TEXT NOT NULL,
+    node TEXT NOT NULL,
+    participants INT NOT NULL,
+    PRIMARY KEY (room, node)
+);
+
+-- When each node last said it was alive.
+CREATE TABLE IF NOT EXISTS webhook_nodes (
+    node TEXT PRIMARY KEY,
+    seen_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
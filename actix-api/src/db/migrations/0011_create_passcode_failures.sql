@@ -0,0 +1,9 @@
+-- Wrong passcodes per meeting and client address, shared by every server. `peer` is empty when
+-- the address is unknown.
+CREATE TABLE IF NOT EXISTS passcode_failures (
+    meeting_id TEXT NOT NULL REFERENCES meetings (id) ON DELETE CASCADE,
+    peer TEXT NOT NULL,
+    first_failed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    failures INTEGER NOT NULL,
+    PRIMARY KEY (meeting_id, peer)
+);
@@ -0,0 +1,13 @@
+-- The sessions in each room over every server, websocket and WebTransport alike. Servers refresh
+-- `seen_at` for their sessions while they last, rows that weren't refreshed in a while belong to
+-- a server that stopped.
+CREATE TABLE IF NOT EXISTS room_presence (
+    session_id TEXT PRIMARY KEY,
+    room TEXT NOT NULL,
+    email TEXT NOT NULL,
+    transport TEXT NOT NULL,
+    joined_at BIGINT NOT NULL,
+    seen_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS room_presence_room ON room_presence (room);
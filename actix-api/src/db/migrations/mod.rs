@@ -0,0 +1,108 @@
+//! Versioned schema migrations compiled into the binary.
+//!
+//! Applied versions are recorded in `schema_version`. Each migration runs in its own transaction
+//! and a Postgres advisory lock keeps several servers starting at once from racing each other.
+use anyhow::{Context, Result as Anysult};
+use tokio_postgres::Client;
+use tracing::info;
+
+/// Arbitrary key for `pg_advisory_lock`, shared by every server running migrations.
+const MIGRATION_LOCK_KEY: i64 = 0x0073_6563_2d61_7069;
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+macro_rules! migration {
+    ($version:expr, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!($name, ".sql")),
+        }
+    };
+}
+
+/// All migrations, in the order they are applied. Never edit or reorder a released entry, add a
+/// new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_create_users"),
+    migration!(2, "0002_create_oauth_requests"),
+    migration!(3, "0003_create_sessions"),
+    migration!(4, "0004_create_meetings"),
+    migration!(5, "0005_add_meeting_hosts"),
+    migration!(6, "0006_add_meeting_access"),
+    migration!(7, "0007_create_chat_messages"),
+    migration!(8, "0008_create_room_participants"),
+    migration!(9, "0009_create_audit_events"),
+    migration!(10, "0010_create_webhooks"),
+    migration!(11, "0011_create_passcode_failures"),
+    migration!(12, "0012_create_room_presence"),
+];
+
+/// Applies every migration newer than the recorded schema version and returns the versions
+/// that were applied.
+pub async fn run_migrations(client: &mut Client) -> Anysult<Vec<i64>> {
+    client
+        .batch_execute(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                version BIGINT PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )",
+        )
+        .await?;
+    // Neither waiting for another server to finish nor a long migration may run into the
+    // statement timeout of the pool, which is put back afterwards.
+    let statement_timeout: String = client
+        .query_one("SHOW statement_timeout", &[])
+        .await?
+        .get(0);
+    client.batch_execute("SET statement_timeout = 0").await?;
+    let result = apply_locked(client).await;
+    client
+        .execute(
+            "SELECT set_config('statement_timeout', $1, false)",
+            &[&statement_timeout],
+        )
+        .await?;
+    result
+}
+
+async fn apply_locked(client: &mut Client) -> Anysult<Vec<i64>> {
+    client
+        .execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK_KEY])
+        .await?;
+    let result = apply_pending(client).await;
+    client
+        .execute("SELECT pg_advisory_unlock($1)", &[&MIGRATION_LOCK_KEY])
+        .await?;
+    result
+}
+
+async fn apply_pending(client: &mut Client) -> Anysult<Vec<i64>> {
+    let current: i64 = client
+        .query_one("SELECT COALESCE(MAX(version), 0) FROM schema_version", &[])
+        .await?
+        .get(0);
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        info!("applying migration {}", migration.name);
+        let transaction = client.transaction().await?;
+        transaction
+            .batch_execute(migration.sql)
+            .await
+            .with_context(|| format!("migration {} failed", migration.name))?;
+        transaction
+            .execute(
+                "INSERT INTO schema_version (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )
+            .await?;
+        transaction.commit().await?;
+        applied.push(migration.version);
+    }
+    Ok(applied)
+}
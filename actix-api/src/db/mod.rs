@@ -1,25 +1,100 @@
-use postgres::NoTls;
-use r2d2::Pool;
-use r2d2::PooledConnection;
-use r2d2_postgres::{postgres, PostgresConnectionManager};
+use anyhow::Context;
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
 use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_postgres::NoTls;
 
-pub type PostgresPool = Pool<PostgresConnectionManager<NoTls>>;
-pub type PostgresConnection = PooledConnection<PostgresConnectionManager<NoTls>>;
+pub mod migrations;
+
+pub type PostgresPool = Pool;
+pub type PostgresConnection = deadpool_postgres::Object;
 
 pub fn get_database_url() -> String {
     env::var("DATABASE_URL").unwrap()
 }
 
+/// Connection pool settings, read from the environment by [DbConfig::from_env].
+#[derive(Debug, Clone)]
+pub struct DbConfig {
+    pub url: String,
+    /// `DATABASE_POOL_SIZE`, defaults to 16.
+    pub max_size: usize,
+    /// `DATABASE_POOL_TIMEOUT_SECS`: how long a handler waits for a free connection.
+    pub wait_timeout: Duration,
+    /// `DATABASE_STATEMENT_TIMEOUT_MS`: server side `statement_timeout` for every connection.
+    pub statement_timeout: Duration,
+    /// `DATABASE_TLS=true`: connect with rustls using the platform's root certificates.
+    pub tls: bool,
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+impl DbConfig {
+    pub fn from_env() -> Self {
+        Self {
+            url: get_database_url(),
+            max_size: env_or("DATABASE_POOL_SIZE", 16),
+            wait_timeout: Duration::from_secs(env_or("DATABASE_POOL_TIMEOUT_SECS", 5)),
+            statement_timeout: Duration::from_millis(env_or(
+                "DATABASE_STATEMENT_TIMEOUT_MS",
+                5_000,
+            )),
+            tls: types::truthy(env::var("DATABASE_TLS").ok().as_deref()),
+        }
+    }
+}
+
+fn rustls_connector() -> anyhow::Result<tokio_postgres_rustls::MakeRustlsConnect> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs()? {
+        roots.add(cert)?;
+    }
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    Ok(tokio_postgres_rustls::MakeRustlsConnect::new(config))
+}
+
+pub fn create_pool(config: &DbConfig) -> anyhow::Result<PostgresPool> {
+    let mut pg_config: tokio_postgres::Config = config
+        .url
+        .parse()
+        .context("Database url is in a bad format.")?;
+    pg_config.options(format!(
+        "-c statement_timeout={}",
+        config.statement_timeout.as_millis()
+    ));
+    let manager_config = ManagerConfig {
+        recycling_method: RecyclingMethod::Fast,
+    };
+    let manager = if config.tls {
+        Manager::from_config(pg_config, rustls_connector()?, manager_config)
+    } else {
+        Manager::from_config(pg_config, NoTls, manager_config)
+    };
+    Ok(Pool::builder(manager)
+        .max_size(config.max_size)
+        .wait_timeout(Some(config.wait_timeout))
+        .create_timeout(Some(config.wait_timeout))
+        .runtime(Runtime::Tokio1)
+        .build()?)
+}
+
 pub fn get_pool() -> PostgresPool {
-    let manager = PostgresConnectionManager::new(
-        get_database_url()
-            .parse()
-            .expect("Database url is in a bad format."),
-        NoTls,
-    );
-    Pool::builder()
-        .max_size(5)
-        .build(manager)
-        .expect("Failed to build a database connection pool")
+    create_pool(&DbConfig::from_env()).expect("Failed to build a database connection pool")
+}
+
+/// Applies pending migrations using a connection from `pool`.
+pub async fn migrate(pool: &PostgresPool) -> anyhow::Result<Vec<i64>> {
+    let mut client = pool.get().await?;
+    migrations::run_migrations(&mut client).await
 }
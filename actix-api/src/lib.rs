@@ -1,7 +1,19 @@
 pub mod actors;
+pub mod api;
+pub mod audit;
 pub mod auth;
+pub mod bus;
+pub mod chat;
 pub mod constants;
 pub mod db;
+pub mod lobby;
+pub mod meetings;
 pub mod messages;
 pub mod models;
+pub mod moderation;
+pub mod recording;
+pub mod resumption;
+pub mod rooms;
+pub mod sender;
+pub mod webhooks;
 pub mod webtransport;
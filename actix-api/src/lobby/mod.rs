@@ -0,0 +1,166 @@
+//! Waiting room: in meetings with `waiting_room` on, everyone but the host waits in the lobby until
+//! the host admits or denies them.
+//!
+//! A waiting session is not subscribed to its room. It subscribes to [lobby_subject] instead, on
+//! which the servers publish `LOBBY` packets: waiting participants announce themselves there every
+//! [ANNOUNCE_INTERVAL], so a host who joins later still learns about them, and the host's decisions
+//! come back the same way. Sessions in the room watch the subject too and pass it on to their
+//! client while they are the host.
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result as Anysult};
+use bytes::Bytes;
+use futures::StreamExt;
+use protobuf::Message;
+use tracing::error;
+use types::protos::lobby_packet::lobby_packet::Status;
+use types::protos::lobby_packet::LobbyPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+use crate::actors::chat_session::{Email, RoomId};
+use crate::bus::{lobby_subject, BusSubscription, RoomBus};
+
+/// How often a waiting participant is announced to the room's host.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What the host decided about a waiting participant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Admitted,
+    Denied,
+}
+
+impl Decision {
+    pub fn status(self) -> Status {
+        match self {
+            Decision::Admitted => Status::ADMITTED,
+            Decision::Denied => Status::DENIED,
+        }
+    }
+}
+
+/// The lobby of one room.
+#[derive(Clone)]
+pub struct Lobby {
+    bus: Arc<dyn RoomBus>,
+    room: RoomId,
+}
+
+impl std::fmt::Debug for Lobby {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lobby").field("room", &self.room).finish()
+    }
+}
+
+impl Lobby {
+    pub fn new(bus: Arc<dyn RoomBus>, room: impl Into<RoomId>) -> Self {
+        Lobby {
+            bus,
+            room: room.into(),
+        }
+    }
+
+    pub fn room(&self) -> &str {
+        &self.room
+    }
+
+    /// Subscribes to the lobby's `LOBBY` packets. `queue` has to be unique to the session.
+    pub async fn watch(&self, queue: &str) -> Anysult<BusSubscription> {
+        self.bus
+            .queue_subscribe(lobby_subject(&self.room), queue.replace(' ', "_"))
+            .await
+    }
+
+    /// Publishes `status` of `email` to the lobby.
+    pub async fn announce(&self, status: Status, email: &str) -> Anysult<()> {
+        self.bus
+            .publish(lobby_subject(&self.room), lobby_packet(status, email))
+            .await
+    }
+
+    /// Waits until the host decides about `email`, announcing the participant every
+    /// [ANNOUNCE_INTERVAL]. When dropped before the host decided, announces that the participant
+    /// left the lobby.
+    pub async fn wait(&self, email: &str, queue: &str) -> Anysult<Decision> {
+        let mut sub = self.watch(queue).await?;
+        let mut waiting = Waiting {
+            lobby: self,
+            email,
+            decided: false,
+        };
+        let mut announce = tokio::time::interval(ANNOUNCE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = announce.tick() => self.announce(Status::WAITING, email).await?,
+                msg = sub.next() => {
+                    let msg = msg.ok_or_else(|| anyhow!("lobby of {} closed", self.room))?;
+                    let decision = match parse(&msg.payload) {
+                        Some((Status::ADMITTED, who)) if who == email => Decision::Admitted,
+                        Some((Status::DENIED, who)) if who == email => Decision::Denied,
+                        _ => continue,
+                    };
+                    waiting.decided = true;
+                    return Ok(decision);
+                }
+            }
+        }
+    }
+}
+
+/// Announces that a participant left the lobby unless the host decided about them.
+struct Waiting<'a> {
+    lobby: &'a Lobby,
+    email: &'a str,
+    decided: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if self.decided {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let room = self.lobby.room.clone();
+        let left = self
+            .lobby
+            .bus
+            .publish(lobby_subject(&room), lobby_packet(Status::LEFT, self.email));
+        runtime.spawn(async move {
+            if let Err(e) = left.await {
+                error!(
+                    "error announcing a departure from the lobby of {}: {}",
+                    room, e
+                );
+            }
+        });
+    }
+}
+
+/// Builds the server generated `LOBBY` packet with the `status` of `email`.
+pub fn lobby_packet(status: Status, email: &str) -> Bytes {
+    let lobby = LobbyPacket {
+        status: status.into(),
+        ..Default::default()
+    };
+    let packet = PacketWrapper {
+        packet_type: PacketType::LOBBY.into(),
+        email: email.to_string(),
+        data: lobby.write_to_bytes().unwrap_or_default(),
+        ..Default::default()
+    };
+    Bytes::from(packet.write_to_bytes().unwrap_or_default())
+}
+
+/// Reads the status and participant of a `LOBBY` packet, `None` for any other packet.
+pub fn parse(data: &[u8]) -> Option<(Status, Email)> {
+    let packet = PacketWrapper::parse_from_bytes(data).ok()?;
+    if packet.packet_type.enum_value() != Ok(PacketType::LOBBY) {
+        return None;
+    }
+    let lobby = LobbyPacket::parse_from_bytes(&packet.data).ok()?;
+    Some((lobby.status.enum_value().ok()?, packet.email))
+}
@@ -0,0 +1,373 @@
+//! Passcodes and invite links.
+//!
+//! A meeting may have a passcode, stored as an Argon2id hash so the database never holds it and
+//! short passcodes are slow to guess offline. Online guesses are limited by [PasscodeAttempts]. An
+//! invite is a signed token naming a row in `meeting_invites`; the token carries its expiry, the
+//! row counts who used it so `max_uses` limits people rather than connections. Anyone holding a
+//! valid invite gets in without the passcode, while an invalid one is refused even when the
+//! meeting has no passcode.
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result as Anysult};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, TimeZone, Utc};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::db::PostgresPool;
+
+const MIN_PASSCODE_LEN: usize = 4;
+const MAX_PASSCODE_LEN: usize = 64;
+
+/// Wrong passcodes someone may try for a room within [PASSCODE_LOCKOUT].
+pub const MAX_PASSCODE_FAILURES: u32 = 5;
+/// How long further guesses are refused once [MAX_PASSCODE_FAILURES] is reached, counted from the
+/// first wrong passcode.
+pub const PASSCODE_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+const DEFAULT_INVITE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_INVITE_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn validate_passcode(passcode: &str) -> Anysult<()> {
+    let len = passcode.chars().count();
+    if !(MIN_PASSCODE_LEN..=MAX_PASSCODE_LEN).contains(&len) {
+        return Err(anyhow!(
+            "passcode must be {} to {} characters",
+            MIN_PASSCODE_LEN,
+            MAX_PASSCODE_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Hashes `passcode` with a fresh salt into an Argon2id PHC string.
+pub fn hash_passcode(passcode: &str) -> Anysult<String> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let hash = Argon2::default()
+        .hash_password(passcode.as_bytes(), &salt)
+        .map_err(|e| anyhow!("failed to hash passcode: {}", e))?;
+    Ok(hash.to_string())
+}
+
+/// Checks `passcode` against a hash made by [hash_passcode].
+pub fn verify_passcode(hash: &str, passcode: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|parsed| {
+        Argon2::default()
+            .verify_password(passcode.as_bytes(), &parsed)
+            .is_ok()
+    })
+}
+
+/// Wrong passcodes per meeting and client address, so each address gets [MAX_PASSCODE_FAILURES]
+/// guesses every [PASSCODE_LOCKOUT] whatever email it claims. Kept in `passcode_failures`, so the
+/// limit holds across servers. Behind a reverse proxy every client shares the proxy's address.
+#[derive(Clone)]
+pub struct PasscodeAttempts {
+    pool: PostgresPool,
+}
+
+impl std::fmt::Debug for PasscodeAttempts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PasscodeAttempts").finish_non_exhaustive()
+    }
+}
+
+/// Key of `peer` in `passcode_failures`, unknown addresses share the empty one.
+fn peer_key(peer: Option<IpAddr>) -> String {
+    peer.map(|ip| ip.to_string()).unwrap_or_default()
+}
+
+impl PasscodeAttempts {
+    pub fn new(pool: PostgresPool) -> Self {
+        PasscodeAttempts { pool }
+    }
+
+    /// Whether `peer` used up its guesses for `room`.
+    pub async fn locked_out(&self, room: &str, peer: Option<IpAddr>) -> Anysult<bool> {
+        let connection = self.pool.get().await?;
+        let row = connection
+            .query_opt(
+                "SELECT 1 FROM passcode_failures
+                    WHERE meeting_id=$1 AND peer=$2 AND failures >= $3
+                    AND first_failed_at > now() - make_interval(secs => $4)",
+                &[
+                    &room,
+                    &peer_key(peer),
+                    &(MAX_PASSCODE_FAILURES as i32),
+                    &PASSCODE_LOCKOUT.as_secs_f64(),
+                ],
+            )
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Counts a wrong passcode, starting over once the previous ones are older than
+    /// [PASSCODE_LOCKOUT].
+    pub async fn failed(&self, room: &str, peer: Option<IpAddr>) -> Anysult<()> {
+        let connection = self.pool.get().await?;
+        let lockout = PASSCODE_LOCKOUT.as_secs_f64();
+        connection
+            .execute(
+                "DELETE FROM passcode_failures
+                    WHERE first_failed_at <= now() - make_interval(secs => $1)",
+                &[&lockout],
+            )
+            .await?;
+        connection
+            .execute(
+                "INSERT INTO passcode_failures (meeting_id, peer, failures) VALUES ($1, $2, 1)
+                    ON CONFLICT (meeting_id, peer) DO UPDATE SET failures = CASE
+                        WHEN passcode_failures.first_failed_at > now() - make_interval(secs => $3)
+                        THEN passcode_failures.failures + 1 ELSE 1 END,
+                    first_failed_at = CASE
+                        WHEN passcode_failures.first_failed_at > now() - make_interval(secs => $3)
+                        THEN passcode_failures.first_failed_at ELSE now() END",
+                &[&room, &peer_key(peer), &lockout],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn succeeded(&self, room: &str, peer: Option<IpAddr>) -> Anysult<()> {
+        let connection = self.pool.get().await?;
+        connection
+            .execute(
+                "DELETE FROM passcode_failures WHERE meeting_id=$1 AND peer=$2",
+                &[&room, &peer_key(peer)],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// What a joining client presents besides its identity.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JoinCredentials {
+    pub passcode: Option<String>,
+    pub invite: Option<String>,
+    /// Address the client connects from, which [PasscodeAttempts] counts wrong passcodes by.
+    pub peer: Option<IpAddr>,
+}
+
+impl JoinCredentials {
+    /// Empty values, which protobuf and query strings produce for missing ones, count as absent.
+    pub fn new(passcode: Option<String>, invite: Option<String>) -> Self {
+        JoinCredentials {
+            passcode: passcode.filter(|s| !s.is_empty()),
+            invite: invite.filter(|s| !s.is_empty()),
+            peer: None,
+        }
+    }
+
+    pub fn with_peer(mut self, peer: Option<IpAddr>) -> Self {
+        self.peer = peer;
+        self
+    }
+}
+
+/// Claims of an invite token.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InviteClaims {
+    /// Id of the invite's row in `meeting_invites`.
+    pub jti: String,
+    pub room: String,
+    pub exp: u64,
+}
+
+/// HS256 key used to sign and verify invite tokens.
+#[derive(Clone)]
+pub struct InviteKey {
+    secret: Vec<u8>,
+}
+
+impl InviteKey {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: secret.into(),
+        }
+    }
+
+    /// A key nobody else knows, so its invites only work until the process exits.
+    pub fn random() -> Self {
+        let mut secret = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        Self::new(secret)
+    }
+
+    /// Reads `INVITE_SECRET`, falling back to `JWT_SECRET`.
+    ///
+    /// Without either a random secret is generated, which invalidates invites on restart.
+    pub fn from_env() -> Self {
+        ["INVITE_SECRET", "JWT_SECRET"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|s| !s.is_empty()))
+            .map(Self::new)
+            .unwrap_or_else(|| {
+                tracing::warn!("INVITE_SECRET is not set, invites will not survive a restart");
+                Self::random()
+            })
+    }
+
+    pub fn sign(&self, claims: &InviteClaims) -> Anysult<String> {
+        Ok(encode(
+            &Header::new(Algorithm::HS256),
+            claims,
+            &EncodingKey::from_secret(&self.secret),
+        )?)
+    }
+
+    /// Checks the signature and expiry of `token`.
+    pub fn verify(&self, token: &str) -> Anysult<InviteClaims> {
+        let data = decode::<InviteClaims>(
+            token,
+            &DecodingKey::from_secret(&self.secret),
+            &Validation::new(Algorithm::HS256),
+        )?;
+        Ok(data.claims)
+    }
+}
+
+impl std::fmt::Debug for InviteKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InviteKey").finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NewInvite {
+    /// Defaults to a day, at most 30 days.
+    pub expires_in_secs: Option<u64>,
+    /// How many people may use the invite, unlimited when absent.
+    pub max_uses: Option<u32>,
+}
+
+impl NewInvite {
+    pub fn validate(&self) -> Anysult<()> {
+        if self.ttl().is_zero() || self.ttl() > MAX_INVITE_TTL {
+            return Err(anyhow!(
+                "expires_in_secs must be between 1 and {}",
+                MAX_INVITE_TTL.as_secs()
+            ));
+        }
+        if self.max_uses == Some(0) || self.max_uses > Some(i32::MAX as u32) {
+            return Err(anyhow!("max_uses must be between 1 and {}", i32::MAX));
+        }
+        Ok(())
+    }
+
+    fn ttl(&self) -> Duration {
+        self.expires_in_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_INVITE_TTL)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Invite {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub max_uses: Option<u32>,
+}
+
+/// Creates an invite to meeting `id` and signs its token with `key`.
+pub async fn create_invite(
+    pool: &PostgresPool,
+    key: &InviteKey,
+    id: &str,
+    created_by: &str,
+    invite: &NewInvite,
+) -> Anysult<Invite> {
+    let exp = (SystemTime::now() + invite.ttl())
+        .duration_since(UNIX_EPOCH)?
+        .as_secs();
+    let expires_at = Utc
+        .timestamp_opt(exp as i64, 0)
+        .single()
+        .ok_or_else(|| anyhow!("invalid expiry {}", exp))?;
+    let claims = InviteClaims {
+        jti: random_hex(16),
+        room: id.to_string(),
+        exp,
+    };
+    let max_uses = invite.max_uses.map(|n| n as i32);
+    let connection = pool.get().await?;
+    connection
+        .execute(
+            "INSERT INTO meeting_invites (id, meeting_id, created_by, expires_at, max_uses)
+                VALUES ($1, $2, $3, $4, $5)",
+            &[&claims.jti, &id, &created_by, &expires_at, &max_uses],
+        )
+        .await?;
+    Ok(Invite {
+        token: key.sign(&claims)?,
+        expires_at,
+        max_uses: invite.max_uses,
+    })
+}
+
+/// Uses the invite `token` for `email` to get into `room`. Returns `false` when the token is not
+/// valid for the room, has expired, or has been used by `max_uses` other people.
+pub async fn redeem_invite(
+    pool: &PostgresPool,
+    key: &InviteKey,
+    room: &str,
+    token: &str,
+    email: &str,
+) -> Anysult<bool> {
+    let Ok(claims) = key.verify(token) else {
+        return Ok(false);
+    };
+    if claims.room != room {
+        return Ok(false);
+    }
+    let mut connection = pool.get().await?;
+    let transaction = connection.transaction().await?;
+    let Some(row) = transaction
+        .query_opt(
+            "SELECT max_uses, uses FROM meeting_invites
+                WHERE id=$1 AND meeting_id=$2 AND expires_at > now()
+                FOR UPDATE",
+            &[&claims.jti, &room],
+        )
+        .await?
+    else {
+        return Ok(false);
+    };
+    let used_before = transaction
+        .query_opt(
+            "SELECT 1 FROM meeting_invite_uses WHERE invite_id=$1 AND email=$2",
+            &[&claims.jti, &email],
+        )
+        .await?
+        .is_some();
+    if !used_before {
+        let max_uses: Option<i32> = row.get("max_uses");
+        let uses: i32 = row.get("uses");
+        if max_uses.is_some_and(|max| uses >= max) {
+            return Ok(false);
+        }
+        transaction
+            .execute(
+                "INSERT INTO meeting_invite_uses (invite_id, email) VALUES ($1, $2)",
+                &[&claims.jti, &email],
+            )
+            .await?;
+        transaction
+            .execute(
+                "UPDATE meeting_invites SET uses = uses + 1 WHERE id=$1",
+                &[&claims.jti],
+            )
+            .await?;
+    }
+    transaction.commit().await?;
+    Ok(true)
+}
@@ -0,0 +1,479 @@
+//! Meetings registered ahead of time, so a meeting link is stable and has an owner.
+//!
+//! Room ids in `/lobby/{email}/{room}` are meeting ids. A meeting carries the [RoomSettings]
+//! sent to everyone who joins it and a host, the owner unless they handed it over. With
+//! `REQUIRE_REGISTERED_MEETING` set, joins to a room without a row in `meetings` are refused.
+//! Meetings may also be closed with a passcode, see [access].
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result as Anysult};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_postgres::Row;
+use types::protos::join_rejected::join_rejected_packet::Reason;
+
+use crate::bus::validate_room_id;
+use crate::db::PostgresPool;
+use crate::rooms::rejection_packet;
+use crate::rooms::settings::RoomSettings;
+
+pub mod access;
+
+pub use access::{
+    create_invite, redeem_invite, Invite, InviteClaims, InviteKey, JoinCredentials, NewInvite,
+    PasscodeAttempts,
+};
+use access::{hash_passcode, validate_passcode, verify_passcode};
+
+const MAX_TITLE_LEN: usize = 200;
+
+const MEETING_COLUMNS: &str =
+    "id, owner_email, title, created_at, settings, COALESCE(host_email, owner_email) AS host_email,
+    passcode_hash";
+
+#[derive(Debug, Serialize)]
+pub struct Meeting {
+    pub id: String,
+    pub owner_email: String,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    pub settings: RoomSettings,
+    pub host_email: String,
+    pub has_passcode: bool,
+    #[serde(skip)]
+    pub passcode_hash: Option<String>,
+}
+
+impl TryFrom<Row> for Meeting {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> Anysult<Self> {
+        let passcode_hash: Option<String> = row.get("passcode_hash");
+        Ok(Meeting {
+            id: row.get("id"),
+            owner_email: row.get("owner_email"),
+            title: row.get("title"),
+            created_at: row.get("created_at"),
+            settings: serde_json::from_value(row.get("settings"))?,
+            host_email: row.get("host_email"),
+            has_passcode: passcode_hash.is_some(),
+            passcode_hash,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewMeeting {
+    /// May be left empty.
+    #[serde(default)]
+    pub title: String,
+    pub settings: Option<RoomSettings>,
+    pub passcode: Option<String>,
+}
+
+/// Partial update, fields left out are kept.
+#[derive(Debug, Default, Deserialize)]
+pub struct MeetingUpdate {
+    pub title: Option<String>,
+    pub settings: Option<RoomSettings>,
+    /// An empty passcode removes it.
+    pub passcode: Option<String>,
+}
+
+/// Reads `REQUIRE_REGISTERED_MEETING`.
+pub fn require_registered_meeting() -> bool {
+    types::truthy(std::env::var("REQUIRE_REGISTERED_MEETING").ok().as_deref())
+}
+
+fn validate_title(title: &str) -> Anysult<()> {
+    if title.len() > MAX_TITLE_LEN {
+        return Err(anyhow!("title must be at most {} bytes", MAX_TITLE_LEN));
+    }
+    Ok(())
+}
+
+fn validate_settings(settings: &RoomSettings) -> Anysult<()> {
+    if settings.max_participants == Some(0) {
+        return Err(anyhow!("max_participants must be at least 1"));
+    }
+    Ok(())
+}
+
+fn settings_json(settings: Option<&RoomSettings>) -> Anysult<Option<Value>> {
+    Ok(settings.map(serde_json::to_value).transpose()?)
+}
+
+impl NewMeeting {
+    pub fn validate(&self) -> Anysult<()> {
+        validate_title(&self.title)?;
+        self.passcode.as_deref().map_or(Ok(()), validate_passcode)?;
+        self.settings.as_ref().map_or(Ok(()), validate_settings)
+    }
+}
+
+impl MeetingUpdate {
+    pub fn validate(&self) -> Anysult<()> {
+        self.title.as_deref().map_or(Ok(()), validate_title)?;
+        match self.passcode.as_deref() {
+            None | Some("") => {}
+            Some(passcode) => validate_passcode(passcode)?,
+        }
+        self.settings.as_ref().map_or(Ok(()), validate_settings)
+    }
+}
+
+/// Meeting ids end up in NATS subjects and lobby paths, so they are plain hex.
+fn new_meeting_id() -> String {
+    uuid::Uuid::new_v4().to_simple().to_string()
+}
+
+pub async fn create_meeting(
+    pool: &PostgresPool,
+    owner_email: &str,
+    meeting: &NewMeeting,
+) -> Anysult<Meeting> {
+    let connection = pool.get().await?;
+    let settings = serde_json::to_value(meeting.settings.clone().unwrap_or_default())?;
+    let passcode_hash = meeting.passcode.as_deref().map(hash_passcode).transpose()?;
+    let row = connection
+        .query_one(
+            &format!(
+                "INSERT INTO meetings (id, owner_email, title, settings, passcode_hash)
+                    VALUES ($1, $2, $3, $4, $5)
+                    RETURNING {}",
+                MEETING_COLUMNS
+            ),
+            &[
+                &new_meeting_id(),
+                &owner_email,
+                &meeting.title,
+                &settings,
+                &passcode_hash,
+            ],
+        )
+        .await?;
+    row.try_into()
+}
+
+/// Meetings owned by `owner_email`, newest first.
+pub async fn list_meetings(pool: &PostgresPool, owner_email: &str) -> Anysult<Vec<Meeting>> {
+    let connection = pool.get().await?;
+    let rows = connection
+        .query(
+            &format!(
+                "SELECT {} FROM meetings WHERE owner_email=$1 ORDER BY created_at DESC",
+                MEETING_COLUMNS
+            ),
+            &[&owner_email],
+        )
+        .await?;
+    rows.into_iter().map(Meeting::try_from).collect()
+}
+
+pub async fn get_meeting(pool: &PostgresPool, id: &str) -> Anysult<Option<Meeting>> {
+    let connection = pool.get().await?;
+    let row = connection
+        .query_opt(
+            &format!("SELECT {} FROM meetings WHERE id=$1", MEETING_COLUMNS),
+            &[&id],
+        )
+        .await?;
+    row.map(Meeting::try_from).transpose()
+}
+
+/// Applies `update` if `owner_email` owns the meeting. Returns `None` otherwise.
+pub async fn update_meeting(
+    pool: &PostgresPool,
+    id: &str,
+    owner_email: &str,
+    update: &MeetingUpdate,
+) -> Anysult<Option<Meeting>> {
+    let connection = pool.get().await?;
+    let passcode_hash = update
+        .passcode
+        .as_deref()
+        .filter(|p| !p.is_empty())
+        .map(hash_passcode)
+        .transpose()?;
+    let row = connection
+        .query_opt(
+            &format!(
+                "UPDATE meetings
+                    SET title = COALESCE($3, title), settings = COALESCE($4, settings),
+                        passcode_hash = CASE WHEN $5 THEN $6 ELSE passcode_hash END
+                    WHERE id=$1 AND owner_email=$2
+                    RETURNING {}",
+                MEETING_COLUMNS
+            ),
+            &[
+                &id,
+                &owner_email,
+                &update.title,
+                &settings_json(update.settings.as_ref())?,
+                &update.passcode.is_some(),
+                &passcode_hash,
+            ],
+        )
+        .await?;
+    row.map(Meeting::try_from).transpose()
+}
+
+/// Deletes the meeting if `owner_email` owns it. Returns whether a meeting was deleted.
+pub async fn delete_meeting(pool: &PostgresPool, id: &str, owner_email: &str) -> Anysult<bool> {
+    let connection = pool.get().await?;
+    let deleted = connection
+        .execute(
+            "DELETE FROM meetings WHERE id=$1 AND owner_email=$2",
+            &[&id, &owner_email],
+        )
+        .await?;
+    Ok(deleted > 0)
+}
+
+/// Makes `email` the host of the meeting. Returns whether the meeting exists.
+pub async fn set_host(pool: &PostgresPool, id: &str, email: &str) -> Anysult<bool> {
+    let connection = pool.get().await?;
+    let updated = connection
+        .execute(
+            "UPDATE meetings SET host_email=$2 WHERE id=$1",
+            &[&id, &email],
+        )
+        .await?;
+    Ok(updated > 0)
+}
+
+/// Keeps `email` out of the meeting until `expires_at`.
+pub async fn ban_participant(
+    pool: &PostgresPool,
+    id: &str,
+    email: &str,
+    expires_at: DateTime<Utc>,
+) -> Anysult<()> {
+    let connection = pool.get().await?;
+    connection
+        .execute(
+            "INSERT INTO meeting_bans (meeting_id, email, expires_at) VALUES ($1, $2, $3)
+                ON CONFLICT (meeting_id, email) DO UPDATE SET expires_at = EXCLUDED.expires_at",
+            &[&id, &email, &expires_at],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Returns when the ban on `email` ends, if they are banned from the meeting.
+pub async fn banned_until(
+    pool: &PostgresPool,
+    id: &str,
+    email: &str,
+) -> Anysult<Option<DateTime<Utc>>> {
+    let connection = pool.get().await?;
+    let row = connection
+        .query_opt(
+            "SELECT expires_at FROM meeting_bans
+                WHERE meeting_id=$1 AND email=$2 AND expires_at > now()",
+            &[&id, &email],
+        )
+        .await?;
+    Ok(row.map(|row| row.get("expires_at")))
+}
+
+/// Where joining sessions look up their meeting.
+#[derive(Debug, Clone)]
+pub struct MeetingDirectory {
+    pool: PostgresPool,
+    require_registered: bool,
+    invites: InviteKey,
+    passcode_attempts: PasscodeAttempts,
+}
+
+impl MeetingDirectory {
+    /// Invites are checked with a random key until [MeetingDirectory::with_invite_key] sets the
+    /// one they were signed with.
+    pub fn new(pool: PostgresPool, require_registered: bool) -> Self {
+        Self {
+            passcode_attempts: PasscodeAttempts::new(pool.clone()),
+            pool,
+            require_registered,
+            invites: InviteKey::random(),
+        }
+    }
+
+    pub fn with_invite_key(mut self, key: InviteKey) -> Self {
+        self.invites = key;
+        self
+    }
+
+    /// Bans `email` from `room` for `duration`. Ad-hoc rooms have no host, so nobody is banned
+    /// from them.
+    pub async fn ban(&self, room: &str, email: &str, duration: Duration) -> Anysult<()> {
+        let expires_at = Utc::now() + chrono::Duration::from_std(duration)?;
+        ban_participant(&self.pool, room, email, expires_at).await
+    }
+
+    pub async fn transfer_host(&self, room: &str, email: &str) -> Anysult<()> {
+        if !set_host(&self.pool, room, email).await? {
+            return Err(anyhow!("meeting {} is not registered", room));
+        }
+        Ok(())
+    }
+}
+
+/// What a session learns about the room it is admitted to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Admission {
+    pub settings: RoomSettings,
+    /// The host of a registered meeting, ad-hoc rooms have none.
+    pub host: Option<String>,
+}
+
+impl Admission {
+    /// Whether `email` has to wait in the lobby until the host lets them in. The host never
+    /// waits, and rooms without a host have no waiting room.
+    pub fn must_wait(&self, email: &str) -> bool {
+        self.settings.waiting_room && self.host.as_deref().is_some_and(|host| host != email)
+    }
+}
+
+/// Why a session may not join a room.
+#[derive(Debug)]
+pub enum JoinRefused {
+    /// The room id is not one [validate_room_id] accepts.
+    InvalidRoom,
+    Unregistered,
+    Banned(DateTime<Utc>),
+    PasscodeRequired,
+    WrongPasscode,
+    /// Too many wrong passcodes, see [access::PasscodeAttempts].
+    TooManyAttempts,
+    /// The presented invite is not valid, expired or used up.
+    InvalidInvite,
+    Failed(anyhow::Error),
+}
+
+impl fmt::Display for JoinRefused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinRefused::InvalidRoom => write!(f, "invalid meeting id"),
+            JoinRefused::Unregistered => write!(f, "meeting is not registered"),
+            JoinRefused::Banned(until) => write!(f, "banned from the meeting until {}", until),
+            JoinRefused::PasscodeRequired => write!(f, "the meeting requires a passcode"),
+            JoinRefused::WrongPasscode => write!(f, "wrong passcode"),
+            JoinRefused::TooManyAttempts => {
+                write!(f, "too many wrong passcodes, try again later")
+            }
+            JoinRefused::InvalidInvite => write!(f, "the invite is invalid, expired or used up"),
+            JoinRefused::Failed(e) => write!(f, "failed to look up the meeting: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for JoinRefused {}
+
+impl JoinRefused {
+    /// The `JOIN_REJECTED` packet for refusals a client can act on.
+    pub fn to_packet(&self) -> Option<Bytes> {
+        let reason = match self {
+            JoinRefused::Banned(_) => Reason::BANNED,
+            JoinRefused::PasscodeRequired => Reason::PASSCODE_REQUIRED,
+            JoinRefused::WrongPasscode | JoinRefused::TooManyAttempts => Reason::WRONG_PASSCODE,
+            JoinRefused::InvalidInvite => Reason::INVALID_INVITE,
+            JoinRefused::InvalidRoom | JoinRefused::Unregistered | JoinRefused::Failed(_) => {
+                return None
+            }
+        };
+        Some(rejection_packet(reason, &self.to_string()))
+    }
+}
+
+impl From<anyhow::Error> for JoinRefused {
+    fn from(e: anyhow::Error) -> Self {
+        JoinRefused::Failed(e)
+    }
+}
+
+/// Returns the settings and host of the room `email` is joining.
+///
+/// Room ids that aren't safe in a bus subject are refused first, see [validate_room_id]. Unknown
+/// rooms get the default settings, or are refused when the directory requires registered meetings.
+/// Without a directory, i.e. without a database, every room uses the defaults. Everyone but the
+/// host needs the passcode or a valid invite to join a meeting that has a passcode, and an invite
+/// that is presented must be valid in any meeting.
+pub async fn authorize_join(
+    meetings: Option<&MeetingDirectory>,
+    room: &str,
+    email: &str,
+    credentials: &JoinCredentials,
+) -> Result<Admission, JoinRefused> {
+    if validate_room_id(room).is_err() {
+        return Err(JoinRefused::InvalidRoom);
+    }
+    let Some(meetings) = meetings else {
+        return Ok(Admission::default());
+    };
+    match get_meeting(&meetings.pool, room).await? {
+        Some(meeting) => {
+            if let Some(until) = banned_until(&meetings.pool, room, email).await? {
+                return Err(JoinRefused::Banned(until));
+            }
+            if meeting.host_email != email {
+                check_access(meetings, &meeting, email, credentials).await?;
+            }
+            Ok(Admission {
+                settings: meeting.settings,
+                host: Some(meeting.host_email),
+            })
+        }
+        None if meetings.require_registered => Err(JoinRefused::Unregistered),
+        None => Ok(Admission::default()),
+    }
+}
+
+async fn check_access(
+    meetings: &MeetingDirectory,
+    meeting: &Meeting,
+    email: &str,
+    credentials: &JoinCredentials,
+) -> Result<(), JoinRefused> {
+    // A presented invite is always redeemed, so its expiry and `max_uses` hold in meetings
+    // without a passcode too.
+    if let Some(invite) = &credentials.invite {
+        let redeemed = redeem_invite(
+            &meetings.pool,
+            &meetings.invites,
+            &meeting.id,
+            invite,
+            email,
+        )
+        .await?;
+        return if redeemed {
+            Ok(())
+        } else {
+            Err(JoinRefused::InvalidInvite)
+        };
+    }
+    let Some(hash) = &meeting.passcode_hash else {
+        return Ok(());
+    };
+    let Some(passcode) = &credentials.passcode else {
+        return Err(JoinRefused::PasscodeRequired);
+    };
+    let attempts = &meetings.passcode_attempts;
+    if attempts.locked_out(&meeting.id, credentials.peer).await? {
+        return Err(JoinRefused::TooManyAttempts);
+    }
+    // Argon2 takes a while on purpose, off the async workers.
+    let (hash, passcode) = (hash.clone(), passcode.clone());
+    let verified = tokio::task::spawn_blocking(move || verify_passcode(&hash, &passcode))
+        .await
+        .map_err(|e| anyhow!("failed to verify passcode: {}", e))?;
+    if verified {
+        attempts.succeeded(&meeting.id, credentials.peer).await?;
+        Ok(())
+    } else {
+        attempts.failed(&meeting.id, credentials.peer).await?;
+        Err(JoinRefused::WrongPasscode)
+    }
+}
@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
-use crate::actors::chat_session::{RoomId, SessionId};
+use crate::actors::chat_session::{Email, RoomId, SessionId};
+use crate::rooms::capacity::CapacityError;
+use crate::rooms::{Participant, RoomSummary, SessionInfo, Transport};
 
-use super::session::Message;
+use super::session::{Close, Message};
 use actix::{Message as ActixMessage, Recipient};
 
 #[derive(ActixMessage)]
@@ -14,11 +16,31 @@ pub struct ClientMessage {
     pub msg: Packet,
 }
 
+/// Returns the token the session can be resumed with, when resumption is enabled.
 #[derive(ActixMessage)]
-#[rtype(result = "Result<(), String>")]
+#[rtype(result = "Result<Option<String>, JoinError>")]
 pub struct JoinRoom {
     pub session: SessionId,
     pub room: RoomId,
+    pub user: Email,
+    pub transport: Transport,
+    /// `max_participants` from the room settings.
+    pub max_participants: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum JoinError {
+    NotConnected(SessionId),
+    Full(CapacityError),
+}
+
+impl std::fmt::Display for JoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinError::NotConnected(session) => write!(f, "session {} is not connected", session),
+            JoinError::Full(e) => write!(f, "{}", e),
+        }
+    }
 }
 
 #[derive(ActixMessage)]
@@ -26,6 +48,7 @@ pub struct JoinRoom {
 pub struct Connect {
     pub id: SessionId,
     pub addr: Recipient<Message>,
+    pub close: Recipient<Close>,
 }
 
 #[derive(ActixMessage)]
@@ -38,6 +61,32 @@ pub struct Packet {
 #[rtype(result = "()")]
 pub struct Disconnect {
     pub session: SessionId,
+    /// `true` when the connection was lost rather than closed, so the session is held for
+    /// resumption.
+    pub resumable: bool,
+    /// The connection that is gone. Ignored once the session has been resumed on another one.
+    pub addr: Recipient<Message>,
+}
+
+/// Moves a session that is in `room` as `user` onto a new connection, if `token` is its current
+/// resumption token. Returns `None` otherwise, and the client joins as a new session.
+#[derive(ActixMessage)]
+#[rtype(result = "Option<Resumed>")]
+pub struct Resume {
+    pub token: String,
+    pub room: RoomId,
+    pub user: Email,
+    pub addr: Recipient<Message>,
+    pub close: Recipient<Close>,
+}
+
+#[derive(Debug)]
+pub struct Resumed {
+    pub session: SessionId,
+    /// The token to resume the session with next time.
+    pub token: String,
+    /// How many buffered packets were replayed to the new connection.
+    pub replayed: usize,
 }
 
 #[derive(ActixMessage)]
@@ -45,3 +94,35 @@ pub struct Disconnect {
 pub struct Leave {
     pub session: SessionId,
 }
+
+#[derive(ActixMessage)]
+#[rtype(result = "Vec<Participant>")]
+pub struct GetParticipants {
+    pub room: RoomId,
+}
+
+#[derive(ActixMessage)]
+#[rtype(result = "Vec<RoomSummary>")]
+pub struct ListRooms;
+
+#[derive(ActixMessage)]
+#[rtype(result = "Option<SessionInfo>")]
+pub struct GetSession {
+    pub session: SessionId,
+}
+
+/// Closes a session with `reason`. Returns whether it was connected.
+#[derive(ActixMessage)]
+#[rtype(result = "bool")]
+pub struct CloseSession {
+    pub session: SessionId,
+    pub reason: String,
+}
+
+/// Closes every session in `room` with `reason`. Returns how many there were.
+#[derive(ActixMessage)]
+#[rtype(result = "usize")]
+pub struct CloseRoom {
+    pub room: RoomId,
+    pub reason: String,
+}
@@ -5,3 +5,10 @@ use actix::Message as ActixMessage;
 pub struct Message {
     pub msg: Vec<u8>,
 }
+
+/// Closes the websocket of a session with `reason`.
+#[derive(ActixMessage)]
+#[rtype(result = "()")]
+pub struct Close {
+    pub reason: String,
+}
@@ -1,9 +1,31 @@
 use actix::Addr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::actors::chat_server::ChatServer;
+use crate::audit::AuditLog;
+use crate::auth::admin::AdminKey;
+use crate::auth::token::ConnectAuth;
+use crate::bus::RoomBus;
+use crate::chat::ChatHistory;
+use crate::meetings::MeetingDirectory;
+use crate::recording::Recorder;
+use crate::rooms::presence::Presence;
 
 pub struct AppState {
     pub chat: Addr<ChatServer>,
+    /// Carries the lobbies of rooms with a waiting room.
+    pub bus: Arc<dyn RoomBus>,
+    pub connect_auth: ConnectAuth,
+    /// Set when the database is enabled, joins read their room settings from it.
+    pub meetings: Option<MeetingDirectory>,
+    pub chat_history: ChatHistory,
+    pub recorder: Recorder,
+    pub audit: AuditLog,
+    /// Set when `ADMIN_API_KEY` is, enables the admin API.
+    pub admin_key: Option<AdminKey>,
+    /// Set when the database is enabled, the admin API sees the sessions of every server in it.
+    pub presence: Option<Presence>,
 }
 
 pub struct AppConfig {
@@ -13,4 +35,6 @@ pub struct AppConfig {
     pub oauth_auth_url: String,
     pub oauth_token_url: String,
     pub after_login_url: String,
+    /// How long a login may take between `/login` and `/login/callback`.
+    pub oauth_state_ttl: Duration,
 }
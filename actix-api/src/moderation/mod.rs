@@ -0,0 +1,248 @@
+//! Host controls: asking a participant to mute or stop their video, removing them from the
+//! meeting and handing the host role to someone else.
+//!
+//! Commands from clients are checked and rebuilt by the host's session before they go on the
+//! bus, so every `CONTROL` packet on the bus was put there by a server. Sessions watch the bus to
+//! learn who the host is and to close themselves when their participant is removed. Admitting
+//! and denying participants waiting in the [Lobby] work the same way, except that the decision
+//! goes to the lobby instead of the room.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result as Anysult};
+use bytes::Bytes;
+use protobuf::Message;
+use types::protos::control_packet::control_packet::Command;
+use types::protos::control_packet::ControlPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+use crate::actors::chat_session::{Email, RoomId};
+use crate::audit::{AuditEvent, AuditLog, EventKind};
+use crate::lobby::{Decision, Lobby};
+use crate::meetings::MeetingDirectory;
+use crate::recording::Recorder;
+use crate::sender::packet_type;
+
+const DEFAULT_REMOVAL_BAN_SECS: u64 = 600;
+
+/// Reads `REMOVAL_BAN_SECS` (default 600): how long a removed participant may not rejoin.
+pub fn removal_ban() -> Duration {
+    let secs = std::env::var("REMOVAL_BAN_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_REMOVAL_BAN_SECS);
+    Duration::from_secs(secs)
+}
+
+/// What a session does with a packet it received from the bus.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Observed {
+    Deliver,
+    /// A `CONTROL` packet, or a `LOBBY` packet for the host, which unlike media must not be
+    /// dropped on the way to the client.
+    Control,
+    /// A `LOBBY` packet while the session is not the host.
+    Ignore,
+    /// The host removed this session's participant. The packet is still delivered so the client
+    /// knows why the session closes.
+    Removed,
+}
+
+/// Host state of one session.
+#[derive(Debug)]
+pub struct HostControls {
+    room: RoomId,
+    email: Email,
+    is_host: AtomicBool,
+    meetings: Option<MeetingDirectory>,
+    lobby: Option<Lobby>,
+    recorder: Option<Recorder>,
+    audit: Option<AuditLog>,
+    removal_ban: Duration,
+}
+
+impl HostControls {
+    /// `host` is the meeting's host when the session joined, `None` for ad-hoc rooms.
+    pub fn new(
+        room: impl Into<RoomId>,
+        email: impl Into<Email>,
+        host: Option<&str>,
+        meetings: Option<MeetingDirectory>,
+    ) -> Self {
+        let email = email.into();
+        HostControls {
+            room: room.into(),
+            is_host: AtomicBool::new(host == Some(email.as_str())),
+            email,
+            meetings,
+            lobby: None,
+            recorder: None,
+            audit: None,
+            removal_ban: removal_ban(),
+        }
+    }
+
+    /// Lets the host admit and deny participants waiting in `lobby`.
+    pub fn with_lobby(mut self, lobby: Option<Lobby>) -> Self {
+        self.lobby = lobby;
+        self
+    }
+
+    /// Lets the host record the room, for meetings that allow recording.
+    pub fn with_recorder(mut self, recorder: Option<Recorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Records who the host removes and hands the host role to.
+    pub fn with_audit(mut self, audit: Option<AuditLog>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    pub fn room(&self) -> &str {
+        &self.room
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    pub fn lobby(&self) -> Option<&Lobby> {
+        self.lobby.as_ref()
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.is_host.load(Ordering::SeqCst)
+    }
+
+    /// Runs a `CONTROL` packet the session's own client sent. Returns the packet to publish to
+    /// the room, which the client should get as well, or `None` when the command went to the
+    /// lobby or the recorder, which announce it themselves.
+    pub async fn command(&self, packet: &PacketWrapper) -> Anysult<Option<Bytes>> {
+        if !self.is_host() {
+            return Err(anyhow!("{} is not the host of {}", self.email, self.room));
+        }
+        let control = ControlPacket::parse_from_bytes(&packet.data)?;
+        let command = control
+            .command
+            .enum_value()
+            .map_err(|c| anyhow!("unknown command {}", c))?;
+        let targeted = !matches!(command, Command::START_RECORDING | Command::STOP_RECORDING);
+        if targeted && (control.target.is_empty() || control.target == self.email) {
+            return Err(anyhow!("invalid target {:?}", control.target));
+        }
+        match command {
+            Command::MUTE | Command::STOP_VIDEO => {}
+            Command::REMOVE => {
+                if let Some(meetings) = &self.meetings {
+                    meetings
+                        .ban(&self.room, &control.target, self.removal_ban)
+                        .await?;
+                }
+                self.audit(EventKind::Removed, &control.target).await;
+            }
+            Command::TRANSFER_HOST => {
+                let meetings = self
+                    .meetings
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("{} has no host to transfer", self.room))?;
+                meetings.transfer_host(&self.room, &control.target).await?;
+                self.is_host.store(false, Ordering::SeqCst);
+                self.audit(EventKind::HostChanged, &control.target).await;
+                return Ok(Some(control_packet(
+                    &self.email,
+                    Command::HOST_CHANGED,
+                    &control.target,
+                )));
+            }
+            Command::ADMIT | Command::DENY => {
+                let lobby = self
+                    .lobby
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("{} has no waiting room", self.room))?;
+                let decision = match command {
+                    Command::ADMIT => Decision::Admitted,
+                    _ => Decision::Denied,
+                };
+                lobby.announce(decision.status(), &control.target).await?;
+                return Ok(None);
+            }
+            Command::START_RECORDING | Command::STOP_RECORDING => {
+                let recorder = self
+                    .recorder
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("{} may not be recorded", self.room))?;
+                if command == Command::START_RECORDING {
+                    recorder.start(&self.room, &self.email).await?;
+                } else {
+                    recorder.stop(&self.room, &self.email).await?;
+                }
+                return Ok(None);
+            }
+            Command::HOST_CHANGED | Command::UNSPECIFIED => {
+                return Err(anyhow!("clients may not send {:?}", command));
+            }
+        }
+        Ok(Some(control_packet(&self.email, command, &control.target)))
+    }
+
+    async fn audit(&self, kind: EventKind, target: &str) {
+        if let Some(audit) = &self.audit {
+            let event = AuditEvent::new(&*self.room, kind, &*self.email).with_target(target);
+            audit.record(event).await;
+        }
+    }
+
+    /// Looks at a packet from the bus before it is delivered to the client. Only `CONTROL` and
+    /// `LOBBY` packets are parsed, media is passed on untouched.
+    pub fn observe(&self, data: &[u8]) -> Observed {
+        match packet_type(data) {
+            Some(PacketType::CONTROL | PacketType::LOBBY) => {}
+            _ => return Observed::Deliver,
+        }
+        let Ok(packet) = PacketWrapper::parse_from_bytes(data) else {
+            return Observed::Deliver;
+        };
+        match packet.packet_type.enum_value() {
+            Ok(PacketType::CONTROL) => {}
+            Ok(PacketType::LOBBY) if self.is_host() => return Observed::Control,
+            Ok(PacketType::LOBBY) => return Observed::Ignore,
+            _ => return Observed::Deliver,
+        }
+        let Ok(control) = ControlPacket::parse_from_bytes(&packet.data) else {
+            return Observed::Deliver;
+        };
+        match control.command.enum_value() {
+            Ok(Command::HOST_CHANGED) => {
+                self.is_host
+                    .store(control.target == self.email, Ordering::SeqCst);
+                Observed::Control
+            }
+            Ok(Command::REMOVE) if control.target == self.email => Observed::Removed,
+            _ => Observed::Control,
+        }
+    }
+}
+
+/// Builds the server generated `HOST_CHANGED` packet a session gets when it joins a meeting.
+pub fn host_packet(host: &str) -> Bytes {
+    control_packet("", Command::HOST_CHANGED, host)
+}
+
+/// Builds a `CONTROL` packet `from` a host or the server.
+pub fn control_packet(from: &str, command: Command, target: &str) -> Bytes {
+    let control = ControlPacket {
+        command: command.into(),
+        target: target.to_string(),
+        ..Default::default()
+    };
+    let packet = PacketWrapper {
+        packet_type: PacketType::CONTROL.into(),
+        email: from.to_string(),
+        data: control.write_to_bytes().unwrap_or_default(),
+        ..Default::default()
+    };
+    Bytes::from(packet.write_to_bytes().unwrap_or_default())
+}
@@ -0,0 +1,397 @@
+//! Server side recording of rooms, which needs no browser.
+//!
+//! A [Recorder] subscribes to `room.{room}.*` like a session does and appends every packet it
+//! gets, with its arrival time and the subject it was published on, to a [packet_log] in its
+//! directory. Payloads are stored as they are, so rooms using end-to-end encryption are recorded
+//! too.
+//!
+//! The host starts and stops recordings with `START_RECORDING` and `STOP_RECORDING` control
+//! packets, the owner and host can also use the REST API. Both are announced to the room, and a
+//! recording ends when its recorder sees the `STOP_RECORDING` packet on the bus, so it can be
+//! stopped from any node. The recorder announces the recording again whenever someone joins.
+//! Only meetings with `recording_allowed` set may be recorded.
+//!
+//! A recording also stops by itself, as set by [RecordingLimits]: when the last session it saw
+//! leaves the room, when nothing was published into the room for a while, or when it gets too
+//! long. Clients send media heartbeats, so every session in the room is seen within seconds,
+//! including those that were there before the recording started.
+//!
+//! [mux] turns a finished packet log into [webm] files, one per participant and media type, and
+//! [replay] publishes it into a room again.
+pub mod mux;
+pub mod packet_log;
+pub mod replay;
+pub mod webm;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result as Anysult};
+use futures::StreamExt;
+use protobuf::Message;
+use serde::Serialize;
+use tokio::time::{timeout_at, Instant};
+use tracing::{error, info};
+use types::protos::control_packet::control_packet::Command;
+use types::protos::control_packet::ControlPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+use crate::actors::chat_session::{Email, RoomId};
+use crate::audit::{AuditEvent, AuditLog, EventKind};
+use crate::bus::{BusSubscription, RoomBus};
+use crate::moderation::control_packet;
+use crate::rooms::now_millis;
+use crate::webhooks::Webhooks;
+use packet_log::{index_path, LogHeader, PacketLogWriter, Record, LOG_EXTENSION};
+
+/// Session part of the subject recorders announce recordings on.
+pub const RECORDER_SESSION: &str = "recorder";
+
+const DEFAULT_RECORDINGS_DIR: &str = "recordings";
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 5 * 60;
+const DEFAULT_MAX_DURATION_SECS: u64 = 4 * 60 * 60;
+
+type LogWriter = PacketLogWriter<BufWriter<File>, BufWriter<File>>;
+
+/// A recording in progress on this node.
+#[derive(Clone, Debug, Serialize)]
+pub struct Recording {
+    pub room: RoomId,
+    pub path: PathBuf,
+    pub started_by: Email,
+    /// Milliseconds since the unix epoch.
+    pub started_at: u64,
+}
+
+/// When recordings stop without a `STOP_RECORDING`, besides when the last session leaves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordingLimits {
+    /// How long the room may stay silent.
+    pub idle_timeout: Duration,
+    pub max_duration: Duration,
+}
+
+impl Default for RecordingLimits {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS),
+            max_duration: Duration::from_secs(DEFAULT_MAX_DURATION_SECS),
+        }
+    }
+}
+
+impl RecordingLimits {
+    /// Reads `RECORDING_IDLE_TIMEOUT_SECS` (default 300) and `RECORDING_MAX_DURATION_SECS`
+    /// (default 14400).
+    pub fn from_env() -> Self {
+        let secs = |name: &str, default: u64| {
+            let secs = std::env::var(name)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default);
+            Duration::from_secs(secs)
+        };
+        Self {
+            idle_timeout: secs("RECORDING_IDLE_TIMEOUT_SECS", DEFAULT_IDLE_TIMEOUT_SECS),
+            max_duration: secs("RECORDING_MAX_DURATION_SECS", DEFAULT_MAX_DURATION_SECS),
+        }
+    }
+}
+
+/// Records rooms into packet logs.
+#[derive(Clone)]
+pub struct Recorder {
+    bus: Arc<dyn RoomBus>,
+    dir: PathBuf,
+    limits: RecordingLimits,
+    active: Arc<Mutex<HashMap<RoomId, Recording>>>,
+    audit: Option<AuditLog>,
+    webhooks: Option<Webhooks>,
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder")
+            .field("dir", &self.dir)
+            .field("limits", &self.limits)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Recorder {
+    pub fn new(bus: Arc<dyn RoomBus>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            bus,
+            dir: dir.into(),
+            limits: RecordingLimits::default(),
+            active: Arc::default(),
+            audit: None,
+            webhooks: None,
+        }
+    }
+
+    /// Writes the recordings to `RECORDINGS_DIR` (default `recordings`), with the
+    /// [RecordingLimits::from_env].
+    pub fn from_env(bus: Arc<dyn RoomBus>) -> Self {
+        let dir = std::env::var("RECORDINGS_DIR")
+            .unwrap_or_else(|_| String::from(DEFAULT_RECORDINGS_DIR));
+        Self::new(bus, dir).with_limits(RecordingLimits::from_env())
+    }
+
+    pub fn with_limits(mut self, limits: RecordingLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Records who starts and stops recordings.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Tells the webhooks of meeting owners when the recordings made on this node are ready.
+    pub fn with_webhooks(mut self, webhooks: Option<Webhooks>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    /// The recording of `room` when this node is recording it.
+    pub fn recording(&self, room: &str) -> Option<Recording> {
+        self.active.lock().unwrap().get(room).cloned()
+    }
+
+    /// Starts recording `room` on this node and announces it to the room.
+    pub async fn start(&self, room: &str, started_by: &str) -> Anysult<Recording> {
+        let started_at = now_millis();
+        let recording = Recording {
+            room: room.to_string(),
+            path: self.dir.join(format!(
+                "{}-{}.{}",
+                file_stem(room),
+                started_at,
+                LOG_EXTENSION
+            )),
+            started_by: started_by.to_string(),
+            started_at,
+        };
+        {
+            let mut active = self.active.lock().unwrap();
+            if active.contains_key(room) {
+                return Err(anyhow!("{} is already being recorded", room));
+            }
+            active.insert(room.to_string(), recording.clone());
+        }
+        let (writer, sub) = match self.open(&recording).await {
+            Ok(opened) => opened,
+            Err(e) => {
+                self.active.lock().unwrap().remove(room);
+                return Err(e);
+            }
+        };
+        tokio::spawn(record(
+            sub,
+            writer,
+            self.bus.clone(),
+            self.active.clone(),
+            recording.clone(),
+            self.limits,
+            self.webhooks.clone(),
+        ));
+        announce(&*self.bus, &recording).await?;
+        info!("{} started recording {}", started_by, room);
+        if let Some(audit) = &self.audit {
+            let event = AuditEvent::new(room, EventKind::RecordingStarted, started_by)
+                .with_detail(recording.path.display().to_string());
+            audit.record(event).await;
+        }
+        Ok(recording)
+    }
+
+    /// Announces the end of the recording of `room`, which stops it on whichever node records
+    /// it.
+    pub async fn stop(&self, room: &str, stopped_by: &str) -> Anysult<()> {
+        self.bus
+            .publish_to_room(
+                room,
+                RECORDER_SESSION,
+                control_packet(stopped_by, Command::STOP_RECORDING, ""),
+            )
+            .await?;
+        if let Some(audit) = &self.audit {
+            let event = AuditEvent::new(room, EventKind::RecordingStopped, stopped_by);
+            audit.record(event).await;
+        }
+        Ok(())
+    }
+
+    async fn open(&self, recording: &Recording) -> Anysult<(LogWriter, BusSubscription)> {
+        fs::create_dir_all(&self.dir)?;
+        let writer = PacketLogWriter::new(
+            BufWriter::new(create_new(&recording.path)?),
+            BufWriter::new(create_new(&index_path(&recording.path))?),
+            &LogHeader {
+                version: packet_log::VERSION,
+                started_at: recording.started_at,
+                room: recording.room.clone(),
+            },
+        )?;
+        let queue = format!("{}-{}", RECORDER_SESSION, recording.started_at);
+        let sub = self.bus.subscribe_to_room(&recording.room, &queue).await?;
+        Ok((writer, sub))
+    }
+}
+
+fn create_new(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+/// Keeps room ids from escaping the recordings directory.
+fn file_stem(room: &str) -> String {
+    room.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Tells the room that it is being recorded.
+async fn announce(bus: &dyn RoomBus, recording: &Recording) -> Anysult<()> {
+    bus.publish_to_room(
+        &recording.room,
+        RECORDER_SESSION,
+        control_packet(&recording.started_by, Command::START_RECORDING, ""),
+    )
+    .await
+}
+
+/// What the recorder does about a packet besides writing it down.
+#[derive(Debug, PartialEq, Eq)]
+enum Seen {
+    Packet,
+    Joined,
+    Left,
+    Stop,
+}
+
+fn inspect(data: &[u8]) -> Seen {
+    let Ok(packet) = PacketWrapper::parse_from_bytes(data) else {
+        return Seen::Packet;
+    };
+    match packet.packet_type.enum_value() {
+        Ok(PacketType::PARTICIPANT_JOINED) => Seen::Joined,
+        Ok(PacketType::PARTICIPANT_LEFT) => Seen::Left,
+        Ok(PacketType::CONTROL) => match ControlPacket::parse_from_bytes(&packet.data) {
+            Ok(control) if control.command.enum_value() == Ok(Command::STOP_RECORDING) => {
+                Seen::Stop
+            }
+            _ => Seen::Packet,
+        },
+        _ => Seen::Packet,
+    }
+}
+
+/// Hands the packets of the room to a blocking writer until the recording is stopped.
+async fn record(
+    mut sub: BusSubscription,
+    writer: LogWriter,
+    bus: Arc<dyn RoomBus>,
+    active: Arc<Mutex<HashMap<RoomId, Recording>>>,
+    recording: Recording,
+    limits: RecordingLimits,
+    webhooks: Option<Webhooks>,
+) {
+    let (records, received) = mpsc::channel();
+    let writing = tokio::task::spawn_blocking(move || write(writer, received));
+    let deadline = Instant::now() + limits.max_duration;
+    // The sessions that published into the room and haven't left.
+    let mut sessions = HashSet::new();
+    let stopped_because = loop {
+        let idle = Instant::now() + limits.idle_timeout;
+        let msg = match timeout_at(idle.min(deadline), sub.next()).await {
+            Ok(Some(msg)) => msg,
+            Ok(None) => break None,
+            Err(_) if Instant::now() >= deadline => break Some("it reached its maximum duration"),
+            Err(_) => break Some("nothing was published for a while"),
+        };
+        let seen = inspect(&msg.payload);
+        let session = msg
+            .subject
+            .rsplit_once('.')
+            .map(|(_, session)| session.to_string())
+            .filter(|session| session != RECORDER_SESSION);
+        let record = Record {
+            arrival: now_millis(),
+            subject: msg.subject,
+            payload: msg.payload.to_vec(),
+        };
+        if records.send(record).is_err() || seen == Seen::Stop {
+            break None;
+        }
+        match (seen, session) {
+            (Seen::Left, Some(session)) => {
+                if sessions.remove(&session) && sessions.is_empty() {
+                    break Some("the last participant left");
+                }
+            }
+            (seen, Some(session)) => {
+                sessions.insert(session);
+                if seen == Seen::Joined {
+                    if let Err(e) = announce(&*bus, &recording).await {
+                        error!(
+                            "error announcing the recording of {}: {}",
+                            recording.room, e
+                        );
+                    }
+                }
+            }
+            (_, None) => {}
+        }
+    };
+    drop(records);
+    if let Some(reason) = stopped_because {
+        info!("stopping the recording of {} as {}", recording.room, reason);
+        // Tells the participants, whose clients still show the recording.
+        let stop = control_packet("", Command::STOP_RECORDING, "");
+        if let Err(e) = bus
+            .publish_to_room(&recording.room, RECORDER_SESSION, stop)
+            .await
+        {
+            error!(
+                "error announcing the end of the recording of {}: {}",
+                recording.room, e
+            );
+        }
+    }
+    match writing.await {
+        Ok(Ok(())) => {
+            info!("stopped recording {}", recording.room);
+            if let Some(webhooks) = &webhooks {
+                webhooks.recording_ready(&recording, now_millis()).await;
+            }
+        }
+        Ok(Err(e)) => error!("error recording {}: {}", recording.room, e),
+        Err(e) => error!("error recording {}: {}", recording.room, e),
+    }
+    active.lock().unwrap().remove(&recording.room);
+}
+
+/// Appends records as they come, flushing whenever it caught up.
+fn write(mut writer: LogWriter, received: mpsc::Receiver<Record>) -> Anysult<()> {
+    while let Ok(record) = received.recv() {
+        writer.append(&record)?;
+        while let Ok(record) = received.try_recv() {
+            writer.append(&record)?;
+        }
+        writer.flush()?;
+    }
+    writer.flush()
+}
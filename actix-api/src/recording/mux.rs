@@ -0,0 +1,376 @@
+//! Turns a [packet_log](super::packet_log) into one WebM file per participant and media type,
+//! without transcoding.
+//!
+//! The `MEDIA` packets of the log carry VP9 frames from cameras and screens and Opus frames from
+//! microphones as the browser encoded them. Their timestamps come from the encoder, so frames are
+//! placed by them, anchored to when the first one arrived. When the encoder restarts, e.g. after
+//! the camera was turned off and on again, the timestamps no longer match the arrival times and
+//! the stream is anchored again, leaving a gap in the file. Video frames after a lost one are
+//! dropped until the next keyframe so players never decode a frame whose reference is missing.
+//!
+//! In rooms with end-to-end encryption every participant encrypts their packets with their own
+//! AES key, which has to be given as a [MediaKey] to read their media.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use anyhow::{anyhow, Context, Result as Anysult};
+use protobuf::Message;
+use tracing::{info, warn};
+use types::protos::media_packet::media_packet::MediaType;
+use types::protos::media_packet::MediaPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+use super::packet_log::PacketLogReader;
+use super::webm::{Track, WebmWriter};
+use crate::actors::chat_session::Email;
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+/// Browsers record mono 48kHz Opus unless the packets say otherwise.
+const DEFAULT_CHANNELS: u8 = 1;
+const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+/// How far encoder time may drift from arrival time before the stream is anchored again.
+const RESYNC_US: i64 = 2_000_000;
+
+/// The AES-128-CBC key and IV a participant encrypts their packets with.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MediaKey {
+    pub key: [u8; 16],
+    pub iv: [u8; 16],
+}
+
+impl std::fmt::Debug for MediaKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MediaKey").finish_non_exhaustive()
+    }
+}
+
+impl MediaKey {
+    /// Reads `key:iv`, both 32 hex digits.
+    pub fn parse(s: &str) -> Anysult<Self> {
+        let (key, iv) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected <key>:<iv>"))?;
+        let mut media_key = MediaKey {
+            key: [0; 16],
+            iv: [0; 16],
+        };
+        hex::decode_to_slice(key, &mut media_key.key).context("invalid key")?;
+        hex::decode_to_slice(iv, &mut media_key.iv).context("invalid iv")?;
+        Ok(media_key)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Anysult<Vec<u8>> {
+        Aes128CbcDec::new(&self.key.into(), &self.iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .map_err(|e| anyhow!("decrypt error: {}", e))
+    }
+}
+
+/// The media a file holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MediaKind {
+    Camera,
+    Screen,
+    Audio,
+}
+
+impl MediaKind {
+    fn of(media_type: MediaType) -> Option<Self> {
+        match media_type {
+            MediaType::VIDEO => Some(MediaKind::Camera),
+            MediaType::SCREEN => Some(MediaKind::Screen),
+            MediaType::AUDIO => Some(MediaKind::Audio),
+            MediaType::HEARTBEAT => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MediaKind::Camera => "camera",
+            MediaKind::Screen => "screen",
+            MediaKind::Audio => "audio",
+        }
+    }
+
+    fn is_video(self) -> bool {
+        self != MediaKind::Audio
+    }
+}
+
+/// A file written by [mux_to_webm].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MuxedFile {
+    pub email: Email,
+    pub kind: MediaKind,
+    pub path: PathBuf,
+    pub frames: usize,
+    /// Frames left out because one before them was lost.
+    pub dropped: usize,
+}
+
+/// What [mux_to_webm] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MuxReport {
+    pub files: Vec<MuxedFile>,
+    /// `MEDIA` packets that could not be read, mostly encrypted ones without their key.
+    pub unreadable: usize,
+}
+
+/// Width and height of a VP9 keyframe, from its uncompressed header.
+pub fn vp9_frame_size(frame: &[u8]) -> Option<(u16, u16)> {
+    let mut bits = BitReader { data: frame, at: 0 };
+    if bits.read(2)? != 2 {
+        return None;
+    }
+    let profile_low = bits.read(1)?;
+    let profile = (bits.read(1)? << 1) | profile_low;
+    if profile == 3 {
+        bits.read(1)?;
+    }
+    // show_existing_frame
+    if bits.read(1)? == 1 {
+        return None;
+    }
+    // frame_type, 0 for keyframes
+    if bits.read(1)? != 0 {
+        return None;
+    }
+    // show_frame and error_resilient_mode
+    bits.read(2)?;
+    if bits.read(24)? != 0x49_83_42 {
+        return None;
+    }
+    if profile >= 2 {
+        // ten_or_twelve_bit
+        bits.read(1)?;
+    }
+    let color_space = bits.read(3)?;
+    if color_space != 7 {
+        // color_range
+        bits.read(1)?;
+        if profile == 1 || profile == 3 {
+            // subsampling_x, subsampling_y and reserved_zero
+            bits.read(3)?;
+        }
+    } else if profile == 1 || profile == 3 {
+        bits.read(1)?;
+    }
+    let width = bits.read(16)? + 1;
+    let height = bits.read(16)? + 1;
+    Some((width as u16, height as u16))
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, n: usize) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..n {
+            let byte = self.data.get(self.at / 8)?;
+            let bit = (byte >> (7 - self.at % 8)) & 1;
+            value = (value << 1) | u32::from(bit);
+            self.at += 1;
+        }
+        Some(value)
+    }
+}
+
+/// One participant's media of one kind.
+struct Stream {
+    path: PathBuf,
+    writer: Option<WebmWriter<BufWriter<File>>>,
+    /// Added to encoder timestamps to get microseconds since the recording started.
+    anchor: Option<i64>,
+    last_time: u64,
+    last_sequence: Option<u64>,
+    needs_keyframe: bool,
+    frames: usize,
+    dropped: usize,
+}
+
+impl Stream {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            writer: None,
+            anchor: None,
+            last_time: 0,
+            last_sequence: None,
+            needs_keyframe: true,
+            frames: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Adds a frame that arrived `arrival` microseconds after the recording started.
+    fn push(&mut self, kind: MediaKind, media: &MediaPacket, arrival: i64) -> Anysult<()> {
+        let sequence = media.video_metadata.sequence;
+        if self.last_sequence == Some(sequence) {
+            return Ok(());
+        }
+        let lost = self
+            .last_sequence
+            .is_some_and(|last| sequence != last.wrapping_add(1));
+        self.last_sequence = Some(sequence);
+
+        let keyframe = !kind.is_video() || media.frame_type == "key";
+        if kind.is_video() {
+            if lost && !keyframe {
+                self.needs_keyframe = true;
+            }
+            if self.needs_keyframe && !keyframe {
+                self.dropped += 1;
+                return Ok(());
+            }
+        }
+        if self.writer.is_none() {
+            let track = match kind {
+                MediaKind::Audio => Track::Opus {
+                    channels: u8::try_from(media.audio_metadata.audio_number_of_channels)
+                        .ok()
+                        .filter(|channels| *channels > 0)
+                        .unwrap_or(DEFAULT_CHANNELS),
+                    sample_rate: Some(media.audio_metadata.audio_sample_rate as u32)
+                        .filter(|rate| *rate > 0)
+                        .unwrap_or(DEFAULT_SAMPLE_RATE),
+                },
+                MediaKind::Camera | MediaKind::Screen => {
+                    let Some((width, height)) = vp9_frame_size(&media.data) else {
+                        self.dropped += 1;
+                        return Ok(());
+                    };
+                    Track::Vp9 { width, height }
+                }
+            };
+            let file = File::create(&self.path)
+                .with_context(|| format!("failed to create {}", self.path.display()))?;
+            self.writer = Some(WebmWriter::new(BufWriter::new(file), &track)?);
+        }
+        self.needs_keyframe = false;
+
+        let timestamp = media.timestamp as i64;
+        let anchor = *self.anchor.get_or_insert(arrival - timestamp);
+        let mut time = timestamp + anchor;
+        if (time - arrival).abs() > RESYNC_US {
+            self.anchor = Some(arrival - timestamp);
+            time = arrival;
+        }
+        let time = ((time.max(0) / 1000) as u64).max(self.last_time);
+        self.last_time = time;
+        let duration = Some((media.duration / 1000.0) as u64).filter(|d| *d > 0);
+        if let Some(writer) = &mut self.writer {
+            writer.write_frame(time, duration, keyframe, &media.data)?;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// Keeps email addresses and room ids from escaping the output directory.
+fn file_part(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Writes the media of the log at `log` to `out_dir` as
+/// `{log name}-{email}-{camera|screen|audio}.webm`. `keys` holds the [MediaKey] of participants
+/// who encrypted their media.
+pub fn mux_to_webm(
+    log: &Path,
+    out_dir: &Path,
+    keys: &HashMap<Email, MediaKey>,
+) -> Anysult<MuxReport> {
+    let file = File::open(log).with_context(|| format!("failed to open {}", log.display()))?;
+    let reader = PacketLogReader::new(BufReader::new(file))?;
+    let started_at = reader.header().started_at;
+    let stem = log
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file_part(&reader.header().room));
+    std::fs::create_dir_all(out_dir)?;
+
+    let mut streams: HashMap<(Email, MediaKind), Stream> = HashMap::new();
+    let mut order = Vec::new();
+    let mut report = MuxReport::default();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("stopping at a damaged record: {}", e);
+                break;
+            }
+        };
+        let Ok(packet) = PacketWrapper::parse_from_bytes(&record.payload) else {
+            continue;
+        };
+        if packet.packet_type.enum_value() != Ok(PacketType::MEDIA) {
+            continue;
+        }
+        let data = match keys.get(&packet.email) {
+            Some(key) => key.decrypt(&packet.data),
+            None => Ok(packet.data),
+        };
+        let Some(media) = data
+            .ok()
+            .and_then(|data| MediaPacket::parse_from_bytes(&data).ok())
+        else {
+            report.unreadable += 1;
+            continue;
+        };
+        let Some(kind) = media.media_type.enum_value().ok().and_then(MediaKind::of) else {
+            continue;
+        };
+        let arrival = (record.arrival.saturating_sub(started_at) * 1000) as i64;
+        let stream = streams
+            .entry((packet.email.clone(), kind))
+            .or_insert_with(|| {
+                order.push((packet.email.clone(), kind));
+                Stream::new(out_dir.join(format!(
+                    "{}-{}-{}.webm",
+                    stem,
+                    file_part(&packet.email),
+                    kind.name()
+                )))
+            });
+        stream.push(kind, &media, arrival)?;
+    }
+
+    for (email, kind) in order {
+        let Some(stream) = streams.remove(&(email.clone(), kind)) else {
+            continue;
+        };
+        let Some(writer) = stream.writer else {
+            warn!("no playable {} of {}", kind.name(), email);
+            continue;
+        };
+        writer.finish()?;
+        info!(
+            "wrote {} frames to {}",
+            stream.frames,
+            stream.path.display()
+        );
+        report.files.push(MuxedFile {
+            email,
+            kind,
+            path: stream.path,
+            frames: stream.frames,
+            dropped: stream.dropped,
+        });
+    }
+    Ok(report)
+}
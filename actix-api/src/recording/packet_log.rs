@@ -0,0 +1,278 @@
+//! The packet log a room is recorded into, version 1. Integers are little endian.
+//!
+//! The log starts with a header: the magic `VCPL`, a `u16` version, the `u64` time the recording
+//! started in milliseconds since the unix epoch and the room id as a `u16` length and UTF-8
+//! bytes. Records follow, each a `u32` length of the rest of the record, the `u64` arrival time,
+//! the bus subject it arrived on as a `u16` length and UTF-8 bytes, and the `PacketWrapper`
+//! bytes, which are stored as they are so end-to-end encrypted media can be recorded too.
+//!
+//! The index is a second file next to the log starting with the magic `VCPI` and a `u16`
+//! version, followed by a `u64` arrival time and `u64` log offset for the first record of every
+//! [INDEX_INTERVAL_MS]. Both files are only ever appended to, and an index entry only after the
+//! record it points at, so a recording cut short by a crash is readable up to its last complete
+//! record.
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result as Anysult};
+
+pub const LOG_MAGIC: &[u8; 4] = b"VCPL";
+pub const INDEX_MAGIC: &[u8; 4] = b"VCPI";
+pub const VERSION: u16 = 1;
+/// Extension of packet log files.
+pub const LOG_EXTENSION: &str = "vcpl";
+/// How far apart in time index entries are.
+pub const INDEX_INTERVAL_MS: u64 = 1000;
+/// Longest record, well above the largest packet the servers accept, so a corrupt length can't
+/// make a reader allocate gigabytes.
+pub const MAX_RECORD_LEN: u32 = 4 * 1024 * 1024;
+/// Shortest record: the arrival time and subject length.
+const MIN_RECORD_LEN: u32 = 8 + 2;
+
+/// Path of the index of the log at `log`.
+pub fn index_path(log: &Path) -> PathBuf {
+    let mut path = log.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogHeader {
+    pub version: u16,
+    /// Milliseconds since the unix epoch.
+    pub started_at: u64,
+    pub room: String,
+}
+
+impl LogHeader {
+    fn len(&self) -> u64 {
+        (LOG_MAGIC.len() + 2 + 8 + 2 + self.room.len()) as u64
+    }
+}
+
+/// One packet as it arrived from the bus.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// Milliseconds since the unix epoch.
+    pub arrival: u64,
+    /// Subject the packet was published on, `room.{room}.{session}`.
+    pub subject: String,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub arrival: u64,
+    /// Offset of the record in the log.
+    pub offset: u64,
+}
+
+/// Offset in the log to start reading at to get every record that arrived at or after `at`.
+pub fn seek_offset(header: &LogHeader, index: &[IndexEntry], at: u64) -> u64 {
+    index
+        .iter()
+        .take_while(|entry| entry.arrival <= at)
+        .last()
+        .map_or_else(|| header.len(), |entry| entry.offset)
+}
+
+fn write_str16(w: &mut impl Write, s: &str) -> io::Result<()> {
+    let len = u16::try_from(s.len())
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "string longer than 65535 bytes"))?;
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_u16(r: &mut impl Read) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_str16(r: &mut impl Read) -> Anysult<String> {
+    let mut buf = vec![0; read_u16(r)? as usize];
+    r.read_exact(&mut buf)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+fn read_magic(r: &mut impl Read, magic: &[u8; 4]) -> Anysult<u16> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    if &buf != magic {
+        bail!("not a {} file", String::from_utf8_lossy(magic));
+    }
+    let version = read_u16(r)?;
+    if version != VERSION {
+        bail!("unsupported version {}", version);
+    }
+    Ok(version)
+}
+
+/// Appends records to a log and its index.
+#[derive(Debug)]
+pub struct PacketLogWriter<L: Write, I: Write> {
+    log: L,
+    index: I,
+    offset: u64,
+    /// Arrival time of the last indexed record.
+    indexed: Option<u64>,
+}
+
+impl<L: Write, I: Write> PacketLogWriter<L, I> {
+    /// Writes the headers of a new log and index.
+    pub fn new(mut log: L, mut index: I, header: &LogHeader) -> Anysult<Self> {
+        log.write_all(LOG_MAGIC)?;
+        log.write_all(&VERSION.to_le_bytes())?;
+        log.write_all(&header.started_at.to_le_bytes())?;
+        write_str16(&mut log, &header.room)?;
+        index.write_all(INDEX_MAGIC)?;
+        index.write_all(&VERSION.to_le_bytes())?;
+        Ok(Self {
+            log,
+            index,
+            offset: header.len(),
+            indexed: None,
+        })
+    }
+
+    pub fn append(&mut self, record: &Record) -> Anysult<()> {
+        let len = 8 + 2 + record.subject.len() + record.payload.len();
+        let len = u32::try_from(len)
+            .ok()
+            .filter(|len| *len <= MAX_RECORD_LEN)
+            .ok_or_else(|| anyhow!("record of {} bytes", len))?;
+        let offset = self.offset;
+        self.log.write_all(&len.to_le_bytes())?;
+        self.log.write_all(&record.arrival.to_le_bytes())?;
+        write_str16(&mut self.log, &record.subject)?;
+        self.log.write_all(&record.payload)?;
+        self.offset += 4 + u64::from(len);
+        if self
+            .indexed
+            .is_none_or(|indexed| record.arrival >= indexed + INDEX_INTERVAL_MS)
+        {
+            // The record has to reach the file before the entry pointing at it does.
+            self.log.flush()?;
+            self.index.write_all(&record.arrival.to_le_bytes())?;
+            self.index.write_all(&offset.to_le_bytes())?;
+            self.indexed = Some(record.arrival);
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Anysult<()> {
+        self.log.flush()?;
+        self.index.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads the records of a log, see [PacketLogReader::records].
+#[derive(Debug)]
+pub struct PacketLogReader<R: Read> {
+    header: LogHeader,
+    log: R,
+}
+
+impl<R: Read> PacketLogReader<R> {
+    pub fn new(mut log: R) -> Anysult<Self> {
+        let version = read_magic(&mut log, LOG_MAGIC)?;
+        let started_at = read_u64(&mut log)?;
+        let room = read_str16(&mut log)?;
+        Ok(Self {
+            header: LogHeader {
+                version,
+                started_at,
+                room,
+            },
+            log,
+        })
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+
+    /// Gives the underlying reader back, e.g. to seek it to an offset from the index.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.log
+    }
+
+    /// Reads the next record, `None` at the end of the log. A record cut short is an error.
+    pub fn next_record(&mut self) -> Anysult<Option<Record>> {
+        let mut len = [0; 4];
+        match self.log.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let len = u32::from_le_bytes(len);
+        if !(MIN_RECORD_LEN..=MAX_RECORD_LEN).contains(&len) {
+            bail!("corrupt record of {} bytes", len);
+        }
+        let mut body = vec![0; len as usize];
+        self.log
+            .read_exact(&mut body)
+            .map_err(|e| anyhow!("truncated record: {}", e))?;
+        let mut body = body.as_slice();
+        let arrival = read_u64(&mut body)?;
+        let subject = read_str16(&mut body)?;
+        Ok(Some(Record {
+            arrival,
+            subject,
+            payload: body.to_vec(),
+        }))
+    }
+
+    pub fn records(self) -> Records<R> {
+        Records {
+            reader: self,
+            failed: false,
+        }
+    }
+}
+
+/// Iterator over the records of a log. Stops after the first error.
+#[derive(Debug)]
+pub struct Records<R: Read> {
+    reader: PacketLogReader<R>,
+    failed: bool,
+}
+
+impl<R: Read> Iterator for Records<R> {
+    type Item = Anysult<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let record = self.reader.next_record().transpose();
+        self.failed = matches!(record, Some(Err(_)));
+        record
+    }
+}
+
+/// Reads every entry of an index. A trailing entry cut short is ignored.
+pub fn read_index(mut index: impl Read) -> Anysult<Vec<IndexEntry>> {
+    read_magic(&mut index, INDEX_MAGIC)?;
+    let mut entries = Vec::new();
+    loop {
+        let mut entry = [0; 16];
+        match index.read_exact(&mut entry) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(entries),
+            Err(e) => return Err(e.into()),
+        }
+        let mut entry = entry.as_slice();
+        entries.push(IndexEntry {
+            arrival: read_u64(&mut entry)?,
+            offset: read_u64(&mut entry)?,
+        });
+    }
+}
@@ -0,0 +1,118 @@
+//! Publishes a [packet_log](super::packet_log) into a room again, keeping the time between
+//! packets, so a meeting can be reproduced without its participants.
+//!
+//! Every packet is published on behalf of `replay-{session}`, where `session` is the one it was
+//! recorded from, so live sessions of the target room get the packets of each recorded session
+//! from a session of its own. `CONTROL` packets are left out: replaying the host's commands would
+//! mute or remove the live participants, and a `STOP_RECORDING` would end a recording of the target
+//! room. So are the packets only the server may send, such as `PARTICIPANT_LEFT` or
+//! `ROOM_SETTINGS`, which would misinform the live sessions about their own room.
+//!
+//! Participants can be replayed under other identities. Only the `email` of the `PacketWrapper` is
+//! changed, media payloads are published as they were recorded.
+use std::collections::HashMap;
+use std::io::Read;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result as Anysult};
+use bytes::Bytes;
+use protobuf::Message;
+use tokio::time::{sleep_until, Instant};
+use tracing::debug;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+use super::packet_log::{PacketLogReader, Record};
+use crate::actors::chat_session::{Email, RoomId};
+use crate::bus::RoomBus;
+use crate::sender::is_server_only;
+
+/// Prefix of the sessions replayed packets are published from.
+pub const REPLAY_SESSION_PREFIX: &str = "replay-";
+
+/// What [Replay::run] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub published: usize,
+    /// Records that were not published: `CONTROL` and server only packets, and those that aren't
+    /// packets.
+    pub skipped: usize,
+}
+
+/// Replays packet logs into a room.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    room: RoomId,
+    speed: f64,
+    identities: HashMap<Email, Email>,
+}
+
+impl Replay {
+    pub fn new(room: impl Into<RoomId>) -> Self {
+        Self {
+            room: room.into(),
+            speed: 1.0,
+            identities: HashMap::new(),
+        }
+    }
+
+    /// Plays the log `speed` times as fast as it was recorded.
+    pub fn with_speed(mut self, speed: f64) -> Anysult<Self> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err(anyhow!("invalid speed {}", speed));
+        }
+        self.speed = speed;
+        Ok(self)
+    }
+
+    /// Replays the packets of `from` as if `to` sent them.
+    pub fn with_identity(mut self, from: impl Into<Email>, to: impl Into<Email>) -> Self {
+        self.identities.insert(from.into(), to.into());
+        self
+    }
+
+    /// The session and payload `record` is published with, `None` when it is not replayed.
+    pub fn packet(&self, record: &Record) -> Option<(String, Bytes)> {
+        let (_, session) = record.subject.rsplit_once('.')?;
+        let mut packet = PacketWrapper::parse_from_bytes(&record.payload).ok()?;
+        let packet_type = packet.packet_type.enum_value().ok()?;
+        if packet_type == PacketType::CONTROL || is_server_only(packet_type) {
+            return None;
+        }
+        let session = format!("{}{}", REPLAY_SESSION_PREFIX, session);
+        let Some(email) = self.identities.get(&packet.email) else {
+            return Some((session, Bytes::copy_from_slice(&record.payload)));
+        };
+        packet.email = email.clone();
+        Some((session, Bytes::from(packet.write_to_bytes().ok()?)))
+    }
+
+    /// When the record that arrived `since_start` milliseconds into the recording is published.
+    fn due(&self, start: Instant, since_start: u64) -> Instant {
+        start + Duration::from_secs_f64(since_start as f64 / 1000.0 / self.speed)
+    }
+
+    /// Publishes the records of `log` into the room, as far apart as they arrived. Reading
+    /// blocks, which a local file hardly does.
+    pub async fn run<R: Read>(
+        &self,
+        bus: &dyn RoomBus,
+        log: PacketLogReader<R>,
+    ) -> Anysult<ReplayReport> {
+        let started_at = log.header().started_at;
+        let start = Instant::now();
+        let mut report = ReplayReport::default();
+        for record in log.records() {
+            let record = record?;
+            let Some((session, payload)) = self.packet(&record) else {
+                report.skipped += 1;
+                continue;
+            };
+            sleep_until(self.due(start, record.arrival.saturating_sub(started_at))).await;
+            debug!("replaying {} into {}", session, self.room);
+            bus.publish_to_room(&self.room, &session, payload).await?;
+            report.published += 1;
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,359 @@
+//! Just enough of WebM to store VP9 and Opus frames as they are, one track per file.
+//!
+//! A file holds an EBML header and a Segment with a SeekHead, Info, Tracks, Clusters of
+//! SimpleBlocks and Cues. Times are in milliseconds. The sizes, the duration and the position of
+//! the Cues are only known once every frame is written, so [WebmWriter::finish] goes back and
+//! fills them in.
+use std::io::{Seek, SeekFrom, Write};
+
+use anyhow::{anyhow, Result as Anysult};
+
+const EBML: u32 = 0x1A45DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const VOID: u32 = 0xEC;
+const SEGMENT: u32 = 0x18538067;
+const SEEK_HEAD: u32 = 0x114D9B74;
+const SEEK: u32 = 0x4DBB;
+const SEEK_ID: u32 = 0x53AB;
+const SEEK_POSITION: u32 = 0x53AC;
+const INFO: u32 = 0x1549A966;
+const TIMECODE_SCALE: u32 = 0x2AD7B1;
+const DURATION: u32 = 0x4489;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const FLAG_LACING: u32 = 0x9C;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63A2;
+const CODEC_DELAY: u32 = 0x56AA;
+const SEEK_PRE_ROLL: u32 = 0x56BB;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+const CLUSTER: u32 = 0x1F43B675;
+const TIMECODE: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+const CUES: u32 = 0x1C53BB6B;
+const CUE_POINT: u32 = 0xBB;
+const CUE_TIME: u32 = 0xB3;
+const CUE_TRACK_POSITIONS: u32 = 0xB7;
+const CUE_TRACK: u32 = 0xF7;
+const CUE_CLUSTER_POSITION: u32 = 0xF1;
+
+/// Nanoseconds per tick, making ticks milliseconds.
+const TIMECODE_SCALE_NS: u64 = 1_000_000;
+/// Clusters are cut at least this often so seeking works without keyframes, and always well
+/// within the 16 bit block timecodes.
+const MAX_CLUSTER_MS: u64 = 5_000;
+/// Opus decoders need 80ms of audio to converge after a seek.
+const OPUS_SEEK_PRE_ROLL_NS: u64 = 80_000_000;
+const TRACK: u64 = 1;
+
+/// What the single track of a file holds.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Track {
+    Vp9 { width: u16, height: u16 },
+    Opus { channels: u8, sample_rate: u32 },
+}
+
+impl Track {
+    fn is_video(&self) -> bool {
+        matches!(self, Track::Vp9 { .. })
+    }
+
+    fn entry(&self) -> Vec<u8> {
+        let mut entry = [
+            uint(TRACK_NUMBER, TRACK),
+            uint(TRACK_UID, TRACK),
+            uint(FLAG_LACING, 0),
+        ]
+        .concat();
+        match self {
+            Track::Vp9 { width, height } => {
+                entry.extend(uint(TRACK_TYPE, 1));
+                entry.extend(string(CODEC_ID, "V_VP9"));
+                entry.extend(element(
+                    VIDEO,
+                    &[
+                        uint(PIXEL_WIDTH, u64::from(*width)),
+                        uint(PIXEL_HEIGHT, u64::from(*height)),
+                    ]
+                    .concat(),
+                ));
+            }
+            Track::Opus {
+                channels,
+                sample_rate,
+            } => {
+                entry.extend(uint(TRACK_TYPE, 2));
+                entry.extend(string(CODEC_ID, "A_OPUS"));
+                entry.extend(element(CODEC_PRIVATE, &opus_head(*channels, *sample_rate)));
+                entry.extend(uint(CODEC_DELAY, 0));
+                entry.extend(uint(SEEK_PRE_ROLL, OPUS_SEEK_PRE_ROLL_NS));
+                entry.extend(element(
+                    AUDIO,
+                    &[
+                        float(SAMPLING_FREQUENCY, f64::from(*sample_rate)),
+                        uint(CHANNELS, u64::from(*channels)),
+                    ]
+                    .concat(),
+                ));
+            }
+        }
+        element(TRACK_ENTRY, &entry)
+    }
+}
+
+/// The identification header Opus tracks carry as codec private data, RFC 7845 section 5.1.
+fn opus_head(channels: u8, sample_rate: u32) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels);
+    // No pre-skip: the browser's encoder output is stored as it was played.
+    head.extend(0u16.to_le_bytes());
+    head.extend(sample_rate.to_le_bytes());
+    // Output gain and channel mapping family.
+    head.extend(0i16.to_le_bytes());
+    head.push(0);
+    head
+}
+
+fn id_bytes(id: u32) -> Vec<u8> {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count();
+    bytes[skip..].to_vec()
+}
+
+/// The shortest EBML variable size integer for `size`.
+fn size_bytes(size: u64) -> Vec<u8> {
+    let len = (1..=8).find(|len| size < (1 << (7 * len)) - 1).unwrap_or(8);
+    sized(size, len)
+}
+
+/// `size` as an EBML variable size integer of `len` bytes.
+fn sized(size: u64, len: usize) -> Vec<u8> {
+    let marked = size | (1 << (7 * len));
+    marked.to_be_bytes()[8 - len..].to_vec()
+}
+
+fn element(id: u32, body: &[u8]) -> Vec<u8> {
+    [id_bytes(id), size_bytes(body.len() as u64), body.to_vec()].concat()
+}
+
+fn uint(id: u32, value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(7);
+    element(id, &bytes[skip..])
+}
+
+/// An unsigned integer always taking 8 bytes, so it can be overwritten later.
+fn fixed_uint(id: u32, value: u64) -> Vec<u8> {
+    element(id, &value.to_be_bytes())
+}
+
+fn float(id: u32, value: f64) -> Vec<u8> {
+    element(id, &value.to_be_bytes())
+}
+
+fn string(id: u32, value: &str) -> Vec<u8> {
+    element(id, value.as_bytes())
+}
+
+fn seek_entry(id: u32, position: u64) -> Vec<u8> {
+    element(
+        SEEK,
+        &[
+            element(SEEK_ID, &id_bytes(id)),
+            fixed_uint(SEEK_POSITION, position),
+        ]
+        .concat(),
+    )
+}
+
+/// Writes the frames of one track into a WebM file.
+#[derive(Debug)]
+pub struct WebmWriter<W: Write + Seek> {
+    out: W,
+    video: bool,
+    /// Where the Segment's size goes.
+    segment_size_at: u64,
+    /// Where the Segment's data starts, positions in the SeekHead and Cues are relative to it.
+    segment_start: u64,
+    duration_at: u64,
+    cues_seek_at: u64,
+    cluster: Vec<u8>,
+    cluster_time: Option<u64>,
+    cues: Vec<(u64, u64)>,
+    last_time: Option<u64>,
+    end_time: u64,
+}
+
+impl<W: Write + Seek> WebmWriter<W> {
+    /// Writes everything that goes before the frames.
+    pub fn new(mut out: W, track: &Track) -> Anysult<Self> {
+        out.write_all(&element(
+            EBML,
+            &[
+                uint(EBML_VERSION, 1),
+                uint(EBML_READ_VERSION, 1),
+                uint(EBML_MAX_ID_LENGTH, 4),
+                uint(EBML_MAX_SIZE_LENGTH, 8),
+                string(DOC_TYPE, "webm"),
+                uint(DOC_TYPE_VERSION, 4),
+                uint(DOC_TYPE_READ_VERSION, 2),
+            ]
+            .concat(),
+        ))?;
+        out.write_all(&id_bytes(SEGMENT))?;
+        let segment_size_at = out.stream_position()?;
+        // Unknown size until finished.
+        out.write_all(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF])?;
+        let segment_start = out.stream_position()?;
+
+        let info = [
+            uint(TIMECODE_SCALE, TIMECODE_SCALE_NS),
+            string(MUXING_APP, "videocall"),
+            string(WRITING_APP, "videocall"),
+        ]
+        .concat();
+        let info = element(INFO, &[info, float(DURATION, 0.0)].concat());
+        let tracks = element(TRACKS, &track.entry());
+        let placeholder = seek_entry(CUES, 0);
+        let seek_head_len = element(SEEK_HEAD, &placeholder.repeat(3)).len() as u64;
+        let info_pos = seek_head_len;
+        let tracks_pos = info_pos + info.len() as u64;
+        let seek_info = seek_entry(INFO, info_pos);
+        let seek_tracks = seek_entry(TRACKS, tracks_pos);
+        let seek_head = element(
+            SEEK_HEAD,
+            &[seek_info, seek_tracks, placeholder.clone()].concat(),
+        );
+        debug_assert_eq!(seek_head.len() as u64, seek_head_len);
+        let cues_seek_at = segment_start + seek_head_len - placeholder.len() as u64;
+        out.write_all(&seek_head)?;
+        // The duration is the last element of Info: an 8 byte float.
+        let duration_at = segment_start + tracks_pos - 8;
+        out.write_all(&info)?;
+        out.write_all(&tracks)?;
+        Ok(Self {
+            out,
+            video: track.is_video(),
+            segment_size_at,
+            segment_start,
+            duration_at,
+            cues_seek_at,
+            cluster: Vec::new(),
+            cluster_time: None,
+            cues: Vec::new(),
+            last_time: None,
+            end_time: 0,
+        })
+    }
+
+    /// Adds a frame at `time`, which may not be earlier than the previous frame's. Audio frames
+    /// are all keyframes.
+    pub fn write_frame(
+        &mut self,
+        time: u64,
+        duration: Option<u64>,
+        keyframe: bool,
+        data: &[u8],
+    ) -> Anysult<()> {
+        if self.last_time.is_some_and(|last| time < last) {
+            return Err(anyhow!(
+                "frame at {}ms after one at {:?}ms",
+                time,
+                self.last_time
+            ));
+        }
+        let new_cluster = match self.cluster_time {
+            None => true,
+            Some(start) => (self.video && keyframe) || time - start >= MAX_CLUSTER_MS,
+        };
+        if new_cluster {
+            self.flush_cluster()?;
+            let position = self.out.stream_position()? - self.segment_start;
+            if keyframe {
+                self.cues.push((time, position));
+            }
+            self.cluster_time = Some(time);
+        }
+        let relative = (time - self.cluster_time.unwrap_or(time)) as i16;
+        let mut block = sized(TRACK, 1);
+        block.extend(relative.to_be_bytes());
+        block.push(if keyframe { 0x80 } else { 0 });
+        block.extend(data);
+        self.cluster.extend(element(SIMPLE_BLOCK, &block));
+        self.last_time = Some(time);
+        self.end_time = self.end_time.max(time + duration.unwrap_or(0));
+        Ok(())
+    }
+
+    fn flush_cluster(&mut self) -> Anysult<()> {
+        let Some(time) = self.cluster_time.take() else {
+            return Ok(());
+        };
+        let body = [uint(TIMECODE, time), std::mem::take(&mut self.cluster)].concat();
+        self.out.write_all(&element(CLUSTER, &body))?;
+        Ok(())
+    }
+
+    /// Writes the Cues and fills in the sizes and the duration. Returns the output.
+    pub fn finish(mut self) -> Anysult<W> {
+        self.flush_cluster()?;
+        let cues_position = self.out.stream_position()? - self.segment_start;
+        if self.cues.is_empty() {
+            // Turn the SeekHead entry pointing to the Cues into padding.
+            let len = seek_entry(CUES, 0).len();
+            self.out.seek(SeekFrom::Start(self.cues_seek_at))?;
+            self.out
+                .write_all(&[id_bytes(VOID), sized(len as u64 - 2, 1)].concat())?;
+            self.out.write_all(&vec![0; len - 2])?;
+        } else {
+            let cues: Vec<u8> = self
+                .cues
+                .iter()
+                .flat_map(|(time, position)| {
+                    let positions = [
+                        uint(CUE_TRACK, TRACK),
+                        uint(CUE_CLUSTER_POSITION, *position),
+                    ]
+                    .concat();
+                    element(
+                        CUE_POINT,
+                        &[
+                            uint(CUE_TIME, *time),
+                            element(CUE_TRACK_POSITIONS, &positions),
+                        ]
+                        .concat(),
+                    )
+                })
+                .collect();
+            self.out.write_all(&element(CUES, &cues))?;
+            self.out.seek(SeekFrom::Start(self.cues_seek_at))?;
+            self.out.write_all(&seek_entry(CUES, cues_position))?;
+        }
+        let end = self.out.seek(SeekFrom::End(0))?;
+        self.out.seek(SeekFrom::Start(self.segment_size_at))?;
+        self.out.write_all(&sized(end - self.segment_start, 8))?;
+        self.out.seek(SeekFrom::Start(self.duration_at))?;
+        let duration = self.end_time.max(self.last_time.unwrap_or(0)) as f64;
+        self.out.write_all(&duration.to_be_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
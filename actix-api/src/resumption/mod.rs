@@ -0,0 +1,197 @@
+//! Session resumption: a websocket session whose connection drops is kept in its room for a grace
+//! window, so a client reconnecting with its resumption token gets the same session id and room
+//! subscription back and its peers never see it leave.
+//!
+//! Sessions get a token in a `RESUMPTION` packet after joining and a new one every time they
+//! resume. While a session is detached its [Outbox] buffers the control packets of the room, and
+//! it always keeps those of the last [BUFFER_WINDOW], which its old connection may have lost. They
+//! are replayed to the resumed session; media is not.
+//!
+//! Detached sessions are held by the server process they were on, so with several replicas the
+//! load balancer has to send a client back to the same one for resumption to work. Clients whose
+//! token is unknown simply join as a new session.
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix::prelude::SendError;
+use actix::Recipient;
+use bytes::Bytes;
+use protobuf::Message as _;
+use rand::RngCore;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+use crate::messages::session::Message;
+use crate::sender::packet_type;
+
+const DEFAULT_GRACE_SECS: u64 = 10;
+/// How far back control packets are kept, counted from when the connection was lost.
+pub const BUFFER_WINDOW: Duration = Duration::from_secs(5);
+/// Most control packets buffered for one session, the oldest are dropped first.
+pub const MAX_BUFFERED: usize = 256;
+
+/// How sessions are held for resumption.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Resumption {
+    /// How long a session whose connection dropped waits to be resumed before it leaves its
+    /// room. Zero disables resumption.
+    pub grace: Duration,
+    pub buffer_window: Duration,
+    pub max_buffered: usize,
+}
+
+impl Default for Resumption {
+    fn default() -> Self {
+        Resumption {
+            grace: Duration::from_secs(DEFAULT_GRACE_SECS),
+            buffer_window: BUFFER_WINDOW,
+            max_buffered: MAX_BUFFERED,
+        }
+    }
+}
+
+impl Resumption {
+    /// Reads the grace window from `SESSION_RESUME_GRACE_SECS` (default 10, 0 disables it).
+    pub fn from_env() -> Self {
+        let grace = std::env::var("SESSION_RESUME_GRACE_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_GRACE_SECS);
+        Resumption {
+            grace: Duration::from_secs(grace),
+            ..Default::default()
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Resumption {
+            grace: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.grace.is_zero()
+    }
+}
+
+/// A new random resumption token.
+pub fn new_token() -> String {
+    let mut token = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut token);
+    hex::encode(token)
+}
+
+/// Builds the server generated `RESUMPTION` packet handing `email` the token to resume its
+/// session with.
+pub fn resumption_packet(email: &str, token: &str) -> Bytes {
+    let packet = PacketWrapper {
+        packet_type: PacketType::RESUMPTION.into(),
+        email: email.to_string(),
+        data: token.as_bytes().to_vec(),
+        ..Default::default()
+    };
+    Bytes::from(packet.write_to_bytes().unwrap_or_default())
+}
+
+/// Packets a resumed session needs to catch up on: everything but media.
+fn is_control(data: &[u8]) -> bool {
+    packet_type(data).is_some_and(|packet_type| packet_type != PacketType::MEDIA)
+}
+
+/// Where the room subscription of a session delivers the packets for its client.
+pub struct Outbox {
+    state: Mutex<OutboxState>,
+    buffer_window: Duration,
+    max_buffered: usize,
+}
+
+struct OutboxState {
+    /// `None` while the session is detached.
+    recipient: Option<Recipient<Message>>,
+    detached_at: Option<Instant>,
+    buffered: VecDeque<(Instant, Vec<u8>)>,
+}
+
+impl std::fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Outbox")
+            .field("buffer_window", &self.buffer_window)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Outbox {
+    /// Buffers nothing when `resumption` is disabled.
+    pub fn new(recipient: Recipient<Message>, resumption: &Resumption) -> Self {
+        let (buffer_window, max_buffered) = if resumption.enabled() {
+            (resumption.buffer_window, resumption.max_buffered)
+        } else {
+            (Duration::ZERO, 0)
+        };
+        Outbox {
+            state: Mutex::new(OutboxState {
+                recipient: Some(recipient),
+                detached_at: None,
+                buffered: VecDeque::new(),
+            }),
+            buffer_window,
+            max_buffered,
+        }
+    }
+
+    /// Passes `packet` on to the session, or only buffers it while the session is detached.
+    pub fn send(&self, packet: Vec<u8>) -> Result<(), SendError<Message>> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if self.max_buffered > 0 && is_control(&packet) {
+            if state.buffered.len() == self.max_buffered {
+                state.buffered.pop_front();
+            }
+            state.buffered.push_back((now, packet.clone()));
+        }
+        self.prune(&mut state, now);
+        match &state.recipient {
+            Some(recipient) => recipient.try_send(Message { msg: packet }),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops the packets from before the buffer window, which ends when the session was detached.
+    fn prune(&self, state: &mut OutboxState, now: Instant) {
+        let Some(cutoff) = state
+            .detached_at
+            .unwrap_or(now)
+            .checked_sub(self.buffer_window)
+        else {
+            return;
+        };
+        while state.buffered.front().is_some_and(|(at, _)| *at < cutoff) {
+            state.buffered.pop_front();
+        }
+    }
+
+    /// Stops delivering packets, which are buffered until the session is resumed.
+    pub fn detach(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.recipient = None;
+        state.detached_at = Some(Instant::now());
+    }
+
+    /// Delivers to `recipient` from now on, starting with the buffered packets.
+    ///
+    /// Returns how many were replayed.
+    pub fn attach(&self, recipient: Recipient<Message>) -> usize {
+        let mut state = self.state.lock().unwrap();
+        self.prune(&mut state, Instant::now());
+        let buffered = std::mem::take(&mut state.buffered);
+        let replayed = buffered.len();
+        for (_, packet) in buffered {
+            recipient.do_send(Message { msg: packet });
+        }
+        state.recipient = Some(recipient);
+        state.detached_at = None;
+        replayed
+    }
+}
@@ -0,0 +1,71 @@
+//! Sessions closed through the admin API.
+//!
+//! The admin API publishes an [AdminCommand] on [ADMIN_SUBJECT] and every server, the websocket
+//! and the webtransport ones alike, closes the sessions it holds that the command names.
+use std::future::Future;
+
+use anyhow::Result as Anysult;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::actors::chat_session::{RoomId, SessionId};
+use crate::bus::RoomBus;
+
+/// Subject the admin API publishes its commands on.
+pub const ADMIN_SUBJECT: &str = "admin.close";
+
+/// Who closed sessions the admin API closed, in the audit log.
+pub const ADMIN_ACTOR: &str = "admin";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloseTarget {
+    Session(SessionId),
+    Room(RoomId),
+}
+
+/// Closes the sessions of `target`, sending them `reason`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminCommand {
+    pub target: CloseTarget,
+    pub reason: String,
+}
+
+impl AdminCommand {
+    /// Whether the command closes `session`, which is in `room`.
+    pub fn closes(&self, session: &str, room: &str) -> bool {
+        match &self.target {
+            CloseTarget::Session(id) => id == session,
+            CloseTarget::Room(id) => id == room,
+        }
+    }
+
+    /// Sends the command to every server.
+    pub async fn publish(&self, bus: &dyn RoomBus) -> Anysult<()> {
+        let payload = serde_json::to_vec(self)?;
+        bus.publish(ADMIN_SUBJECT.to_string(), Bytes::from(payload))
+            .await
+    }
+}
+
+/// Subscribes to the admin commands. Every subscription gets its own queue, so each server sees
+/// every command.
+///
+/// The subscription is made right away, the returned future only waits for it to be set up.
+pub fn admin_commands(
+    bus: &dyn RoomBus,
+) -> impl Future<Output = Anysult<BoxStream<'static, AdminCommand>>> + Send + 'static {
+    let queue = format!("admin-{}", uuid::Uuid::new_v4().to_simple());
+    let subscription = bus.queue_subscribe(ADMIN_SUBJECT.to_string(), queue);
+    async move {
+        let commands = subscription.await?.filter_map(|msg| async move {
+            serde_json::from_slice(&msg.payload)
+                .map_err(|e| warn!("ignoring invalid admin command: {}", e))
+                .ok()
+        });
+        Ok(commands.boxed())
+    }
+}
@@ -0,0 +1,283 @@
+//! Per-room and server wide participant limits.
+//!
+//! With the database enabled, `MAX_PARTICIPANTS_PER_ROOM` is counted over every server in
+//! [Presence]; without it each server counts its own sessions, so a room can hold up to the limit
+//! on every replica. `MAX_PARTICIPANTS` protects a single server and is always counted per process.
+//! When the database can't be reached, joins are only checked against the counts of the server.
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::StreamExt;
+use tracing::{error, info};
+use types::protos::join_rejected::join_rejected_packet::Reason;
+
+use super::admin::{admin_commands, AdminCommand};
+use super::presence::Presence;
+use super::{rejection_packet, Participant};
+use crate::actors::chat_session::{RoomId, SessionId};
+use crate::bus::RoomBus;
+
+/// How many sessions a server admits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capacity {
+    /// `MAX_PARTICIPANTS_PER_ROOM`, no limit when unset. A room's `max_participants` setting can
+    /// only lower it.
+    pub per_room: Option<u32>,
+    /// `MAX_PARTICIPANTS`: sessions over all rooms of a server, no limit when unset.
+    pub total: Option<u32>,
+}
+
+fn env_limit(name: &str) -> Option<u32> {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|limit| *limit > 0)
+}
+
+impl Capacity {
+    pub fn from_env() -> Self {
+        Capacity {
+            per_room: env_limit("MAX_PARTICIPANTS_PER_ROOM"),
+            total: env_limit("MAX_PARTICIPANTS"),
+        }
+    }
+
+    /// The limit of a room whose settings allow `room_max` participants.
+    pub fn room_limit(&self, room_max: Option<u32>) -> Option<u32> {
+        match (self.per_room, room_max) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Checks whether one more session fits into a room of `in_room` sessions while the server
+    /// holds `total` sessions.
+    pub fn check(
+        &self,
+        in_room: usize,
+        total: usize,
+        room_max: Option<u32>,
+    ) -> Result<(), CapacityError> {
+        if self
+            .room_limit(room_max)
+            .is_some_and(|limit| in_room >= limit as usize)
+        {
+            return Err(CapacityError::RoomFull);
+        }
+        if self.total.is_some_and(|limit| total >= limit as usize) {
+            return Err(CapacityError::ServerFull);
+        }
+        Ok(())
+    }
+}
+
+/// Why a session was not admitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CapacityError {
+    RoomFull,
+    ServerFull,
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapacityError::RoomFull => write!(f, "room is full"),
+            CapacityError::ServerFull => write!(f, "server is full"),
+        }
+    }
+}
+
+impl std::error::Error for CapacityError {}
+
+impl CapacityError {
+    /// Builds the server generated `JOIN_REJECTED` packet sent right before the session is closed,
+    /// so clients can tell a full room from a lost connection.
+    pub fn to_packet(&self) -> Bytes {
+        let reason = match self {
+            CapacityError::RoomFull => Reason::ROOM_FULL,
+            CapacityError::ServerFull => Reason::SERVER_FULL,
+        };
+        rejection_packet(reason, &self.to_string())
+    }
+}
+
+/// Closes a session, telling it why.
+type Closer = Box<dyn Fn(&str) + Send + Sync>;
+
+struct Held {
+    room: RoomId,
+    participant: Participant,
+    close: Closer,
+}
+
+#[derive(Default)]
+struct Sessions {
+    rooms: HashMap<RoomId, usize>,
+    held: HashMap<SessionId, Held>,
+}
+
+/// Sessions of servers without a [RoomRegistry](super::RoomRegistry), such as the webtransport
+/// server. Admitted sessions hold a [Seat] for as long as they are in the room, and are written
+/// to [Presence] meanwhile when it is set.
+pub struct Occupancy {
+    capacity: Capacity,
+    presence: Option<Presence>,
+    sessions: Mutex<Sessions>,
+}
+
+impl fmt::Debug for Occupancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Occupancy")
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Occupancy {
+    pub fn new(capacity: Capacity, presence: Option<Presence>) -> Arc<Self> {
+        Arc::new(Occupancy {
+            capacity,
+            presence,
+            sessions: Mutex::default(),
+        })
+    }
+
+    /// Takes a seat in `room` for `participant` if the room and the server have one left.
+    /// `close` closes the session when an [AdminCommand] names it.
+    pub async fn admit(
+        self: &Arc<Self>,
+        room: &str,
+        participant: Participant,
+        room_max: Option<u32>,
+        close: impl Fn(&str) + Send + Sync + 'static,
+    ) -> Result<Seat, CapacityError> {
+        let session = participant.session_id.clone();
+        {
+            let mut sessions = self.sessions.lock().unwrap();
+            let in_room = sessions.rooms.get(room).copied().unwrap_or_default();
+            self.capacity
+                .check(in_room, sessions.held.len(), room_max)?;
+            *sessions.rooms.entry(room.to_string()).or_default() += 1;
+            sessions.held.insert(
+                session.clone(),
+                Held {
+                    room: room.to_string(),
+                    participant: participant.clone(),
+                    close: Box::new(close),
+                },
+            );
+        }
+        let seat = Seat {
+            occupancy: self.clone(),
+            session,
+        };
+        if let Some(presence) = &self.presence {
+            match presence
+                .admit(room, &participant, self.capacity.per_room)
+                .await
+            {
+                Ok(true) => {}
+                Ok(false) => return Err(CapacityError::RoomFull),
+                Err(e) => error!(
+                    "error counting room {} over every server, admitting {}: {}",
+                    room, participant.email, e
+                ),
+            }
+        }
+        Ok(seat)
+    }
+
+    pub fn room_size(&self, room: &str) -> usize {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.rooms.get(room).copied().unwrap_or_default()
+    }
+
+    pub fn total(&self) -> usize {
+        self.sessions.lock().unwrap().held.len()
+    }
+
+    /// Every session holding a seat.
+    pub fn session_ids(&self) -> Vec<SessionId> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.held.keys().cloned().collect()
+    }
+
+    /// Closes the sessions `command` names, returning how many. They give their seats back once
+    /// they are done.
+    pub fn run(&self, command: &AdminCommand) -> usize {
+        let sessions = self.sessions.lock().unwrap();
+        let mut closed = 0;
+        for (session, held) in &sessions.held {
+            if command.closes(session, &held.room) {
+                info!("closing session {}: {}", session, command.reason);
+                (held.close)(&command.reason);
+                closed += 1;
+            }
+        }
+        closed
+    }
+
+    /// Runs the admin commands published on `bus` for as long as it delivers them.
+    ///
+    /// The subscription is made right away, the returned future only runs the commands.
+    pub fn follow_admin_commands(
+        self: &Arc<Self>,
+        bus: &dyn RoomBus,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let commands = admin_commands(bus);
+        let occupancy = self.clone();
+        async move {
+            let mut commands = match commands.await {
+                Ok(commands) => commands,
+                Err(e) => {
+                    error!("error subscribing to admin commands: {}", e);
+                    return;
+                }
+            };
+            while let Some(command) = commands.next().await {
+                occupancy.run(&command);
+            }
+        }
+    }
+
+    fn release(&self, session: &SessionId) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(held) = sessions.held.remove(session) else {
+            return;
+        };
+        if let Some(in_room) = sessions.rooms.get_mut(&held.room) {
+            *in_room -= 1;
+            if *in_room == 0 {
+                sessions.rooms.remove(&held.room);
+            }
+        }
+        if let Some(presence) = self.presence.clone() {
+            let participant = held.participant;
+            tokio::spawn(async move {
+                if let Err(e) = presence.leave(&participant).await {
+                    error!(
+                        "error removing {} from room_presence: {}",
+                        participant.email, e
+                    );
+                }
+            });
+        }
+    }
+}
+
+/// A session's place in a room, given back when dropped.
+#[derive(Debug)]
+pub struct Seat {
+    occupancy: Arc<Occupancy>,
+    session: SessionId,
+}
+
+impl Drop for Seat {
+    fn drop(&mut self) {
+        self.occupancy.release(&self.session);
+    }
+}
@@ -0,0 +1,226 @@
+//! Bookkeeping of which sessions are in which room.
+//!
+//! Each server keeps its own sessions in a [RoomRegistry] or [Occupancy](capacity::Occupancy).
+//! With the database enabled they are also written to [Presence](presence::Presence), which is
+//! how the admin API sees the sessions of every server.
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use protobuf::Message;
+use serde::Serialize;
+use types::protos::join_rejected::join_rejected_packet::Reason;
+use types::protos::join_rejected::JoinRejectedPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+use crate::actors::chat_session::{Email, RoomId, SessionId};
+use capacity::{Capacity, CapacityError};
+
+pub mod admin;
+pub mod capacity;
+pub mod presence;
+pub mod settings;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    WebSocket,
+    WebTransport,
+    /// Raw QUIC clients of the webtransport server.
+    Quic,
+}
+
+impl Transport {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Transport::WebSocket => "websocket",
+            Transport::WebTransport => "webtransport",
+            Transport::Quic => "quic",
+        }
+    }
+}
+
+impl FromStr for Transport {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "websocket" => Ok(Transport::WebSocket),
+            "webtransport" => Ok(Transport::WebTransport),
+            "quic" => Ok(Transport::Quic),
+            other => Err(anyhow!("unknown transport {:?}", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Participant {
+    pub session_id: SessionId,
+    pub email: Email,
+    /// Milliseconds since the unix epoch.
+    pub joined_at: u64,
+    pub transport: Transport,
+}
+
+impl Participant {
+    pub fn new(session_id: SessionId, email: Email, transport: Transport) -> Self {
+        Participant {
+            session_id,
+            email,
+            joined_at: now_millis(),
+            transport,
+        }
+    }
+}
+
+/// A room with sessions in it.
+#[derive(Clone, Debug, Serialize)]
+pub struct RoomSummary {
+    pub room: RoomId,
+    pub participants: usize,
+    /// How many of the participants use each transport.
+    pub transports: BTreeMap<Transport, usize>,
+}
+
+/// A session in a room.
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionInfo {
+    pub room: RoomId,
+    #[serde(flatten)]
+    pub participant: Participant,
+}
+
+/// Room -> sessions index, kept up to date by the server as sessions join and leave.
+#[derive(Debug, Default)]
+pub struct RoomRegistry {
+    rooms: HashMap<RoomId, HashMap<SessionId, Participant>>,
+    session_rooms: HashMap<SessionId, RoomId>,
+}
+
+impl RoomRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `participant` to `room`, moving it out of any room it was in before.
+    pub fn join(&mut self, room: RoomId, participant: Participant) {
+        self.leave(&participant.session_id);
+        self.session_rooms
+            .insert(participant.session_id.clone(), room.clone());
+        self.rooms
+            .entry(room)
+            .or_default()
+            .insert(participant.session_id.clone(), participant);
+    }
+
+    /// Removes `session` from its room, returning the room and the participant it was.
+    pub fn leave(&mut self, session: &SessionId) -> Option<(RoomId, Participant)> {
+        let room = self.session_rooms.remove(session)?;
+        let participants = self.rooms.get_mut(&room)?;
+        let participant = participants.remove(session)?;
+        if participants.is_empty() {
+            self.rooms.remove(&room);
+        }
+        Some((room, participant))
+    }
+
+    /// Checks whether `session` fits into `room`. A session already in the server does not count
+    /// against the limits, so moving between rooms only needs a seat in the new one.
+    pub fn admit(
+        &self,
+        room: &str,
+        session: &SessionId,
+        capacity: &Capacity,
+        room_max: Option<u32>,
+    ) -> Result<(), CapacityError> {
+        let in_room = self.rooms.get(room).map_or(0, |participants| {
+            participants.len() - usize::from(participants.contains_key(session))
+        });
+        let total =
+            self.session_rooms.len() - usize::from(self.session_rooms.contains_key(session));
+        capacity.check(in_room, total, room_max)
+    }
+
+    /// Participants of `room` in the order they joined.
+    pub fn participants(&self, room: &str) -> Vec<Participant> {
+        let mut participants: Vec<Participant> = self
+            .rooms
+            .get(room)
+            .map(|participants| participants.values().cloned().collect())
+            .unwrap_or_default();
+        participants.sort_by_key(|p| p.joined_at);
+        participants
+    }
+
+    /// Every room with sessions in it, by room id.
+    pub fn rooms(&self) -> Vec<RoomSummary> {
+        let mut rooms: Vec<RoomSummary> = self
+            .rooms
+            .iter()
+            .map(|(room, participants)| {
+                let mut transports = BTreeMap::new();
+                for participant in participants.values() {
+                    *transports.entry(participant.transport).or_default() += 1;
+                }
+                RoomSummary {
+                    room: room.clone(),
+                    participants: participants.len(),
+                    transports,
+                }
+            })
+            .collect();
+        rooms.sort_by(|a, b| a.room.cmp(&b.room));
+        rooms
+    }
+
+    /// Every session in a room.
+    pub fn session_ids(&self) -> Vec<SessionId> {
+        self.session_rooms.keys().cloned().collect()
+    }
+
+    pub fn session(&self, session: &SessionId) -> Option<SessionInfo> {
+        let room = self.session_rooms.get(session)?;
+        let participant = self.rooms.get(room)?.get(session)?;
+        Some(SessionInfo {
+            room: room.clone(),
+            participant: participant.clone(),
+        })
+    }
+}
+
+/// Builds the server generated `PARTICIPANT_JOINED` / `PARTICIPANT_LEFT` packet announcing
+/// `email` to the rest of the room.
+pub fn participant_packet(packet_type: PacketType, email: &str) -> Bytes {
+    let packet = PacketWrapper {
+        packet_type: packet_type.into(),
+        email: email.to_string(),
+        ..Default::default()
+    };
+    Bytes::from(packet.write_to_bytes().unwrap_or_default())
+}
+
+/// Builds the server generated `JOIN_REJECTED` packet sent right before a session is closed, so
+/// clients can tell a refused join from a lost connection.
+pub fn rejection_packet(reason: Reason, message: &str) -> Bytes {
+    let rejected = JoinRejectedPacket {
+        reason: reason.into(),
+        message: message.to_string(),
+        ..Default::default()
+    };
+    let packet = PacketWrapper {
+        packet_type: PacketType::JOIN_REJECTED.into(),
+        data: rejected.write_to_bytes().unwrap_or_default(),
+        ..Default::default()
+    };
+    Bytes::from(packet.write_to_bytes().unwrap_or_default())
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
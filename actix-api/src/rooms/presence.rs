@@ -0,0 +1,212 @@
+//! The sessions in each room over every server, kept in `room_presence`.
+//!
+//! Servers write their sessions as they join and leave, and refresh them every
+//! [PRESENCE_REFRESH]. Joins are checked against `MAX_PARTICIPANTS_PER_ROOM` here, so the limit
+//! holds over every server. Sessions that weren't refreshed for [PRESENCE_TTL] belong to a server
+//! that stopped, or left while their join was still being written, and are no longer counted.
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::Result as Anysult;
+use tokio_postgres::Row;
+use tracing::error;
+
+use super::{Participant, RoomSummary, SessionInfo, Transport};
+use crate::actors::chat_session::SessionId;
+use crate::db::PostgresPool;
+
+/// How often servers refresh their sessions.
+pub const PRESENCE_REFRESH: Duration = Duration::from_secs(10);
+/// How long after its last refresh a session is no longer counted.
+pub const PRESENCE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct Presence {
+    pool: PostgresPool,
+}
+
+impl std::fmt::Debug for Presence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Presence").finish_non_exhaustive()
+    }
+}
+
+fn participant(row: &Row) -> Anysult<Participant> {
+    let transport: String = row.get("transport");
+    let joined_at: i64 = row.get("joined_at");
+    Ok(Participant {
+        session_id: row.get("session_id"),
+        email: row.get("email"),
+        joined_at: joined_at as u64,
+        transport: transport.parse()?,
+    })
+}
+
+impl Presence {
+    pub fn new(pool: PostgresPool) -> Self {
+        Presence { pool }
+    }
+
+    /// Puts `participant` into `room`, moving it out of any room it was in before, unless the
+    /// room already holds `limit` other sessions over every server. Returns whether it did.
+    ///
+    /// Joins of a room wait for each other on an advisory lock, so two servers can't both take
+    /// the last seat.
+    pub async fn admit(
+        &self,
+        room: &str,
+        participant: &Participant,
+        limit: Option<u32>,
+    ) -> Anysult<bool> {
+        let mut connection = self.pool.get().await?;
+        let ttl = PRESENCE_TTL.as_secs_f64();
+        let transaction = connection.transaction().await?;
+        transaction
+            .execute(
+                "SELECT pg_advisory_xact_lock(hashtext('room_presence.' || $1::TEXT))",
+                &[&room],
+            )
+            .await?;
+        if let Some(limit) = limit {
+            let others: i64 = transaction
+                .query_one(
+                    "SELECT count(*) AS sessions FROM room_presence
+                        WHERE room=$1 AND session_id<>$2
+                        AND seen_at > now() - make_interval(secs => $3)",
+                    &[&room, &participant.session_id, &ttl],
+                )
+                .await?
+                .get("sessions");
+            if others >= i64::from(limit) {
+                return Ok(false);
+            }
+        }
+        transaction
+            .execute(
+                "DELETE FROM room_presence WHERE seen_at <= now() - make_interval(secs => $1)",
+                &[&ttl],
+            )
+            .await?;
+        transaction
+            .execute(
+                "INSERT INTO room_presence (session_id, room, email, transport, joined_at)
+                    VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (session_id) DO UPDATE SET room = $2, email = $3, transport = $4,
+                        joined_at = $5, seen_at = now()",
+                &[
+                    &participant.session_id,
+                    &room,
+                    &participant.email,
+                    &participant.transport.as_str(),
+                    &(participant.joined_at as i64),
+                ],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    /// Takes `participant` out of its room, unless it joined a room again since. Servers don't
+    /// wait for one write before the next, so the join may be written first.
+    pub async fn leave(&self, participant: &Participant) -> Anysult<()> {
+        let connection = self.pool.get().await?;
+        connection
+            .execute(
+                "DELETE FROM room_presence WHERE session_id=$1 AND joined_at=$2",
+                &[&participant.session_id, &(participant.joined_at as i64)],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Marks `sessions` as still there.
+    pub async fn refresh(&self, sessions: &[SessionId]) -> Anysult<()> {
+        if sessions.is_empty() {
+            return Ok(());
+        }
+        let connection = self.pool.get().await?;
+        connection
+            .execute(
+                "UPDATE room_presence SET seen_at = now() WHERE session_id = ANY($1)",
+                &[&sessions],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Refreshes the sessions `sessions` returns every [PRESENCE_REFRESH], for as long as the
+    /// server runs.
+    pub async fn keep_alive(self, sessions: impl Fn() -> Vec<SessionId>) {
+        let mut interval = tokio::time::interval(PRESENCE_REFRESH);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.refresh(&sessions()).await {
+                error!("error refreshing the sessions in room_presence: {}", e);
+            }
+        }
+    }
+
+    /// Participants of `room` in the order they joined.
+    pub async fn participants(&self, room: &str) -> Anysult<Vec<Participant>> {
+        let connection = self.pool.get().await?;
+        let rows = connection
+            .query(
+                "SELECT session_id, email, transport, joined_at FROM room_presence
+                    WHERE room=$1 AND seen_at > now() - make_interval(secs => $2)
+                    ORDER BY joined_at, session_id",
+                &[&room, &PRESENCE_TTL.as_secs_f64()],
+            )
+            .await?;
+        rows.iter().map(participant).collect()
+    }
+
+    /// Every room with sessions in it, by room id.
+    pub async fn rooms(&self) -> Anysult<Vec<RoomSummary>> {
+        let connection = self.pool.get().await?;
+        let rows = connection
+            .query(
+                "SELECT room, transport, count(*) AS sessions FROM room_presence
+                    WHERE seen_at > now() - make_interval(secs => $1)
+                    GROUP BY room, transport
+                    ORDER BY room",
+                &[&PRESENCE_TTL.as_secs_f64()],
+            )
+            .await?;
+        let mut rooms: Vec<RoomSummary> = Vec::new();
+        for row in rows {
+            let room: String = row.get("room");
+            let transport: Transport = row.get::<_, String>("transport").parse()?;
+            let sessions = row.get::<_, i64>("sessions") as usize;
+            if rooms.last().is_none_or(|summary| summary.room != room) {
+                rooms.push(RoomSummary {
+                    room,
+                    participants: 0,
+                    transports: BTreeMap::new(),
+                });
+            }
+            if let Some(summary) = rooms.last_mut() {
+                summary.participants += sessions;
+                summary.transports.insert(transport, sessions);
+            }
+        }
+        Ok(rooms)
+    }
+
+    pub async fn session(&self, session: &str) -> Anysult<Option<SessionInfo>> {
+        let connection = self.pool.get().await?;
+        let row = connection
+            .query_opt(
+                "SELECT session_id, room, email, transport, joined_at FROM room_presence
+                    WHERE session_id=$1 AND seen_at > now() - make_interval(secs => $2)",
+                &[&session, &PRESENCE_TTL.as_secs_f64()],
+            )
+            .await?;
+        row.map(|row| {
+            Ok(SessionInfo {
+                room: row.get("room"),
+                participant: participant(&row)?,
+            })
+        })
+        .transpose()
+    }
+}
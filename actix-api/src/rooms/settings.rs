@@ -0,0 +1,44 @@
+use bytes::Bytes;
+use protobuf::Message;
+use serde::{Deserialize, Serialize};
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+use types::protos::room_settings::RoomSettingsPacket;
+
+/// Settings every participant of a room has to agree on. Registered meetings keep them in
+/// `meetings.settings`, ad-hoc rooms get the defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomSettings {
+    pub e2ee_required: bool,
+    /// `None` means no limit.
+    pub max_participants: Option<u32>,
+    /// Emails allowed to publish media, empty means everyone.
+    pub publishers: Vec<String>,
+    pub recording_allowed: bool,
+    /// Participants other than the host wait in the lobby until the host admits them.
+    pub waiting_room: bool,
+}
+
+impl RoomSettings {
+    pub fn may_publish(&self, email: &str) -> bool {
+        self.publishers.is_empty() || self.publishers.iter().any(|p| p == email)
+    }
+
+    /// Builds the server generated `ROOM_SETTINGS` packet sent to a session when it joins.
+    pub fn to_packet(&self) -> Bytes {
+        let settings = RoomSettingsPacket {
+            e2ee_required: self.e2ee_required,
+            max_participants: self.max_participants.unwrap_or_default(),
+            publishers: self.publishers.clone(),
+            recording_allowed: self.recording_allowed,
+            ..Default::default()
+        };
+        let packet = PacketWrapper {
+            packet_type: PacketType::ROOM_SETTINGS.into(),
+            data: settings.write_to_bytes().unwrap_or_default(),
+            ..Default::default()
+        };
+        Bytes::from(packet.write_to_bytes().unwrap_or_default())
+    }
+}
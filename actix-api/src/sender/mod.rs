@@ -0,0 +1,185 @@
+use protobuf::{CodedInputStream, Message};
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use types::protos::media_packet::media_packet::MediaType;
+use types::protos::media_packet::MediaPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+const DEFAULT_MAX_SENDER_VIOLATIONS: u32 = 50;
+
+static VIOLATIONS_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// Number of client packets refused by any [SenderGuard] since the process started.
+pub fn violations_total() -> u64 {
+    VIOLATIONS_TOTAL.load(Ordering::Relaxed)
+}
+
+/// Packet types that only the server may put on the bus.
+pub fn is_server_only(packet_type: PacketType) -> bool {
+    matches!(
+        packet_type,
+        PacketType::PARTICIPANT_JOINED
+            | PacketType::PARTICIPANT_LEFT
+            | PacketType::ROOM_SETTINGS
+            | PacketType::JOIN_REJECTED
+            | PacketType::LOBBY
+            | PacketType::RESUMPTION
+    )
+}
+
+/// Reads the packet type of a serialized `PacketWrapper` without copying its payload, for hot
+/// paths that only need the full packet for a few types. Packets written by other encoders may
+/// order their fields differently, so check the type again after parsing.
+pub fn packet_type(data: &[u8]) -> Option<PacketType> {
+    let mut input = CodedInputStream::from_bytes(data);
+    match input.read_raw_tag_or_eof().ok()? {
+        // Field 1 as a varint, written first when it is not the default.
+        Some(8) => input
+            .read_enum_or_unknown::<PacketType>()
+            .ok()?
+            .enum_value()
+            .ok(),
+        _ => Some(PacketType::RSA_PUB_KEY),
+    }
+}
+
+/// Why a client packet was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    Malformed,
+    SpoofedSender { claimed: String },
+    ServerOnly(PacketType),
+    NotPublisher,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Malformed => write!(f, "packet is not a PacketWrapper"),
+            Violation::SpoofedSender { claimed } => {
+                write!(f, "packet claims to be from {}", claimed)
+            }
+            Violation::ServerOnly(packet_type) => {
+                write!(
+                    f,
+                    "{:?} packets can only be sent by the server",
+                    packet_type
+                )
+            }
+            Violation::NotPublisher => write!(f, "sender may not publish media in this room"),
+        }
+    }
+}
+
+/// What the transport should do with a packet received from its client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Forward,
+    /// A host command, for the session's [HostControls](crate::moderation::HostControls)
+    /// instead of the bus.
+    Control(PacketWrapper),
+    /// A chat message, for the session's [Chat](crate::chat::Chat) to stamp and store.
+    Chat(PacketWrapper),
+    Drop(Violation),
+    Disconnect(Violation),
+}
+
+/// Checks the unencrypted `PacketWrapper` header of every packet a session sends against the
+/// identity it authenticated with.
+///
+/// The media payload is only inspected to keep non-publishers from sending audio, video or screen
+/// frames, which cannot be done with end-to-end encryption, where clients enforce it themselves.
+#[derive(Debug)]
+pub struct SenderGuard {
+    identity: String,
+    violations: AtomicU32,
+    max_violations: u32,
+    may_publish: bool,
+}
+
+impl SenderGuard {
+    pub fn new(identity: impl Into<String>, max_violations: u32) -> Self {
+        Self {
+            identity: identity.into(),
+            violations: AtomicU32::new(0),
+            max_violations,
+            may_publish: true,
+        }
+    }
+
+    /// Sets whether the session may publish media, see [RoomSettings::may_publish].
+    ///
+    /// [RoomSettings::may_publish]: crate::rooms::settings::RoomSettings::may_publish
+    pub fn with_publishing(mut self, may_publish: bool) -> Self {
+        self.may_publish = may_publish;
+        self
+    }
+
+    /// Uses `MAX_SENDER_VIOLATIONS` (default 50) as the number of refused packets after which
+    /// the session is disconnected.
+    pub fn from_env(identity: impl Into<String>) -> Self {
+        let max_violations = std::env::var("MAX_SENDER_VIOLATIONS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_MAX_SENDER_VIOLATIONS);
+        Self::new(identity, max_violations)
+    }
+
+    pub fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    pub fn violations(&self) -> u32 {
+        self.violations.load(Ordering::Relaxed)
+    }
+
+    pub fn admit(&self, data: &[u8]) -> Verdict {
+        let violation = match check_packet(data, &self.identity) {
+            Ok(packet) if !self.may_publish && is_published_media(&packet) => {
+                // Not counted: a client may still be sending when the room settings reach it.
+                return Verdict::Drop(Violation::NotPublisher);
+            }
+            Ok(packet) if packet.packet_type.enum_value() == Ok(PacketType::CONTROL) => {
+                return Verdict::Control(packet);
+            }
+            Ok(packet) if packet.packet_type.enum_value() == Ok(PacketType::CHAT) => {
+                return Verdict::Chat(packet);
+            }
+            Ok(_) => return Verdict::Forward,
+            Err(violation) => violation,
+        };
+        VIOLATIONS_TOTAL.fetch_add(1, Ordering::Relaxed);
+        let count = self.violations.fetch_add(1, Ordering::Relaxed) + 1;
+        if count >= self.max_violations {
+            Verdict::Disconnect(violation)
+        } else {
+            Verdict::Drop(violation)
+        }
+    }
+}
+
+fn check_packet(data: &[u8], identity: &str) -> Result<PacketWrapper, Violation> {
+    let packet = PacketWrapper::parse_from_bytes(data).map_err(|_| Violation::Malformed)?;
+    if let Ok(packet_type) = packet.packet_type.enum_value() {
+        if is_server_only(packet_type) {
+            return Err(Violation::ServerOnly(packet_type));
+        }
+    }
+    if packet.email != identity {
+        return Err(Violation::SpoofedSender {
+            claimed: packet.email,
+        });
+    }
+    Ok(packet)
+}
+
+/// Audio, video and screen frames. Heartbeats and packets that do not parse as a plain
+/// `MediaPacket`, such as encrypted ones, are let through.
+fn is_published_media(packet: &PacketWrapper) -> bool {
+    if packet.packet_type.enum_value() != Ok(PacketType::MEDIA) {
+        return false;
+    }
+    MediaPacket::parse_from_bytes(&packet.data)
+        .is_ok_and(|media| media.media_type.enum_value() != Ok(MediaType::HEARTBEAT))
+}
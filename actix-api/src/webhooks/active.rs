@@ -0,0 +1,305 @@
+//! Counts the sessions in each room to tell when meetings start and end.
+//!
+//! Every node counts its own sessions per room in `active_room_nodes`, next to the total in
+//! `active_rooms`. Changes to a room are applied one at a time in the order they were made on a
+//! node, so a quick join and leave can't be counted the wrong way round. Nodes running
+//! [Webhooks::dispatch] say they are alive every [HEARTBEAT_INTERVAL]; the sessions of a node
+//! that hasn't for [NODE_TTL], because it crashed or restarted, are counted out by the others,
+//! which ends the meetings nobody else is in.
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result as Anysult;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tokio::sync::{mpsc, oneshot};
+use tracing::error;
+
+use super::{WebhookEvent, Webhooks};
+
+/// How often a node says it is alive.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// How long after it last said it was alive a node's sessions are counted out.
+pub const NODE_TTL: Duration = Duration::from_secs(60);
+
+enum Change {
+    Joined,
+    Left,
+}
+
+/// A change to a room, and who to tell once it was applied.
+struct Queued {
+    change: Change,
+    email: String,
+    applied: oneshot::Sender<()>,
+}
+
+/// The changes waiting to be applied to each room. A room's queue is drained by one task, which
+/// removes it once it is empty.
+#[derive(Default)]
+pub(super) struct RoomQueues {
+    rooms: Mutex<HashMap<String, mpsc::UnboundedSender<Queued>>>,
+}
+
+impl Webhooks {
+    /// Counts `email` into `room`, which starts the meeting when it was empty.
+    ///
+    /// The change is queued right away, the returned future only waits for it to be applied.
+    pub fn participant_joined(
+        &self,
+        room: &str,
+        email: &str,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        self.queue(room, email, Change::Joined)
+    }
+
+    /// Counts `email` out of `room`, which ends the meeting when it was the last one.
+    ///
+    /// The change is queued right away, the returned future only waits for it to be applied.
+    pub fn participant_left(
+        &self,
+        room: &str,
+        email: &str,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        self.queue(room, email, Change::Left)
+    }
+
+    fn queue(
+        &self,
+        room: &str,
+        email: &str,
+        change: Change,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let (applied, done) = oneshot::channel();
+        let mut queued = Queued {
+            change,
+            email: email.to_string(),
+            applied,
+        };
+        let mut rooms = self.queues.rooms.lock().unwrap();
+        if let Some(queue) = rooms.get(room) {
+            match queue.send(queued) {
+                Ok(()) => return wait(done),
+                Err(mpsc::error::SendError(unsent)) => queued = unsent,
+            }
+        }
+        let (queue, changes) = mpsc::unbounded_channel();
+        let _ = queue.send(queued);
+        rooms.insert(room.to_string(), queue);
+        tokio::spawn(self.clone().apply_changes(room.to_string(), changes));
+        wait(done)
+    }
+
+    async fn apply_changes(self, room: String, mut changes: mpsc::UnboundedReceiver<Queued>) {
+        loop {
+            let queued = match changes.try_recv() {
+                Ok(queued) => queued,
+                Err(_) => {
+                    // Checked again under the lock, so nothing is queued after the queue is gone.
+                    let mut rooms = self.queues.rooms.lock().unwrap();
+                    match changes.try_recv() {
+                        Ok(queued) => queued,
+                        Err(_) => {
+                            rooms.remove(&room);
+                            return;
+                        }
+                    }
+                }
+            };
+            let (result, action) = match queued.change {
+                Change::Joined => (self.joined(&room, &queued.email).await, "joining"),
+                Change::Left => (self.left(&room, &queued.email).await, "leaving"),
+            };
+            if let Err(e) = result {
+                error!(
+                    "error queueing webhooks for {} {} {}: {}",
+                    queued.email, action, room, e
+                );
+            }
+            let _ = queued.applied.send(());
+        }
+    }
+
+    async fn joined(&self, room: &str, email: &str) -> Anysult<()> {
+        let participants: i32 = {
+            let mut connection = self.pool.get().await?;
+            let transaction = connection.transaction().await?;
+            transaction
+                .execute(
+                    "INSERT INTO webhook_nodes (node) VALUES ($1)
+                        ON CONFLICT (node) DO UPDATE SET seen_at = now()",
+                    &[&self.node],
+                )
+                .await?;
+            let participants = transaction
+                .query_one(
+                    "INSERT INTO active_rooms (room, participants) VALUES ($1, 1)
+                        ON CONFLICT (room) DO UPDATE SET participants = active_rooms.participants + 1
+                        RETURNING participants",
+                    &[&room],
+                )
+                .await?
+                .get("participants");
+            transaction
+                .execute(
+                    "INSERT INTO active_room_nodes (room, node, participants) VALUES ($1, $2, 1)
+                        ON CONFLICT (room, node)
+                        DO UPDATE SET participants = active_room_nodes.participants + 1",
+                    &[&room, &self.node],
+                )
+                .await?;
+            transaction.commit().await?;
+            participants
+        };
+        if participants == 1 {
+            self.emit(room, WebhookEvent::MeetingStarted, json!({}))
+                .await?;
+        }
+        self.emit(
+            room,
+            WebhookEvent::ParticipantJoined,
+            json!({ "email": email }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn left(&self, room: &str, email: &str) -> Anysult<()> {
+        let ended = {
+            let mut connection = self.pool.get().await?;
+            let transaction = connection.transaction().await?;
+            // Nothing is left to count out when the others took this node for dead already.
+            let counted = transaction
+                .query_opt(
+                    "UPDATE active_room_nodes SET participants = participants - 1
+                        WHERE room=$1 AND node=$2
+                        RETURNING participants",
+                    &[&room, &self.node],
+                )
+                .await?;
+            let ended = match counted {
+                Some(counted) => {
+                    let participants: i32 = counted.get("participants");
+                    if participants <= 0 {
+                        transaction
+                            .execute(
+                                "DELETE FROM active_room_nodes WHERE room=$1 AND node=$2",
+                                &[&room, &self.node],
+                            )
+                            .await?;
+                    }
+                    transaction
+                        .execute(
+                            "UPDATE active_rooms SET participants = participants - 1
+                                WHERE room=$1",
+                            &[&room],
+                        )
+                        .await?;
+                    transaction
+                        .query_opt(
+                            "DELETE FROM active_rooms WHERE room=$1 AND participants <= 0
+                                RETURNING started_at",
+                            &[&room],
+                        )
+                        .await?
+                }
+                None => None,
+            };
+            transaction.commit().await?;
+            ended
+        };
+        self.emit(
+            room,
+            WebhookEvent::ParticipantLeft,
+            json!({ "email": email }),
+        )
+        .await?;
+        if let Some(ended) = ended {
+            let started_at: DateTime<Utc> = ended.get("started_at");
+            self.emit(
+                room,
+                WebhookEvent::MeetingEnded,
+                json!({ "started_at": started_at }),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Records that this node is alive, so the others leave its sessions counted.
+    pub async fn heartbeat(&self) -> Anysult<()> {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "INSERT INTO webhook_nodes (node) VALUES ($1)
+                    ON CONFLICT (node) DO UPDATE SET seen_at = now()",
+                &[&self.node],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Counts out the sessions of nodes that haven't said they are alive for `ttl`. Returns how
+    /// many meetings that ended.
+    pub async fn expire_nodes(&self, ttl: Duration) -> Anysult<usize> {
+        let ended = {
+            let mut connection = self.pool.get().await?;
+            let transaction = connection.transaction().await?;
+            transaction
+                .execute(
+                    "DELETE FROM webhook_nodes WHERE seen_at < now() - make_interval(secs => $1)",
+                    &[&ttl.as_secs_f64()],
+                )
+                .await?;
+            let expired = transaction
+                .query(
+                    "DELETE FROM active_room_nodes n
+                        WHERE NOT EXISTS (SELECT 1 FROM webhook_nodes w WHERE w.node = n.node)
+                        RETURNING room, participants",
+                    &[],
+                )
+                .await?;
+            let mut rooms: HashMap<String, i32> = HashMap::new();
+            for row in &expired {
+                *rooms.entry(row.get("room")).or_default() += row.get::<_, i32>("participants");
+            }
+            for (room, participants) in &rooms {
+                transaction
+                    .execute(
+                        "UPDATE active_rooms SET participants = participants - $2 WHERE room=$1",
+                        &[room, participants],
+                    )
+                    .await?;
+            }
+            let rooms: Vec<&String> = rooms.keys().collect();
+            let ended = transaction
+                .query(
+                    "DELETE FROM active_rooms WHERE room = ANY($1) AND participants <= 0
+                        RETURNING room, started_at",
+                    &[&rooms],
+                )
+                .await?;
+            transaction.commit().await?;
+            ended
+        };
+        for row in &ended {
+            let room: &str = row.get("room");
+            let started_at: DateTime<Utc> = row.get("started_at");
+            self.emit(
+                room,
+                WebhookEvent::MeetingEnded,
+                json!({ "started_at": started_at }),
+            )
+            .await?;
+        }
+        Ok(ended.len())
+    }
+}
+
+/// Waits for a queued change to be applied.
+async fn wait(done: oneshot::Receiver<()>) {
+    let _ = done.await;
+}
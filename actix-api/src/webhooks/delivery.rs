@@ -0,0 +1,365 @@
+//! POSTs queued deliveries to their webhooks.
+//!
+//! Every request carries the event in [EVENT_HEADER], the delivery id in [DELIVERY_HEADER], the
+//! unix time it was sent at in [TIMESTAMP_HEADER] and a [sign]ature in [SIGNATURE_HEADER].
+//! Receivers recompute the signature with their secret and should refuse old timestamps, which
+//! keeps captured requests from being replayed. Anything but a 2xx response is retried.
+//!
+//! Webhook urls are chosen by users, so deliveries only go to public addresses: the host is
+//! resolved when connecting and loopback, private and link-local addresses are refused, unless
+//! the operator lists the host in [AllowedHosts].
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result as Anysult};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Url;
+use sha2::Sha256;
+use tracing::{debug, error};
+
+use super::active::{HEARTBEAT_INTERVAL, NODE_TTL};
+use super::{DeliveryStatus, Webhooks};
+
+pub const EVENT_HEADER: &str = "X-Videocall-Event";
+pub const DELIVERY_HEADER: &str = "X-Videocall-Delivery";
+pub const TIMESTAMP_HEADER: &str = "X-Videocall-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Videocall-Signature";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How many deliveries one pass claims.
+const BATCH_LEN: i64 = 50;
+/// How long a claimed delivery is left to the node that claimed it before another may retry it.
+const LEASE: Duration = Duration::from_secs(60);
+const MAX_ERROR_LEN: usize = 500;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}` keyed with the webhook secret.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// When failed deliveries are retried: after `first_delay`, then twice as long after every
+/// failure up to `max_delay`, until `max_attempts` were made.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: i32,
+    pub first_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Gives up after about two hours.
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            first_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait after the `attempts`th failed attempt.
+    pub fn delay(&self, attempts: i32) -> Duration {
+        let doublings = attempts.clamp(1, 31) as u32 - 1;
+        self.first_delay
+            .saturating_mul(1 << doublings)
+            .min(self.max_delay)
+    }
+}
+
+/// Hosts webhooks may be delivered to even though they are not public, e.g. a CRM on the
+/// internal network.
+#[derive(Debug, Clone, Default)]
+pub struct AllowedHosts {
+    hosts: Vec<String>,
+}
+
+impl AllowedHosts {
+    pub fn new<I: IntoIterator<Item = S>, S: Into<String>>(hosts: I) -> Self {
+        Self {
+            hosts: hosts
+                .into_iter()
+                .map(|host| host.into().to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Reads the comma separated host names and addresses in `WEBHOOK_ALLOWED_HOSTS`.
+    pub fn from_env() -> Self {
+        let hosts = std::env::var("WEBHOOK_ALLOWED_HOSTS").unwrap_or_default();
+        Self::new(
+            hosts
+                .split(',')
+                .map(str::trim)
+                .filter(|host| !host.is_empty()),
+        )
+    }
+
+    fn allows(&self, host: &str) -> bool {
+        self.hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+
+    /// Refuses urls naming a non-public address outright, the resolver checks host names.
+    fn check(&self, url: &Url) -> Anysult<()> {
+        let Some(ip) = url.host_str().and_then(|host| {
+            host.trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse()
+                .ok()
+        }) else {
+            return Ok(());
+        };
+        if is_public(ip) || self.allows(&ip.to_string()) {
+            Ok(())
+        } else {
+            Err(anyhow!("refusing to deliver to non-public address {}", ip))
+        }
+    }
+}
+
+/// Whether `ip` is reachable on the internet, rather than this host or a private network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Carrier-grade NAT, 100.64.0.0/10.
+                || (a == 100 && (b & 0xc0) == 64)
+                || a == 0)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Resolves webhook hosts to their public addresses only. Checking when connecting, rather than
+/// when the webhook is registered, keeps a host from resolving to a public address first and a
+/// private one later.
+struct PublicResolver {
+    allowed: AllowedHosts,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowed = self.allowed.allows(name.as_str());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| allowed || is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+pub(super) fn client(allowed: AllowedHosts) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        // A redirect would resend the signed body somewhere the owner didn't register.
+        .redirect(reqwest::redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver { allowed }))
+        .build()
+        .expect("failed to build the webhook http client")
+}
+
+/// `e` and its sources, which say why a request could not be sent.
+fn error_chain(e: &(dyn std::error::Error + 'static)) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
+
+/// A delivery claimed by this node.
+struct Due {
+    id: String,
+    event: String,
+    body: String,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+impl Webhooks {
+    /// Delivers queued events every second, forever. Every node may run this, each delivery is
+    /// claimed by one of them at a time. Also keeps this node counted as alive, and counts out
+    /// the sessions of nodes that stopped, see [super::active].
+    pub async fn dispatch(self) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        let mut heartbeat: Option<Instant> = None;
+        loop {
+            interval.tick().await;
+            if heartbeat.is_none_or(|at| at.elapsed() >= HEARTBEAT_INTERVAL) {
+                heartbeat = Some(Instant::now());
+                if let Err(e) = self.heartbeat().await {
+                    error!("failed to record webhook node heartbeat: {:?}", e);
+                }
+                match self.expire_nodes(NODE_TTL).await {
+                    Ok(0) => {}
+                    Ok(ended) => debug!("ended {} meetings of stopped nodes", ended),
+                    Err(e) => error!("failed to expire webhook nodes: {:?}", e),
+                }
+            }
+            match self.deliver_due().await {
+                Ok(0) => {}
+                Ok(attempted) => debug!("attempted {} webhook deliveries", attempted),
+                Err(e) => error!("failed to deliver webhooks: {:?}", e),
+            }
+        }
+    }
+
+    /// Attempts the deliveries that are due once. Returns how many were attempted.
+    pub async fn deliver_due(&self) -> Anysult<usize> {
+        let due = self.claim().await?;
+        let attempted = due.len();
+        futures::future::join_all(due.into_iter().map(|due| self.deliver(due))).await;
+        Ok(attempted)
+    }
+
+    /// Pushes the next attempt of the due deliveries past the lease, so other nodes leave them
+    /// alone while this one sends them.
+    async fn claim(&self) -> Anysult<Vec<Due>> {
+        let connection = self.pool.get().await?;
+        let rows = connection
+            .query(
+                "WITH claimed AS (
+                    UPDATE webhook_deliveries
+                        SET next_attempt_at = now() + make_interval(secs => $2)
+                        WHERE id IN (
+                            SELECT id FROM webhook_deliveries
+                                WHERE status='pending' AND next_attempt_at <= now()
+                                ORDER BY next_attempt_at LIMIT $1
+                                FOR UPDATE SKIP LOCKED
+                        )
+                        RETURNING id, webhook_id, event, body, attempts
+                )
+                SELECT c.id, c.event, c.body, c.attempts, w.url, w.secret
+                    FROM claimed c JOIN webhooks w ON w.id = c.webhook_id",
+                &[&BATCH_LEN, &LEASE.as_secs_f64()],
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| Due {
+                id: row.get("id"),
+                event: row.get("event"),
+                body: row.get("body"),
+                attempts: row.get("attempts"),
+                url: row.get("url"),
+                secret: row.get("secret"),
+            })
+            .collect())
+    }
+
+    async fn deliver(&self, due: Due) {
+        let (status_code, failure) = match self.send(&due).await {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None)
+            }
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                Some(format!("responded {}", response.status())),
+            ),
+            Err(e) => (None, Some(e)),
+        };
+        let attempts = due.attempts + 1;
+        let status = match &failure {
+            None => DeliveryStatus::Delivered,
+            Some(_) if attempts >= self.retry.max_attempts => DeliveryStatus::Failed,
+            Some(_) => DeliveryStatus::Pending,
+        };
+        let last_error = failure.map(|mut e| {
+            e.truncate(e.floor_char_boundary(MAX_ERROR_LEN));
+            e
+        });
+        if let Err(e) = self
+            .record_attempt(&due.id, status, attempts, status_code, last_error)
+            .await
+        {
+            error!("error recording webhook delivery {}: {:?}", due.id, e);
+        }
+    }
+
+    async fn send(&self, due: &Due) -> Result<reqwest::Response, String> {
+        let url = Url::parse(&due.url).map_err(|e| format!("invalid url: {}", e))?;
+        self.allowed_hosts.check(&url).map_err(|e| e.to_string())?;
+        let timestamp = Utc::now().timestamp();
+        self.client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, &due.event)
+            .header(DELIVERY_HEADER, &due.id)
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, sign(&due.secret, timestamp, &due.body))
+            .body(due.body.clone())
+            .send()
+            .await
+            .map_err(|e| error_chain(&e))
+    }
+
+    async fn record_attempt(
+        &self,
+        id: &str,
+        status: DeliveryStatus,
+        attempts: i32,
+        status_code: Option<u16>,
+        last_error: Option<String>,
+    ) -> Anysult<()> {
+        let retry_in = self.retry.delay(attempts).as_secs_f64();
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "UPDATE webhook_deliveries SET status=$2, attempts=$3, last_status_code=$4,
+                    last_error=$5, next_attempt_at = now() + make_interval(secs => $6),
+                    delivered_at = CASE WHEN $2 = 'delivered' THEN now() END
+                    WHERE id=$1",
+                &[
+                    &id,
+                    &status.as_str(),
+                    &attempts,
+                    &status_code.map(i32::from),
+                    &last_error,
+                    &retry_in,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+//! Outbound webhooks, which tell other systems such as a CRM what happens in meetings.
+//!
+//! Users register HTTP endpoints for some [WebhookEvent]s of the meetings they own, see
+//! [crate::api::webhooks]. Events are queued in `webhook_deliveries` and POSTed by the
+//! [Webhooks::dispatch] loop of any server, signed with the secret handed out at registration
+//! and retried with backoff, see [delivery]. Like audit events, queueing an event never fails
+//! the action it reports, errors are only logged.
+//!
+//! A meeting starts when the first session joins its room on any node and ends when the last
+//! one leaves, see [active] for how sessions are counted and what happens when a node dies.
+pub mod active;
+mod delivery;
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result as Anysult};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio_postgres::Row;
+use tracing::error;
+
+pub use delivery::{
+    sign, AllowedHosts, RetryPolicy, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER,
+    TIMESTAMP_HEADER,
+};
+
+use crate::db::PostgresPool;
+use crate::recording::Recording;
+use active::RoomQueues;
+
+const MAX_URL_LEN: usize = 2048;
+
+const WEBHOOK_COLUMNS: &str = "id, owner_email, url, events, secret, created_at";
+
+const DELIVERY_COLUMNS: &str = "id, webhook_id, event, body, status, attempts, next_attempt_at,
+    last_status_code, last_error, created_at, delivered_at";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "meeting.started")]
+    MeetingStarted,
+    #[serde(rename = "meeting.ended")]
+    MeetingEnded,
+    #[serde(rename = "participant.joined")]
+    ParticipantJoined,
+    #[serde(rename = "participant.left")]
+    ParticipantLeft,
+    /// A recording was stopped and its packet log is complete.
+    #[serde(rename = "recording.ready")]
+    RecordingReady,
+}
+
+impl WebhookEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::MeetingStarted => "meeting.started",
+            WebhookEvent::MeetingEnded => "meeting.ended",
+            WebhookEvent::ParticipantJoined => "participant.joined",
+            WebhookEvent::ParticipantLeft => "participant.left",
+            WebhookEvent::RecordingReady => "recording.ready",
+        }
+    }
+}
+
+impl std::str::FromStr for WebhookEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Anysult<Self> {
+        Ok(match s {
+            "meeting.started" => WebhookEvent::MeetingStarted,
+            "meeting.ended" => WebhookEvent::MeetingEnded,
+            "participant.joined" => WebhookEvent::ParticipantJoined,
+            "participant.left" => WebhookEvent::ParticipantLeft,
+            "recording.ready" => WebhookEvent::RecordingReady,
+            _ => return Err(anyhow!("unknown webhook event {}", s)),
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Webhook {
+    pub id: String,
+    pub owner_email: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: DateTime<Utc>,
+    /// Signs the deliveries. Only shown to the owner when the webhook is created.
+    #[serde(skip)]
+    pub secret: String,
+}
+
+impl TryFrom<Row> for Webhook {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> Anysult<Self> {
+        let events: Vec<String> = row.get("events");
+        Ok(Webhook {
+            id: row.get("id"),
+            owner_email: row.get("owner_email"),
+            url: row.get("url"),
+            events: events.iter().map(|e| e.parse()).collect::<Anysult<_>>()?,
+            created_at: row.get("created_at"),
+            secret: row.get("secret"),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewWebhook {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+impl NewWebhook {
+    pub fn validate(&self) -> Anysult<()> {
+        if self.url.len() > MAX_URL_LEN {
+            return Err(anyhow!("url must be at most {} bytes", MAX_URL_LEN));
+        }
+        let url = reqwest::Url::parse(&self.url).map_err(|e| anyhow!("invalid url: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!("url must be http or https"));
+        }
+        if self.events.is_empty() {
+            return Err(anyhow!("events must not be empty"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Not attempted yet, or waiting for a retry.
+    Pending,
+    Delivered,
+    /// Gave up after the last attempt of the [RetryPolicy].
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for DeliveryStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Anysult<Self> {
+        Ok(match s {
+            "pending" => DeliveryStatus::Pending,
+            "delivered" => DeliveryStatus::Delivered,
+            "failed" => DeliveryStatus::Failed,
+            _ => return Err(anyhow!("unknown delivery status {}", s)),
+        })
+    }
+}
+
+/// The body POSTed to a webhook.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    /// Id of the delivery, the same on every attempt so receivers can drop duplicates.
+    pub id: String,
+    pub event: WebhookEvent,
+    pub room: String,
+    pub occurred_at: DateTime<Utc>,
+    pub data: Value,
+}
+
+/// An event queued for a webhook, with the outcome of its last attempt.
+#[derive(Debug, Serialize)]
+pub struct Delivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: WebhookEvent,
+    pub payload: WebhookPayload,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    /// HTTP status of the last attempt, `None` when it got no response.
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<Row> for Delivery {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> Anysult<Self> {
+        Ok(Delivery {
+            id: row.get("id"),
+            webhook_id: row.get("webhook_id"),
+            event: row.get::<_, &str>("event").parse()?,
+            payload: serde_json::from_str(row.get("body"))?,
+            status: row.get::<_, &str>("status").parse()?,
+            attempts: row.get("attempts"),
+            next_attempt_at: row.get("next_attempt_at"),
+            last_status_code: row.get("last_status_code"),
+            last_error: row.get("last_error"),
+            created_at: row.get("created_at"),
+            delivered_at: row.get("delivered_at"),
+        })
+    }
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_simple().to_string()
+}
+
+fn new_secret() -> String {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    hex::encode(secret)
+}
+
+pub async fn create_webhook(
+    pool: &PostgresPool,
+    owner_email: &str,
+    webhook: &NewWebhook,
+) -> Anysult<Webhook> {
+    let connection = pool.get().await?;
+    let mut events: Vec<&str> = webhook.events.iter().map(|e| e.as_str()).collect();
+    events.sort_unstable();
+    events.dedup();
+    let row = connection
+        .query_one(
+            &format!(
+                "INSERT INTO webhooks (id, owner_email, url, events, secret)
+                    VALUES ($1, $2, $3, $4, $5)
+                    RETURNING {}",
+                WEBHOOK_COLUMNS
+            ),
+            &[
+                &new_id(),
+                &owner_email,
+                &webhook.url,
+                &events,
+                &new_secret(),
+            ],
+        )
+        .await?;
+    row.try_into()
+}
+
+/// Webhooks registered by `owner_email`, newest first.
+pub async fn list_webhooks(pool: &PostgresPool, owner_email: &str) -> Anysult<Vec<Webhook>> {
+    let connection = pool.get().await?;
+    let rows = connection
+        .query(
+            &format!(
+                "SELECT {} FROM webhooks WHERE owner_email=$1 ORDER BY created_at DESC",
+                WEBHOOK_COLUMNS
+            ),
+            &[&owner_email],
+        )
+        .await?;
+    rows.into_iter().map(Webhook::try_from).collect()
+}
+
+pub async fn get_webhook(pool: &PostgresPool, id: &str) -> Anysult<Option<Webhook>> {
+    let connection = pool.get().await?;
+    let row = connection
+        .query_opt(
+            &format!("SELECT {} FROM webhooks WHERE id=$1", WEBHOOK_COLUMNS),
+            &[&id],
+        )
+        .await?;
+    row.map(Webhook::try_from).transpose()
+}
+
+/// Deletes a webhook and its deliveries, pending ones included. Returns whether `owner_email`
+/// had such a webhook.
+pub async fn delete_webhook(pool: &PostgresPool, id: &str, owner_email: &str) -> Anysult<bool> {
+    let connection = pool.get().await?;
+    let deleted = connection
+        .execute(
+            "DELETE FROM webhooks WHERE id=$1 AND owner_email=$2",
+            &[&id, &owner_email],
+        )
+        .await?;
+    Ok(deleted > 0)
+}
+
+/// The last `limit` deliveries of a webhook, newest first.
+pub async fn list_deliveries(
+    pool: &PostgresPool,
+    webhook_id: &str,
+    limit: usize,
+) -> Anysult<Vec<Delivery>> {
+    let connection = pool.get().await?;
+    let rows = connection
+        .query(
+            &format!(
+                "SELECT {} FROM webhook_deliveries WHERE webhook_id=$1
+                    ORDER BY created_at DESC, id DESC LIMIT $2",
+                DELIVERY_COLUMNS
+            ),
+            &[&webhook_id, &(limit as i64)],
+        )
+        .await?;
+    rows.into_iter().map(Delivery::try_from).collect()
+}
+
+/// Queues the events of rooms for the webhooks of their meeting owners and delivers them.
+#[derive(Clone)]
+pub struct Webhooks {
+    pool: PostgresPool,
+    client: reqwest::Client,
+    allowed_hosts: AllowedHosts,
+    retry: RetryPolicy,
+    /// Identifies this process in `active_room_nodes`.
+    node: String,
+    queues: Arc<RoomQueues>,
+}
+
+impl std::fmt::Debug for Webhooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Webhooks")
+            .field("node", &self.node)
+            .field("allowed_hosts", &self.allowed_hosts)
+            .field("retry", &self.retry)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Webhooks {
+    pub fn new(pool: PostgresPool) -> Self {
+        Self {
+            pool,
+            client: delivery::client(AllowedHosts::default()),
+            allowed_hosts: AllowedHosts::default(),
+            retry: RetryPolicy::default(),
+            node: new_id(),
+            queues: Arc::default(),
+        }
+    }
+
+    /// Lets deliveries go to `allowed_hosts` even though they are not public.
+    pub fn with_allowed_hosts(mut self, allowed_hosts: AllowedHosts) -> Self {
+        self.client = delivery::client(allowed_hosts.clone());
+        self.allowed_hosts = allowed_hosts;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Queues `event` for every webhook subscribed to it by the owner of the meeting in `room`.
+    /// Returns how many deliveries were queued, none for ad-hoc rooms.
+    pub async fn emit(&self, room: &str, event: WebhookEvent, data: Value) -> Anysult<usize> {
+        let connection = self.pool.get().await?;
+        let webhooks = connection
+            .query(
+                "SELECT w.id FROM webhooks w JOIN meetings m ON m.owner_email = w.owner_email
+                    WHERE m.id=$1 AND $2 = ANY(w.events)",
+                &[&room, &event.as_str()],
+            )
+            .await?;
+        let occurred_at = Utc::now();
+        for webhook in &webhooks {
+            let webhook_id: &str = webhook.get("id");
+            let payload = WebhookPayload {
+                id: new_id(),
+                event,
+                room: room.to_string(),
+                occurred_at,
+                data: data.clone(),
+            };
+            connection
+                .execute(
+                    "INSERT INTO webhook_deliveries (id, webhook_id, event, body)
+                        VALUES ($1, $2, $3, $4)",
+                    &[
+                        &payload.id,
+                        &webhook_id,
+                        &event.as_str(),
+                        &serde_json::to_string(&payload)?,
+                    ],
+                )
+                .await?;
+        }
+        Ok(webhooks.len())
+    }
+
+    pub async fn recording_ready(&self, recording: &Recording, stopped_at: u64) {
+        let data = json!({
+            "path": recording.path,
+            "started_by": recording.started_by,
+            "started_at": recording.started_at,
+            "stopped_at": stopped_at,
+        });
+        if let Err(e) = self
+            .emit(&recording.room, WebhookEvent::RecordingReady, data)
+            .await
+        {
+            error!(
+                "error queueing webhooks for the recording of {}: {}",
+                recording.room, e
+            );
+        }
+    }
+}
@@ -1,4 +1,18 @@
+use crate::audit::{AuditEvent, AuditLog, EventKind};
+use crate::auth::token::{authorize_connection, ConnectAuth};
+use crate::bus::{room_subject, session_subject, validate_room_id, RoomBus};
+use crate::chat::{Chat, ChatHistory};
+use crate::lobby::{lobby_packet, Decision, Lobby};
+use crate::meetings::{authorize_join, Admission, JoinCredentials, JoinRefused, MeetingDirectory};
+use crate::moderation::{host_packet, HostControls, Observed};
+use crate::recording::Recorder;
+use crate::rooms::capacity::{Capacity, CapacityError, Occupancy, Seat};
+use crate::rooms::presence::Presence;
+use crate::rooms::{participant_packet, Participant, Transport};
+use crate::sender::{packet_type, SenderGuard, Verdict};
+use crate::webhooks::Webhooks;
 use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
 use futures::StreamExt;
 use protobuf::Message;
 use quinn::crypto::rustls::HandshakeData;
@@ -6,12 +20,14 @@ use quinn::VarInt;
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use std::io::Read;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 use std::time::Duration;
 use std::{fs, io};
 use std::{net::SocketAddr, path::PathBuf, sync::Arc};
 use tokio::sync::{watch, RwLock};
-use tracing::{error, info, trace_span};
+use tracing::{error, info, trace_span, warn};
 use types::protos::connection_packet::ConnectionPacket;
+use types::protos::lobby_packet::lobby_packet::Status;
 use types::protos::packet_wrapper::packet_wrapper::PacketType;
 use types::protos::packet_wrapper::PacketWrapper;
 use web_transport_quinn::Session;
@@ -21,11 +37,62 @@ pub const WEB_TRANSPORT_ALPN: &[&[u8]] = &[b"h3", b"h3-32", b"h3-31", b"h3-30",
 pub const QUIC_ALPN: &[u8] = b"hq-29";
 
 const MAX_UNIDIRECTIONAL_STREAM_SIZE: usize = 500_000;
+/// How long a rejected client gets to acknowledge the packet saying why.
+const REJECTION_DELIVERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Application close code sent when a connection presents a missing or mismatched connect token.
+const UNAUTHORIZED_CLOSE_CODE: u32 = 0x3;
+
+/// Application close code sent when a client keeps sending packets under someone else's identity.
+const SENDER_VIOLATION_CLOSE_CODE: u32 = 0x4;
+
+/// Application close code sent when `REQUIRE_REGISTERED_MEETING` is on and the room is unknown.
+const UNKNOWN_MEETING_CLOSE_CODE: u32 = 0x5;
+
+/// Application close code sent when the room already holds its maximum of participants.
+pub const ROOM_FULL_CLOSE_CODE: u32 = 0x6;
+
+/// Application close code sent when the server already holds `MAX_PARTICIPANTS` sessions.
+pub const SERVER_FULL_CLOSE_CODE: u32 = 0x7;
+
+/// Application close code sent when the host removed the participant from the meeting.
+pub const REMOVED_CLOSE_CODE: u32 = 0x8;
+
+/// Application close code sent when a removed participant tries to rejoin during their ban.
+pub const BANNED_CLOSE_CODE: u32 = 0x9;
+
+/// Application close code sent when the host denied a participant waiting in the lobby.
+pub const DENIED_CLOSE_CODE: u32 = 0xA;
+
+/// Application close code sent when the meeting has a passcode and none was given.
+pub const PASSCODE_REQUIRED_CLOSE_CODE: u32 = 0xB;
+
+/// Application close code sent when the given passcode is wrong.
+pub const WRONG_PASSCODE_CLOSE_CODE: u32 = 0xC;
+
+/// Application close code sent when the invite is invalid, expired or used up.
+pub const INVALID_INVITE_CLOSE_CODE: u32 = 0xD;
+
+/// Application close code sent when an operator closed the session through the admin API.
+pub const ADMIN_CLOSE_CODE: u32 = 0xE;
+
+/// Application close code sent when a raw QUIC connection sends a second connection packet.
+const ALREADY_CONNECTING_CLOSE_CODE: u32 = 0xF;
 
 #[derive(Debug)]
 pub struct WebTransportOpt {
     pub listen: SocketAddr,
     pub certs: Certs,
+    pub connect_auth: ConnectAuth,
+    /// Set when the database is enabled, joins read their room settings from it.
+    pub meetings: Option<MeetingDirectory>,
+    pub capacity: Capacity,
+    pub chat_history: ChatHistory,
+    pub audit: AuditLog,
+    /// Set when the database is enabled.
+    pub webhooks: Option<Webhooks>,
+    /// Set when the database is enabled, lets the admin API see the sessions of this server.
+    pub presence: Option<Presence>,
 }
 
 #[derive(Debug, Clone)]
@@ -75,10 +142,27 @@ pub fn is_http3(conn: &quinn::Connection) -> bool {
     false
 }
 
-pub async fn start(opt: WebTransportOpt) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn start(
+    opt: WebTransportOpt,
+    bus: Arc<dyn RoomBus>,
+) -> Result<(), Box<dyn std::error::Error>> {
     info!("WebTransportOpt: {opt:#?}");
 
     let (key, certs) = get_key_and_cert_chain(opt.certs)?;
+    let connect_auth = opt.connect_auth;
+    let meetings = opt.meetings;
+    let occupancy = Occupancy::new(opt.capacity, opt.presence.clone());
+    tokio::spawn(occupancy.follow_admin_commands(&*bus));
+    if let Some(presence) = opt.presence {
+        let occupancy = occupancy.clone();
+        tokio::spawn(presence.keep_alive(move || occupancy.session_ids()));
+    }
+    let chat_history = opt.chat_history;
+    let audit = opt.audit;
+    let webhooks = opt.webhooks;
+    let recorder = Recorder::from_env(bus.clone())
+        .with_audit(audit.clone())
+        .with_webhooks(webhooks.clone());
 
     let mut config = rustls::ServerConfig::builder_with_provider(Arc::new(
         rustls::crypto::ring::default_provider(),
@@ -106,30 +190,54 @@ pub async fn start(opt: WebTransportOpt) -> Result<(), Box<dyn std::error::Error
     let server = quinn::Endpoint::server(config, opt.listen)?;
 
     info!("listening on {}", opt.listen);
-    let nats_url = std::env::var("NATS_URL").expect("NATS_URL env var must be defined");
-    info!("nats url {}", nats_url);
-
-    let nc =
-        async_nats::connect(std::env::var("NATS_URL").expect("NATS_URL env var must be defined"))
-            .await
-            .unwrap();
 
     // 2. Accept new quic connections and spawn a new task to handle them
     while let Some(new_conn) = server.accept().await {
         trace_span!("New connection being attempted");
-        let nc = nc.clone();
+        let bus = bus.clone();
+        let connect_auth = connect_auth.clone();
+        let meetings = meetings.clone();
+        let occupancy = occupancy.clone();
+        let chat_history = chat_history.clone();
+        let recorder = recorder.clone();
+        let audit = audit.clone();
+        let webhooks = webhooks.clone();
         tokio::spawn(async move {
             match new_conn.await {
                 Ok(conn) => {
                     if is_http3(&conn) {
                         info!("new http3 established");
-                        if let Err(err) = run_webtransport_connection(conn.clone(), nc).await {
+                        if let Err(err) = run_webtransport_connection(
+                            conn.clone(),
+                            bus,
+                            connect_auth,
+                            meetings,
+                            occupancy,
+                            chat_history,
+                            recorder,
+                            audit,
+                            webhooks,
+                        )
+                        .await
+                        {
                             error!("Failed to handle connection: {err:?}");
                         }
                     } else {
                         info!("new quic established");
-                        let nc = nc.clone();
-                        if let Err(err) = handle_quic_connection(conn, nc).await {
+                        let bus = bus.clone();
+                        if let Err(err) = handle_quic_connection(
+                            conn,
+                            bus,
+                            connect_auth,
+                            meetings,
+                            occupancy,
+                            chat_history,
+                            recorder,
+                            audit,
+                            webhooks,
+                        )
+                        .await
+                        {
                             error!("Failed to handle connection: {err:?}");
                         }
                     }
@@ -147,15 +255,24 @@ pub async fn start(opt: WebTransportOpt) -> Result<(), Box<dyn std::error::Error
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_webtransport_connection(
     conn: quinn::Connection,
-    nc: async_nats::client::Client,
+    bus: Arc<dyn RoomBus>,
+    connect_auth: ConnectAuth,
+    meetings: Option<MeetingDirectory>,
+    occupancy: Arc<Occupancy>,
+    chat_history: ChatHistory,
+    recorder: Recorder,
+    audit: AuditLog,
+    webhooks: Option<Webhooks>,
 ) -> anyhow::Result<()> {
     info!("received new QUIC connection");
 
     // Perform the WebTransport handshake.
     let request = web_transport_quinn::accept(conn.clone()).await?;
-    info!("received WebTransport request: {}", request.url());
+    // Only the path, the query carries connect tokens, passcodes and invites.
+    info!("received WebTransport request: {}", request.url().path());
     let url = request.url();
 
     let uri = url;
@@ -174,39 +291,164 @@ async fn run_webtransport_connection(
         conn.close(VarInt::from_u32(0x1), b"Invalid path wrong prefix");
         return Err(anyhow!("Invalid path wrong prefix"));
     }
+    if let Err(e) = validate_room_id(parts[2]) {
+        conn.close(VarInt::from_u32(0x1), b"Invalid path input chars");
+        return Err(e);
+    }
+
+    let query = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    let token = query("token");
+    let credentials = JoinCredentials::new(query("passcode"), query("invite"))
+        .with_peer(Some(conn.remote_address().ip()));
+    if let Err(e) = authorize_connection(&connect_auth, parts[1], token.as_deref()).await {
+        let event = AuditEvent::new(*parts[2], EventKind::AuthFailed, *parts[1])
+            .with_detail("invalid connect token");
+        audit.record(event).await;
+        conn.close(
+            VarInt::from_u32(UNAUTHORIZED_CLOSE_CODE),
+            b"Invalid connect token",
+        );
+        return Err(e.context("Invalid connect token"));
+    }
 
-    let username = parts[1].replace(' ', "_");
     let lobby_id = parts[2].replace(' ', "_");
-    let re = regex::Regex::new("^[a-zA-Z0-9_]*$").unwrap();
-    if !re.is_match(&username) && !re.is_match(&lobby_id) {
-        conn.close(VarInt::from_u32(0x1), b"Invalid path input chars");
-        return Err(anyhow!("Invalid path input chars"));
+    let admission = authorize_join(meetings.as_ref(), &lobby_id, parts[1], &credentials).await;
+    if let Err(e) = &admission {
+        let event = AuditEvent::new(&*lobby_id, EventKind::AuthFailed, *parts[1])
+            .with_detail(e.to_string());
+        audit.record(event).await;
+    }
+    if let Err(
+        e @ (JoinRefused::InvalidRoom | JoinRefused::Unregistered | JoinRefused::Failed(_)),
+    ) = &admission
+    {
+        conn.close(
+            VarInt::from_u32(UNKNOWN_MEETING_CLOSE_CODE),
+            b"Meeting is not registered",
+        );
+        return Err(anyhow!("{}", e));
     }
 
     // Accept the session.
     let session = request.ok().await.context("failed to accept session")?;
     info!("accepted session");
 
+    let admission = match admission {
+        Ok(admission) => admission,
+        Err(e) => {
+            let packet = e.to_packet().unwrap_or_default();
+            reject_session(&session, refusal_close_code(&e), &packet, &e.to_string()).await;
+            return Err(anyhow!("{}", e));
+        }
+    };
+    let host = HostControls::new(
+        lobby_id.clone(),
+        *parts[1],
+        admission.host.as_deref(),
+        meetings,
+    )
+    .with_lobby(
+        admission
+            .settings
+            .waiting_room
+            .then(|| Lobby::new(bus.clone(), lobby_id.clone())),
+    )
+    .with_recorder(admission.settings.recording_allowed.then_some(recorder))
+    .with_audit(Some(audit.clone()));
+
+    let chat = chat_history.chat(lobby_id.clone(), *parts[1]);
+
     // Run the session
-    if let Err(err) = handle_session(session, &username, &lobby_id, nc).await {
+    if let Err(err) = handle_session(
+        session, parts[1], &lobby_id, admission, host, chat, bus, occupancy, audit, webhooks,
+    )
+    .await
+    {
         info!("closing session: {}", err);
     }
     Ok(())
 }
 
-#[tracing::instrument(level = "trace", skip(session))]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    level = "trace",
+    skip(session, admission, host, chat, bus, occupancy, audit, webhooks)
+)]
 async fn handle_session(
     session: Session,
-    username: &str,
+    email: &str,
     lobby_id: &str,
-    nc: async_nats::client::Client,
+    admission: Admission,
+    host: HostControls,
+    chat: Chat,
+    bus: Arc<dyn RoomBus>,
+    occupancy: Arc<Occupancy>,
+    audit: AuditLog,
+    webhooks: Option<Webhooks>,
 ) -> anyhow::Result<()> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let subject = room_subject(lobby_id);
+    // Emails may hold dots, which would split the subject into more tokens.
+    let specific_subject = session_subject(lobby_id, &session_id);
+    if let Some(lobby) = host.lobby() {
+        if admission.must_wait(email) {
+            wait_in_lobby(&session, lobby, email, &specific_subject).await?;
+        }
+    }
+    let settings = admission.settings;
+    let participant = Participant::new(session_id, email.to_string(), Transport::WebTransport);
+    let closer = session.clone();
+    let close = move |reason: &str| closer.close(ADMIN_CLOSE_CODE, reason.as_bytes());
+    // Taken once out of the lobby, like websocket sessions joining their room.
+    let seat = occupancy
+        .admit(lobby_id, participant, settings.max_participants, close)
+        .await;
+    let _seat = match seat {
+        Ok(seat) => seat,
+        Err(e) => {
+            reject_session(
+                &session,
+                capacity_close_code(e),
+                &e.to_packet(),
+                &e.to_string(),
+            )
+            .await;
+            return Err(e.into());
+        }
+    };
+    session
+        .open_uni()
+        .await?
+        .write_all(&settings.to_packet())
+        .await?;
+    if let Some(host) = &admission.host {
+        session
+            .open_uni()
+            .await?
+            .write_all(&host_packet(host))
+            .await?;
+    }
+    match chat.join().await {
+        Ok(messages) => {
+            for message in messages {
+                send_to_session(&session, &message).await?;
+            }
+        }
+        Err(e) => error!("Error replaying the chat of {}: {}", lobby_id, e),
+    }
+
     let session = Arc::new(RwLock::new(session));
     let should_run = Arc::new(AtomicBool::new(true));
+    let sender =
+        Arc::new(SenderGuard::from_env(email).with_publishing(settings.may_publish(email)));
+    let host = Arc::new(host);
+    let chat = Arc::new(chat);
 
-    let subject = format!("room.{}.*", lobby_id).replace(' ', "_");
-    let specific_subject = format!("room.{}.{}", lobby_id, username).replace(' ', "_");
-    let mut sub = match nc
+    let sub = match bus
         .queue_subscribe(subject.clone(), specific_subject.clone())
         .await
     {
@@ -220,12 +462,28 @@ async fn handle_session(
             return Err(anyhow!(err));
         }
     };
+    let mut sub = match host.lobby() {
+        Some(lobby) => futures::stream::select(sub, lobby.watch(&specific_subject).await?).boxed(),
+        None => sub,
+    };
+
+    let joined = participant_packet(PacketType::PARTICIPANT_JOINED, email);
+    if let Err(e) = bus.publish(specific_subject.clone(), joined).await {
+        error!("Error publishing to subject {}: {}", specific_subject, e);
+    }
+    audit
+        .record(AuditEvent::new(lobby_id, EventKind::Joined, email))
+        .await;
+    if let Some(webhooks) = &webhooks {
+        webhooks.participant_joined(lobby_id, email).await;
+    }
 
     let specific_subject_clone = specific_subject.clone();
 
-    let nats_receive_task = {
+    let bus_receive_task = {
         let session = session.clone();
         let should_run = should_run.clone();
+        let host = host.clone();
         tokio::spawn(async move {
             while let Some(msg) = sub.next().await {
                 if !should_run.load(Ordering::SeqCst) {
@@ -235,7 +493,19 @@ async fn handle_session(
                     continue;
                 }
                 let session = session.read().await;
-                if msg.payload.len() > 400 {
+                let observed = host.observe(&msg.payload);
+                if observed == Observed::Ignore {
+                    continue;
+                }
+                if observed != Observed::Deliver {
+                    if let Err(e) = send_to_session(&session, &msg.payload).await {
+                        error!("Error sending control packet: {}", e);
+                    }
+                    if observed == Observed::Removed {
+                        session.close(REMOVED_CLOSE_CODE, b"Removed by the host");
+                        break;
+                    }
+                } else if msg.payload.len() > 400 {
                     let stream = session.open_uni().await;
                     tokio::spawn(async move {
                         match stream {
@@ -258,20 +528,53 @@ async fn handle_session(
 
     let quic_task = {
         let session = session.clone();
-        let nc = nc.clone();
+        let bus = bus.clone();
         let specific_subject = specific_subject.clone();
+        let sender = sender.clone();
+        let host = host.clone();
+        let chat = chat.clone();
         tokio::spawn(async move {
             let session = session.read().await;
             while let Ok(mut uni_stream) = session.accept_uni().await {
-                let nc = nc.clone();
+                let bus = bus.clone();
                 let specific_subject = specific_subject.clone();
+                let sender = sender.clone();
+                let host = host.clone();
+                let chat = chat.clone();
+                let session = session.clone();
                 tokio::spawn(async move {
                     let result = uni_stream.read_to_end(1_000_000).await;
                     match result {
                         Ok(buf) => {
+                            match admit_packet(&sender, &buf, || {
+                                session.close(SENDER_VIOLATION_CLOSE_CODE, b"Invalid sender")
+                            }) {
+                                Inbound::Publish => {}
+                                Inbound::Control(packet) => {
+                                    let control =
+                                        run_command(&host, &packet, &*bus, &specific_subject).await;
+                                    if let Some(control) = control {
+                                        if let Err(e) = send_to_session(&session, &control).await {
+                                            error!("Error sending control packet: {}", e);
+                                        }
+                                    }
+                                    return;
+                                }
+                                Inbound::Chat(packet) => {
+                                    let message =
+                                        post_chat(&chat, &packet, &*bus, &specific_subject).await;
+                                    if let Some(message) = message {
+                                        if let Err(e) = send_to_session(&session, &message).await {
+                                            error!("Error sending chat message: {}", e);
+                                        }
+                                    }
+                                    return;
+                                }
+                                Inbound::Refused => return,
+                            }
                             tokio::spawn(async move {
                                 if let Err(e) =
-                                    nc.publish(specific_subject.clone(), buf.into()).await
+                                    bus.publish(specific_subject.clone(), buf.into()).await
                                 {
                                     error!(
                                         "Error publishing to subject {}: {}",
@@ -290,44 +593,95 @@ async fn handle_session(
     };
 
     let _datagrams_task = {
+        let bus = bus.clone();
+        let specific_subject = specific_subject.clone();
         tokio::spawn(async move {
             let session = session.read().await;
             while let Ok(buf) = session.read_datagram().await {
-                let nc = nc.clone();
-                if let Err(e) = nc.publish(specific_subject.clone(), buf).await {
+                match admit_packet(&sender, &buf, || {
+                    session.close(SENDER_VIOLATION_CLOSE_CODE, b"Invalid sender")
+                }) {
+                    Inbound::Publish => {}
+                    Inbound::Control(packet) => {
+                        let control = run_command(&host, &packet, &*bus, &specific_subject).await;
+                        if let Some(control) = control {
+                            if let Err(e) = send_to_session(&session, &control).await {
+                                error!("Error sending control packet: {}", e);
+                            }
+                        }
+                        continue;
+                    }
+                    Inbound::Chat(packet) => {
+                        let message = post_chat(&chat, &packet, &*bus, &specific_subject).await;
+                        if let Some(message) = message {
+                            if let Err(e) = send_to_session(&session, &message).await {
+                                error!("Error sending chat message: {}", e);
+                            }
+                        }
+                        continue;
+                    }
+                    Inbound::Refused => continue,
+                }
+                if let Err(e) = bus.publish(specific_subject.clone(), buf).await {
                     error!("Error publishing to subject {}: {}", specific_subject, e);
                 }
             }
         })
     };
-    quic_task.await?;
+    let result = quic_task.await;
     should_run.store(false, Ordering::SeqCst);
-    nats_receive_task.abort();
+    bus_receive_task.abort();
+    let left = participant_packet(PacketType::PARTICIPANT_LEFT, email);
+    if let Err(e) = bus.publish(specific_subject.clone(), left).await {
+        error!("Error publishing to subject {}: {}", specific_subject, e);
+    }
+    audit
+        .record(AuditEvent::new(lobby_id, EventKind::Left, email))
+        .await;
+    if let Some(webhooks) = &webhooks {
+        webhooks.participant_left(lobby_id, email).await;
+    }
+    result?;
     info!("Finished handling session");
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_quic_connection(
     conn: quinn::Connection,
-    nc: async_nats::client::Client,
+    bus: Arc<dyn RoomBus>,
+    connect_auth: ConnectAuth,
+    meetings: Option<MeetingDirectory>,
+    occupancy: Arc<Occupancy>,
+    chat_history: ChatHistory,
+    recorder: Recorder,
+    audit: AuditLog,
+    webhooks: Option<Webhooks>,
 ) -> Result<()> {
-    let _session_id = conn.stable_id();
+    let session_id = uuid::Uuid::new_v4().to_string();
     let session = Arc::new(RwLock::new(conn));
     let should_run = Arc::new(AtomicBool::new(true));
-    let (specific_subject_tx, mut specific_subject_rx) = watch::channel::<Option<String>>(None);
+    let (specific_subject_tx, specific_subject_rx) = watch::channel::<Option<String>>(None);
+    let sender = Arc::new(OnceLock::<SenderGuard>::new());
+    let seat = Arc::new(OnceLock::<Seat>::new());
+    let host = Arc::new(OnceLock::<HostControls>::new());
+    let chat = Arc::new(OnceLock::<Chat>::new());
+    // Claimed by the first connection packet, before it waits on auth, the meeting or the lobby.
+    let connecting = Arc::new(AtomicBool::new(false));
 
-    let nats_task = {
+    let bus_task = {
         let session = session.clone();
         let should_run = should_run.clone();
-        let nc_clone = nc.clone();
+        let host = host.clone();
+        let bus_clone = bus.clone();
         let specific_subject_rx_clone = specific_subject_rx.clone();
         tokio::spawn(async move {
             let mut specific_subject_rx = specific_subject_rx_clone;
-            let nc = nc_clone;
+            let bus = bus_clone;
             specific_subject_rx.changed().await.unwrap();
             let specific_subject = specific_subject_rx.borrow().clone().unwrap();
             let subject = session_subject_to_lobby_subject(&specific_subject);
-            let mut sub = match nc
+            let sub = match bus
                 .queue_subscribe(subject.clone(), specific_subject.clone())
                 .await
             {
@@ -341,6 +695,17 @@ async fn handle_quic_connection(
                     return;
                 }
             };
+            let lobby = host.get().and_then(HostControls::lobby);
+            let mut sub = match lobby.map(|lobby| lobby.watch(&specific_subject)) {
+                Some(watch) => match watch.await {
+                    Ok(watch) => futures::stream::select(sub, watch).boxed(),
+                    Err(e) => {
+                        error!("error watching the lobby: {}", e);
+                        return;
+                    }
+                },
+                None => sub,
+            };
             while let Some(msg) = sub.next().await {
                 if !should_run.load(Ordering::SeqCst) {
                     break;
@@ -349,7 +714,21 @@ async fn handle_quic_connection(
                     continue;
                 }
                 let session = session.read().await;
-                if msg.payload.len() > 400 {
+                let observed = host
+                    .get()
+                    .map_or(Observed::Deliver, |host| host.observe(&msg.payload));
+                if observed == Observed::Ignore {
+                    continue;
+                }
+                if observed != Observed::Deliver {
+                    if let Err(e) = send_to_connection(&session, &msg.payload).await {
+                        error!("Error sending control packet: {}", e);
+                    }
+                    if observed == Observed::Removed {
+                        session.close(VarInt::from_u32(REMOVED_CLOSE_CODE), b"Removed by the host");
+                        break;
+                    }
+                } else if msg.payload.len() > 400 {
                     let stream = session.open_uni().await;
                     tokio::spawn(async move {
                         match stream {
@@ -373,37 +752,289 @@ async fn handle_quic_connection(
     let quic_task = {
         let specific_subject_rx_clone = specific_subject_rx.clone();
         let session = session.clone();
-        let nc = nc.clone();
+        let bus = bus.clone();
+        let sender = sender.clone();
+        let connect_auth = connect_auth.clone();
+        let meetings = meetings.clone();
+        let seat = seat.clone();
+        let host = host.clone();
+        let chat = chat.clone();
+        let audit = audit.clone();
+        let webhooks = webhooks.clone();
+        let connecting = connecting.clone();
         tokio::spawn(async move {
             let session = session.read().await;
             let specific_subject_tx = Arc::new(specific_subject_tx);
             while let Ok(mut uni_stream) = session.accept_uni().await {
-                let nc = nc.clone();
+                let bus = bus.clone();
                 let specific_subject_tx_clone = specific_subject_tx.clone();
                 let specific_subject_rx = specific_subject_rx_clone.clone();
+                let sender = sender.clone();
+                let connect_auth = connect_auth.clone();
+                let meetings = meetings.clone();
+                let occupancy = occupancy.clone();
+                let seat = seat.clone();
+                let host = host.clone();
+                let chat = chat.clone();
+                let chat_history = chat_history.clone();
+                let recorder = recorder.clone();
+                let audit = audit.clone();
+                let webhooks = webhooks.clone();
+                let session_id = session_id.clone();
+                let connecting = connecting.clone();
+                let conn = session.clone();
                 tokio::spawn(async move {
                     if let Ok(d) = uni_stream.read_to_end(MAX_UNIDIRECTIONAL_STREAM_SIZE).await {
                         if specific_subject_rx.borrow().is_none() {
                             if let Ok(packet_wrapper) = PacketWrapper::parse_from_bytes(&d) {
                                 if packet_wrapper.packet_type == PacketType::CONNECTION.into() {
                                     info!("Got connection packet");
+                                    if connecting
+                                        .compare_exchange(
+                                            false,
+                                            true,
+                                            Ordering::SeqCst,
+                                            Ordering::SeqCst,
+                                        )
+                                        .is_err()
+                                    {
+                                        warn!("Closing quic connection: second connection packet");
+                                        conn.close(
+                                            VarInt::from_u32(ALREADY_CONNECTING_CLOSE_CODE),
+                                            b"Already connected",
+                                        );
+                                        return;
+                                    }
                                     let connection_packet =
                                         ConnectionPacket::parse_from_bytes(&packet_wrapper.data)
                                             .unwrap();
-                                    let specific_subject = format!(
-                                        "room.{}.{}",
-                                        connection_packet.meeting_id, packet_wrapper.email
+                                    if let Err(e) = validate_room_id(&connection_packet.meeting_id)
+                                    {
+                                        error!("Rejecting quic connection: {}", e);
+                                        conn.close(VarInt::from_u32(0x1), b"Invalid meeting id");
+                                        return;
+                                    }
+                                    if let Err(e) = authorize_connection(
+                                        &connect_auth,
+                                        &packet_wrapper.email,
+                                        Some(&connection_packet.token)
+                                            .filter(|t| !t.is_empty())
+                                            .map(String::as_str),
+                                    )
+                                    .await
+                                    {
+                                        error!("Rejecting quic connection: {}", e);
+                                        let event = AuditEvent::new(
+                                            &*connection_packet.meeting_id,
+                                            EventKind::AuthFailed,
+                                            &*packet_wrapper.email,
+                                        )
+                                        .with_detail("invalid connect token");
+                                        audit.record(event).await;
+                                        conn.close(
+                                            VarInt::from_u32(UNAUTHORIZED_CLOSE_CODE),
+                                            b"Invalid connect token",
+                                        );
+                                        return;
+                                    }
+                                    let credentials = JoinCredentials::new(
+                                        Some(connection_packet.passcode.clone()),
+                                        Some(connection_packet.invite.clone()),
+                                    )
+                                    .with_peer(Some(conn.remote_address().ip()));
+                                    let admission = match authorize_join(
+                                        meetings.as_ref(),
+                                        &connection_packet.meeting_id,
+                                        &packet_wrapper.email,
+                                        &credentials,
+                                    )
+                                    .await
+                                    {
+                                        Ok(admission) => admission,
+                                        Err(e) => {
+                                            error!("Rejecting quic connection: {}", e);
+                                            let event = AuditEvent::new(
+                                                &*connection_packet.meeting_id,
+                                                EventKind::AuthFailed,
+                                                &*packet_wrapper.email,
+                                            )
+                                            .with_detail(e.to_string());
+                                            audit.record(event).await;
+                                            reject_connection(
+                                                &conn,
+                                                refusal_close_code(&e),
+                                                &e.to_packet().unwrap_or_default(),
+                                                &e.to_string(),
+                                            )
+                                            .await;
+                                            return;
+                                        }
+                                    };
+                                    let settings = &admission.settings;
+                                    let specific_subject =
+                                        session_subject(&connection_packet.meeting_id, &session_id);
+                                    let controls = HostControls::new(
+                                        connection_packet.meeting_id.clone(),
+                                        packet_wrapper.email.clone(),
+                                        admission.host.as_deref(),
+                                        meetings,
                                     )
-                                    .replace(' ', "_");
+                                    .with_lobby(settings.waiting_room.then(|| {
+                                        Lobby::new(
+                                            bus.clone(),
+                                            connection_packet.meeting_id.clone(),
+                                        )
+                                    }))
+                                    .with_recorder(settings.recording_allowed.then_some(recorder))
+                                    .with_audit(Some(audit.clone()));
+                                    if let Some(lobby) = controls.lobby() {
+                                        if admission.must_wait(&packet_wrapper.email) {
+                                            if let Err(e) = wait_in_lobby_quic(
+                                                &conn,
+                                                lobby,
+                                                &packet_wrapper.email,
+                                                &specific_subject,
+                                            )
+                                            .await
+                                            {
+                                                info!("Closing quic connection: {}", e);
+                                                return;
+                                            }
+                                        }
+                                    }
+                                    let participant = Participant::new(
+                                        session_id,
+                                        packet_wrapper.email.clone(),
+                                        Transport::Quic,
+                                    );
+                                    let closer = conn.clone();
+                                    let close = move |reason: &str| {
+                                        closer.close(
+                                            VarInt::from_u32(ADMIN_CLOSE_CODE),
+                                            reason.as_bytes(),
+                                        )
+                                    };
+                                    let admitted = occupancy
+                                        .admit(
+                                            &connection_packet.meeting_id,
+                                            participant,
+                                            settings.max_participants,
+                                            close,
+                                        )
+                                        .await;
+                                    match admitted {
+                                        Ok(admitted) => {
+                                            let _ = seat.set(admitted);
+                                        }
+                                        Err(e) => {
+                                            info!("Rejecting quic connection: {}", e);
+                                            reject_connection(
+                                                &conn,
+                                                capacity_close_code(e),
+                                                &e.to_packet(),
+                                                &e.to_string(),
+                                            )
+                                            .await;
+                                            return;
+                                        }
+                                    }
                                     info!("Specific subject: {}", specific_subject);
+                                    let guard = SenderGuard::from_env(packet_wrapper.email.clone())
+                                        .with_publishing(
+                                            settings.may_publish(&packet_wrapper.email),
+                                        );
+                                    // Only the first connection packet gets here, see `connecting`.
+                                    let _ = sender.set(guard);
+                                    let _ = host.set(controls);
+                                    let _ = chat.set(chat_history.chat(
+                                        connection_packet.meeting_id.clone(),
+                                        packet_wrapper.email.clone(),
+                                    ));
                                     specific_subject_tx_clone
                                         .send(Some(specific_subject.clone()))
                                         .unwrap();
+                                    if let Err(e) =
+                                        send_to_connection(&conn, &settings.to_packet()).await
+                                    {
+                                        error!("Error sending room settings: {}", e);
+                                    }
+                                    if let Some(host) = &admission.host {
+                                        if let Err(e) =
+                                            send_to_connection(&conn, &host_packet(host)).await
+                                        {
+                                            error!("Error sending the host: {}", e);
+                                        }
+                                    }
+                                    if let Some(chat) = chat.get() {
+                                        replay_chat_quic(&conn, chat).await;
+                                    }
+                                    let joined = participant_packet(
+                                        PacketType::PARTICIPANT_JOINED,
+                                        &packet_wrapper.email,
+                                    );
+                                    if let Err(e) =
+                                        bus.publish(specific_subject.clone(), joined).await
+                                    {
+                                        error!(
+                                            "Error publishing to subject {}: {}",
+                                            &specific_subject, e
+                                        );
+                                    }
+                                    let event = AuditEvent::new(
+                                        &*connection_packet.meeting_id,
+                                        EventKind::Joined,
+                                        &*packet_wrapper.email,
+                                    );
+                                    audit.record(event).await;
+                                    if let Some(webhooks) = &webhooks {
+                                        webhooks
+                                            .participant_joined(
+                                                &connection_packet.meeting_id,
+                                                &packet_wrapper.email,
+                                            )
+                                            .await;
+                                    }
                                 }
                             }
+                        } else if packet_type(&d) == Some(PacketType::CONNECTION) {
+                            warn!("Closing quic connection: second connection packet");
+                            conn.close(
+                                VarInt::from_u32(ALREADY_CONNECTING_CLOSE_CODE),
+                                b"Already connected",
+                            );
                         } else {
                             let specific_subject = specific_subject_rx.borrow().clone().unwrap();
-                            if let Err(e) = nc.publish(specific_subject.clone(), d.into()).await {
+                            match admit_quic_packet(&sender, &d, &conn) {
+                                Inbound::Publish => {}
+                                Inbound::Control(packet) => {
+                                    let Some(host) = host.get() else {
+                                        return;
+                                    };
+                                    let control =
+                                        run_command(host, &packet, &*bus, &specific_subject).await;
+                                    if let Some(control) = control {
+                                        if let Err(e) = send_to_connection(&conn, &control).await {
+                                            error!("Error sending control packet: {}", e);
+                                        }
+                                    }
+                                    return;
+                                }
+                                Inbound::Chat(packet) => {
+                                    let Some(chat) = chat.get() else {
+                                        return;
+                                    };
+                                    let message =
+                                        post_chat(chat, &packet, &*bus, &specific_subject).await;
+                                    if let Some(message) = message {
+                                        if let Err(e) = send_to_connection(&conn, &message).await {
+                                            error!("Error sending chat message: {}", e);
+                                        }
+                                    }
+                                    return;
+                                }
+                                Inbound::Refused => return,
+                            }
+                            if let Err(e) = bus.publish(specific_subject.clone(), d.into()).await {
                                 error!("Error publishing to subject {}: {}", &specific_subject, e);
                             }
                         }
@@ -416,6 +1047,11 @@ async fn handle_quic_connection(
     };
 
     let _datagrams_task = {
+        let bus = bus.clone();
+        let sender = sender.clone();
+        let host = host.clone();
+        let chat = chat.clone();
+        let mut specific_subject_rx = specific_subject_rx.clone();
         tokio::spawn(async move {
             let session = session.read().await;
             if specific_subject_rx.borrow().is_none() {
@@ -423,20 +1059,286 @@ async fn handle_quic_connection(
             }
             let specific_subject = specific_subject_rx.borrow().clone().unwrap();
             while let Ok(datagram) = session.read_datagram().await {
-                let nc = nc.clone();
-                if let Err(e) = nc.publish(specific_subject.clone(), datagram).await {
+                match admit_quic_packet(&sender, &datagram, &session) {
+                    Inbound::Publish => {}
+                    Inbound::Control(packet) => {
+                        let Some(host) = host.get() else {
+                            continue;
+                        };
+                        let control = run_command(host, &packet, &*bus, &specific_subject).await;
+                        if let Some(control) = control {
+                            if let Err(e) = send_to_connection(&session, &control).await {
+                                error!("Error sending control packet: {}", e);
+                            }
+                        }
+                        continue;
+                    }
+                    Inbound::Chat(packet) => {
+                        let Some(chat) = chat.get() else {
+                            continue;
+                        };
+                        let message = post_chat(chat, &packet, &*bus, &specific_subject).await;
+                        if let Some(message) = message {
+                            if let Err(e) = send_to_connection(&session, &message).await {
+                                error!("Error sending chat message: {}", e);
+                            }
+                        }
+                        continue;
+                    }
+                    Inbound::Refused => continue,
+                }
+                if let Err(e) = bus.publish(specific_subject.clone(), datagram).await {
                     error!("Error publishing to subject {}: {}", specific_subject, e);
                 }
             }
         })
     };
-    quic_task.await?;
+    let result = quic_task.await;
     should_run.store(false, Ordering::SeqCst);
-    nats_task.abort();
+    bus_task.abort();
+    let specific_subject = specific_subject_rx.borrow().clone();
+    if let (Some(specific_subject), Some(sender)) = (specific_subject, sender.get()) {
+        let left = participant_packet(PacketType::PARTICIPANT_LEFT, sender.identity());
+        if let Err(e) = bus.publish(specific_subject.clone(), left).await {
+            error!("Error publishing to subject {}: {}", specific_subject, e);
+        }
+        if let Some(host) = host.get() {
+            let event = AuditEvent::new(host.room(), EventKind::Left, sender.identity());
+            audit.record(event).await;
+            if let Some(webhooks) = &webhooks {
+                webhooks
+                    .participant_left(host.room(), sender.identity())
+                    .await;
+            }
+        }
+    }
+    result?;
     info!("Finished handling session");
     Ok(())
 }
 
+fn refusal_close_code(e: &JoinRefused) -> u32 {
+    match e {
+        JoinRefused::Banned(_) => BANNED_CLOSE_CODE,
+        JoinRefused::PasscodeRequired => PASSCODE_REQUIRED_CLOSE_CODE,
+        JoinRefused::WrongPasscode | JoinRefused::TooManyAttempts => WRONG_PASSCODE_CLOSE_CODE,
+        JoinRefused::InvalidInvite => INVALID_INVITE_CLOSE_CODE,
+        JoinRefused::InvalidRoom | JoinRefused::Unregistered | JoinRefused::Failed(_) => {
+            UNKNOWN_MEETING_CLOSE_CODE
+        }
+    }
+}
+
+fn capacity_close_code(e: CapacityError) -> u32 {
+    match e {
+        CapacityError::RoomFull => ROOM_FULL_CLOSE_CODE,
+        CapacityError::ServerFull => SERVER_FULL_CLOSE_CODE,
+    }
+}
+
+/// Sends a server generated packet to a WebTransport client on its own unidirectional stream,
+/// which unlike a datagram is not dropped.
+async fn send_to_session(session: &Session, packet: &[u8]) -> Result<()> {
+    session.open_uni().await?.write_all(packet).await?;
+    Ok(())
+}
+
+/// Sends a server generated packet to a raw QUIC client on its own unidirectional stream.
+async fn send_to_connection(conn: &quinn::Connection, packet: &[u8]) -> Result<()> {
+    let mut stream = conn.open_uni().await?;
+    stream.write_all(packet).await?;
+    stream.finish()?;
+    Ok(())
+}
+
+/// Keeps a WebTransport session out of its room until the host decides about it.
+async fn wait_in_lobby(session: &Session, lobby: &Lobby, email: &str, queue: &str) -> Result<()> {
+    send_to_session(session, &lobby_packet(Status::WAITING, email)).await?;
+    let decision = tokio::select! {
+        decision = lobby.wait(email, queue) => decision?,
+        e = session.closed() => return Err(anyhow!("left the lobby: {}", e)),
+    };
+    let packet = lobby_packet(decision.status(), email);
+    if decision == Decision::Denied {
+        reject_session(session, DENIED_CLOSE_CODE, &packet, "Denied by the host").await;
+        return Err(anyhow!("denied by the host"));
+    }
+    send_to_session(session, &packet).await
+}
+
+/// [wait_in_lobby] for raw QUIC clients.
+async fn wait_in_lobby_quic(
+    conn: &quinn::Connection,
+    lobby: &Lobby,
+    email: &str,
+    queue: &str,
+) -> Result<()> {
+    send_to_connection(conn, &lobby_packet(Status::WAITING, email)).await?;
+    let decision = tokio::select! {
+        decision = lobby.wait(email, queue) => decision?,
+        e = conn.closed() => return Err(anyhow!("left the lobby: {}", e)),
+    };
+    send_to_connection(conn, &lobby_packet(decision.status(), email)).await?;
+    if decision == Decision::Denied {
+        conn.close(VarInt::from_u32(DENIED_CLOSE_CODE), b"Denied by the host");
+        return Err(anyhow!("denied by the host"));
+    }
+    Ok(())
+}
+
+/// Closes a WebTransport session after sending it `packet`, if any, telling the client why.
+///
+/// Like [reject_connection], this waits for the client to acknowledge the packet for at most
+/// [REJECTION_DELIVERY_TIMEOUT] before closing.
+async fn reject_session(session: &Session, code: u32, packet: &[u8], reason: &str) {
+    if !packet.is_empty() {
+        let delivered = async {
+            let mut stream = session.open_uni().await?;
+            stream.write_all(packet).await?;
+            stream.finish()?;
+            stream.stopped().await?;
+            anyhow::Ok(())
+        };
+        match tokio::time::timeout(REJECTION_DELIVERY_TIMEOUT, delivered).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!(
+                "Error sending the rejection to the WebTransport client: {}",
+                e
+            ),
+            Err(_) => warn!("WebTransport client did not acknowledge its rejection in time"),
+        }
+    }
+    session.close(code, reason.as_bytes());
+}
+
+/// Closes a raw QUIC connection after sending it `packet`, if any, telling the client why.
+///
+/// Closing drops whatever wasn't delivered yet, so this waits for the client to acknowledge the
+/// packet, for at most [REJECTION_DELIVERY_TIMEOUT].
+async fn reject_connection(conn: &quinn::Connection, code: u32, packet: &[u8], reason: &str) {
+    if !packet.is_empty() {
+        let delivered = async {
+            let mut stream = conn.open_uni().await?;
+            stream.write_all(packet).await?;
+            stream.finish()?;
+            stream.stopped().await?;
+            anyhow::Ok(())
+        };
+        match tokio::time::timeout(REJECTION_DELIVERY_TIMEOUT, delivered).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Error sending the rejection to the quic client: {}", e),
+            Err(_) => warn!("Quic client did not acknowledge its rejection in time"),
+        }
+    }
+    conn.close(VarInt::from_u32(code), reason.as_bytes());
+}
+
+/// Runs a host command from the client and publishes the result to the room. Returns the packet
+/// to send back to the client.
+async fn run_command(
+    host: &HostControls,
+    packet: &PacketWrapper,
+    bus: &dyn RoomBus,
+    subject: &str,
+) -> Option<Bytes> {
+    let control = match host.command(packet).await {
+        Ok(Some(control)) => control,
+        Ok(None) => return None,
+        Err(e) => {
+            warn!("Refusing command from {}: {}", host.email(), e);
+            return None;
+        }
+    };
+    if let Err(e) = bus.publish(subject.to_string(), control.clone()).await {
+        error!("Error publishing to subject {}: {}", subject, e);
+    }
+    Some(control)
+}
+
+/// Stores a chat message from the client and publishes it to the room. Returns the packet to
+/// send back to the client.
+async fn post_chat(
+    chat: &Chat,
+    packet: &PacketWrapper,
+    bus: &dyn RoomBus,
+    subject: &str,
+) -> Option<Bytes> {
+    let message = match chat.post(packet).await {
+        Ok(message) => message,
+        Err(e) => {
+            warn!("Refusing chat message from {}: {}", chat.email(), e);
+            return None;
+        }
+    };
+    if let Err(e) = bus.publish(subject.to_string(), message.clone()).await {
+        error!("Error publishing to subject {}: {}", subject, e);
+    }
+    Some(message)
+}
+
+/// Records the participant and sends the room's recent chat messages to a raw QUIC client
+/// that just joined.
+async fn replay_chat_quic(conn: &quinn::Connection, chat: &Chat) {
+    let messages = match chat.join().await {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Error replaying the chat: {}", e);
+            return;
+        }
+    };
+    for message in messages {
+        if let Err(e) = send_to_connection(conn, &message).await {
+            error!("Error sending chat message: {}", e);
+            return;
+        }
+    }
+}
+
+/// What to do with a packet from the client once the [SenderGuard] has seen it.
+enum Inbound {
+    Publish,
+    Control(PacketWrapper),
+    Chat(PacketWrapper),
+    Refused,
+}
+
+/// Runs `packet` through the session's [SenderGuard], calling `close` once the client has sent
+/// too many invalid packets.
+fn admit_packet(sender: &SenderGuard, packet: &[u8], close: impl FnOnce()) -> Inbound {
+    match sender.admit(packet) {
+        Verdict::Forward => Inbound::Publish,
+        Verdict::Control(packet) => Inbound::Control(packet),
+        Verdict::Chat(packet) => Inbound::Chat(packet),
+        Verdict::Drop(violation) => {
+            warn!("Dropping packet from {}: {}", sender.identity(), violation);
+            Inbound::Refused
+        }
+        Verdict::Disconnect(violation) => {
+            warn!("Disconnecting {}: {}", sender.identity(), violation);
+            close();
+            Inbound::Refused
+        }
+    }
+}
+
+/// [admit_packet] for raw QUIC clients, whose [SenderGuard] is only known once they sent their
+/// connection packet.
+fn admit_quic_packet(
+    sender: &OnceLock<SenderGuard>,
+    packet: &[u8],
+    conn: &quinn::Connection,
+) -> Inbound {
+    let Some(sender) = sender.get() else {
+        return Inbound::Refused;
+    };
+    admit_packet(sender, packet, || {
+        conn.close(
+            VarInt::from_u32(SENDER_VIOLATION_CLOSE_CODE),
+            b"Invalid sender",
+        )
+    })
+}
+
 fn session_subject_to_lobby_subject(subject: &str) -> String {
     let parts = subject.split('.').collect::<Vec<&str>>();
     let mut lobby_subject = String::from("room.");
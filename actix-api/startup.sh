@@ -1,6 +1,4 @@
 #!/bin/bash -e
-## Only run dbmate is DATABASE_ENABLED is true, else run the websocket server
-if [ "$DATABASE_ENABLED" = "true" ]; then
-  /usr/src/app/dbmate/startup.sh
-fi
+## Migrations are embedded in the binary and applied on startup when DATABASE_ENABLED is true.
+## They can also be applied on their own with `websocket_server migrate`.
 websocket_server
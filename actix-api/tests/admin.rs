@@ -0,0 +1,346 @@
+mod common;
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix::{Actor, ActorContext, Addr, Context, Handler};
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::StatusCode;
+use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+use actix_web::{web, App};
+use sec_api::actors::chat_server::ChatServer;
+use sec_api::api;
+use sec_api::audit::{AuditLog, AuditStore, EventKind, FileAuditStore};
+use sec_api::auth::admin::AdminKey;
+use sec_api::bus::{LocalBus, RoomBus};
+use sec_api::messages::server::{Connect, JoinRoom};
+use sec_api::messages::session::{Close, Message};
+use sec_api::models::AppState;
+use sec_api::rooms::admin::ADMIN_ACTOR;
+use sec_api::rooms::capacity::{Capacity, Occupancy};
+use sec_api::rooms::presence::Presence;
+use sec_api::rooms::{Participant, Transport};
+use serde_json::{json, Value};
+
+const KEY: &str = "operator-key";
+
+/// Stands in for a websocket session, keeping the reasons it was closed with.
+struct FakeSession {
+    closed: Arc<Mutex<Vec<String>>>,
+}
+
+impl Actor for FakeSession {
+    type Context = Context<Self>;
+}
+
+impl Handler<Message> for FakeSession {
+    type Result = ();
+
+    fn handle(&mut self, _: Message, _ctx: &mut Self::Context) {}
+}
+
+impl Handler<Close> for FakeSession {
+    type Result = ();
+
+    fn handle(&mut self, Close { reason }: Close, ctx: &mut Self::Context) {
+        self.closed.lock().unwrap().push(reason);
+        ctx.stop();
+    }
+}
+
+async fn join(
+    chat: &Addr<ChatServer>,
+    closed: &Arc<Mutex<Vec<String>>>,
+    session: &str,
+    room: &str,
+) {
+    let addr = FakeSession {
+        closed: closed.clone(),
+    }
+    .start();
+    chat.send(Connect {
+        id: session.to_string(),
+        addr: addr.clone().recipient(),
+        close: addr.recipient(),
+    })
+    .await
+    .unwrap();
+    chat.send(JoinRoom {
+        session: session.to_string(),
+        room: room.to_string(),
+        user: format!("{}@example.com", session),
+        transport: Transport::WebSocket,
+        max_participants: None,
+    })
+    .await
+    .unwrap()
+    .unwrap();
+}
+
+/// The state of a server whose admin API takes [KEY].
+fn admin_state(chat: Addr<ChatServer>, bus: Arc<dyn RoomBus>, audit: AuditLog) -> AppState {
+    AppState {
+        chat,
+        audit,
+        admin_key: Some(AdminKey::new(KEY)),
+        ..common::app_state(bus)
+    }
+}
+
+fn authorized(req: TestRequest) -> actix_http::Request {
+    req.insert_header((AUTHORIZATION, format!("Bearer {}", KEY)))
+        .to_request()
+}
+
+/// Sessions are closed by the servers once they see the command, after the request returned.
+async fn eventually<F: Future<Output = bool>>(mut check: impl FnMut() -> F) -> bool {
+    for _ in 0..40 {
+        if check().await {
+            return true;
+        }
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+    }
+    false
+}
+
+async fn status<S>(app: &S, req: TestRequest) -> StatusCode
+where
+    S: Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    call_service(app, authorized(req)).await.status()
+}
+
+#[test]
+fn admin_key_needs_the_exact_bearer_token() {
+    let key = AdminKey::new(KEY);
+    assert!(key.verify(KEY));
+    assert!(key.verify_header(&format!("Bearer {}", KEY)));
+    assert!(!key.verify_header(KEY));
+    assert!(!key.verify_header(&format!("Basic {}", KEY)));
+    assert!(!key.verify_header("Bearer operator-ke"));
+    assert!(!key.verify_header("Bearer "));
+    assert!(!key.verify(""));
+}
+
+#[actix_rt::test]
+async fn operators_list_inspect_and_close_sessions() {
+    let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+    let store = Arc::new(FileAuditStore::new(&path));
+    let audit = AuditLog::new(store.clone());
+    let bus: Arc<dyn RoomBus> = Arc::new(LocalBus::new());
+    let chat = ChatServer::new(bus.clone())
+        .with_audit(audit.clone())
+        .start();
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    join(&chat, &closed, "s1", "standup").await;
+    join(&chat, &closed, "s2", "standup").await;
+    join(&chat, &closed, "s3", "retro").await;
+
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(admin_state(
+                chat.clone(),
+                bus.clone(),
+                audit,
+            )))
+            .configure(api::admin::configure),
+    )
+    .await;
+    let anonymous = TestRequest::get().uri("/admin/rooms").to_request();
+    let resp = call_service(&app, anonymous).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let wrong = TestRequest::get()
+        .uri("/admin/rooms")
+        .insert_header((AUTHORIZATION, "Bearer guess"))
+        .to_request();
+    let resp = call_service(&app, wrong).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let rooms: Value =
+        call_and_read_body_json(&app, authorized(TestRequest::get().uri("/admin/rooms"))).await;
+    assert_eq!(
+        rooms,
+        json!([
+            {"room": "retro", "participants": 1, "transports": {"websocket": 1}},
+            {"room": "standup", "participants": 2, "transports": {"websocket": 2}},
+        ])
+    );
+
+    let session: Value = call_and_read_body_json(
+        &app,
+        authorized(TestRequest::get().uri("/admin/sessions/s2")),
+    )
+    .await;
+    assert_eq!(session["room"], "standup");
+    assert_eq!(session["email"], "s2@example.com");
+    assert_eq!(session["transport"], "websocket");
+
+    let disconnect = || TestRequest::post().uri("/admin/sessions/s3/disconnect");
+    assert_eq!(status(&app, disconnect()).await, StatusCode::NO_CONTENT);
+    assert!(
+        eventually(|| async {
+            status(&app, TestRequest::get().uri("/admin/sessions/s3")).await
+                == StatusCode::NOT_FOUND
+        })
+        .await
+    );
+    assert_eq!(status(&app, disconnect()).await, StatusCode::NOT_FOUND);
+
+    let too_long = format!("/admin/rooms/standup/close?reason={}", "x".repeat(200));
+    let resp = call_service(&app, authorized(TestRequest::post().uri(&too_long))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = call_and_read_body_json(
+        &app,
+        authorized(TestRequest::post().uri("/admin/rooms/standup/close?reason=maintenance")),
+    )
+    .await;
+    assert_eq!(body, json!({"closed": 2}));
+
+    assert!(
+        eventually(|| async {
+            let rooms: Value =
+                call_and_read_body_json(&app, authorized(TestRequest::get().uri("/admin/rooms")))
+                    .await;
+            rooms == json!([])
+        })
+        .await
+    );
+    assert_eq!(
+        status(&app, TestRequest::get().uri("/admin/sessions/s1")).await,
+        StatusCode::NOT_FOUND
+    );
+
+    // The sessions got their close frames.
+    assert!(eventually(|| async { closed.lock().unwrap().len() == 3 }).await);
+    let mut reasons = closed.lock().unwrap().clone();
+    reasons.sort();
+    assert_eq!(
+        reasons,
+        ["closed by an administrator", "maintenance", "maintenance"]
+    );
+
+    let mut removed = store.events("standup", None, 10).await.unwrap();
+    removed.retain(|e| e.kind == EventKind::Removed);
+    assert_eq!(removed.len(), 2);
+    assert!(removed
+        .iter()
+        .all(|e| e.actor == ADMIN_ACTOR && e.detail.as_deref() == Some("maintenance")));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[actix_rt::test]
+async fn admin_api_is_disabled_without_a_key() {
+    let bus: Arc<dyn RoomBus> = Arc::new(LocalBus::new());
+    let chat = ChatServer::new(bus.clone()).start();
+    let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+    let audit = AuditLog::new(Arc::new(FileAuditStore::new(path)));
+    let state = AppState {
+        admin_key: None,
+        ..admin_state(chat, bus, audit)
+    };
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .configure(api::admin::configure),
+    )
+    .await;
+    let req = TestRequest::get()
+        .uri("/admin/rooms")
+        .insert_header((AUTHORIZATION, format!("Bearer {}", KEY)))
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn operators_see_and_close_webtransport_sessions() {
+    let pool = common::test_pool(&[]).await;
+    let presence = Presence::new(pool.clone());
+    let bus: Arc<dyn RoomBus> = Arc::new(LocalBus::new());
+    let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+    let audit = AuditLog::new(Arc::new(FileAuditStore::new(&path)));
+    let chat = ChatServer::new(bus.clone())
+        .with_presence(Some(presence.clone()))
+        .start();
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    join(&chat, &closed, "ws1", "standup").await;
+
+    // Stands in for the webtransport server, sharing the bus and the database.
+    let occupancy = Occupancy::new(Capacity::default(), Some(presence.clone()));
+    actix_rt::spawn(occupancy.follow_admin_commands(&*bus));
+    let seat = {
+        let closed = closed.clone();
+        let participant = Participant::new(
+            "wt1".to_string(),
+            "wt1@example.com".to_string(),
+            Transport::WebTransport,
+        );
+        let close = move |reason: &str| closed.lock().unwrap().push(format!("wt1 {}", reason));
+        occupancy
+            .admit("standup", participant, None, close)
+            .await
+            .unwrap()
+    };
+
+    let state = AppState {
+        presence: Some(presence),
+        ..admin_state(chat, bus.clone(), audit)
+    };
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .configure(api::admin::configure),
+    )
+    .await;
+
+    let both = json!([{
+        "room": "standup",
+        "participants": 2,
+        "transports": {"websocket": 1, "webtransport": 1},
+    }]);
+    assert!(
+        eventually(|| async {
+            let rooms: Value =
+                call_and_read_body_json(&app, authorized(TestRequest::get().uri("/admin/rooms")))
+                    .await;
+            rooms == both
+        })
+        .await
+    );
+    let session: Value = call_and_read_body_json(
+        &app,
+        authorized(TestRequest::get().uri("/admin/sessions/wt1")),
+    )
+    .await;
+    assert_eq!(session["room"], "standup");
+    assert_eq!(session["email"], "wt1@example.com");
+    assert_eq!(session["transport"], "webtransport");
+
+    let disconnect = TestRequest::post().uri("/admin/sessions/wt1/disconnect?reason=bye");
+    assert_eq!(status(&app, disconnect).await, StatusCode::NO_CONTENT);
+    assert!(eventually(|| async { closed.lock().unwrap().as_slice() == ["wt1 bye"] }).await);
+    // The session gives its seat back once it is closed.
+    drop(seat);
+    assert!(
+        eventually(|| async {
+            status(&app, TestRequest::get().uri("/admin/sessions/wt1")).await
+                == StatusCode::NOT_FOUND
+        })
+        .await
+    );
+
+    let body: Value = call_and_read_body_json(
+        &app,
+        authorized(TestRequest::post().uri("/admin/rooms/standup/close?reason=done")),
+    )
+    .await;
+    assert_eq!(body, json!({"closed": 1}));
+    assert!(eventually(|| async { closed.lock().unwrap().len() == 2 }).await);
+    assert_eq!(closed.lock().unwrap()[1], "done");
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,215 @@
+mod common;
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use protobuf::Message;
+use sec_api::audit::{
+    AuditEvent, AuditLog, AuditStore, EventKind, FileAuditStore, PostgresAuditStore,
+};
+use sec_api::bus::{LocalBus, RoomBus};
+use sec_api::moderation::HostControls;
+use sec_api::recording::Recorder;
+use types::protos::control_packet::control_packet::Command;
+use types::protos::control_packet::ControlPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+fn temp_path() -> PathBuf {
+    std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()))
+}
+
+fn event(room: &str, kind: EventKind, actor: &str, seconds: i64) -> AuditEvent {
+    AuditEvent {
+        at: Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap(),
+        ..AuditEvent::new(room, kind, actor)
+    }
+}
+
+fn kinds(events: &[AuditEvent]) -> Vec<(EventKind, &str)> {
+    events.iter().map(|e| (e.kind, e.actor.as_str())).collect()
+}
+
+async fn pages_through_events(store: Arc<dyn AuditStore>) {
+    let removed = event("standup", EventKind::Removed, "alice", 3).with_target("mallory");
+    // Appended out of order, as concurrent sessions may.
+    for event in [
+        event("standup", EventKind::Joined, "alice", 0),
+        event("standup", EventKind::Joined, "mallory", 2),
+        event("standup", EventKind::Joined, "bob", 1),
+        event("other", EventKind::Joined, "carol", 1),
+        removed.clone(),
+        event("standup", EventKind::AuthFailed, "mallory", 4).with_detail("banned"),
+    ] {
+        store.append(event).await.unwrap();
+    }
+
+    let first = store.events("standup", None, 2).await.unwrap();
+    assert_eq!(
+        kinds(&first),
+        [(EventKind::Joined, "alice"), (EventKind::Joined, "bob")]
+    );
+    let rest = store
+        .events("standup", Some(&first[1].id), 10)
+        .await
+        .unwrap();
+    assert_eq!(
+        kinds(&rest),
+        [
+            (EventKind::Joined, "mallory"),
+            (EventKind::Removed, "alice"),
+            (EventKind::AuthFailed, "mallory"),
+        ]
+    );
+    assert_eq!(rest[1].id, removed.id);
+    assert_eq!(rest[1].target.as_deref(), Some("mallory"));
+    assert_eq!(rest[1].at, removed.at);
+    assert_eq!(rest[2].detail.as_deref(), Some("banned"));
+
+    assert!(store
+        .events("standup", Some(&rest[2].id), 10)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(store
+        .events("standup", Some("unknown"), 10)
+        .await
+        .unwrap()
+        .is_empty());
+    let other = store.events("other", None, 10).await.unwrap();
+    assert_eq!(kinds(&other), [(EventKind::Joined, "carol")]);
+    assert!(store
+        .events("other", Some(&first[0].id), 10)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[actix_rt::test]
+async fn file_store_pages_through_events() {
+    let path = temp_path();
+    let store = FileAuditStore::new(&path);
+    assert!(store.events("standup", None, 10).await.unwrap().is_empty());
+    pages_through_events(Arc::new(store.clone())).await;
+
+    // A line cut short by a crash does not hide the others.
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(b"{\"id\":\"cut").unwrap();
+    drop(file);
+    store
+        .append(event("other", EventKind::Left, "carol", 5))
+        .await
+        .unwrap();
+    let other = store.events("other", None, 10).await.unwrap();
+    assert_eq!(
+        kinds(&other),
+        [(EventKind::Joined, "carol"), (EventKind::Left, "carol")]
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[actix_rt::test]
+async fn refused_connections_are_recorded_once_per_window() {
+    let path = temp_path();
+    let store = FileAuditStore::new(&path);
+    let audit =
+        AuditLog::new(Arc::new(store.clone())).with_auth_failure_window(Duration::from_millis(200));
+    let refused = |actor: &str| {
+        AuditEvent::new("standup", EventKind::AuthFailed, actor).with_detail("wrong passcode")
+    };
+    for _ in 0..5 {
+        audit.record(refused("mallory")).await;
+    }
+    audit.record(refused("bob")).await;
+    audit
+        .record(AuditEvent::new("standup", EventKind::Joined, "alice"))
+        .await;
+    audit
+        .record(AuditEvent::new("standup", EventKind::Joined, "alice"))
+        .await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    audit.record(refused("mallory")).await;
+
+    let events = store.events("standup", None, 10).await.unwrap();
+    assert_eq!(
+        kinds(&events),
+        [
+            (EventKind::AuthFailed, "mallory"),
+            (EventKind::AuthFailed, "bob"),
+            (EventKind::Joined, "alice"),
+            (EventKind::Joined, "alice"),
+            (EventKind::AuthFailed, "mallory"),
+        ]
+    );
+    assert_eq!(events[0].detail.as_deref(), Some("wrong passcode"));
+    assert_eq!(
+        events[4].detail.as_deref(),
+        Some("wrong passcode (4 more refused since the last one recorded)")
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn postgres_store_pages_through_events() {
+    let pool = common::test_pool(&[]).await;
+    pages_through_events(Arc::new(PostgresAuditStore::new(pool.clone()))).await;
+}
+
+#[actix_rt::test]
+async fn removals_and_recordings_are_audited() {
+    let path = temp_path();
+    let store = Arc::new(FileAuditStore::new(&path));
+    let audit = AuditLog::new(store.clone());
+    let bus: Arc<dyn RoomBus> = Arc::new(LocalBus::new());
+    let dir = std::env::temp_dir().join(format!("recordings-{}", uuid::Uuid::new_v4()));
+    let recorder = Recorder::new(bus, &dir).with_audit(audit.clone());
+
+    let host = HostControls::new("standup", "alice", Some("alice"), None)
+        .with_recorder(Some(recorder))
+        .with_audit(Some(audit));
+    let command = |command: Command, target: &str| PacketWrapper {
+        packet_type: PacketType::CONTROL.into(),
+        email: "alice".to_string(),
+        data: ControlPacket {
+            command: command.into(),
+            target: target.to_string(),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap(),
+        ..Default::default()
+    };
+    host.command(&command(Command::REMOVE, "mallory"))
+        .await
+        .unwrap();
+    host.command(&command(Command::MUTE, "bob")).await.unwrap();
+    host.command(&command(Command::START_RECORDING, ""))
+        .await
+        .unwrap();
+    host.command(&command(Command::STOP_RECORDING, ""))
+        .await
+        .unwrap();
+
+    let events = store.events("standup", None, 10).await.unwrap();
+    assert_eq!(
+        kinds(&events),
+        [
+            (EventKind::Removed, "alice"),
+            (EventKind::RecordingStarted, "alice"),
+            (EventKind::RecordingStopped, "alice"),
+        ]
+    );
+    assert_eq!(events[0].target.as_deref(), Some("mallory"));
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
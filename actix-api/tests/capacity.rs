@@ -0,0 +1,251 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use actix::Actor;
+use protobuf::Message;
+use sec_api::actors::chat_server::ChatServer;
+use sec_api::bus::LocalBus;
+use sec_api::messages::server::{JoinError, JoinRoom};
+use sec_api::rooms::admin::{AdminCommand, CloseTarget};
+use sec_api::rooms::capacity::{Capacity, CapacityError, Occupancy, Seat};
+use sec_api::rooms::presence::Presence;
+use sec_api::rooms::{Participant, RoomRegistry, Transport};
+use types::protos::join_rejected::join_rejected_packet::Reason;
+use types::protos::join_rejected::JoinRejectedPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+fn join(registry: &mut RoomRegistry, room: &str, session: &str) {
+    registry.join(
+        room.to_string(),
+        Participant::new(
+            session.to_string(),
+            session.to_string(),
+            Transport::WebSocket,
+        ),
+    );
+}
+
+#[test]
+fn room_settings_can_only_lower_the_configured_limit() {
+    let capacity = Capacity {
+        per_room: Some(10),
+        total: None,
+    };
+    assert_eq!(capacity.room_limit(None), Some(10));
+    assert_eq!(capacity.room_limit(Some(3)), Some(3));
+    assert_eq!(capacity.room_limit(Some(50)), Some(10));
+    assert_eq!(Capacity::default().room_limit(Some(4)), Some(4));
+    assert_eq!(Capacity::default().room_limit(None), None);
+}
+
+#[test]
+fn registry_refuses_full_rooms_and_servers() {
+    let capacity = Capacity {
+        per_room: Some(2),
+        total: Some(3),
+    };
+    let mut registry = RoomRegistry::new();
+    join(&mut registry, "a", "s1");
+    join(&mut registry, "a", "s2");
+
+    assert_eq!(
+        registry.admit("a", &"s3".to_string(), &capacity, None),
+        Err(CapacityError::RoomFull)
+    );
+    assert_eq!(
+        registry.admit("b", &"s3".to_string(), &capacity, Some(1)),
+        Ok(())
+    );
+    // Rejoining the same room does not need another seat.
+    assert_eq!(
+        registry.admit("a", &"s1".to_string(), &capacity, None),
+        Ok(())
+    );
+
+    join(&mut registry, "b", "s3");
+    assert_eq!(
+        registry.admit("b", &"s4".to_string(), &capacity, None),
+        Err(CapacityError::ServerFull)
+    );
+    // Moving between rooms keeps the server total unchanged.
+    assert_eq!(
+        registry.admit("c", &"s3".to_string(), &capacity, None),
+        Ok(())
+    );
+}
+
+#[test]
+fn registry_follows_sessions_joining_moving_and_leaving() {
+    let mut registry = RoomRegistry::new();
+    join(&mut registry, "a", "s1");
+    join(&mut registry, "a", "s2");
+    join(&mut registry, "b", "s3");
+    // Sorted, as sessions joining in the same millisecond may be listed in any order.
+    let sessions = |registry: &RoomRegistry, room: &str| -> Vec<String> {
+        let mut sessions: Vec<String> = registry
+            .participants(room)
+            .into_iter()
+            .map(|p| p.session_id)
+            .collect();
+        sessions.sort();
+        sessions
+    };
+    assert_eq!(sessions(&registry, "a"), ["s1", "s2"]);
+    assert_eq!(sessions(&registry, "b"), ["s3"]);
+
+    // Joining another room leaves the one the session was in.
+    join(&mut registry, "b", "s1");
+    assert_eq!(sessions(&registry, "a"), ["s2"]);
+    assert_eq!(registry.participants("b").len(), 2);
+
+    let (room, participant) = registry.leave(&"s2".to_string()).unwrap();
+    assert_eq!((room.as_str(), participant.email.as_str()), ("a", "s2"));
+    assert!(registry.participants("a").is_empty());
+    assert!(registry.leave(&"s2".to_string()).is_none());
+    let rooms: Vec<String> = registry.rooms().into_iter().map(|r| r.room).collect();
+    assert_eq!(rooms, ["b"]);
+}
+
+async fn admit(
+    occupancy: &Arc<Occupancy>,
+    session: &str,
+    room: &str,
+    room_max: Option<u32>,
+) -> Result<Seat, CapacityError> {
+    let participant = Participant::new(
+        session.to_string(),
+        format!("{}@example.com", session),
+        Transport::WebTransport,
+    );
+    occupancy.admit(room, participant, room_max, |_| {}).await
+}
+
+#[actix_rt::test]
+async fn seats_are_given_back_when_dropped() {
+    let occupancy = Occupancy::new(
+        Capacity {
+            per_room: None,
+            total: Some(2),
+        },
+        None,
+    );
+    let first = admit(&occupancy, "s1", "a", Some(1)).await.unwrap();
+    assert_eq!(
+        admit(&occupancy, "s2", "a", Some(1)).await.unwrap_err(),
+        CapacityError::RoomFull
+    );
+    let second = admit(&occupancy, "s3", "b", None).await.unwrap();
+    assert_eq!(
+        admit(&occupancy, "s4", "c", None).await.unwrap_err(),
+        CapacityError::ServerFull
+    );
+    assert_eq!(occupancy.total(), 2);
+
+    drop(first);
+    assert_eq!(occupancy.room_size("a"), 0);
+    assert_eq!(occupancy.total(), 1);
+    let _again = admit(&occupancy, "s5", "a", Some(1)).await.unwrap();
+    drop(second);
+    assert_eq!(occupancy.total(), 1);
+}
+
+#[actix_rt::test]
+async fn admin_commands_close_the_sessions_they_name() {
+    let occupancy = Occupancy::new(Capacity::default(), None);
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    let mut seats = Vec::new();
+    for (session, room) in [("s1", "a"), ("s2", "a"), ("s3", "b")] {
+        let closed = closed.clone();
+        let participant = Participant::new(
+            session.to_string(),
+            format!("{}@example.com", session),
+            Transport::Quic,
+        );
+        let close = move |reason: &str| {
+            closed
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", session, reason))
+        };
+        seats.push(
+            occupancy
+                .admit(room, participant, None, close)
+                .await
+                .unwrap(),
+        );
+    }
+    assert_eq!(occupancy.session_ids().len(), 3);
+
+    let close_room = AdminCommand {
+        target: CloseTarget::Room("a".to_string()),
+        reason: "maintenance".to_string(),
+    };
+    assert_eq!(occupancy.run(&close_room), 2);
+    let close_session = AdminCommand {
+        target: CloseTarget::Session("s3".to_string()),
+        reason: "bye".to_string(),
+    };
+    assert_eq!(occupancy.run(&close_session), 1);
+    let mut reasons = closed.lock().unwrap().clone();
+    reasons.sort();
+    assert_eq!(reasons, ["s1 maintenance", "s2 maintenance", "s3 bye"]);
+}
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn room_limit_holds_over_every_server() {
+    let pool = common::test_pool(&[]).await;
+    let presence = Presence::new(pool.clone());
+    let capacity = Capacity {
+        per_room: Some(2),
+        total: None,
+    };
+    // A websocket server and two webtransport servers sharing the database.
+    let chat = ChatServer::new(Arc::new(LocalBus::new()))
+        .with_capacity(capacity)
+        .with_presence(Some(presence.clone()))
+        .start();
+    let first = Occupancy::new(capacity, Some(presence.clone()));
+    let second = Occupancy::new(capacity, Some(presence));
+
+    let _seat = admit(&first, "s1", "a", None).await.unwrap();
+    let _seat = admit(&second, "s2", "a", None).await.unwrap();
+    assert_eq!(
+        admit(&first, "s3", "a", None).await.unwrap_err(),
+        CapacityError::RoomFull
+    );
+    assert_eq!(
+        admit(&second, "s4", "a", None).await.unwrap_err(),
+        CapacityError::RoomFull
+    );
+    let joined = chat
+        .send(JoinRoom {
+            session: "s5".to_string(),
+            room: "a".to_string(),
+            user: "s5@example.com".to_string(),
+            transport: Transport::WebSocket,
+            max_participants: None,
+        })
+        .await
+        .unwrap();
+    // Not connected, but the room is checked first.
+    assert!(matches!(
+        joined,
+        Err(JoinError::Full(CapacityError::RoomFull))
+    ));
+
+    let _seat = admit(&second, "s6", "b", None).await.unwrap();
+}
+
+#[test]
+fn rejection_packet_names_the_reason() {
+    let packet = PacketWrapper::parse_from_bytes(&CapacityError::ServerFull.to_packet()).unwrap();
+    assert_eq!(
+        packet.packet_type.enum_value(),
+        Ok(PacketType::JOIN_REJECTED)
+    );
+    let rejected = JoinRejectedPacket::parse_from_bytes(&packet.data).unwrap();
+    assert_eq!(rejected.reason.enum_value(), Ok(Reason::SERVER_FULL));
+}
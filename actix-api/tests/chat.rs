@@ -0,0 +1,220 @@
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use protobuf::Message;
+use sec_api::chat::{
+    transcript, ChatHistory, ChatMessage, ChatStore, MemoryChatStore, PostgresChatStore,
+};
+use sec_api::sender::{SenderGuard, Verdict};
+use types::protos::chat_packet::ChatPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+fn chat_packet(from: &str, text: &str, reply_to: &str) -> PacketWrapper {
+    PacketWrapper {
+        packet_type: PacketType::CHAT.into(),
+        email: from.to_string(),
+        data: ChatPacket {
+            // Whatever the client claims is replaced by the server.
+            id: "forged".to_string(),
+            sender: "mallory".to_string(),
+            text: text.to_string(),
+            reply_to: reply_to.to_string(),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap(),
+        ..Default::default()
+    }
+}
+
+async fn posts_and_replays(history: ChatHistory) {
+    let alice = history.chat("standup", "alice");
+    let first = alice
+        .post(&chat_packet("alice", "hello", ""))
+        .await
+        .unwrap();
+    let first = ChatMessage::parse("standup", &first).unwrap();
+    assert_eq!(first.sender, "alice");
+    assert_ne!(first.id, "forged");
+    assert_eq!(first.reply_to, None);
+
+    let bob = history.chat("standup", "bob");
+    for text in ["one", "two", "three"] {
+        bob.post(&chat_packet("bob", text, &first.id))
+            .await
+            .unwrap();
+    }
+    history
+        .chat("retro", "carol")
+        .post(&chat_packet("carol", "elsewhere", ""))
+        .await
+        .unwrap();
+
+    let replayed: Vec<_> = history
+        .chat("standup", "dave")
+        .join()
+        .await
+        .unwrap()
+        .iter()
+        .map(|packet| ChatMessage::parse("standup", packet).unwrap())
+        .collect();
+    let texts: Vec<_> = replayed.iter().map(|m| m.text.as_str()).collect();
+    assert_eq!(texts, ["one", "two", "three"]);
+    assert!(replayed
+        .iter()
+        .all(|m| m.reply_to.as_deref() == Some(first.id.as_str())));
+
+    assert!(alice.post(&chat_packet("alice", "  ", "")).await.is_err());
+    let long = "a".repeat(sec_api::chat::MAX_CHAT_MESSAGE_LEN + 1);
+    assert!(alice.post(&chat_packet("alice", &long, "")).await.is_err());
+
+    let store = history.store();
+    assert!(store.is_participant("standup", "dave").await.unwrap());
+    assert!(!store.is_participant("retro", "dave").await.unwrap());
+    assert!(!store.is_participant("standup", "carol").await.unwrap());
+}
+
+async fn pages_backwards(history: ChatHistory) {
+    let chat = history.chat("standup", "alice");
+    for text in ["one", "two", "three", "four", "five"] {
+        chat.post(&chat_packet("alice", text, "")).await.unwrap();
+    }
+    let store = history.store();
+    let texts = |messages: &[ChatMessage]| -> Vec<String> {
+        messages.iter().map(|m| m.text.clone()).collect()
+    };
+
+    let newest = store.recent("standup", None, 2).await.unwrap();
+    assert_eq!(texts(&newest), ["four", "five"]);
+    let older = store
+        .recent("standup", Some(&newest[0].id), 2)
+        .await
+        .unwrap();
+    assert_eq!(texts(&older), ["two", "three"]);
+    let oldest = store
+        .recent("standup", Some(&older[0].id), 2)
+        .await
+        .unwrap();
+    assert_eq!(texts(&oldest), ["one"]);
+
+    assert!(store
+        .recent("retro", Some(&newest[0].id), 2)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(store
+        .recent("standup", Some("unknown"), 2)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn memory_history_replays_the_last_messages_of_the_room() {
+    posts_and_replays(ChatHistory::new(Arc::new(MemoryChatStore::default()), 3)).await;
+}
+
+#[tokio::test]
+async fn memory_history_pages_backwards() {
+    pages_backwards(ChatHistory::new(Arc::new(MemoryChatStore::default()), 3)).await;
+}
+
+/// One test, so the database is wiped before each part rather than while the other one runs.
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn postgres_history_replays_and_pages_backwards() {
+    let pool = common::test_pool(&[]).await;
+    posts_and_replays(ChatHistory::new(
+        Arc::new(PostgresChatStore::new(pool.clone())),
+        3,
+    ))
+    .await;
+    // Lets go of the database, so it can be wiped for the second part.
+    drop(pool);
+    let pool = common::test_pool(&[]).await;
+    pages_backwards(ChatHistory::new(
+        Arc::new(PostgresChatStore::new(pool.clone())),
+        3,
+    ))
+    .await;
+}
+
+#[test]
+fn transcripts_have_a_line_per_message() {
+    let message = |sender: &str, text: &str, seconds: i64| ChatMessage {
+        id: seconds.to_string(),
+        room: "standup".to_string(),
+        sender: sender.to_string(),
+        text: text.to_string(),
+        sent_at: Utc.timestamp_opt(seconds, 0).unwrap(),
+        reply_to: None,
+    };
+    assert_eq!(
+        transcript(&[
+            message("alice", "hello", 0),
+            message("bob", "first line\nsecond line", 61)
+        ]),
+        "[1970-01-01 00:00:00 UTC] alice: hello\n\
+         [1970-01-01 00:01:01 UTC] bob: first line\n    second line\n"
+    );
+}
+
+#[tokio::test]
+async fn memory_history_forgets_the_oldest_messages() {
+    let store = MemoryChatStore::new(2);
+    let history = ChatHistory::new(Arc::new(store.clone()), 10);
+    let chat = history.chat("standup", "alice");
+    for text in ["one", "two", "three"] {
+        chat.post(&chat_packet("alice", text, "")).await.unwrap();
+    }
+    let texts: Vec<_> = store
+        .recent("standup", None, 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|m| m.text)
+        .collect();
+    assert_eq!(texts, ["two", "three"]);
+}
+
+#[tokio::test]
+async fn memory_history_forgets_the_least_recently_written_rooms() {
+    let store = MemoryChatStore::new(10).with_max_rooms(2);
+    let history = ChatHistory::new(Arc::new(store.clone()), 10);
+    for room in ["standup", "retro"] {
+        let chat = history.chat(room, "alice");
+        chat.join().await.unwrap();
+        chat.post(&chat_packet("alice", "hello", "")).await.unwrap();
+    }
+    // Writing to "standup" again makes "retro" the least recently written room.
+    let standup = history.chat("standup", "alice");
+    standup
+        .post(&chat_packet("alice", "again", ""))
+        .await
+        .unwrap();
+    history.chat("planning", "alice").join().await.unwrap();
+
+    assert!(store.recent("retro", None, 10).await.unwrap().is_empty());
+    assert!(!store.is_participant("retro", "alice").await.unwrap());
+    assert_eq!(store.recent("standup", None, 10).await.unwrap().len(), 2);
+    assert!(store.is_participant("standup", "alice").await.unwrap());
+    assert!(store.is_participant("planning", "alice").await.unwrap());
+}
+
+#[test]
+fn chat_packets_go_to_the_chat() {
+    let guard = SenderGuard::new("alice", 3);
+    let packet = chat_packet("alice", "hello", "");
+    assert_eq!(
+        guard.admit(&packet.write_to_bytes().unwrap()),
+        Verdict::Chat(packet)
+    );
+    let spoofed = chat_packet("bob", "hello", "");
+    assert!(matches!(
+        guard.admit(&spoofed.write_to_bytes().unwrap()),
+        Verdict::Drop(_)
+    ));
+}
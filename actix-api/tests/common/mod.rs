@@ -0,0 +1,136 @@
+//! The database the Postgres tests share, in `TEST_DATABASE_URL`, which each test wipes first.
+//! They are marked `#[ignore = "needs TEST_DATABASE_URL"]` and run with `cargo test -- --ignored`.
+//!
+//! Also holds the fixtures several test files share. Each file uses only some of them.
+#![allow(dead_code)]
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::Actor;
+use bytes::Bytes;
+use futures::StreamExt;
+use protobuf::Message;
+use sec_api::actors::chat_server::ChatServer;
+use sec_api::audit::{AuditLog, FileAuditStore};
+use sec_api::auth::token::ConnectAuth;
+use sec_api::bus::{BusSubscription, RoomBus};
+use sec_api::chat::{ChatHistory, MemoryChatStore};
+use sec_api::db::{create_pool, migrate, DbConfig, PostgresConnection, PostgresPool};
+use sec_api::models::AppState;
+use sec_api::recording::Recorder;
+use types::protos::control_packet::control_packet::Command;
+use types::protos::control_packet::ControlPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+/// Arbitrary key for `pg_advisory_lock`, held by the test using the database.
+const TEST_DATABASE_LOCK_KEY: i64 = 0x0074_6573_742d_6462;
+
+/// The test database, held by one test at a time.
+pub struct TestDb {
+    pool: PostgresPool,
+    /// Holds the lock until the test is done with the database, dropping it releases the lock.
+    _lock: PostgresConnection,
+}
+
+impl Deref for TestDb {
+    type Target = PostgresPool;
+
+    fn deref(&self) -> &PostgresPool {
+        &self.pool
+    }
+}
+
+fn config(url: String, max_size: usize) -> DbConfig {
+    DbConfig {
+        url,
+        max_size,
+        wait_timeout: Duration::from_secs(5),
+        statement_timeout: Duration::from_secs(5),
+        tls: false,
+    }
+}
+
+/// Waits for the other tests using the database to finish, then wipes it, migrates it and adds
+/// `users`.
+pub async fn test_pool(users: &[&str]) -> TestDb {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+    let lock = create_pool(&config(url.clone(), 1))
+        .unwrap()
+        .get()
+        .await
+        .unwrap();
+    lock.batch_execute("SET statement_timeout = 0")
+        .await
+        .unwrap();
+    lock.execute("SELECT pg_advisory_lock($1)", &[&TEST_DATABASE_LOCK_KEY])
+        .await
+        .unwrap();
+
+    let pool = create_pool(&config(url, 2)).unwrap();
+    pool.get()
+        .await
+        .unwrap()
+        .batch_execute("DROP SCHEMA public CASCADE; CREATE SCHEMA public;")
+        .await
+        .unwrap();
+    migrate(&pool).await.unwrap();
+    let client = pool.get().await.unwrap();
+    for email in users {
+        client
+            .execute("INSERT INTO users (email) VALUES ($1)", &[email])
+            .await
+            .unwrap();
+    }
+    drop(client);
+    TestDb { pool, _lock: lock }
+}
+
+/// The state of a server on `bus` without a database: chat history in memory, the audit log in a
+/// temporary file, and neither an admin key nor presence. Tests override the fields they need.
+pub fn app_state(bus: Arc<dyn RoomBus>) -> AppState {
+    let audit_path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+    AppState {
+        chat: ChatServer::new(bus.clone()).start(),
+        bus: bus.clone(),
+        connect_auth: ConnectAuth::Insecure,
+        meetings: None,
+        chat_history: ChatHistory::new(Arc::new(MemoryChatStore::new(10)), 10),
+        recorder: Recorder::new(bus, std::env::temp_dir()),
+        audit: AuditLog::new(Arc::new(FileAuditStore::new(audit_path))),
+        admin_key: None,
+        presence: None,
+    }
+}
+
+/// A host command as `from`'s client sends it, `target` is empty for commands about the room.
+pub fn control_command(from: &str, command: Command, target: &str) -> PacketWrapper {
+    PacketWrapper {
+        packet_type: PacketType::CONTROL.into(),
+        email: from.to_string(),
+        data: ControlPacket {
+            command: command.into(),
+            target: target.to_string(),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap(),
+        ..Default::default()
+    }
+}
+
+/// The payload of the next message on `sub`, `None` when none comes within a second.
+pub async fn next_payload(sub: &mut BusSubscription) -> Option<Bytes> {
+    tokio::time::timeout(Duration::from_secs(1), sub.next())
+        .await
+        .ok()
+        .flatten()
+        .map(|msg| msg.payload)
+}
+
+/// A path under the system temporary directory that no other test uses, starting with `prefix`.
+pub fn temp_dir(prefix: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}-{}", prefix, uuid::Uuid::new_v4()))
+}
@@ -0,0 +1,62 @@
+use sec_api::auth::token::{authorize_connection, ConnectAuth, ConnectTokenKey};
+use std::time::Duration;
+
+fn auth() -> ConnectAuth {
+    ConnectAuth::Tokens(ConnectTokenKey::new("test-secret", Duration::from_secs(60)))
+}
+
+#[actix_rt::test]
+async fn accepts_token_issued_for_the_same_user() {
+    let auth = auth();
+    let token = auth
+        .key()
+        .unwrap()
+        .issue("alice@example.com", "s1")
+        .unwrap();
+    authorize_connection(&auth, "alice@example.com", Some(&token))
+        .await
+        .unwrap();
+}
+
+#[actix_rt::test]
+async fn rejects_missing_forged_or_mismatched_tokens() {
+    let auth = auth();
+    let token = auth
+        .key()
+        .unwrap()
+        .issue("alice@example.com", "s1")
+        .unwrap();
+    assert!(
+        authorize_connection(&auth, "mallory@example.com", Some(&token))
+            .await
+            .is_err()
+    );
+    assert!(authorize_connection(&auth, "alice@example.com", None)
+        .await
+        .is_err());
+
+    let other = ConnectTokenKey::new("other-secret", Duration::from_secs(60));
+    let forged = other.issue("alice@example.com", "s1").unwrap();
+    assert!(
+        authorize_connection(&auth, "alice@example.com", Some(&forged))
+            .await
+            .is_err()
+    );
+}
+
+#[actix_rt::test]
+async fn refuses_everything_without_a_key_unless_explicitly_insecure() {
+    let token = auth()
+        .key()
+        .unwrap()
+        .issue("alice@example.com", "s1")
+        .unwrap();
+    assert!(
+        authorize_connection(&ConnectAuth::Refused, "alice@example.com", Some(&token))
+            .await
+            .is_err()
+    );
+    authorize_connection(&ConnectAuth::Insecure, "alice@example.com", None)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use std::net::IpAddr;
+
+use sec_api::meetings::access::{hash_passcode, verify_passcode, MAX_PASSCODE_FAILURES};
+use sec_api::meetings::{
+    authorize_join, create_invite, create_meeting, InviteKey, JoinCredentials, JoinRefused,
+    MeetingDirectory, MeetingUpdate, NewInvite, NewMeeting,
+};
+
+fn passcode(passcode: &str) -> JoinCredentials {
+    JoinCredentials::new(Some(passcode.to_string()), None)
+}
+
+fn invite(token: &str) -> JoinCredentials {
+    JoinCredentials::new(None, Some(token.to_string()))
+}
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn passcodes_and_invites_gate_joins() {
+    let pool = common::test_pool(&["alice@example.com"]).await;
+    let key = InviteKey::new("secret");
+    let meetings = MeetingDirectory::new(pool.clone(), true).with_invite_key(key.clone());
+    let meeting = create_meeting(
+        &pool,
+        "alice@example.com",
+        &NewMeeting {
+            title: "Board".to_string(),
+            settings: None,
+            passcode: Some("hunter22".to_string()),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(meeting.has_passcode);
+    let join = |email: &'static str, credentials: JoinCredentials| {
+        let meetings = meetings.clone();
+        let id = meeting.id.clone();
+        async move { authorize_join(Some(&meetings), &id, email, &credentials).await }
+    };
+
+    assert!(join("alice@example.com", JoinCredentials::default())
+        .await
+        .is_ok());
+    assert!(matches!(
+        join("bob@example.com", JoinCredentials::default()).await,
+        Err(JoinRefused::PasscodeRequired)
+    ));
+    assert!(matches!(
+        join("bob@example.com", passcode("hunter2")).await,
+        Err(JoinRefused::WrongPasscode)
+    ));
+    assert!(join("bob@example.com", passcode("hunter22")).await.is_ok());
+
+    // Guessing locks out that address for the meeting whatever email it claims, on every server,
+    // and nobody else.
+    let mallory: IpAddr = "203.0.113.7".parse().unwrap();
+    for i in 0..MAX_PASSCODE_FAILURES {
+        let email = ["mallory@example.com", "eve@example.com"][i as usize % 2];
+        assert!(matches!(
+            join(email, passcode("guess").with_peer(Some(mallory))).await,
+            Err(JoinRefused::WrongPasscode)
+        ));
+    }
+    let other_server = MeetingDirectory::new(pool.clone(), true);
+    assert!(matches!(
+        authorize_join(
+            Some(&other_server),
+            &meeting.id,
+            "trent@example.com",
+            &passcode("hunter22").with_peer(Some(mallory))
+        )
+        .await,
+        Err(JoinRefused::TooManyAttempts)
+    ));
+    assert!(join("bob@example.com", passcode("hunter22")).await.is_ok());
+
+    let once = create_invite(
+        &pool,
+        &key,
+        &meeting.id,
+        "alice@example.com",
+        &NewInvite {
+            expires_in_secs: None,
+            max_uses: Some(1),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(join("carol@example.com", invite(&once.token)).await.is_ok());
+    // Reconnecting does not use the invite up.
+    assert!(join("carol@example.com", invite(&once.token)).await.is_ok());
+    assert!(matches!(
+        join("dave@example.com", invite(&once.token)).await,
+        Err(JoinRefused::InvalidInvite)
+    ));
+    // A used up invite is refused even next to the right passcode.
+    assert!(matches!(
+        join(
+            "dave@example.com",
+            JoinCredentials::new(Some("hunter22".to_string()), Some(once.token.clone()))
+        )
+        .await,
+        Err(JoinRefused::InvalidInvite)
+    ));
+
+    let forged = create_invite(
+        &pool,
+        &InviteKey::new("other"),
+        &meeting.id,
+        "alice@example.com",
+        &NewInvite::default(),
+    )
+    .await
+    .unwrap();
+    assert!(matches!(
+        join("erin@example.com", invite(&forged.token)).await,
+        Err(JoinRefused::InvalidInvite)
+    ));
+
+    let cleared = sec_api::meetings::update_meeting(
+        &pool,
+        &meeting.id,
+        "alice@example.com",
+        &MeetingUpdate {
+            passcode: Some(String::new()),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert!(!cleared.has_passcode);
+    assert!(join("erin@example.com", JoinCredentials::default())
+        .await
+        .is_ok());
+    // Invites are still checked and used up without a passcode.
+    assert!(matches!(
+        join("erin@example.com", invite(&forged.token)).await,
+        Err(JoinRefused::InvalidInvite)
+    ));
+    assert!(matches!(
+        join("frank@example.com", invite(&once.token)).await,
+        Err(JoinRefused::InvalidInvite)
+    ));
+    assert!(join("carol@example.com", invite(&once.token)).await.is_ok());
+}
+
+#[test]
+fn passcodes_are_salted_and_validated() {
+    let hash = hash_passcode("hunter22").unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    assert_ne!(hash, hash_passcode("hunter22").unwrap());
+    assert!(!hash.contains("hunter22"));
+    assert!(verify_passcode(&hash, "hunter22"));
+    assert!(!verify_passcode(&hash, "hunter2"));
+    assert!(!verify_passcode("garbage", "hunter22"));
+
+    let short = NewMeeting {
+        title: "Board".to_string(),
+        settings: None,
+        passcode: Some("123".to_string()),
+    };
+    assert!(short.validate().is_err());
+    let clear = MeetingUpdate {
+        passcode: Some(String::new()),
+        ..Default::default()
+    };
+    assert!(clear.validate().is_ok());
+    assert!(NewInvite {
+        expires_in_secs: None,
+        max_uses: Some(0),
+    }
+    .validate()
+    .is_err());
+    assert_eq!(
+        JoinCredentials::new(Some(String::new()), Some(String::new())),
+        JoinCredentials::default()
+    );
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::control_command;
+use futures::StreamExt;
+use protobuf::Message;
+use sec_api::bus::{BusSubscription, LocalBus};
+use sec_api::lobby::{lobby_packet, parse, Decision, Lobby};
+use sec_api::meetings::Admission;
+use sec_api::moderation::{HostControls, Observed};
+use sec_api::rooms::capacity::{Capacity, CapacityError, Occupancy};
+use sec_api::rooms::settings::RoomSettings;
+use sec_api::rooms::{Participant, Transport};
+use sec_api::sender::{SenderGuard, Verdict};
+use types::protos::control_packet::control_packet::Command;
+use types::protos::lobby_packet::lobby_packet::Status;
+use types::protos::packet_wrapper::PacketWrapper;
+
+async fn next_status(sub: &mut BusSubscription) -> (Status, String) {
+    let msg = tokio::time::timeout(Duration::from_secs(1), sub.next())
+        .await
+        .unwrap()
+        .unwrap();
+    parse(&msg.payload).unwrap()
+}
+
+#[tokio::test]
+async fn host_admits_and_denies_waiting_participants() {
+    let lobby = Lobby::new(Arc::new(LocalBus::new()), "standup");
+    let host =
+        HostControls::new("standup", "alice", Some("alice"), None).with_lobby(Some(lobby.clone()));
+    let mut watch = lobby.watch("alice-session").await.unwrap();
+
+    let bob = tokio::spawn({
+        let lobby = lobby.clone();
+        async move { lobby.wait("bob", "bob-session").await }
+    });
+    assert_eq!(
+        next_status(&mut watch).await,
+        (Status::WAITING, "bob".to_string())
+    );
+    assert_eq!(
+        host.command(&control_command("alice", Command::ADMIT, "bob"))
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(bob.await.unwrap().unwrap(), Decision::Admitted);
+    assert_eq!(
+        next_status(&mut watch).await,
+        (Status::ADMITTED, "bob".to_string())
+    );
+
+    let carol = tokio::spawn({
+        let lobby = lobby.clone();
+        async move { lobby.wait("carol", "carol-session").await }
+    });
+    assert_eq!(
+        next_status(&mut watch).await,
+        (Status::WAITING, "carol".to_string())
+    );
+    host.command(&control_command("alice", Command::DENY, "carol"))
+        .await
+        .unwrap();
+    assert_eq!(carol.await.unwrap().unwrap(), Decision::Denied);
+
+    // Only the host decides.
+    let guest =
+        HostControls::new("standup", "bob", Some("alice"), None).with_lobby(Some(lobby.clone()));
+    assert!(guest
+        .command(&control_command("bob", Command::ADMIT, "carol"))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn participants_leaving_the_lobby_are_announced() {
+    let lobby = Lobby::new(Arc::new(LocalBus::new()), "standup");
+    let mut watch = lobby.watch("alice-session").await.unwrap();
+
+    let dave = tokio::spawn({
+        let lobby = lobby.clone();
+        async move { lobby.wait("dave", "dave-session").await }
+    });
+    assert_eq!(
+        next_status(&mut watch).await,
+        (Status::WAITING, "dave".to_string())
+    );
+    dave.abort();
+    assert_eq!(
+        next_status(&mut watch).await,
+        (Status::LEFT, "dave".to_string())
+    );
+}
+
+#[tokio::test]
+async fn participants_take_their_seat_once_admitted() {
+    let lobby = Lobby::new(Arc::new(LocalBus::new()), "standup");
+    let host =
+        HostControls::new("standup", "alice", Some("alice"), None).with_lobby(Some(lobby.clone()));
+    let occupancy = Occupancy::new(
+        Capacity {
+            per_room: Some(1),
+            total: None,
+        },
+        None,
+    );
+    let participant = |email: &str| {
+        Participant::new(
+            format!("{}-session", email),
+            email.to_string(),
+            Transport::WebTransport,
+        )
+    };
+    let mut watch = lobby.watch("alice-session").await.unwrap();
+
+    let bob = tokio::spawn({
+        let lobby = lobby.clone();
+        async move { lobby.wait("bob", "bob-session").await }
+    });
+    assert_eq!(
+        next_status(&mut watch).await,
+        (Status::WAITING, "bob".to_string())
+    );
+    // Waiting in the lobby holds no seat, so the host still fits in the room.
+    assert_eq!(occupancy.room_size("standup"), 0);
+    let _alice = occupancy
+        .admit("standup", participant("alice"), None, |_| {})
+        .await
+        .unwrap();
+
+    host.command(&control_command("alice", Command::ADMIT, "bob"))
+        .await
+        .unwrap();
+    assert_eq!(bob.await.unwrap().unwrap(), Decision::Admitted);
+    // Like a websocket session joining its room, an admitted participant may find it full.
+    assert_eq!(
+        occupancy
+            .admit("standup", participant("bob"), None, |_| {})
+            .await
+            .unwrap_err(),
+        CapacityError::RoomFull
+    );
+}
+
+#[test]
+fn only_hosts_see_the_lobby() {
+    let waiting = lobby_packet(Status::WAITING, "bob");
+    let host = HostControls::new("standup", "alice", Some("alice"), None);
+    let guest = HostControls::new("standup", "carol", Some("alice"), None);
+    assert_eq!(host.observe(&waiting), Observed::Control);
+    assert_eq!(guest.observe(&waiting), Observed::Ignore);
+
+    // Clients cannot speak for the lobby.
+    let guard = SenderGuard::new("bob", 3);
+    let mut forged = PacketWrapper::parse_from_bytes(&waiting).unwrap();
+    forged.email = "bob".to_string();
+    assert!(matches!(
+        guard.admit(&forged.write_to_bytes().unwrap()),
+        Verdict::Drop(_)
+    ));
+}
+
+#[test]
+fn everyone_but_the_host_waits() {
+    let admission = Admission {
+        settings: RoomSettings {
+            waiting_room: true,
+            ..Default::default()
+        },
+        host: Some("alice".to_string()),
+    };
+    assert!(!admission.must_wait("alice"));
+    assert!(admission.must_wait("bob"));
+    assert!(!Admission {
+        host: None,
+        ..admission.clone()
+    }
+    .must_wait("bob"));
+    assert!(!Admission::default().must_wait("bob"));
+}
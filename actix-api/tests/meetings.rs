@@ -0,0 +1,206 @@
+mod common;
+
+use sec_api::meetings::{
+    authorize_join, create_meeting, delete_meeting, get_meeting, list_meetings, update_meeting,
+    Admission, JoinCredentials, JoinRefused, MeetingDirectory, MeetingUpdate, NewMeeting,
+};
+use sec_api::rooms::settings::RoomSettings;
+use serde_json::json;
+use std::time::Duration;
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn meetings_are_owned_by_their_creator_and_gate_joins() {
+    let pool = common::test_pool(&["alice@example.com", "bob@example.com"]).await;
+    let meeting = create_meeting(
+        &pool,
+        "alice@example.com",
+        &NewMeeting {
+            title: "Standup".to_string(),
+            settings: None,
+            passcode: None,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(meeting.settings, RoomSettings::default());
+    assert!(meeting.id.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(
+        list_meetings(&pool, "alice@example.com")
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(list_meetings(&pool, "bob@example.com")
+        .await
+        .unwrap()
+        .is_empty());
+
+    let update = MeetingUpdate {
+        title: Some("Retro".to_string()),
+        settings: None,
+        passcode: None,
+    };
+    assert!(
+        update_meeting(&pool, &meeting.id, "bob@example.com", &update)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    let updated = update_meeting(&pool, &meeting.id, "alice@example.com", &update)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.title, "Retro");
+
+    assert!(!delete_meeting(&pool, &meeting.id, "bob@example.com")
+        .await
+        .unwrap());
+    assert!(delete_meeting(&pool, &meeting.id, "alice@example.com")
+        .await
+        .unwrap());
+    assert!(get_meeting(&pool, &meeting.id).await.unwrap().is_none());
+
+    let no_credentials = JoinCredentials::default();
+    let open = MeetingDirectory::new(pool.clone(), false);
+    let registered_only = MeetingDirectory::new(pool.clone(), true);
+    assert_eq!(
+        authorize_join(None, "adhoc", "bob@example.com", &no_credentials)
+            .await
+            .unwrap(),
+        Admission::default()
+    );
+    assert_eq!(
+        authorize_join(Some(&open), "adhoc", "bob@example.com", &no_credentials)
+            .await
+            .unwrap(),
+        Admission::default()
+    );
+    assert!(matches!(
+        authorize_join(
+            Some(&registered_only),
+            "adhoc",
+            "bob@example.com",
+            &no_credentials
+        )
+        .await,
+        Err(JoinRefused::Unregistered)
+    ));
+    for room in ["room.*", ">"] {
+        assert!(matches!(
+            authorize_join(Some(&open), room, "bob@example.com", &no_credentials).await,
+            Err(JoinRefused::InvalidRoom)
+        ));
+        assert!(matches!(
+            authorize_join(None, room, "bob@example.com", &no_credentials).await,
+            Err(JoinRefused::InvalidRoom)
+        ));
+    }
+
+    let settings = RoomSettings {
+        e2ee_required: true,
+        publishers: vec!["alice@example.com".to_string()],
+        ..Default::default()
+    };
+    let meeting = create_meeting(
+        &pool,
+        "alice@example.com",
+        &NewMeeting {
+            title: "Standup".to_string(),
+            settings: Some(settings.clone()),
+            passcode: None,
+        },
+    )
+    .await
+    .unwrap();
+    let admission = authorize_join(
+        Some(&registered_only),
+        &meeting.id,
+        "bob@example.com",
+        &no_credentials,
+    )
+    .await
+    .unwrap();
+    assert_eq!(admission.settings, settings);
+    assert_eq!(admission.host.as_deref(), Some("alice@example.com"));
+
+    registered_only
+        .transfer_host(&meeting.id, "bob@example.com")
+        .await
+        .unwrap();
+    assert_eq!(
+        get_meeting(&pool, &meeting.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .host_email,
+        "bob@example.com"
+    );
+    assert!(registered_only
+        .transfer_host("adhoc", "bob@example.com")
+        .await
+        .is_err());
+
+    registered_only
+        .ban(&meeting.id, "carol@example.com", Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert!(matches!(
+        authorize_join(
+            Some(&registered_only),
+            &meeting.id,
+            "carol@example.com",
+            &no_credentials
+        )
+        .await,
+        Err(JoinRefused::Banned(_))
+    ));
+    registered_only
+        .ban(&meeting.id, "dave@example.com", Duration::ZERO)
+        .await
+        .unwrap();
+    assert!(authorize_join(
+        Some(&registered_only),
+        &meeting.id,
+        "dave@example.com",
+        &no_credentials
+    )
+    .await
+    .is_ok());
+}
+
+#[test]
+fn meeting_input_is_validated() {
+    let untitled: NewMeeting = serde_json::from_value(json!({})).unwrap();
+    assert!(untitled.validate().is_ok());
+    let long = NewMeeting {
+        title: "a".repeat(201),
+        settings: None,
+        passcode: None,
+    };
+    assert!(long.validate().is_err());
+    let empty_room = MeetingUpdate {
+        title: None,
+        settings: Some(RoomSettings {
+            max_participants: Some(0),
+            ..Default::default()
+        }),
+        passcode: None,
+    };
+    assert!(empty_room.validate().is_err());
+    assert!(MeetingUpdate::default().validate().is_ok());
+}
+
+#[test]
+fn settings_fill_in_defaults_and_restrict_publishers() {
+    let settings: RoomSettings = serde_json::from_value(json!({
+        "publishers": ["alice@example.com"]
+    }))
+    .unwrap();
+    assert!(!settings.e2ee_required);
+    assert_eq!(settings.max_participants, None);
+    assert!(settings.may_publish("alice@example.com"));
+    assert!(!settings.may_publish("bob@example.com"));
+    assert!(RoomSettings::default().may_publish("bob@example.com"));
+}
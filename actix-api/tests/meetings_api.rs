@@ -0,0 +1,67 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+use actix_web::{web, App};
+use sec_api::api;
+use sec_api::auth::session::{create_session, SessionSettings};
+use sec_api::meetings::{create_meeting, NewMeeting};
+use serde_json::Value;
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn meetings_are_only_shown_to_their_owner_and_host() {
+    let pool = common::test_pool(&["alice@example.com", "bob@example.com"]).await;
+    let meeting = create_meeting(
+        &pool,
+        "alice@example.com",
+        &NewMeeting {
+            title: "Standup".to_string(),
+            settings: None,
+            passcode: None,
+        },
+    )
+    .await
+    .unwrap();
+    let settings = SessionSettings::new("secret", Duration::from_secs(60));
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(settings.clone()))
+            .service(api::meetings::get),
+    )
+    .await;
+    let uri = format!("/meetings/{}", meeting.id);
+    let cookie = |email| {
+        let pool = pool.clone();
+        let settings = settings.clone();
+        async move {
+            let session = create_session(&pool, email, settings.ttl).await.unwrap();
+            settings.cookie(&session)
+        }
+    };
+
+    let anonymous = TestRequest::get().uri(&uri).to_request();
+    assert_eq!(
+        call_service(&app, anonymous).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    let stranger = TestRequest::get()
+        .uri(&uri)
+        .cookie(cookie("bob@example.com").await)
+        .to_request();
+    assert_eq!(
+        call_service(&app, stranger).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    let owner = TestRequest::get()
+        .uri(&uri)
+        .cookie(cookie("alice@example.com").await)
+        .to_request();
+    let resp = call_service(&app, owner).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = read_body_json(resp).await;
+    assert_eq!(body["title"], "Standup");
+}
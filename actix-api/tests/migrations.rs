@@ -0,0 +1,45 @@
+use sec_api::db::migrations::{run_migrations, MIGRATIONS};
+use tokio_postgres::{Client, NoTls};
+
+/// Connects to the database in `TEST_DATABASE_URL` and wipes it.
+async fn test_client() -> Client {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+    let (client, connection) = tokio_postgres::connect(&url, NoTls).await.unwrap();
+    actix_rt::spawn(connection);
+    client
+        .batch_execute("DROP SCHEMA public CASCADE; CREATE SCHEMA public;")
+        .await
+        .unwrap();
+    client
+}
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn migrations_apply_once_and_create_the_auth_tables() {
+    let mut client = test_client().await;
+    client
+        .batch_execute("SET statement_timeout = 5000")
+        .await
+        .unwrap();
+    let applied = run_migrations(&mut client).await.unwrap();
+    assert_eq!(applied.len(), MIGRATIONS.len());
+    assert!(run_migrations(&mut client).await.unwrap().is_empty());
+    // Lifted while migrating only.
+    let statement_timeout: String = client
+        .query_one("SHOW statement_timeout", &[])
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(statement_timeout, "5s");
+
+    client
+        .batch_execute(
+            "INSERT INTO users (email) VALUES ('alice@example.com');
+             INSERT INTO oauth_requests (csrf_state, pkce_challenge, pkce_verifier)
+                VALUES ('state', 'challenge', 'verifier');
+             INSERT INTO sessions (id, email, expires_at)
+                VALUES ('session', 'alice@example.com', now() + interval '1 hour');",
+        )
+        .await
+        .unwrap();
+}
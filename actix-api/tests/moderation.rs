@@ -0,0 +1,108 @@
+mod common;
+
+use common::control_command;
+use protobuf::Message;
+use sec_api::moderation::{host_packet, HostControls, Observed};
+use sec_api::sender::{SenderGuard, Verdict};
+use types::protos::control_packet::control_packet::Command;
+use types::protos::control_packet::ControlPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+fn parse(packet: &[u8]) -> (String, ControlPacket) {
+    let packet = PacketWrapper::parse_from_bytes(packet).unwrap();
+    assert_eq!(packet.packet_type.enum_value(), Ok(PacketType::CONTROL));
+    (
+        packet.email,
+        ControlPacket::parse_from_bytes(&packet.data).unwrap(),
+    )
+}
+
+#[test]
+fn control_packets_go_to_the_session_instead_of_the_bus() {
+    let guard = SenderGuard::new("alice", 3);
+    let packet = control_command("alice", Command::MUTE, "bob");
+    assert_eq!(
+        guard.admit(&packet.write_to_bytes().unwrap()),
+        Verdict::Control(packet)
+    );
+    let spoofed = control_command("bob", Command::MUTE, "carol");
+    assert!(matches!(
+        guard.admit(&spoofed.write_to_bytes().unwrap()),
+        Verdict::Drop(_)
+    ));
+}
+
+#[actix_rt::test]
+async fn only_the_host_may_send_commands() {
+    let host = HostControls::new("room", "alice", Some("alice"), None);
+    let guest = HostControls::new("room", "bob", Some("alice"), None);
+    assert!(host.is_host());
+    assert!(!guest.is_host());
+
+    assert!(guest
+        .command(&control_command("bob", Command::MUTE, "alice"))
+        .await
+        .is_err());
+    let (from, control) = parse(
+        &host
+            .command(&control_command("alice", Command::STOP_VIDEO, "bob"))
+            .await
+            .unwrap()
+            .unwrap(),
+    );
+    assert_eq!(from, "alice");
+    assert_eq!(control.command.enum_value(), Ok(Command::STOP_VIDEO));
+    assert_eq!(control.target, "bob");
+
+    assert!(host
+        .command(&control_command("alice", Command::MUTE, "alice"))
+        .await
+        .is_err());
+    assert!(host
+        .command(&control_command("alice", Command::HOST_CHANGED, "bob"))
+        .await
+        .is_err());
+    // Ad-hoc rooms have no meeting to hand over.
+    assert!(host
+        .command(&control_command("alice", Command::TRANSFER_HOST, "bob"))
+        .await
+        .is_err());
+    assert!(HostControls::new("room", "alice", None, None)
+        .command(&control_command("alice", Command::MUTE, "bob"))
+        .await
+        .is_err());
+}
+
+#[actix_rt::test]
+async fn sessions_follow_host_changes_and_removals() {
+    let bob = HostControls::new("room", "bob", Some("alice"), None);
+    assert_eq!(bob.observe(&host_packet("bob")), Observed::Control);
+    assert!(bob.is_host());
+    assert_eq!(bob.observe(&host_packet("carol")), Observed::Control);
+    assert!(!bob.is_host());
+
+    let alice = HostControls::new("room", "alice", Some("alice"), None);
+    let removal = alice
+        .command(&control_command("alice", Command::REMOVE, "bob"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(bob.observe(&removal), Observed::Removed);
+    let carol = HostControls::new("room", "carol", Some("alice"), None);
+    assert_eq!(carol.observe(&removal), Observed::Control);
+
+    let media = PacketWrapper {
+        packet_type: PacketType::MEDIA.into(),
+        email: "alice".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(
+        bob.observe(&media.write_to_bytes().unwrap()),
+        Observed::Deliver
+    );
+    // Media is passed on without parsing its payload.
+    let mut truncated = media.write_to_bytes().unwrap();
+    truncated.truncate(4);
+    assert_eq!(bob.observe(&truncated), Observed::Deliver);
+}
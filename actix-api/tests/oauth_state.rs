@@ -0,0 +1,53 @@
+mod common;
+
+use std::time::Duration;
+
+use sec_api::auth::{
+    consume_oauth_request, generate_and_store_oauth_request, sweep_oauth_requests,
+};
+
+const TTL: Duration = Duration::from_secs(600);
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn oauth_states_are_consumed_once_and_expire() {
+    let pool = common::test_pool(&[]).await;
+    let (state, challenge) = generate_and_store_oauth_request(&pool).await.unwrap();
+    let state = state.secret().clone();
+    let request = consume_oauth_request(&pool, state.clone(), TTL)
+        .await
+        .unwrap();
+    assert_eq!(request.csrf_state, state);
+    assert_eq!(request.pkce_challenge, challenge.as_str());
+    // A replayed callback finds nothing.
+    assert!(consume_oauth_request(&pool, state, TTL).await.is_err());
+
+    // Of two callbacks racing for the same state, only one gets it.
+    let (state, _) = generate_and_store_oauth_request(&pool).await.unwrap();
+    let state = state.secret().clone();
+    let (first, second) = futures::join!(
+        consume_oauth_request(&pool, state.clone(), TTL),
+        consume_oauth_request(&pool, state.clone(), TTL),
+    );
+    assert!(first.is_ok() != second.is_ok());
+
+    let (stale, _) = generate_and_store_oauth_request(&pool).await.unwrap();
+    let (fresh, _) = generate_and_store_oauth_request(&pool).await.unwrap();
+    pool.get()
+        .await
+        .unwrap()
+        .execute(
+            "UPDATE oauth_requests SET created_at = now() - interval '1 hour'
+                WHERE csrf_state=$1",
+            &[stale.secret()],
+        )
+        .await
+        .unwrap();
+    assert!(consume_oauth_request(&pool, stale.secret().clone(), TTL)
+        .await
+        .is_err());
+    assert_eq!(sweep_oauth_requests(&pool, TTL).await.unwrap(), 1);
+    assert!(consume_oauth_request(&pool, fresh.secret().clone(), TTL)
+        .await
+        .is_ok());
+}
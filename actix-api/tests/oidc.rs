@@ -0,0 +1,177 @@
+use actix_web::{web, App, HttpResponse, HttpServer};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use sec_api::auth::oidc::OidcProvider;
+use sec_api::auth::request_token;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CLIENT_ID: &str = "test-client";
+const PRIVATE_KEY: &[u8] = include_bytes!("fixtures/oidc_rsa_key.pem");
+const JWKS: &str = include_str!("fixtures/oidc_jwks.json");
+/// Where the stub sends users back to after logging in.
+const REDIRECT_URL: &str = "https://app.example.com/login/callback";
+
+/// Serves discovery and JWKS documents and a token endpoint for `http://127.0.0.1:{port}` and
+/// returns the issuer.
+fn start_stub_idp() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let issuer = format!("http://{}", listener.local_addr().unwrap());
+    let discovery = json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/authorize", issuer),
+        "token_endpoint": format!("{}/token", issuer),
+        "jwks_uri": format!("{}/jwks", issuer),
+    });
+    let token_issuer = issuer.clone();
+    let server = HttpServer::new(move || {
+        let discovery = discovery.clone();
+        let issuer = token_issuer.clone();
+        App::new()
+            .route(
+                "/.well-known/openid-configuration",
+                web::get().to(move || {
+                    let discovery = discovery.clone();
+                    async move { HttpResponse::Ok().json(discovery) }
+                }),
+            )
+            .route(
+                "/jwks",
+                web::get().to(|| async {
+                    HttpResponse::Ok()
+                        .content_type("application/json")
+                        .body(JWKS)
+                }),
+            )
+            .route(
+                "/token",
+                web::post().to(move |form: web::Form<HashMap<String, String>>| {
+                    let issuer = issuer.clone();
+                    async move {
+                        // Like real providers, only for the redirect URL the code was issued for.
+                        if form.get("redirect_uri").map(String::as_str) != Some(REDIRECT_URL) {
+                            return HttpResponse::BadRequest().json(json!({
+                                "error": "invalid_grant",
+                            }));
+                        }
+                        HttpResponse::Ok().json(json!({
+                            "access_token": "access",
+                            "token_type": "Bearer",
+                            "id_token": id_token(&issuer, CLIENT_ID, now() + 300),
+                        }))
+                    }
+                }),
+            )
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_rt::spawn(server);
+    issuer
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn id_token(issuer: &str, audience: &str, exp: u64) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some("test-key".to_string());
+    let claims = json!({
+        "iss": issuer,
+        "aud": audience,
+        "sub": "1234",
+        "email": "alice@example.com",
+        "name": "Alice",
+        "iat": now(),
+        "exp": exp,
+    });
+    encode(
+        &header,
+        &claims,
+        &EncodingKey::from_rsa_pem(PRIVATE_KEY).unwrap(),
+    )
+    .unwrap()
+}
+
+#[actix_rt::test]
+async fn verifies_tokens_signed_by_the_provider() {
+    let issuer = start_stub_idp();
+    let provider = OidcProvider::discover(&issuer, CLIENT_ID).await.unwrap();
+    assert_eq!(
+        provider.metadata().token_endpoint,
+        format!("{}/token", issuer)
+    );
+
+    let claims = provider
+        .verify_id_token(&id_token(&issuer, CLIENT_ID, now() + 300))
+        .await
+        .unwrap();
+    assert_eq!(claims.email, "alice@example.com");
+    assert_eq!(claims.name, "Alice");
+}
+
+#[actix_rt::test]
+async fn rejects_invalid_tokens() {
+    let issuer = start_stub_idp();
+    let provider = OidcProvider::discover(&issuer, CLIENT_ID).await.unwrap();
+
+    let wrong_audience = id_token(&issuer, "someone-else", now() + 300);
+    assert!(provider.verify_id_token(&wrong_audience).await.is_err());
+
+    let wrong_issuer = id_token("https://evil.example.com", CLIENT_ID, now() + 300);
+    assert!(provider.verify_id_token(&wrong_issuer).await.is_err());
+
+    let expired = id_token(&issuer, CLIENT_ID, now() - 3600);
+    assert!(provider.verify_id_token(&expired).await.is_err());
+
+    let mut header = Header::new(Algorithm::HS256);
+    header.kid = Some("test-key".to_string());
+    let forged = encode(
+        &header,
+        &json!({ "iss": issuer, "aud": CLIENT_ID, "email": "alice@example.com", "exp": now() + 300 }),
+        &EncodingKey::from_secret(b"guessed"),
+    )
+    .unwrap();
+    assert!(provider.verify_id_token(&forged).await.is_err());
+
+    // Valid signature, but over different claims.
+    let signed = id_token(&issuer, CLIENT_ID, now() + 300);
+    let other = id_token(&issuer, CLIENT_ID, now() + 600);
+    let (_, signature) = signed.rsplit_once('.').unwrap();
+    let (message, _) = other.rsplit_once('.').unwrap();
+    let tampered = format!("{}.{}", message, signature);
+    assert!(provider.verify_id_token(&tampered).await.is_err());
+}
+
+#[actix_rt::test]
+async fn codes_are_exchanged_for_the_redirect_url_of_the_login() {
+    let issuer = start_stub_idp();
+    let provider = OidcProvider::discover(&issuer, CLIENT_ID).await.unwrap();
+    let token_url = provider.metadata().token_endpoint.clone();
+    let exchange = |redirect_url: String| {
+        let provider = &provider;
+        let token_url = token_url.clone();
+        async move {
+            request_token(
+                provider,
+                &redirect_url,
+                CLIENT_ID,
+                "secret",
+                "verifier",
+                &token_url,
+                "code",
+            )
+            .await
+        }
+    };
+
+    let (_, claims) = exchange(REDIRECT_URL.to_string()).await.unwrap();
+    assert_eq!(claims.email, "alice@example.com");
+    let authorization_endpoint = provider.metadata().authorization_endpoint.clone();
+    assert!(exchange(authorization_endpoint).await.is_err());
+}
@@ -0,0 +1,408 @@
+mod common;
+
+use std::fs::File;
+use std::io::{Cursor, Seek, SeekFrom};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use common::{control_command, next_payload, temp_dir};
+use protobuf::Message;
+use sec_api::bus::{LocalBus, RoomBus};
+use sec_api::moderation::HostControls;
+use sec_api::recording::packet_log::{
+    index_path, read_index, seek_offset, IndexEntry, LogHeader, PacketLogReader, PacketLogWriter,
+    Record, MAX_RECORD_LEN, VERSION,
+};
+use sec_api::recording::replay::Replay;
+use sec_api::recording::{Recorder, RecordingLimits};
+use sec_api::resumption::resumption_packet;
+use sec_api::rooms::participant_packet;
+use types::protos::control_packet::control_packet::Command;
+use types::protos::control_packet::ControlPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+fn record(arrival: u64, payload: &[u8]) -> Record {
+    Record {
+        arrival,
+        subject: "room.standup.session".to_string(),
+        payload: payload.to_vec(),
+    }
+}
+
+fn parse_command(data: &[u8]) -> Option<Command> {
+    let packet = PacketWrapper::parse_from_bytes(data).ok()?;
+    if packet.packet_type.enum_value() != Ok(PacketType::CONTROL) {
+        return None;
+    }
+    ControlPacket::parse_from_bytes(&packet.data)
+        .ok()?
+        .command
+        .enum_value()
+        .ok()
+}
+
+#[test]
+fn packet_logs_round_trip_and_are_indexed() {
+    let header = LogHeader {
+        version: VERSION,
+        started_at: 1_000,
+        room: "standup".to_string(),
+    };
+    let mut log = Vec::new();
+    let mut index = Vec::new();
+    let records = [
+        record(1_000, b"one"),
+        record(1_500, b"two"),
+        record(2_000, b"three"),
+        record(3_200, b""),
+    ];
+    {
+        let mut writer = PacketLogWriter::new(&mut log, &mut index, &header).unwrap();
+        for record in &records {
+            writer.append(record).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    let reader = PacketLogReader::new(Cursor::new(&log)).unwrap();
+    assert_eq!(reader.header(), &header);
+    let read: Vec<Record> = reader.records().collect::<Result<_, _>>().unwrap();
+    assert_eq!(read, records);
+
+    let index = read_index(Cursor::new(&index)).unwrap();
+    let arrivals: Vec<u64> = index.iter().map(|entry| entry.arrival).collect();
+    assert_eq!(arrivals, [1_000, 2_000, 3_200]);
+
+    let mut reader = PacketLogReader::new(Cursor::new(&log)).unwrap();
+    let offset = seek_offset(reader.header(), &index, 2_500);
+    reader.get_mut().seek(SeekFrom::Start(offset)).unwrap();
+    assert_eq!(reader.next_record().unwrap(), Some(records[2].clone()));
+    let start = seek_offset(reader.header(), &[] as &[IndexEntry], 2_500);
+    reader.get_mut().seek(SeekFrom::Start(start)).unwrap();
+    assert_eq!(reader.next_record().unwrap(), Some(records[0].clone()));
+}
+
+#[test]
+fn truncated_logs_are_readable_up_to_the_last_complete_record() {
+    let header = LogHeader {
+        version: VERSION,
+        started_at: 0,
+        room: "standup".to_string(),
+    };
+    let mut log = Vec::new();
+    let mut writer = PacketLogWriter::new(&mut log, Vec::new(), &header).unwrap();
+    writer.append(&record(1, b"complete")).unwrap();
+    writer.append(&record(2, b"cut short")).unwrap();
+    drop(writer);
+    log.truncate(log.len() - 3);
+
+    let mut records = PacketLogReader::new(Cursor::new(&log)).unwrap().records();
+    assert_eq!(records.next().unwrap().unwrap(), record(1, b"complete"));
+    assert!(records.next().unwrap().is_err());
+    assert!(records.next().is_none());
+
+    assert!(PacketLogReader::new(Cursor::new(b"VCPI\x01\x00")).is_err());
+}
+
+#[test]
+fn records_longer_than_the_limit_are_refused() {
+    let header = LogHeader {
+        version: VERSION,
+        started_at: 0,
+        room: "standup".to_string(),
+    };
+    let mut log = Vec::new();
+    let mut index = Vec::new();
+    {
+        let mut writer = PacketLogWriter::new(&mut log, &mut index, &header).unwrap();
+        let huge = vec![0; MAX_RECORD_LEN as usize];
+        assert!(writer.append(&record(1, &huge)).is_err());
+    }
+    assert_eq!(read_index(Cursor::new(&index)).unwrap(), []);
+
+    // A corrupt length is an error rather than an allocation of that size.
+    log.extend_from_slice(&u32::MAX.to_le_bytes());
+    let mut reader = PacketLogReader::new(Cursor::new(&log)).unwrap();
+    assert!(reader.next_record().is_err());
+}
+
+#[actix_rt::test]
+async fn recorder_writes_the_room_until_stopped() {
+    let bus: Arc<dyn RoomBus> = Arc::new(LocalBus::new());
+    let dir = temp_dir("recordings");
+    let recorder = Recorder::new(bus.clone(), &dir);
+    let mut room = bus.subscribe_to_room("standup", "observer").await.unwrap();
+
+    let recording = recorder.start("standup", "alice").await.unwrap();
+    assert!(recorder.start("standup", "alice").await.is_err());
+    assert_eq!(
+        parse_command(&next_payload(&mut room).await.unwrap()),
+        Some(Command::START_RECORDING)
+    );
+
+    bus.publish_to_room("standup", "bob", Bytes::from_static(b"opaque media"))
+        .await
+        .unwrap();
+    bus.publish_to_room("other", "carol", Bytes::from_static(b"elsewhere"))
+        .await
+        .unwrap();
+    bus.publish_to_room(
+        "standup",
+        "dave",
+        participant_packet(PacketType::PARTICIPANT_JOINED, "dave"),
+    )
+    .await
+    .unwrap();
+    next_payload(&mut room).await.unwrap();
+    next_payload(&mut room).await.unwrap();
+    // Late joiners learn that the room is being recorded.
+    assert_eq!(
+        parse_command(&next_payload(&mut room).await.unwrap()),
+        Some(Command::START_RECORDING)
+    );
+
+    recorder.stop("standup", "alice").await.unwrap();
+    for _ in 0..100 {
+        if recorder.recording("standup").is_none() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(recorder.recording("standup").is_none());
+
+    let reader = PacketLogReader::new(File::open(&recording.path).unwrap()).unwrap();
+    assert_eq!(reader.header().room, "standup");
+    let records: Vec<Record> = reader.records().collect::<Result<_, _>>().unwrap();
+    let subjects: Vec<&str> = records.iter().map(|r| r.subject.as_str()).collect();
+    assert_eq!(
+        subjects,
+        [
+            "room.standup.recorder",
+            "room.standup.bob",
+            "room.standup.dave",
+            "room.standup.recorder",
+            "room.standup.recorder",
+        ]
+    );
+    assert_eq!(records[1].payload, b"opaque media");
+    assert_eq!(
+        parse_command(&records[4].payload),
+        Some(Command::STOP_RECORDING)
+    );
+    let index = read_index(File::open(index_path(&recording.path)).unwrap()).unwrap();
+    assert_eq!(index[0].arrival, records[0].arrival);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+async fn wait_until_stopped(recorder: &Recorder, room: &str) {
+    for _ in 0..100 {
+        if recorder.recording(room).is_none() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("the recording of {} did not stop", room);
+}
+
+#[actix_rt::test]
+async fn recordings_stop_when_the_room_empties_goes_idle_or_runs_long() {
+    let bus: Arc<dyn RoomBus> = Arc::new(LocalBus::new());
+    let dir = temp_dir("recordings");
+    let recorder = Recorder::new(bus.clone(), &dir).with_limits(RecordingLimits {
+        idle_timeout: Duration::from_millis(300),
+        max_duration: Duration::from_secs(60),
+    });
+    let mut room = bus.subscribe_to_room("standup", "observer").await.unwrap();
+
+    recorder.start("standup", "alice").await.unwrap();
+    next_payload(&mut room).await.unwrap();
+    for session in ["alice", "bob"] {
+        bus.publish_to_room("standup", session, Bytes::from_static(b"heartbeat"))
+            .await
+            .unwrap();
+        next_payload(&mut room).await.unwrap();
+    }
+    for session in ["alice", "bob"] {
+        let left = participant_packet(PacketType::PARTICIPANT_LEFT, session);
+        bus.publish_to_room("standup", session, left).await.unwrap();
+        next_payload(&mut room).await.unwrap();
+    }
+    // Participants are told the recording is over.
+    assert_eq!(
+        parse_command(&next_payload(&mut room).await.unwrap()),
+        Some(Command::STOP_RECORDING)
+    );
+    wait_until_stopped(&recorder, "standup").await;
+
+    recorder.start("standup", "alice").await.unwrap();
+    next_payload(&mut room).await.unwrap();
+    let started = Instant::now();
+    assert_eq!(
+        parse_command(&next_payload(&mut room).await.unwrap()),
+        Some(Command::STOP_RECORDING)
+    );
+    assert!(started.elapsed() >= Duration::from_millis(250));
+    wait_until_stopped(&recorder, "standup").await;
+
+    let recorder = recorder.with_limits(RecordingLimits {
+        idle_timeout: Duration::from_secs(60),
+        max_duration: Duration::from_millis(300),
+    });
+    recorder.start("standup", "alice").await.unwrap();
+    next_payload(&mut room).await.unwrap();
+    loop {
+        bus.publish_to_room("standup", "bob", Bytes::from_static(b"heartbeat"))
+            .await
+            .unwrap();
+        if parse_command(&next_payload(&mut room).await.unwrap()) == Some(Command::STOP_RECORDING) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    wait_until_stopped(&recorder, "standup").await;
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[actix_rt::test]
+async fn only_the_host_of_a_recordable_meeting_may_record() {
+    let bus: Arc<dyn RoomBus> = Arc::new(LocalBus::new());
+    let dir = temp_dir("recordings");
+    let recorder = Recorder::new(bus, &dir);
+
+    let not_allowed = HostControls::new("standup", "alice", Some("alice"), None);
+    assert!(not_allowed
+        .command(&control_command("alice", Command::START_RECORDING, ""))
+        .await
+        .is_err());
+
+    let guest = HostControls::new("standup", "bob", Some("alice"), None)
+        .with_recorder(Some(recorder.clone()));
+    assert!(guest
+        .command(&control_command("bob", Command::START_RECORDING, ""))
+        .await
+        .is_err());
+    assert!(recorder.recording("standup").is_none());
+
+    let host = HostControls::new("standup", "alice", Some("alice"), None)
+        .with_recorder(Some(recorder.clone()));
+    assert_eq!(
+        host.command(&control_command("alice", Command::START_RECORDING, ""))
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(recorder.recording("standup").unwrap().started_by, "alice");
+    host.command(&control_command("alice", Command::STOP_RECORDING, ""))
+        .await
+        .unwrap();
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[actix_rt::test]
+async fn recordings_are_replayed_with_their_timing() {
+    let header = LogHeader {
+        version: VERSION,
+        started_at: 1_000,
+        room: "standup".to_string(),
+    };
+    let media = |email: &str| {
+        PacketWrapper {
+            packet_type: PacketType::MEDIA.into(),
+            email: email.to_string(),
+            data: b"opaque media".to_vec(),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap()
+    };
+    let mut log = Vec::new();
+    {
+        let mut writer = PacketLogWriter::new(&mut log, Vec::new(), &header).unwrap();
+        let records = [
+            (
+                1_000,
+                "recorder",
+                control_command("alice", Command::START_RECORDING, ""),
+            ),
+            (
+                1_100,
+                "s1",
+                PacketWrapper::parse_from_bytes(&media("alice")).unwrap(),
+            ),
+            (
+                1_300,
+                "s2",
+                PacketWrapper::parse_from_bytes(&media("bob")).unwrap(),
+            ),
+            (1_400, "s1", control_command("alice", Command::MUTE, "")),
+            (
+                1_500,
+                "s1",
+                PacketWrapper::parse_from_bytes(&media("alice")).unwrap(),
+            ),
+        ];
+        // The server's own packets would tell the live sessions about people who aren't there.
+        let server_only = [
+            participant_packet(PacketType::PARTICIPANT_JOINED, "alice"),
+            participant_packet(PacketType::PARTICIPANT_LEFT, "bob"),
+            resumption_packet("alice", "t0k3n"),
+        ]
+        .map(|payload| {
+            (
+                1_500,
+                "s1",
+                PacketWrapper::parse_from_bytes(&payload).unwrap(),
+            )
+        });
+        for (arrival, session, packet) in records.into_iter().chain(server_only) {
+            writer
+                .append(&Record {
+                    arrival,
+                    subject: format!("room.standup.{}", session),
+                    payload: packet.write_to_bytes().unwrap(),
+                })
+                .unwrap();
+        }
+        writer.append(&record(1_600, b"not a packet")).unwrap();
+    }
+
+    let bus = LocalBus::new();
+    let mut demo = bus.subscribe_to_room("demo", "observer").await.unwrap();
+    let replay = Replay::new("demo")
+        .with_identity("alice", "demo-alice")
+        .with_speed(5.0)
+        .unwrap();
+    assert!(Replay::new("demo").with_speed(0.0).is_err());
+    let started = Instant::now();
+    let report = replay
+        .run(&bus, PacketLogReader::new(Cursor::new(&log)).unwrap())
+        .await
+        .unwrap();
+    // The last packet arrived 500ms into the recording.
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    assert_eq!(report.published, 3);
+    assert_eq!(report.skipped, 6);
+
+    let mut replayed = Vec::new();
+    for _ in 0..3 {
+        let msg = tokio::time::timeout(Duration::from_secs(1), demo.next())
+            .await
+            .unwrap()
+            .unwrap();
+        let packet = PacketWrapper::parse_from_bytes(&msg.payload).unwrap();
+        replayed.push((msg.subject, packet.email));
+    }
+    assert_eq!(
+        replayed,
+        [
+            ("room.demo.replay-s1".to_string(), "demo-alice".to_string()),
+            ("room.demo.replay-s2".to_string(), "bob".to_string()),
+            ("room.demo.replay-s1".to_string(), "demo-alice".to_string()),
+        ]
+    );
+}
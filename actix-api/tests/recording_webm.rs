@@ -0,0 +1,363 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Cursor};
+
+use aes::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+use common::temp_dir;
+use protobuf::Message;
+use sec_api::recording::mux::{mux_to_webm, vp9_frame_size, MediaKey, MediaKind};
+use sec_api::recording::packet_log::{index_path, LogHeader, PacketLogWriter, Record, VERSION};
+use sec_api::recording::webm::{Track, WebmWriter};
+use types::protos::media_packet::media_packet::MediaType;
+use types::protos::media_packet::{AudioMetadata, MediaPacket, VideoMetadata};
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+const SEGMENT: u32 = 0x18538067;
+const SEEK_HEAD: u32 = 0x114D9B74;
+const SEEK: u32 = 0x4DBB;
+const SEEK_ID: u32 = 0x53AB;
+const SEEK_POSITION: u32 = 0x53AC;
+const INFO: u32 = 0x1549A966;
+const DURATION: u32 = 0x4489;
+const TRACKS: u32 = 0x1654AE6B;
+const CLUSTER: u32 = 0x1F43B675;
+const TIMECODE: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+const CUES: u32 = 0x1C53BB6B;
+const VOID: u32 = 0xEC;
+
+struct Element<'a> {
+    id: u32,
+    offset: usize,
+    body: &'a [u8],
+}
+
+fn vint(data: &[u8], at: &mut usize, keep_marker: bool) -> u64 {
+    let first = data[*at];
+    let len = first.leading_zeros() as usize + 1;
+    let mut value = if keep_marker {
+        u64::from(first)
+    } else {
+        u64::from(first & (0xFFu16 >> len) as u8)
+    };
+    for byte in &data[*at + 1..*at + len] {
+        value = (value << 8) | u64::from(*byte);
+    }
+    *at += len;
+    value
+}
+
+fn elements(data: &[u8]) -> Vec<Element<'_>> {
+    let mut at = 0;
+    let mut elements = Vec::new();
+    while at < data.len() {
+        let offset = at;
+        let id = vint(data, &mut at, true) as u32;
+        let size = vint(data, &mut at, false) as usize;
+        elements.push(Element {
+            id,
+            offset,
+            body: &data[at..at + size],
+        });
+        at += size;
+    }
+    elements
+}
+
+fn child<'a>(elements: &'a [Element<'a>], id: u32) -> &'a Element<'a> {
+    elements.iter().find(|e| e.id == id).unwrap()
+}
+
+fn be_uint(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |value, b| (value << 8) | u64::from(*b))
+}
+
+/// A SimpleBlock as `(time, keyframe, data)`.
+type Block = (u64, bool, Vec<u8>);
+
+/// Checks the layout of a WebM file and returns the ids of its Segment's children, its duration
+/// and its blocks.
+fn parse_webm(file: &[u8]) -> (Vec<u32>, f64, Vec<Block>) {
+    let top = elements(file);
+    assert_eq!(top.len(), 2);
+    assert_eq!(top[1].id, SEGMENT);
+    let segment = elements(top[1].body);
+    let ids: Vec<u32> = segment.iter().map(|e| e.id).collect();
+
+    // Every SeekHead entry points at the element it names.
+    for seek in elements(child(&segment, SEEK_HEAD).body) {
+        if seek.id == VOID {
+            continue;
+        }
+        assert_eq!(seek.id, SEEK);
+        let fields = elements(seek.body);
+        let id = be_uint(child(&fields, SEEK_ID).body) as u32;
+        let position = be_uint(child(&fields, SEEK_POSITION).body) as usize;
+        assert_eq!(child(&segment, id).offset, position);
+    }
+    let info = elements(child(&segment, INFO).body);
+    let duration = f64::from_be_bytes(child(&info, DURATION).body.try_into().unwrap());
+
+    let mut blocks = Vec::new();
+    for cluster in segment.iter().filter(|e| e.id == CLUSTER) {
+        let children = elements(cluster.body);
+        let time = be_uint(child(&children, TIMECODE).body);
+        for block in children.iter().filter(|e| e.id == SIMPLE_BLOCK) {
+            assert_eq!(block.body[0], 0x81);
+            let relative = i16::from_be_bytes([block.body[1], block.body[2]]);
+            blocks.push((
+                time + relative as u64,
+                block.body[3] & 0x80 != 0,
+                block.body[4..].to_vec(),
+            ));
+        }
+    }
+    (ids, duration, blocks)
+}
+
+/// The start of a profile 0 VP9 keyframe of the given size.
+fn vp9_keyframe(width: u16, height: u16) -> Vec<u8> {
+    let mut frame = vec![0x82, 0x49, 0x83, 0x42];
+    // BT.601, studio range, then the size minus one.
+    let bits = (0b0100u64 << 32) | (u64::from(width - 1) << 16) | u64::from(height - 1);
+    frame.extend(&(bits << 4).to_be_bytes()[3..]);
+    frame.extend(b"picture");
+    frame
+}
+
+fn media(media_type: MediaType, sequence: u64, timestamp: f64, key: bool) -> MediaPacket {
+    MediaPacket {
+        media_type: media_type.into(),
+        data: if key {
+            vp9_keyframe(640, 480)
+        } else {
+            format!("frame {}", sequence).into_bytes()
+        },
+        frame_type: if key { "key" } else { "delta" }.to_string(),
+        timestamp,
+        duration: 20_000.0,
+        video_metadata: Some(VideoMetadata {
+            sequence,
+            ..Default::default()
+        })
+        .into(),
+        ..Default::default()
+    }
+}
+
+fn audio(sequence: u64, timestamp: f64) -> MediaPacket {
+    MediaPacket {
+        audio_metadata: Some(AudioMetadata {
+            audio_number_of_channels: 2,
+            audio_sample_rate: 48_000.0,
+            ..Default::default()
+        })
+        .into(),
+        data: format!("opus {}", sequence).into_bytes(),
+        ..media(MediaType::AUDIO, sequence, timestamp, false)
+    }
+}
+
+fn wrap(email: &str, data: Vec<u8>) -> PacketWrapper {
+    PacketWrapper {
+        packet_type: PacketType::MEDIA.into(),
+        email: email.to_string(),
+        data,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn webm_files_are_well_formed() {
+    let mut writer = WebmWriter::new(
+        Cursor::new(Vec::new()),
+        &Track::Opus {
+            channels: 1,
+            sample_rate: 48_000,
+        },
+    )
+    .unwrap();
+    for time in (0..6_000).step_by(20) {
+        writer.write_frame(time, Some(20), true, b"opus").unwrap();
+    }
+    assert!(writer.write_frame(5_000, None, true, b"late").is_err());
+    let file = writer.finish().unwrap().into_inner();
+
+    let (ids, duration, blocks) = parse_webm(&file);
+    // Audio clusters are cut every five seconds.
+    assert_eq!(ids, [SEEK_HEAD, INFO, TRACKS, CLUSTER, CLUSTER, CUES]);
+    assert_eq!(duration, 6_000.0);
+    assert_eq!(blocks.len(), 300);
+    assert!(blocks.iter().all(|(_, keyframe, _)| *keyframe));
+    assert_eq!(blocks[299].0, 5_980);
+
+    // Without keyframes there is nothing to seek to, so there are no Cues.
+    let mut writer = WebmWriter::new(
+        Cursor::new(Vec::new()),
+        &Track::Vp9 {
+            width: 640,
+            height: 480,
+        },
+    )
+    .unwrap();
+    writer.write_frame(0, None, false, b"delta").unwrap();
+    let (ids, duration, _) = parse_webm(&writer.finish().unwrap().into_inner());
+    assert_eq!(ids, [SEEK_HEAD, INFO, TRACKS, CLUSTER]);
+    assert_eq!(duration, 0.0);
+}
+
+#[test]
+fn vp9_frame_sizes_are_read_from_keyframes() {
+    assert_eq!(vp9_frame_size(&vp9_keyframe(640, 480)), Some((640, 480)));
+    assert_eq!(
+        vp9_frame_size(&vp9_keyframe(1920, 1080)),
+        Some((1920, 1080))
+    );
+    let mut interframe = vp9_keyframe(640, 480);
+    interframe[0] |= 0x04;
+    assert_eq!(vp9_frame_size(&interframe), None);
+    assert_eq!(vp9_frame_size(&[0x82, 0x49]), None);
+}
+
+#[test]
+fn recordings_are_muxed_per_participant_and_media_type() {
+    let dir = temp_dir("webm");
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("standup-10000.vcpl");
+    let alice_key =
+        MediaKey::parse("000102030405060708090a0b0c0d0e0f:f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff")
+            .unwrap();
+    let encrypt = |key: &MediaKey, packet: &MediaPacket| {
+        cbc::Encryptor::<aes::Aes128>::new(&key.key.into(), &key.iv.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(&packet.write_to_bytes().unwrap())
+    };
+    let carol_key = MediaKey {
+        key: [7; 16],
+        iv: [9; 16],
+    };
+
+    let camera = |sequence, timestamp, key| media(MediaType::VIDEO, sequence, timestamp, key);
+    let packets = [
+        (
+            10_100,
+            wrap("alice", encrypt(&alice_key, &camera(0, 0.0, true))),
+        ),
+        (10_050, wrap("bob", audio(0, 0.0).write_to_bytes().unwrap())),
+        (
+            10_140,
+            wrap("alice", encrypt(&alice_key, &camera(1, 33_000.0, false))),
+        ),
+        (
+            10_070,
+            wrap("bob", audio(1, 20_000.0).write_to_bytes().unwrap()),
+        ),
+        (
+            10_090,
+            wrap("bob", audio(2, 40_000.0).write_to_bytes().unwrap()),
+        ),
+        // Sequence 2 was lost, so 3 can't be decoded.
+        (
+            10_200,
+            wrap("alice", encrypt(&alice_key, &camera(3, 100_000.0, false))),
+        ),
+        (
+            10_240,
+            wrap("alice", encrypt(&alice_key, &camera(4, 133_000.0, true))),
+        ),
+        (
+            10_250,
+            wrap("alice", encrypt(&alice_key, &camera(4, 133_000.0, true))),
+        ),
+        (
+            10_300,
+            wrap(
+                "alice",
+                encrypt(&alice_key, &media(MediaType::HEARTBEAT, 0, 0.0, false)),
+            ),
+        ),
+        (
+            10_300,
+            wrap(
+                "alice",
+                encrypt(&alice_key, &media(MediaType::SCREEN, 0, 0.0, false)),
+            ),
+        ),
+        (10_400, wrap("carol", encrypt(&carol_key, &audio(0, 0.0)))),
+        // The camera was turned off and on again, which restarts the encoder.
+        (
+            30_000,
+            wrap("alice", encrypt(&alice_key, &camera(0, 0.0, true))),
+        ),
+    ];
+    {
+        let mut writer = PacketLogWriter::new(
+            BufWriter::new(File::create(&log).unwrap()),
+            BufWriter::new(File::create(index_path(&log)).unwrap()),
+            &LogHeader {
+                version: VERSION,
+                started_at: 10_000,
+                room: "standup".to_string(),
+            },
+        )
+        .unwrap();
+        for (arrival, packet) in packets {
+            writer
+                .append(&Record {
+                    arrival,
+                    subject: format!("room.standup.{}", packet.email),
+                    payload: packet.write_to_bytes().unwrap(),
+                })
+                .unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    let out = dir.join("webm");
+    let keys = HashMap::from([("alice".to_string(), alice_key)]);
+    let report = mux_to_webm(&log, &out, &keys).unwrap();
+    assert_eq!(report.unreadable, 1);
+    let files: Vec<(&str, MediaKind, usize, usize)> = report
+        .files
+        .iter()
+        .map(|f| (f.email.as_str(), f.kind, f.frames, f.dropped))
+        .collect();
+    assert_eq!(
+        files,
+        [
+            ("alice", MediaKind::Camera, 4, 1),
+            ("bob", MediaKind::Audio, 3, 0)
+        ]
+    );
+    assert_eq!(
+        report.files[0].path,
+        out.join("standup-10000-alice-camera.webm")
+    );
+
+    let camera = std::fs::read(&report.files[0].path).unwrap();
+    let (ids, duration, blocks) = parse_webm(&camera);
+    assert_eq!(
+        ids,
+        [SEEK_HEAD, INFO, TRACKS, CLUSTER, CLUSTER, CLUSTER, CUES]
+    );
+    let frames: Vec<(u64, bool)> = blocks.iter().map(|(t, k, _)| (*t, *k)).collect();
+    assert_eq!(
+        frames,
+        [(100, true), (133, false), (233, true), (20_000, true)]
+    );
+    assert_eq!(blocks[1].2, b"frame 1");
+    assert_eq!(duration, 20_020.0);
+
+    let audio = std::fs::read(&report.files[1].path).unwrap();
+    let (_, _, blocks) = parse_webm(&audio);
+    let frames: Vec<u64> = blocks.iter().map(|(t, _, _)| *t).collect();
+    assert_eq!(frames, [50, 70, 90]);
+    let head = audio.windows(8).position(|w| w == b"OpusHead").unwrap();
+    assert_eq!(audio[head + 9], 2);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
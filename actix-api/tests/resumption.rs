@@ -0,0 +1,345 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix::{Actor, ActorContext, Addr, Context, Handler};
+use protobuf::Message as _;
+use sec_api::actors::chat_server::ChatServer;
+use sec_api::bus::{LocalBus, RoomBus};
+use sec_api::messages::server::{
+    ClientMessage, Connect, Disconnect, GetParticipants, GetSession, JoinRoom, Packet, Resume,
+};
+use sec_api::messages::session::{Close, Message};
+use sec_api::resumption::{resumption_packet, Resumption};
+use sec_api::rooms::Transport;
+use sec_api::sender::is_server_only;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+/// Stands in for a websocket session, keeping what it was sent.
+#[derive(Clone, Default)]
+struct Received {
+    packets: Arc<Mutex<Vec<PacketWrapper>>>,
+    closed: Arc<Mutex<Vec<String>>>,
+}
+
+impl Received {
+    fn types(&self) -> Vec<(PacketType, String)> {
+        self.packets
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|p| (p.packet_type.enum_value().unwrap(), p.email.clone()))
+            .collect()
+    }
+}
+
+struct FakeSession {
+    received: Received,
+}
+
+impl Actor for FakeSession {
+    type Context = Context<Self>;
+}
+
+impl Handler<Message> for FakeSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: Message, _ctx: &mut Self::Context) {
+        let packet = PacketWrapper::parse_from_bytes(&msg.msg).unwrap();
+        self.received.packets.lock().unwrap().push(packet);
+    }
+}
+
+impl Handler<Close> for FakeSession {
+    type Result = ();
+
+    fn handle(&mut self, Close { reason }: Close, ctx: &mut Self::Context) {
+        self.received.closed.lock().unwrap().push(reason);
+        ctx.stop();
+    }
+}
+
+fn start_session() -> (Addr<FakeSession>, Received) {
+    let received = Received::default();
+    let addr = FakeSession {
+        received: received.clone(),
+    }
+    .start();
+    (addr, received)
+}
+
+async fn join(
+    chat: &Addr<ChatServer>,
+    session: &str,
+    user: &str,
+) -> (Addr<FakeSession>, Received, Option<String>) {
+    let (addr, received) = start_session();
+    chat.send(Connect {
+        id: session.to_string(),
+        addr: addr.clone().recipient(),
+        close: addr.clone().recipient(),
+    })
+    .await
+    .unwrap();
+    let token = chat
+        .send(JoinRoom {
+            session: session.to_string(),
+            room: "standup".to_string(),
+            user: user.to_string(),
+            transport: Transport::WebSocket,
+            max_participants: None,
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    (addr, received, token)
+}
+
+fn resume(token: &str, user: &str, addr: &Addr<FakeSession>) -> Resume {
+    Resume {
+        token: token.to_string(),
+        room: "standup".to_string(),
+        user: user.to_string(),
+        addr: addr.clone().recipient(),
+        close: addr.clone().recipient(),
+    }
+}
+
+fn publish(chat: &Addr<ChatServer>, session: &str, user: &str, packet_type: PacketType) {
+    let packet = PacketWrapper {
+        packet_type: packet_type.into(),
+        email: user.to_string(),
+        data: b"hello".to_vec(),
+        ..Default::default()
+    };
+    chat.do_send(ClientMessage {
+        session: session.to_string(),
+        user: user.to_string(),
+        room: "standup".to_string(),
+        msg: Packet {
+            data: Arc::new(packet.write_to_bytes().unwrap()),
+        },
+    });
+}
+
+async fn settle() {
+    actix_rt::time::sleep(Duration::from_millis(200)).await;
+}
+
+fn chat_server(grace: Duration) -> Addr<ChatServer> {
+    let bus: Arc<dyn RoomBus> = Arc::new(LocalBus::new());
+    ChatServer::new(bus)
+        .with_resumption(Resumption {
+            grace,
+            ..Default::default()
+        })
+        .start()
+}
+
+#[test]
+fn resumption_tokens_come_from_the_server_only() {
+    assert!(is_server_only(PacketType::RESUMPTION));
+    let packet = PacketWrapper::parse_from_bytes(&resumption_packet("alice", "t0k3n")).unwrap();
+    assert_eq!(packet.packet_type.enum_value(), Ok(PacketType::RESUMPTION));
+    assert_eq!(packet.email, "alice");
+    assert_eq!(packet.data, b"t0k3n");
+}
+
+#[actix_rt::test]
+async fn dropped_sessions_are_resumed_without_peers_noticing() {
+    let chat = chat_server(Duration::from_secs(5));
+    let (alice, _, token) = join(&chat, "s1", "alice").await;
+    let (_bob, bob_received, _) = join(&chat, "s2", "bob").await;
+    let token = token.expect("no resumption token");
+    settle().await;
+
+    chat.send(Disconnect {
+        session: "s1".to_string(),
+        resumable: true,
+        addr: alice.recipient(),
+    })
+    .await
+    .unwrap();
+    publish(&chat, "s2", "bob", PacketType::CHAT);
+    publish(&chat, "s2", "bob", PacketType::MEDIA);
+    settle().await;
+    assert!(!bob_received
+        .types()
+        .contains(&(PacketType::PARTICIPANT_LEFT, "alice".to_string())));
+
+    let (alice, alice_received) = start_session();
+    assert!(chat
+        .send(resume("guess", "alice", &alice))
+        .await
+        .unwrap()
+        .is_none());
+    assert!(chat
+        .send(resume(&token, "mallory", &alice))
+        .await
+        .unwrap()
+        .is_none());
+    let resumed = chat
+        .send(resume(&token, "alice", &alice))
+        .await
+        .unwrap()
+        .expect("session was not resumed");
+    assert_eq!(resumed.session, "s1");
+    assert_ne!(resumed.token, token);
+    settle().await;
+    // The chat message sent while alice was away is replayed, the media is not.
+    let replayed = alice_received.types();
+    assert_eq!(replayed.len(), resumed.replayed);
+    assert!(replayed.contains(&(PacketType::CHAT, "bob".to_string())));
+    assert!(!replayed.iter().any(|(t, _)| *t == PacketType::MEDIA));
+
+    // The subscription delivers to the new connection, and the old token is spent.
+    publish(&chat, "s2", "bob", PacketType::MEDIA);
+    settle().await;
+    assert_eq!(
+        alice_received.types().last(),
+        Some(&(PacketType::MEDIA, "bob".to_string()))
+    );
+    assert!(chat
+        .send(resume(&token, "alice", &alice))
+        .await
+        .unwrap()
+        .is_none());
+    let info = chat
+        .send(GetSession {
+            session: "s1".to_string(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.participant.email, "alice");
+}
+
+#[actix_rt::test]
+async fn sessions_leave_when_not_resumed_in_time() {
+    let chat = chat_server(Duration::from_millis(300));
+    let (alice, _, token) = join(&chat, "s1", "alice").await;
+    let (carol, _, _) = join(&chat, "s3", "carol").await;
+    let (_bob, bob_received, _) = join(&chat, "s2", "bob").await;
+    settle().await;
+
+    chat.send(Disconnect {
+        session: "s1".to_string(),
+        resumable: true,
+        addr: alice.recipient(),
+    })
+    .await
+    .unwrap();
+    // Closed on purpose, so carol leaves right away.
+    chat.send(Disconnect {
+        session: "s3".to_string(),
+        resumable: false,
+        addr: carol.recipient(),
+    })
+    .await
+    .unwrap();
+    settle().await;
+    assert_eq!(
+        bob_received
+            .types()
+            .into_iter()
+            .filter(|(t, _)| *t == PacketType::PARTICIPANT_LEFT)
+            .collect::<Vec<_>>(),
+        [(PacketType::PARTICIPANT_LEFT, "carol".to_string())]
+    );
+
+    settle().await;
+    assert!(bob_received
+        .types()
+        .contains(&(PacketType::PARTICIPANT_LEFT, "alice".to_string())));
+    let (alice, _) = start_session();
+    assert!(chat
+        .send(resume(&token.unwrap(), "alice", &alice))
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[actix_rt::test]
+async fn resuming_a_live_session_replaces_its_connection() {
+    let chat = chat_server(Duration::from_secs(5));
+    let (old, old_received, token) = join(&chat, "s1", "alice").await;
+    let (new, _) = start_session();
+    let resumed = chat
+        .send(resume(&token.unwrap(), "alice", &new))
+        .await
+        .unwrap()
+        .expect("session was not resumed");
+    assert_eq!(resumed.session, "s1");
+    settle().await;
+    assert_eq!(
+        *old_received.closed.lock().unwrap(),
+        ["resumed on another connection"]
+    );
+
+    // The old connection going away does not take the session with it.
+    chat.send(Disconnect {
+        session: "s1".to_string(),
+        resumable: false,
+        addr: old.recipient(),
+    })
+    .await
+    .unwrap();
+    assert!(chat
+        .send(GetSession {
+            session: "s1".to_string(),
+        })
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[actix_rt::test]
+async fn participants_are_listed_until_they_disconnect() {
+    let chat = chat_server(Duration::from_secs(5));
+    let (alice, _, _) = join(&chat, "s1", "alice").await;
+    let (_bob, _, _) = join(&chat, "s2", "bob").await;
+    let emails = || async {
+        let mut emails: Vec<String> = chat
+            .send(GetParticipants {
+                room: "standup".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| p.email)
+            .collect();
+        emails.sort();
+        emails
+    };
+    assert_eq!(emails().await, ["alice", "bob"]);
+
+    chat.send(Disconnect {
+        session: "s1".to_string(),
+        resumable: false,
+        addr: alice.recipient(),
+    })
+    .await
+    .unwrap();
+    assert_eq!(emails().await, ["bob"]);
+}
+
+#[actix_rt::test]
+async fn resumption_can_be_disabled() {
+    let chat = chat_server(Duration::ZERO);
+    let (alice, _, token) = join(&chat, "s1", "alice").await;
+    assert_eq!(token, None);
+    chat.send(Disconnect {
+        session: "s1".to_string(),
+        resumable: true,
+        addr: alice.recipient(),
+    })
+    .await
+    .unwrap();
+    assert!(chat
+        .send(GetSession {
+            session: "s1".to_string(),
+        })
+        .await
+        .unwrap()
+        .is_none());
+}
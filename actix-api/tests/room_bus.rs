@@ -0,0 +1,90 @@
+mod common;
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use common::next_payload;
+use futures::StreamExt;
+use sec_api::bus::{
+    room_subject, session_subject, validate_room_id, LocalBus, RoomBus, MAX_ROOM_ID_LEN,
+};
+
+#[tokio::test]
+async fn local_bus_fans_out_to_every_session_in_room() {
+    let bus = LocalBus::new();
+    let mut alice = bus
+        .subscribe_to_room("standup", "alice-standup")
+        .await
+        .unwrap();
+    let mut bob = bus
+        .subscribe_to_room("standup", "bob-standup")
+        .await
+        .unwrap();
+
+    bus.publish_to_room("standup", "carol", Bytes::from_static(b"hello"))
+        .await
+        .unwrap();
+
+    assert_eq!(next_payload(&mut alice).await.unwrap(), "hello");
+    assert_eq!(next_payload(&mut bob).await.unwrap(), "hello");
+}
+
+#[tokio::test]
+async fn local_bus_keeps_rooms_apart() {
+    let bus = LocalBus::new();
+    let mut standup = bus
+        .subscribe_to_room("standup", "alice-standup")
+        .await
+        .unwrap();
+
+    bus.publish_to_room("retro", "carol", Bytes::from_static(b"wrong room"))
+        .await
+        .unwrap();
+    bus.publish_to_room("standup", "carol", Bytes::from_static(b"right room"))
+        .await
+        .unwrap();
+
+    assert_eq!(next_payload(&mut standup).await.unwrap(), "right room");
+    assert!(next_payload(&mut standup).await.is_none());
+}
+
+#[tokio::test]
+async fn local_bus_reports_publishing_subject() {
+    let bus = LocalBus::new();
+    let mut sub = bus
+        .queue_subscribe(room_subject("my room"), String::from("alice"))
+        .await
+        .unwrap();
+
+    bus.publish_to_room("my room", "bob", Bytes::from_static(b"hi"))
+        .await
+        .unwrap();
+
+    let msg = tokio::time::timeout(Duration::from_millis(200), sub.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(msg.subject, session_subject("my room", "bob"));
+    assert_eq!(msg.subject, "room.my_room.bob");
+}
+
+#[test]
+fn room_ids_cannot_carry_wildcards_or_separators() {
+    for room in ["standup", "a1b2c3", "team_sync", "q3-review"] {
+        validate_room_id(room).unwrap();
+    }
+    validate_room_id(&"a".repeat(MAX_ROOM_ID_LEN)).unwrap();
+    let too_long = "a".repeat(MAX_ROOM_ID_LEN + 1);
+    for room in [
+        "",
+        "*",
+        ">",
+        "a.b",
+        "standup.>",
+        "my room",
+        "caf\u{e9}",
+        &*too_long,
+    ] {
+        assert!(validate_room_id(room).is_err(), "{:?} was accepted", room);
+    }
+}
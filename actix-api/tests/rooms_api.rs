@@ -0,0 +1,233 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::http::header::CONTENT_DISPOSITION;
+use actix_web::http::StatusCode;
+use actix_web::test::{
+    call_and_read_body, call_and_read_body_json, call_service, init_service, read_body_json,
+    TestRequest,
+};
+use actix_web::{web, App};
+use chrono::{TimeZone, Utc};
+use sec_api::api;
+use sec_api::auth::session::{create_session, SessionSettings};
+use sec_api::bus::LocalBus;
+use sec_api::chat::{ChatHistory, ChatMessage, ChatStore, MemoryChatStore};
+use sec_api::meetings::{create_meeting, NewMeeting};
+use sec_api::models::AppState;
+use sec_api::rooms::presence::Presence;
+use sec_api::rooms::{Participant, Transport};
+use serde_json::Value;
+
+/// More than two pages of the export.
+const MESSAGES: usize = 1001;
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn room_history_and_participants_are_shown_to_participants_and_owners() {
+    let pool =
+        common::test_pool(&["alice@example.com", "bob@example.com", "dave@example.com"]).await;
+    let meeting = create_meeting(
+        &pool,
+        "dave@example.com",
+        &NewMeeting {
+            title: "Standup".to_string(),
+            settings: None,
+            passcode: None,
+        },
+    )
+    .await
+    .unwrap();
+    let room = meeting.id.clone();
+    let store = MemoryChatStore::new(MESSAGES);
+    store
+        .add_participant(&room, "alice@example.com")
+        .await
+        .unwrap();
+    for i in 0..MESSAGES {
+        store
+            .append(ChatMessage {
+                id: format!("m{:04}", i),
+                room: room.clone(),
+                sender: "alice@example.com".to_string(),
+                text: format!("message {}", i),
+                sent_at: Utc.timestamp_opt(i as i64, 0).unwrap(),
+                reply_to: None,
+            })
+            .await
+            .unwrap();
+    }
+    let settings = SessionSettings::new("secret", Duration::from_secs(60));
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(settings.clone()))
+            .app_data(web::Data::new(AppState {
+                chat_history: ChatHistory::new(Arc::new(store), 10),
+                ..common::app_state(Arc::new(LocalBus::new()))
+            }))
+            .service(api::rooms::export_messages)
+            .service(api::rooms::list_messages)
+            .service(api::rooms::participants),
+    )
+    .await;
+    let mut cookies = Vec::new();
+    for email in ["alice@example.com", "bob@example.com", "dave@example.com"] {
+        let session = create_session(&pool, email, settings.ttl).await.unwrap();
+        cookies.push(settings.cookie(&session));
+    }
+    let [alice, bob, dave] = cookies.try_into().unwrap();
+    let messages = format!("/rooms/{}/messages", room);
+    let export = format!("/rooms/{}/messages/export", room);
+    let participants = format!("/rooms/{}/participants", room);
+
+    for uri in [&messages, &export, &participants] {
+        let anonymous = TestRequest::get().uri(uri).to_request();
+        assert_eq!(
+            call_service(&app, anonymous).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        let stranger = TestRequest::get().uri(uri).cookie(bob.clone()).to_request();
+        assert_eq!(
+            call_service(&app, stranger).await.status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    let listed: Value = call_and_read_body_json(
+        &app,
+        TestRequest::get()
+            .uri(&participants)
+            .cookie(alice.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(listed, serde_json::json!([]));
+
+    let ids = |messages: &Value| -> Vec<String> {
+        messages
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let page: Value = call_and_read_body_json(
+        &app,
+        TestRequest::get()
+            .uri(&messages)
+            .cookie(alice.clone())
+            .to_request(),
+    )
+    .await;
+    let newest = ids(&page["messages"]);
+    assert_eq!(newest.len(), 50);
+    assert_eq!(newest.first().unwrap(), "m0951");
+    assert_eq!(newest.last().unwrap(), "m1000");
+    assert_eq!(page["next_before"], "m0951");
+    let page: Value = call_and_read_body_json(
+        &app,
+        TestRequest::get()
+            .uri(&format!("{}?before=m0951&limit=2", messages))
+            .cookie(alice.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(ids(&page["messages"]), ["m0949", "m0950"]);
+    let page: Value = call_and_read_body_json(
+        &app,
+        TestRequest::get()
+            .uri(&format!("{}?before=m0002&limit=5", messages))
+            .cookie(alice.clone())
+            .to_request(),
+    )
+    .await;
+    assert_eq!(ids(&page["messages"]), ["m0000", "m0001"]);
+    assert!(page["next_before"].is_null());
+
+    // The owner may export the room without having joined it.
+    let resp = call_service(
+        &app,
+        TestRequest::get().uri(&export).cookie(dave).to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(CONTENT_DISPOSITION).unwrap(),
+        format!("attachment; filename=\"{}-chat.json\"", room).as_str()
+    );
+    let exported: Value = read_body_json(resp).await;
+    let expected: Vec<String> = (0..MESSAGES).map(|i| format!("m{:04}", i)).collect();
+    assert_eq!(ids(&exported), expected);
+
+    let transcript = call_and_read_body(
+        &app,
+        TestRequest::get()
+            .uri(&format!("{}?format=text", export))
+            .cookie(alice)
+            .to_request(),
+    )
+    .await;
+    let transcript = String::from_utf8(transcript.to_vec()).unwrap();
+    assert_eq!(transcript.lines().count(), MESSAGES);
+    assert!(transcript.starts_with("[1970-01-01 00:00:00 UTC] alice@example.com: message 0\n"));
+    assert!(transcript.ends_with("alice@example.com: message 1000\n"));
+}
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn participants_on_every_server_and_transport_are_listed() {
+    let pool = common::test_pool(&["alice@example.com", "dave@example.com"]).await;
+    let meeting = create_meeting(
+        &pool,
+        "dave@example.com",
+        &NewMeeting {
+            title: "Standup".to_string(),
+            settings: None,
+            passcode: None,
+        },
+    )
+    .await
+    .unwrap();
+    let room = meeting.id.clone();
+    // Stands in for the webtransport server, sharing the database.
+    let presence = Presence::new(pool.clone());
+    let participant = Participant::new(
+        "wt-alice".to_string(),
+        "alice@example.com".to_string(),
+        Transport::WebTransport,
+    );
+    assert!(presence.admit(&room, &participant, None).await.unwrap());
+
+    let settings = SessionSettings::new("secret", Duration::from_secs(60));
+    let state = AppState {
+        presence: Some(presence),
+        ..common::app_state(Arc::new(LocalBus::new()))
+    };
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(settings.clone()))
+            .app_data(web::Data::new(state))
+            .service(api::rooms::participants),
+    )
+    .await;
+    let session = create_session(&pool, "dave@example.com", settings.ttl)
+        .await
+        .unwrap();
+    let listed: Value = call_and_read_body_json(
+        &app,
+        TestRequest::get()
+            .uri(&format!("/rooms/{}/participants", room))
+            .cookie(settings.cookie(&session))
+            .to_request(),
+    )
+    .await;
+    let listed = listed.as_array().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["session_id"], "wt-alice");
+    assert_eq!(listed[0]["email"], "alice@example.com");
+    assert_eq!(listed[0]["transport"], "webtransport");
+}
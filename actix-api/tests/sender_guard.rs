@@ -0,0 +1,108 @@
+use protobuf::Message;
+use sec_api::sender::{packet_type, SenderGuard, Verdict, Violation};
+use types::protos::media_packet::media_packet::MediaType;
+use types::protos::media_packet::MediaPacket;
+use types::protos::packet_wrapper::packet_wrapper::PacketType;
+use types::protos::packet_wrapper::PacketWrapper;
+
+fn packet(packet_type: PacketType, email: &str) -> Vec<u8> {
+    PacketWrapper {
+        packet_type: packet_type.into(),
+        email: email.to_string(),
+        data: vec![1, 2, 3],
+        ..Default::default()
+    }
+    .write_to_bytes()
+    .unwrap()
+}
+
+#[test]
+fn forwards_packets_from_the_authenticated_sender() {
+    let guard = SenderGuard::new("alice", 3);
+    assert_eq!(
+        guard.admit(&packet(PacketType::MEDIA, "alice")),
+        Verdict::Forward
+    );
+    assert_eq!(guard.violations(), 0);
+}
+
+#[test]
+fn drops_spoofed_and_server_only_packets() {
+    let guard = SenderGuard::new("alice", 10);
+    assert_eq!(
+        guard.admit(&packet(PacketType::MEDIA, "bob")),
+        Verdict::Drop(Violation::SpoofedSender {
+            claimed: "bob".to_string()
+        })
+    );
+    assert_eq!(
+        guard.admit(&packet(PacketType::PARTICIPANT_LEFT, "alice")),
+        Verdict::Drop(Violation::ServerOnly(PacketType::PARTICIPANT_LEFT))
+    );
+    assert_eq!(guard.violations(), 2);
+}
+
+#[test]
+fn disconnects_after_too_many_violations() {
+    let guard = SenderGuard::new("alice", 2);
+    let spoofed = packet(PacketType::MEDIA, "bob");
+    assert!(matches!(guard.admit(&spoofed), Verdict::Drop(_)));
+    assert!(matches!(guard.admit(&spoofed), Verdict::Disconnect(_)));
+}
+
+fn media(media_type: MediaType, email: &str) -> Vec<u8> {
+    PacketWrapper {
+        packet_type: PacketType::MEDIA.into(),
+        email: email.to_string(),
+        data: MediaPacket {
+            media_type: media_type.into(),
+            email: email.to_string(),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap(),
+        ..Default::default()
+    }
+    .write_to_bytes()
+    .unwrap()
+}
+
+#[test]
+fn drops_media_from_non_publishers_without_counting_it() {
+    let guard = SenderGuard::new("alice", 1).with_publishing(false);
+    assert_eq!(
+        guard.admit(&media(MediaType::VIDEO, "alice")),
+        Verdict::Drop(Violation::NotPublisher)
+    );
+    assert_eq!(
+        guard.admit(&media(MediaType::HEARTBEAT, "alice")),
+        Verdict::Forward
+    );
+    assert_eq!(
+        guard.admit(&packet(PacketType::RSA_PUB_KEY, "alice")),
+        Verdict::Forward
+    );
+    assert_eq!(guard.violations(), 0);
+}
+
+#[test]
+fn packet_types_are_read_without_parsing_the_payload() {
+    assert_eq!(
+        packet_type(&packet(PacketType::MEDIA, "alice")),
+        Some(PacketType::MEDIA)
+    );
+    assert_eq!(
+        packet_type(&packet(PacketType::CONTROL, "alice")),
+        Some(PacketType::CONTROL)
+    );
+    // The default type is not written at all.
+    assert_eq!(
+        packet_type(&packet(PacketType::RSA_PUB_KEY, "alice")),
+        Some(PacketType::RSA_PUB_KEY)
+    );
+    // Only the header has to be valid.
+    let mut truncated = packet(PacketType::MEDIA, "alice");
+    truncated.truncate(4);
+    assert_eq!(packet_type(&truncated), Some(PacketType::MEDIA));
+    assert_eq!(packet_type(&[8, 0xff]), None);
+}
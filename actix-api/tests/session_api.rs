@@ -0,0 +1,153 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::cookie::Cookie;
+use actix_web::dev::ServiceResponse;
+use actix_web::http::StatusCode;
+use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+use actix_web::{web, App, HttpResponse, HttpServer};
+use sec_api::api;
+use sec_api::auth::session::{create_session, SessionSettings, SESSION_COOKIE};
+use sec_api::auth::token::{
+    authorize_connection, ConnectAuth, ConnectTokenKey, CONNECT_TOKEN_COOKIE,
+};
+use sec_api::bus::LocalBus;
+use sec_api::models::{AppConfig, AppState};
+use serde_json::json;
+
+const ALICE: &str = "alice@example.com";
+
+/// Serves a token endpoint that refreshes any refresh token, and returns its url.
+fn start_stub_token_endpoint() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/token", listener.local_addr().unwrap());
+    let server = HttpServer::new(|| {
+        App::new().route(
+            "/token",
+            web::post().to(|| async {
+                HttpResponse::Ok().json(json!({
+                    "access_token": "fresh",
+                    "token_type": "Bearer",
+                }))
+            }),
+        )
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    actix_rt::spawn(server);
+    url
+}
+
+fn cookie(response: &ServiceResponse, name: &str) -> Cookie<'static> {
+    response
+        .response()
+        .cookies()
+        .find(|c| c.name() == name)
+        .unwrap_or_else(|| panic!("no {} cookie", name))
+        .into_owned()
+}
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn logging_out_or_refreshing_revokes_connect_tokens() {
+    let pool = common::test_pool(&[ALICE]).await;
+    pool.get()
+        .await
+        .unwrap()
+        .execute(
+            "UPDATE users SET refresh_token='refresh' WHERE email=$1",
+            &[&ALICE],
+        )
+        .await
+        .unwrap();
+    let settings = SessionSettings::new("secret", Duration::from_secs(60));
+    let auth = ConnectAuth::Tokens(ConnectTokenKey::new("jwt-secret", Duration::from_secs(60)))
+        .with_sessions(pool.clone());
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(settings.clone()))
+            .app_data(web::Data::new(AppState {
+                connect_auth: auth.clone(),
+                ..common::app_state(Arc::new(LocalBus::new()))
+            }))
+            .app_data(web::Data::new(AppConfig {
+                oauth_client_id: "client".to_string(),
+                oauth_secret: "secret".to_string(),
+                oauth_redirect_url: String::new(),
+                oauth_auth_url: String::new(),
+                oauth_token_url: start_stub_token_endpoint(),
+                after_login_url: String::new(),
+                oauth_state_ttl: Duration::from_secs(60),
+            }))
+            .service(api::session::current)
+            .service(api::session::refresh)
+            .service(api::session::logout),
+    )
+    .await;
+    let session = create_session(&pool, ALICE, settings.ttl).await.unwrap();
+    let login = settings.cookie(&session);
+    let current = |cookie: &Cookie<'static>| {
+        TestRequest::get()
+            .uri("/session")
+            .cookie(cookie.clone())
+            .to_request()
+    };
+    // The UI connects with the email the connect token is issued for.
+    let user: serde_json::Value = call_and_read_body_json(&app, current(&login)).await;
+    assert_eq!(user, json!({ "email": ALICE }));
+    let token = auth.key().unwrap().issue(ALICE, &session.id).unwrap();
+    authorize_connection(&auth, ALICE, Some(&token))
+        .await
+        .unwrap();
+
+    // Refreshing rotates the session, so the old one and its connect token stop working.
+    let refresh = |cookie: &Cookie<'static>| {
+        TestRequest::post()
+            .uri("/session/refresh")
+            .cookie(cookie.clone())
+            .to_request()
+    };
+    let refreshed = call_service(&app, refresh(&login)).await;
+    assert_eq!(refreshed.status(), StatusCode::NO_CONTENT);
+    let rotated_login = cookie(&refreshed, SESSION_COOKIE);
+    let rotated_token = cookie(&refreshed, CONNECT_TOKEN_COOKIE);
+    assert_ne!(rotated_login.value(), login.value());
+    authorize_connection(&auth, ALICE, Some(rotated_token.value()))
+        .await
+        .unwrap();
+    assert!(authorize_connection(&auth, ALICE, Some(&token))
+        .await
+        .is_err());
+    // A revoked session can't be refreshed.
+    assert_eq!(
+        call_service(&app, refresh(&login)).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    let logout = TestRequest::post()
+        .uri("/logout")
+        .cookie(rotated_login.clone())
+        .to_request();
+    assert_eq!(
+        call_service(&app, logout).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    assert!(
+        authorize_connection(&auth, ALICE, Some(rotated_token.value()))
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        call_service(&app, refresh(&rotated_login)).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        call_service(&app, current(&rotated_login)).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
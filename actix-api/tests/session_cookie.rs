@@ -0,0 +1,16 @@
+use sec_api::auth::session::SessionSettings;
+use std::time::Duration;
+
+#[test]
+fn session_cookies_are_signed() {
+    let settings = SessionSettings::new("test-secret", Duration::from_secs(60));
+    let value = settings.sign("abc123");
+    assert_eq!(settings.verify(&value).as_deref(), Some("abc123"));
+
+    let (_, signature) = value.split_once('.').unwrap();
+    assert_eq!(settings.verify(&format!("abc124.{}", signature)), None);
+    assert_eq!(settings.verify("abc123"), None);
+
+    let other = SessionSettings::new("other-secret", Duration::from_secs(60));
+    assert_eq!(other.verify(&value), None);
+}
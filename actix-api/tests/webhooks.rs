@@ -0,0 +1,408 @@
+mod common;
+
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use sec_api::bus::{LocalBus, RoomBus};
+use sec_api::meetings::{create_meeting, NewMeeting};
+use sec_api::recording::Recorder;
+use sec_api::webhooks::{
+    create_webhook, delete_webhook, list_deliveries, list_webhooks, sign, AllowedHosts,
+    DeliveryStatus, NewWebhook, RetryPolicy, WebhookEvent, WebhookPayload, Webhooks,
+    DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+};
+use serde_json::json;
+
+/// A request received by the stub.
+#[derive(Debug, Clone)]
+struct Received {
+    event: String,
+    delivery: String,
+    timestamp: i64,
+    signature: String,
+    body: String,
+}
+
+/// Answers every POST with `status` and keeps what it received.
+struct StubReceiver {
+    url: String,
+    status: Arc<AtomicU16>,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl StubReceiver {
+    fn start() -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        let status = Arc::new(AtomicU16::new(200));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (status_2, received_2) = (status.clone(), received.clone());
+        let server = HttpServer::new(move || {
+            let status = status_2.clone();
+            let received = received_2.clone();
+            App::new().route(
+                "/hooks",
+                web::post().to(move |req: HttpRequest, body: String| {
+                    let status = status.clone();
+                    let received = received.clone();
+                    async move {
+                        let header = |name: &str| {
+                            req.headers()
+                                .get(name)
+                                .and_then(|v| v.to_str().ok())
+                                .unwrap_or_default()
+                                .to_string()
+                        };
+                        received.lock().unwrap().push(Received {
+                            event: header(EVENT_HEADER),
+                            delivery: header(DELIVERY_HEADER),
+                            timestamp: header(TIMESTAMP_HEADER).parse().unwrap(),
+                            signature: header(SIGNATURE_HEADER),
+                            body,
+                        });
+                        let status = status.load(Ordering::SeqCst);
+                        HttpResponse::build(status.try_into().unwrap()).finish()
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_rt::spawn(server);
+        Self {
+            url,
+            status,
+            received,
+        }
+    }
+
+    fn respond_with(&self, status: u16) {
+        self.status.store(status, Ordering::SeqCst);
+    }
+
+    fn take(&self) -> Vec<Received> {
+        std::mem::take(&mut *self.received.lock().unwrap())
+    }
+}
+
+fn events(received: &[Received]) -> Vec<&str> {
+    received.iter().map(|r| r.event.as_str()).collect()
+}
+
+/// Sorts deliveries, which arrive in any order, by when their events occurred.
+fn by_occurrence(mut received: Vec<Received>) -> Vec<Received> {
+    received.sort_by_key(|r| {
+        let payload: WebhookPayload = serde_json::from_str(&r.body).unwrap();
+        payload.occurred_at
+    });
+    received
+}
+
+#[test]
+fn signatures_cover_timestamp_and_body() {
+    let signature = sign("secret", 1_700_000_000, "{}");
+    assert!(signature.starts_with("sha256="));
+    assert_eq!(signature.len(), "sha256=".len() + 64);
+    assert_eq!(signature, sign("secret", 1_700_000_000, "{}"));
+    assert_ne!(signature, sign("secret", 1_700_000_001, "{}"));
+    assert_ne!(signature, sign("secret", 1_700_000_000, "{ }"));
+    assert_ne!(signature, sign("other", 1_700_000_000, "{}"));
+}
+
+#[test]
+fn retries_back_off_up_to_the_max_delay() {
+    let retry = RetryPolicy {
+        max_attempts: 8,
+        first_delay: Duration::from_secs(30),
+        max_delay: Duration::from_secs(300),
+    };
+    let delays: Vec<u64> = (1..=6).map(|n| retry.delay(n).as_secs()).collect();
+    assert_eq!(delays, [30, 60, 120, 240, 300, 300]);
+    assert_eq!(retry.delay(1000).as_secs(), 300);
+}
+
+#[test]
+fn webhook_input_is_validated() {
+    let webhook = |url: &str, events: Vec<WebhookEvent>| NewWebhook {
+        url: url.to_string(),
+        events,
+    };
+    let all = vec![WebhookEvent::MeetingStarted];
+    assert!(webhook("https://crm.example.com/hooks", all.clone())
+        .validate()
+        .is_ok());
+    assert!(webhook("ftp://crm.example.com/hooks", all.clone())
+        .validate()
+        .is_err());
+    assert!(webhook("not a url", all.clone()).validate().is_err());
+    assert!(webhook(&format!("https://{}.com", "a".repeat(3000)), all)
+        .validate()
+        .is_err());
+    assert!(webhook("https://crm.example.com/hooks", vec![])
+        .validate()
+        .is_err());
+    let parsed: NewWebhook = serde_json::from_str(
+        r#"{"url": "https://crm.example.com", "events": ["participant.joined", "recording.ready"]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        parsed.events,
+        [
+            WebhookEvent::ParticipantJoined,
+            WebhookEvent::RecordingReady
+        ]
+    );
+    assert!(serde_json::from_str::<NewWebhook>(
+        r#"{"url": "https://crm.example.com", "events": ["meeting.paused"]}"#
+    )
+    .is_err());
+}
+
+#[actix_rt::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn room_events_are_queued_signed_and_retried() {
+    let pool = common::test_pool(&["alice@example.com"]).await;
+    let stub = StubReceiver::start();
+    let meeting = create_meeting(
+        &pool,
+        "alice@example.com",
+        &NewMeeting {
+            title: "Standup".to_string(),
+            settings: None,
+            passcode: None,
+        },
+    )
+    .await
+    .unwrap();
+    let webhook = create_webhook(
+        &pool,
+        "alice@example.com",
+        &NewWebhook {
+            url: stub.url.clone(),
+            events: vec![
+                WebhookEvent::MeetingStarted,
+                WebhookEvent::MeetingEnded,
+                WebhookEvent::ParticipantJoined,
+                WebhookEvent::ParticipantLeft,
+                WebhookEvent::RecordingReady,
+                WebhookEvent::ParticipantJoined,
+            ],
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(webhook.events.len(), 5);
+    assert_eq!(webhook.secret.len(), 64);
+    assert_eq!(
+        list_webhooks(&pool, "alice@example.com").await.unwrap()[0].id,
+        webhook.id
+    );
+    let retry = RetryPolicy {
+        max_attempts: 2,
+        first_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+    };
+    let webhooks = Webhooks::new(pool.clone())
+        .with_retry(retry)
+        .with_allowed_hosts(AllowedHosts::new(["127.0.0.1", "localhost"]));
+
+    webhooks.participant_joined(&meeting.id, "alice").await;
+    webhooks.participant_joined(&meeting.id, "bob").await;
+    webhooks.participant_left(&meeting.id, "bob").await;
+    webhooks.participant_left(&meeting.id, "alice").await;
+    // Ad-hoc rooms have no owner to tell.
+    webhooks.participant_joined("adhoc", "carol").await;
+    webhooks.participant_left("adhoc", "carol").await;
+
+    // The receiver is down at first, so every delivery is retried.
+    stub.respond_with(503);
+    assert_eq!(webhooks.deliver_due().await.unwrap(), 6);
+    let failed = stub.take();
+    let log = list_deliveries(&pool, &webhook.id, 10).await.unwrap();
+    assert!(log
+        .iter()
+        .all(|d| d.status == DeliveryStatus::Pending && d.attempts == 1));
+    assert_eq!(log[0].last_status_code, Some(503));
+    assert_eq!(
+        log[0].last_error.as_deref(),
+        Some("responded 503 Service Unavailable")
+    );
+
+    stub.respond_with(200);
+    assert_eq!(webhooks.deliver_due().await.unwrap(), 6);
+    assert_eq!(webhooks.deliver_due().await.unwrap(), 0);
+    let received = stub.take();
+    let payloads: Vec<WebhookPayload> = received
+        .iter()
+        .map(|r| serde_json::from_str(&r.body).unwrap())
+        .collect();
+    for (r, payload) in received.iter().zip(&payloads) {
+        assert_eq!(r.signature, sign(&webhook.secret, r.timestamp, &r.body));
+        assert_eq!(r.delivery, payload.id);
+        assert_eq!(r.event, payload.event.as_str());
+        assert_eq!(payload.room, meeting.id);
+        assert!(failed
+            .iter()
+            .any(|f| f.delivery == r.delivery && f.body == r.body));
+    }
+    assert_eq!(
+        events(&by_occurrence(received)),
+        [
+            "meeting.started",
+            "participant.joined",
+            "participant.joined",
+            "participant.left",
+            "participant.left",
+            "meeting.ended",
+        ]
+    );
+    let log = list_deliveries(&pool, &webhook.id, 10).await.unwrap();
+    assert!(log.iter().all(|d| d.status == DeliveryStatus::Delivered
+        && d.attempts == 2
+        && d.last_status_code == Some(200)
+        && d.last_error.is_none()
+        && d.delivered_at.is_some()));
+
+    // Stopping a recording reports the finished packet log.
+    let bus: Arc<dyn RoomBus> = Arc::new(LocalBus::new());
+    let dir = std::env::temp_dir().join(format!("recordings-{}", uuid::Uuid::new_v4()));
+    let recorder = Recorder::new(bus, &dir).with_webhooks(Some(webhooks.clone()));
+    let recording = recorder.start(&meeting.id, "alice").await.unwrap();
+    recorder.stop(&meeting.id, "alice").await.unwrap();
+    let mut ready = None;
+    for _ in 0..50 {
+        let log = list_deliveries(&pool, &webhook.id, 1).await.unwrap();
+        if log[0].event == WebhookEvent::RecordingReady {
+            ready = Some(log.into_iter().next().unwrap());
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    let ready = ready.expect("recording.ready was not queued");
+    assert_eq!(
+        ready.payload.data["path"],
+        recording.path.display().to_string()
+    );
+    assert_eq!(ready.payload.data["started_by"], "alice");
+
+    // It gives up after the last attempt.
+    stub.respond_with(500);
+    assert_eq!(webhooks.deliver_due().await.unwrap(), 1);
+    assert_eq!(webhooks.deliver_due().await.unwrap(), 1);
+    assert_eq!(webhooks.deliver_due().await.unwrap(), 0);
+    assert_eq!(events(&stub.take()), ["recording.ready", "recording.ready"]);
+    let log = list_deliveries(&pool, &webhook.id, 1).await.unwrap();
+    assert_eq!(log[0].status, DeliveryStatus::Failed);
+    assert_eq!(log[0].attempts, 2);
+
+    assert!(!delete_webhook(&pool, &webhook.id, "bob@example.com")
+        .await
+        .unwrap());
+    assert!(delete_webhook(&pool, &webhook.id, "alice@example.com")
+        .await
+        .unwrap());
+    assert!(list_deliveries(&pool, &webhook.id, 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Private addresses are refused unless allowed, whether named or resolved.
+    let mut private = Vec::new();
+    for url in [stub.url.clone(), stub.url.replace("127.0.0.1", "localhost")] {
+        let webhook = create_webhook(
+            &pool,
+            "alice@example.com",
+            &NewWebhook {
+                url,
+                events: vec![WebhookEvent::ParticipantJoined],
+            },
+        )
+        .await
+        .unwrap();
+        private.push(webhook.id);
+    }
+    stub.respond_with(200);
+    let public_only = Webhooks::new(pool.clone()).with_retry(retry);
+    public_only
+        .emit(&meeting.id, WebhookEvent::ParticipantJoined, json!({}))
+        .await
+        .unwrap();
+    assert_eq!(public_only.deliver_due().await.unwrap(), 2);
+    assert!(stub.take().is_empty());
+    let refused = list_deliveries(&pool, &private[0], 1).await.unwrap();
+    assert_eq!(
+        refused[0].last_error.as_deref(),
+        Some("refusing to deliver to non-public address 127.0.0.1")
+    );
+    let refused = list_deliveries(&pool, &private[1], 1).await.unwrap();
+    assert_eq!(refused[0].status, DeliveryStatus::Pending);
+    assert!(refused[0]
+        .last_error
+        .as_deref()
+        .unwrap()
+        .contains("localhost has no public address"));
+    assert_eq!(webhooks.deliver_due().await.unwrap(), 2);
+    assert_eq!(
+        events(&stub.take()),
+        ["participant.joined", "participant.joined"]
+    );
+
+    // Changes to a room apply in the order they were made, even when nobody waits for them.
+    for id in &private {
+        assert!(delete_webhook(&pool, id, "alice@example.com")
+            .await
+            .unwrap());
+    }
+    create_webhook(
+        &pool,
+        "alice@example.com",
+        &NewWebhook {
+            url: stub.url.clone(),
+            events: vec![
+                WebhookEvent::MeetingStarted,
+                WebhookEvent::MeetingEnded,
+                WebhookEvent::ParticipantJoined,
+                WebhookEvent::ParticipantLeft,
+            ],
+        },
+    )
+    .await
+    .unwrap();
+    actix_rt::spawn(webhooks.participant_joined(&meeting.id, "alice"));
+    webhooks.participant_left(&meeting.id, "alice").await;
+    assert_eq!(webhooks.deliver_due().await.unwrap(), 4);
+    assert_eq!(
+        events(&by_occurrence(stub.take())),
+        [
+            "meeting.started",
+            "participant.joined",
+            "participant.left",
+            "meeting.ended",
+        ]
+    );
+
+    // The sessions of a node that stopped saying it is alive are counted out by the others.
+    webhooks.participant_joined(&meeting.id, "bob").await;
+    let other = Webhooks::new(pool.clone());
+    assert_eq!(
+        other.expire_nodes(Duration::from_secs(60)).await.unwrap(),
+        0
+    );
+    assert_eq!(other.expire_nodes(Duration::ZERO).await.unwrap(), 1);
+    webhooks.participant_left(&meeting.id, "bob").await;
+    assert_eq!(webhooks.deliver_due().await.unwrap(), 4);
+    assert_eq!(
+        events(&by_occurrence(stub.take())),
+        [
+            "meeting.started",
+            "participant.joined",
+            "meeting.ended",
+            "participant.left",
+        ]
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -25,6 +25,30 @@ impl std::fmt::Display for protos::packet_wrapper::packet_wrapper::PacketType {
             protos::packet_wrapper::packet_wrapper::PacketType::CONNECTION => {
                 write!(f, "CONNECTION")
             }
+            protos::packet_wrapper::packet_wrapper::PacketType::PARTICIPANT_JOINED => {
+                write!(f, "PARTICIPANT_JOINED")
+            }
+            protos::packet_wrapper::packet_wrapper::PacketType::PARTICIPANT_LEFT => {
+                write!(f, "PARTICIPANT_LEFT")
+            }
+            protos::packet_wrapper::packet_wrapper::PacketType::ROOM_SETTINGS => {
+                write!(f, "ROOM_SETTINGS")
+            }
+            protos::packet_wrapper::packet_wrapper::PacketType::JOIN_REJECTED => {
+                write!(f, "JOIN_REJECTED")
+            }
+            protos::packet_wrapper::packet_wrapper::PacketType::CONTROL => {
+                write!(f, "CONTROL")
+            }
+            protos::packet_wrapper::packet_wrapper::PacketType::LOBBY => {
+                write!(f, "LOBBY")
+            }
+            protos::packet_wrapper::packet_wrapper::PacketType::CHAT => {
+                write!(f, "CHAT")
+            }
+            protos::packet_wrapper::packet_wrapper::PacketType::RESUMPTION => {
+                write!(f, "RESUMPTION")
+            }
         }
     }
 }
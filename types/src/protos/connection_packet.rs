@@ -31,6 +31,12 @@ pub struct ConnectionPacket {
     // message fields
     // @@protoc_insertion_point(field:ConnectionPacket.meeting_id)
     pub meeting_id: ::std::string::String,
+    // @@protoc_insertion_point(field:ConnectionPacket.token)
+    pub token: ::std::string::String,
+    // @@protoc_insertion_point(field:ConnectionPacket.passcode)
+    pub passcode: ::std::string::String,
+    // @@protoc_insertion_point(field:ConnectionPacket.invite)
+    pub invite: ::std::string::String,
     // special fields
     // @@protoc_insertion_point(special_field:ConnectionPacket.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -48,13 +54,28 @@ impl ConnectionPacket {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut fields = ::std::vec::Vec::with_capacity(4);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "meeting_id",
             |m: &ConnectionPacket| { &m.meeting_id },
             |m: &mut ConnectionPacket| { &mut m.meeting_id },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "token",
+            |m: &ConnectionPacket| { &m.token },
+            |m: &mut ConnectionPacket| { &mut m.token },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "passcode",
+            |m: &ConnectionPacket| { &m.passcode },
+            |m: &mut ConnectionPacket| { &mut m.passcode },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "invite",
+            |m: &ConnectionPacket| { &m.invite },
+            |m: &mut ConnectionPacket| { &mut m.invite },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ConnectionPacket>(
             "ConnectionPacket",
             fields,
@@ -76,6 +97,15 @@ impl ::protobuf::Message for ConnectionPacket {
                 10 => {
                     self.meeting_id = is.read_string()?;
                 },
+                18 => {
+                    self.token = is.read_string()?;
+                },
+                26 => {
+                    self.passcode = is.read_string()?;
+                },
+                34 => {
+                    self.invite = is.read_string()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -91,6 +121,15 @@ impl ::protobuf::Message for ConnectionPacket {
         if !self.meeting_id.is_empty() {
             my_size += ::protobuf::rt::string_size(1, &self.meeting_id);
         }
+        if !self.token.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.token);
+        }
+        if !self.passcode.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.passcode);
+        }
+        if !self.invite.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.invite);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -100,6 +139,15 @@ impl ::protobuf::Message for ConnectionPacket {
         if !self.meeting_id.is_empty() {
             os.write_string(1, &self.meeting_id)?;
         }
+        if !self.token.is_empty() {
+            os.write_string(2, &self.token)?;
+        }
+        if !self.passcode.is_empty() {
+            os.write_string(3, &self.passcode)?;
+        }
+        if !self.invite.is_empty() {
+            os.write_string(4, &self.invite)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -118,12 +166,18 @@ impl ::protobuf::Message for ConnectionPacket {
 
     fn clear(&mut self) {
         self.meeting_id.clear();
+        self.token.clear();
+        self.passcode.clear();
+        self.invite.clear();
         self.special_fields.clear();
     }
 
     fn default_instance() -> &'static ConnectionPacket {
         static instance: ConnectionPacket = ConnectionPacket {
             meeting_id: ::std::string::String::new(),
+            token: ::std::string::String::new(),
+            passcode: ::std::string::String::new(),
+            invite: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -148,13 +202,10 @@ impl ::protobuf::reflect::ProtobufValue for ConnectionPacket {
 }
 
 static file_descriptor_proto_data: &'static [u8] = b"\
-    \n\x1dtypes/connection_packet.proto\"1\n\x10ConnectionPacket\x12\x1d\n\n\
-    meeting_id\x18\x01\x20\x01(\tR\tmeetingIdJa\n\x06\x12\x04\0\0\x04\x01\n\
-    \x08\n\x01\x0c\x12\x03\0\0\x12\n\n\n\x02\x04\0\x12\x04\x02\0\x04\x01\n\n\
-    \n\x03\x04\0\x01\x12\x03\x02\x08\x18\n\x0b\n\x04\x04\0\x02\0\x12\x03\x03\
-    \x02\x18\n\x0c\n\x05\x04\0\x02\0\x05\x12\x03\x03\x02\x08\n\x0c\n\x05\x04\
-    \0\x02\0\x01\x12\x03\x03\t\x13\n\x0c\n\x05\x04\0\x02\0\x03\x12\x03\x03\
-    \x16\x17b\x06proto3\
+    \n\x1dtypes/connection_packet.proto\"{\n\x10ConnectionPacket\x12\x1d\n\n\
+    meeting_id\x18\x01\x20\x01(\tR\tmeetingId\x12\x14\n\x05token\x18\x02\x20\
+    \x01(\tR\x05token\x12\x1a\n\x08passcode\x18\x03\x20\x01(\tR\x08passcode\
+    \x12\x16\n\x06invite\x18\x04\x20\x01(\tR\x06inviteb\x06proto3\
 ";
 
 /// `FileDescriptorProto` object which was a source for this generated file
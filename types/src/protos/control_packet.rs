@@ -0,0 +1,312 @@
+// This file is generated by rust-protobuf 3.3.0. Do not edit
+// .proto file is parsed by protoc --rust-out=...
+// @generated
+
+// https://github.com/rust-lang/rust-clippy/issues/702
+#![allow(unknown_lints)]
+#![allow(clippy::all)]
+
+#![allow(unused_attributes)]
+#![cfg_attr(rustfmt, rustfmt::skip)]
+
+#![allow(box_pointers)]
+#![allow(dead_code)]
+#![allow(missing_docs)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(trivial_casts)]
+#![allow(unused_results)]
+#![allow(unused_mut)]
+
+//! Generated file from `types/control_packet.proto`
+
+/// Generated files are compatible only with the same version
+/// of protobuf runtime.
+const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_3_3_0;
+
+// @@protoc_insertion_point(message:ControlPacket)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct ControlPacket {
+    // message fields
+    // @@protoc_insertion_point(field:ControlPacket.command)
+    pub command: ::protobuf::EnumOrUnknown<control_packet::Command>,
+    // @@protoc_insertion_point(field:ControlPacket.target)
+    pub target: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:ControlPacket.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ControlPacket {
+    fn default() -> &'a ControlPacket {
+        <ControlPacket as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ControlPacket {
+    pub fn new() -> ControlPacket {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(2);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "command",
+            |m: &ControlPacket| { &m.command },
+            |m: &mut ControlPacket| { &mut m.command },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "target",
+            |m: &ControlPacket| { &m.target },
+            |m: &mut ControlPacket| { &mut m.target },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ControlPacket>(
+            "ControlPacket",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for ControlPacket {
+    const NAME: &'static str = "ControlPacket";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.command = is.read_enum_or_unknown()?;
+                },
+                18 => {
+                    self.target = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.command != ::protobuf::EnumOrUnknown::new(control_packet::Command::UNSPECIFIED) {
+            my_size += ::protobuf::rt::int32_size(1, self.command.value());
+        }
+        if !self.target.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.target);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.command != ::protobuf::EnumOrUnknown::new(control_packet::Command::UNSPECIFIED) {
+            os.write_enum(1, ::protobuf::EnumOrUnknown::value(&self.command))?;
+        }
+        if !self.target.is_empty() {
+            os.write_string(2, &self.target)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ControlPacket {
+        ControlPacket::new()
+    }
+
+    fn clear(&mut self) {
+        self.command = ::protobuf::EnumOrUnknown::new(control_packet::Command::UNSPECIFIED);
+        self.target.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ControlPacket {
+        static instance: ControlPacket = ControlPacket {
+            command: ::protobuf::EnumOrUnknown::from_i32(0),
+            target: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for ControlPacket {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("ControlPacket").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for ControlPacket {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for ControlPacket {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+/// Nested message and enums of message `ControlPacket`
+pub mod control_packet {
+    #[derive(Clone,Copy,PartialEq,Eq,Debug,Hash)]
+    // @@protoc_insertion_point(enum:ControlPacket.Command)
+    pub enum Command {
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.UNSPECIFIED)
+        UNSPECIFIED = 0,
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.MUTE)
+        MUTE = 1,
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.STOP_VIDEO)
+        STOP_VIDEO = 2,
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.REMOVE)
+        REMOVE = 3,
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.TRANSFER_HOST)
+        TRANSFER_HOST = 4,
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.HOST_CHANGED)
+        HOST_CHANGED = 5,
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.ADMIT)
+        ADMIT = 6,
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.DENY)
+        DENY = 7,
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.START_RECORDING)
+        START_RECORDING = 8,
+        // @@protoc_insertion_point(enum_value:ControlPacket.Command.STOP_RECORDING)
+        STOP_RECORDING = 9,
+    }
+
+    impl ::protobuf::Enum for Command {
+        const NAME: &'static str = "Command";
+
+        fn value(&self) -> i32 {
+            *self as i32
+        }
+
+        fn from_i32(value: i32) -> ::std::option::Option<Command> {
+            match value {
+                0 => ::std::option::Option::Some(Command::UNSPECIFIED),
+                1 => ::std::option::Option::Some(Command::MUTE),
+                2 => ::std::option::Option::Some(Command::STOP_VIDEO),
+                3 => ::std::option::Option::Some(Command::REMOVE),
+                4 => ::std::option::Option::Some(Command::TRANSFER_HOST),
+                5 => ::std::option::Option::Some(Command::HOST_CHANGED),
+                6 => ::std::option::Option::Some(Command::ADMIT),
+                7 => ::std::option::Option::Some(Command::DENY),
+                8 => ::std::option::Option::Some(Command::START_RECORDING),
+                9 => ::std::option::Option::Some(Command::STOP_RECORDING),
+                _ => ::std::option::Option::None
+            }
+        }
+
+        fn from_str(str: &str) -> ::std::option::Option<Command> {
+            match str {
+                "UNSPECIFIED" => ::std::option::Option::Some(Command::UNSPECIFIED),
+                "MUTE" => ::std::option::Option::Some(Command::MUTE),
+                "STOP_VIDEO" => ::std::option::Option::Some(Command::STOP_VIDEO),
+                "REMOVE" => ::std::option::Option::Some(Command::REMOVE),
+                "TRANSFER_HOST" => ::std::option::Option::Some(Command::TRANSFER_HOST),
+                "HOST_CHANGED" => ::std::option::Option::Some(Command::HOST_CHANGED),
+                "ADMIT" => ::std::option::Option::Some(Command::ADMIT),
+                "DENY" => ::std::option::Option::Some(Command::DENY),
+                "START_RECORDING" => ::std::option::Option::Some(Command::START_RECORDING),
+                "STOP_RECORDING" => ::std::option::Option::Some(Command::STOP_RECORDING),
+                _ => ::std::option::Option::None
+            }
+        }
+
+        const VALUES: &'static [Command] = &[
+            Command::UNSPECIFIED,
+            Command::MUTE,
+            Command::STOP_VIDEO,
+            Command::REMOVE,
+            Command::TRANSFER_HOST,
+            Command::HOST_CHANGED,
+            Command::ADMIT,
+            Command::DENY,
+            Command::START_RECORDING,
+            Command::STOP_RECORDING,
+        ];
+    }
+
+    impl ::protobuf::EnumFull for Command {
+        fn enum_descriptor() -> ::protobuf::reflect::EnumDescriptor {
+            static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::EnumDescriptor> = ::protobuf::rt::Lazy::new();
+            descriptor.get(|| super::file_descriptor().enum_by_package_relative_name("ControlPacket.Command").unwrap()).clone()
+        }
+
+        fn descriptor(&self) -> ::protobuf::reflect::EnumValueDescriptor {
+            let index = *self as usize;
+            Self::enum_descriptor().value_by_index(index)
+        }
+    }
+
+    impl ::std::default::Default for Command {
+        fn default() -> Self {
+            Command::UNSPECIFIED
+        }
+    }
+
+    impl Command {
+        pub(in super) fn generated_enum_descriptor_data() -> ::protobuf::reflect::GeneratedEnumDescriptorData {
+            ::protobuf::reflect::GeneratedEnumDescriptorData::new::<Command>("ControlPacket.Command")
+        }
+    }
+}
+
+static file_descriptor_proto_data: &'static [u8] = b"\
+    \n\x1atypes/control_packet.proto\"\xff\x01\n\rControlPacket\x120\n\x07co\
+    mmand\x18\x01\x20\x01(\x0e2\x16.ControlPacket.CommandR\x07command\x12\
+    \x16\n\x06target\x18\x02\x20\x01(\tR\x06target\"\xa3\x01\n\x07Command\
+    \x12\x0f\n\x0bUNSPECIFIED\x10\0\x12\x08\n\x04MUTE\x10\x01\x12\x0e\n\nSTO\
+    P_VIDEO\x10\x02\x12\n\n\x06REMOVE\x10\x03\x12\x11\n\rTRANSFER_HOST\x10\
+    \x04\x12\x10\n\x0cHOST_CHANGED\x10\x05\x12\t\n\x05ADMIT\x10\x06\x12\x08\
+    \n\x04DENY\x10\x07\x12\x13\n\x0fSTART_RECORDING\x10\x08\x12\x12\n\x0eSTO\
+    P_RECORDING\x10\tb\x06proto3\
+";
+
+/// `FileDescriptorProto` object which was a source for this generated file
+fn file_descriptor_proto() -> &'static ::protobuf::descriptor::FileDescriptorProto {
+    static file_descriptor_proto_lazy: ::protobuf::rt::Lazy<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::rt::Lazy::new();
+    file_descriptor_proto_lazy.get(|| {
+        ::protobuf::Message::parse_from_bytes(file_descriptor_proto_data).unwrap()
+    })
+}
+
+/// `FileDescriptor` object which allows dynamic access to files
+pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
+    static generated_file_descriptor_lazy: ::protobuf::rt::Lazy<::protobuf::reflect::GeneratedFileDescriptor> = ::protobuf::rt::Lazy::new();
+    static file_descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::FileDescriptor> = ::protobuf::rt::Lazy::new();
+    file_descriptor.get(|| {
+        let generated_file_descriptor = generated_file_descriptor_lazy.get(|| {
+            let mut deps = ::std::vec::Vec::with_capacity(0);
+            let mut messages = ::std::vec::Vec::with_capacity(1);
+            messages.push(ControlPacket::generated_message_descriptor_data());
+            let mut enums = ::std::vec::Vec::with_capacity(1);
+            enums.push(control_packet::Command::generated_enum_descriptor_data());
+            ::protobuf::reflect::GeneratedFileDescriptor::new_generated(
+                file_descriptor_proto(),
+                deps,
+                messages,
+                enums,
+            )
+        });
+        ::protobuf::reflect::FileDescriptor::new_generated_2(generated_file_descriptor)
+    })
+}
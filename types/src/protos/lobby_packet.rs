@@ -0,0 +1,266 @@
+// This file is generated by rust-protobuf 3.3.0. Do not edit
+// .proto file is parsed by protoc --rust-out=...
+// @generated
+
+// https://github.com/rust-lang/rust-clippy/issues/702
+#![allow(unknown_lints)]
+#![allow(clippy::all)]
+
+#![allow(unused_attributes)]
+#![cfg_attr(rustfmt, rustfmt::skip)]
+
+#![allow(box_pointers)]
+#![allow(dead_code)]
+#![allow(missing_docs)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(trivial_casts)]
+#![allow(unused_results)]
+#![allow(unused_mut)]
+
+//! Generated file from `types/lobby_packet.proto`
+
+/// Generated files are compatible only with the same version
+/// of protobuf runtime.
+const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_3_3_0;
+
+// @@protoc_insertion_point(message:LobbyPacket)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct LobbyPacket {
+    // message fields
+    // @@protoc_insertion_point(field:LobbyPacket.status)
+    pub status: ::protobuf::EnumOrUnknown<lobby_packet::Status>,
+    // special fields
+    // @@protoc_insertion_point(special_field:LobbyPacket.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a LobbyPacket {
+    fn default() -> &'a LobbyPacket {
+        <LobbyPacket as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl LobbyPacket {
+    pub fn new() -> LobbyPacket {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "status",
+            |m: &LobbyPacket| { &m.status },
+            |m: &mut LobbyPacket| { &mut m.status },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<LobbyPacket>(
+            "LobbyPacket",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for LobbyPacket {
+    const NAME: &'static str = "LobbyPacket";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.status = is.read_enum_or_unknown()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.status != ::protobuf::EnumOrUnknown::new(lobby_packet::Status::UNSPECIFIED) {
+            my_size += ::protobuf::rt::int32_size(1, self.status.value());
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.status != ::protobuf::EnumOrUnknown::new(lobby_packet::Status::UNSPECIFIED) {
+            os.write_enum(1, ::protobuf::EnumOrUnknown::value(&self.status))?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> LobbyPacket {
+        LobbyPacket::new()
+    }
+
+    fn clear(&mut self) {
+        self.status = ::protobuf::EnumOrUnknown::new(lobby_packet::Status::UNSPECIFIED);
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static LobbyPacket {
+        static instance: LobbyPacket = LobbyPacket {
+            status: ::protobuf::EnumOrUnknown::from_i32(0),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for LobbyPacket {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("LobbyPacket").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for LobbyPacket {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for LobbyPacket {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+/// Nested message and enums of message `LobbyPacket`
+pub mod lobby_packet {
+    #[derive(Clone,Copy,PartialEq,Eq,Debug,Hash)]
+    // @@protoc_insertion_point(enum:LobbyPacket.Status)
+    pub enum Status {
+        // @@protoc_insertion_point(enum_value:LobbyPacket.Status.UNSPECIFIED)
+        UNSPECIFIED = 0,
+        // @@protoc_insertion_point(enum_value:LobbyPacket.Status.WAITING)
+        WAITING = 1,
+        // @@protoc_insertion_point(enum_value:LobbyPacket.Status.ADMITTED)
+        ADMITTED = 2,
+        // @@protoc_insertion_point(enum_value:LobbyPacket.Status.DENIED)
+        DENIED = 3,
+        // @@protoc_insertion_point(enum_value:LobbyPacket.Status.LEFT)
+        LEFT = 4,
+    }
+
+    impl ::protobuf::Enum for Status {
+        const NAME: &'static str = "Status";
+
+        fn value(&self) -> i32 {
+            *self as i32
+        }
+
+        fn from_i32(value: i32) -> ::std::option::Option<Status> {
+            match value {
+                0 => ::std::option::Option::Some(Status::UNSPECIFIED),
+                1 => ::std::option::Option::Some(Status::WAITING),
+                2 => ::std::option::Option::Some(Status::ADMITTED),
+                3 => ::std::option::Option::Some(Status::DENIED),
+                4 => ::std::option::Option::Some(Status::LEFT),
+                _ => ::std::option::Option::None
+            }
+        }
+
+        fn from_str(str: &str) -> ::std::option::Option<Status> {
+            match str {
+                "UNSPECIFIED" => ::std::option::Option::Some(Status::UNSPECIFIED),
+                "WAITING" => ::std::option::Option::Some(Status::WAITING),
+                "ADMITTED" => ::std::option::Option::Some(Status::ADMITTED),
+                "DENIED" => ::std::option::Option::Some(Status::DENIED),
+                "LEFT" => ::std::option::Option::Some(Status::LEFT),
+                _ => ::std::option::Option::None
+            }
+        }
+
+        const VALUES: &'static [Status] = &[
+            Status::UNSPECIFIED,
+            Status::WAITING,
+            Status::ADMITTED,
+            Status::DENIED,
+            Status::LEFT,
+        ];
+    }
+
+    impl ::protobuf::EnumFull for Status {
+        fn enum_descriptor() -> ::protobuf::reflect::EnumDescriptor {
+            static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::EnumDescriptor> = ::protobuf::rt::Lazy::new();
+            descriptor.get(|| super::file_descriptor().enum_by_package_relative_name("LobbyPacket.Status").unwrap()).clone()
+        }
+
+        fn descriptor(&self) -> ::protobuf::reflect::EnumValueDescriptor {
+            let index = *self as usize;
+            Self::enum_descriptor().value_by_index(index)
+        }
+    }
+
+    impl ::std::default::Default for Status {
+        fn default() -> Self {
+            Status::UNSPECIFIED
+        }
+    }
+
+    impl Status {
+        pub(in super) fn generated_enum_descriptor_data() -> ::protobuf::reflect::GeneratedEnumDescriptorData {
+            ::protobuf::reflect::GeneratedEnumDescriptorData::new::<Status>("LobbyPacket.Status")
+        }
+    }
+}
+
+static file_descriptor_proto_data: &'static [u8] = b"\
+    \n\x18types/lobby_packet.proto\"\x86\x01\n\x0bLobbyPacket\x12+\n\x06stat\
+    us\x18\x01\x20\x01(\x0e2\x13.LobbyPacket.StatusR\x06status\"J\n\x06Statu\
+    s\x12\x0f\n\x0bUNSPECIFIED\x10\0\x12\x0b\n\x07WAITING\x10\x01\x12\x0c\n\
+    \x08ADMITTED\x10\x02\x12\n\n\x06DENIED\x10\x03\x12\x08\n\x04LEFT\x10\x04\
+    b\x06proto3\
+";
+
+/// `FileDescriptorProto` object which was a source for this generated file
+fn file_descriptor_proto() -> &'static ::protobuf::descriptor::FileDescriptorProto {
+    static file_descriptor_proto_lazy: ::protobuf::rt::Lazy<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::rt::Lazy::new();
+    file_descriptor_proto_lazy.get(|| {
+        ::protobuf::Message::parse_from_bytes(file_descriptor_proto_data).unwrap()
+    })
+}
+
+/// `FileDescriptor` object which allows dynamic access to files
+pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
+    static generated_file_descriptor_lazy: ::protobuf::rt::Lazy<::protobuf::reflect::GeneratedFileDescriptor> = ::protobuf::rt::Lazy::new();
+    static file_descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::FileDescriptor> = ::protobuf::rt::Lazy::new();
+    file_descriptor.get(|| {
+        let generated_file_descriptor = generated_file_descriptor_lazy.get(|| {
+            let mut deps = ::std::vec::Vec::with_capacity(0);
+            let mut messages = ::std::vec::Vec::with_capacity(1);
+            messages.push(LobbyPacket::generated_message_descriptor_data());
+            let mut enums = ::std::vec::Vec::with_capacity(1);
+            enums.push(lobby_packet::Status::generated_enum_descriptor_data());
+            ::protobuf::reflect::GeneratedFileDescriptor::new_generated(
+                file_descriptor_proto(),
+                deps,
+                messages,
+                enums,
+            )
+        });
+        ::protobuf::reflect::FileDescriptor::new_generated_2(generated_file_descriptor)
+    })
+}
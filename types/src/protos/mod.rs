@@ -1,7 +1,12 @@
 // @generated
 
 pub mod aes_packet;
+pub mod chat_packet;
 pub mod connection_packet;
+pub mod control_packet;
+pub mod join_rejected;
+pub mod lobby_packet;
 pub mod media_packet;
 pub mod packet_wrapper;
+pub mod room_settings;
 pub mod rsa_packet;
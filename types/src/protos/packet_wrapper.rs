@@ -196,6 +196,22 @@ pub mod packet_wrapper {
         MEDIA = 2,
         // @@protoc_insertion_point(enum_value:PacketWrapper.PacketType.CONNECTION)
         CONNECTION = 3,
+        // @@protoc_insertion_point(enum_value:PacketWrapper.PacketType.PARTICIPANT_JOINED)
+        PARTICIPANT_JOINED = 4,
+        // @@protoc_insertion_point(enum_value:PacketWrapper.PacketType.PARTICIPANT_LEFT)
+        PARTICIPANT_LEFT = 5,
+        // @@protoc_insertion_point(enum_value:PacketWrapper.PacketType.ROOM_SETTINGS)
+        ROOM_SETTINGS = 6,
+        // @@protoc_insertion_point(enum_value:PacketWrapper.PacketType.JOIN_REJECTED)
+        JOIN_REJECTED = 7,
+        // @@protoc_insertion_point(enum_value:PacketWrapper.PacketType.CONTROL)
+        CONTROL = 8,
+        // @@protoc_insertion_point(enum_value:PacketWrapper.PacketType.LOBBY)
+        LOBBY = 9,
+        // @@protoc_insertion_point(enum_value:PacketWrapper.PacketType.CHAT)
+        CHAT = 10,
+        // @@protoc_insertion_point(enum_value:PacketWrapper.PacketType.RESUMPTION)
+        RESUMPTION = 11,
     }
 
     impl ::protobuf::Enum for PacketType {
@@ -211,6 +227,14 @@ pub mod packet_wrapper {
                 1 => ::std::option::Option::Some(PacketType::AES_KEY),
                 2 => ::std::option::Option::Some(PacketType::MEDIA),
                 3 => ::std::option::Option::Some(PacketType::CONNECTION),
+                4 => ::std::option::Option::Some(PacketType::PARTICIPANT_JOINED),
+                5 => ::std::option::Option::Some(PacketType::PARTICIPANT_LEFT),
+                6 => ::std::option::Option::Some(PacketType::ROOM_SETTINGS),
+                7 => ::std::option::Option::Some(PacketType::JOIN_REJECTED),
+                8 => ::std::option::Option::Some(PacketType::CONTROL),
+                9 => ::std::option::Option::Some(PacketType::LOBBY),
+                10 => ::std::option::Option::Some(PacketType::CHAT),
+                11 => ::std::option::Option::Some(PacketType::RESUMPTION),
                 _ => ::std::option::Option::None
             }
         }
@@ -221,6 +245,14 @@ pub mod packet_wrapper {
                 "AES_KEY" => ::std::option::Option::Some(PacketType::AES_KEY),
                 "MEDIA" => ::std::option::Option::Some(PacketType::MEDIA),
                 "CONNECTION" => ::std::option::Option::Some(PacketType::CONNECTION),
+                "PARTICIPANT_JOINED" => ::std::option::Option::Some(PacketType::PARTICIPANT_JOINED),
+                "PARTICIPANT_LEFT" => ::std::option::Option::Some(PacketType::PARTICIPANT_LEFT),
+                "ROOM_SETTINGS" => ::std::option::Option::Some(PacketType::ROOM_SETTINGS),
+                "JOIN_REJECTED" => ::std::option::Option::Some(PacketType::JOIN_REJECTED),
+                "CONTROL" => ::std::option::Option::Some(PacketType::CONTROL),
+                "LOBBY" => ::std::option::Option::Some(PacketType::LOBBY),
+                "CHAT" => ::std::option::Option::Some(PacketType::CHAT),
+                "RESUMPTION" => ::std::option::Option::Some(PacketType::RESUMPTION),
                 _ => ::std::option::Option::None
             }
         }
@@ -230,6 +262,14 @@ pub mod packet_wrapper {
             PacketType::AES_KEY,
             PacketType::MEDIA,
             PacketType::CONNECTION,
+            PacketType::PARTICIPANT_JOINED,
+            PacketType::PARTICIPANT_LEFT,
+            PacketType::ROOM_SETTINGS,
+            PacketType::JOIN_REJECTED,
+            PacketType::CONTROL,
+            PacketType::LOBBY,
+            PacketType::CHAT,
+            PacketType::RESUMPTION,
         ];
     }
 
@@ -259,32 +299,16 @@ pub mod packet_wrapper {
 }
 
 static file_descriptor_proto_data: &'static [u8] = b"\
-    \n\x1atypes/packet_wrapper.proto\"\xbc\x01\n\rPacketWrapper\x12:\n\x0bpa\
+    \n\x1atypes/packet_wrapper.proto\"\xc3\x02\n\rPacketWrapper\x12:\n\x0bpa\
     cket_type\x18\x01\x20\x01(\x0e2\x19.PacketWrapper.PacketTypeR\npacketTyp\
     e\x12\x14\n\x05email\x18\x02\x20\x01(\tR\x05email\x12\x12\n\x04data\x18\
-    \x03\x20\x01(\x0cR\x04data\"E\n\nPacketType\x12\x0f\n\x0bRSA_PUB_KEY\x10\
-    \0\x12\x0b\n\x07AES_KEY\x10\x01\x12\t\n\x05MEDIA\x10\x02\x12\x0e\n\nCONN\
-    ECTION\x10\x03J\xa7\x03\n\x06\x12\x04\0\0\x0c\x01\n\x08\n\x01\x0c\x12\
-    \x03\0\0\x12\n\n\n\x02\x04\0\x12\x04\x02\0\x0c\x01\n\n\n\x03\x04\0\x01\
-    \x12\x03\x02\x08\x15\n\x0c\n\x04\x04\0\x04\0\x12\x04\x03\x02\x08\x03\n\
-    \x0c\n\x05\x04\0\x04\0\x01\x12\x03\x03\x07\x11\n\r\n\x06\x04\0\x04\0\x02\
-    \0\x12\x03\x04\x04\x14\n\x0e\n\x07\x04\0\x04\0\x02\0\x01\x12\x03\x04\x04\
-    \x0f\n\x0e\n\x07\x04\0\x04\0\x02\0\x02\x12\x03\x04\x12\x13\n\r\n\x06\x04\
-    \0\x04\0\x02\x01\x12\x03\x05\x04\x10\n\x0e\n\x07\x04\0\x04\0\x02\x01\x01\
-    \x12\x03\x05\x04\x0b\n\x0e\n\x07\x04\0\x04\0\x02\x01\x02\x12\x03\x05\x0e\
-    \x0f\n\r\n\x06\x04\0\x04\0\x02\x02\x12\x03\x06\x04\x0e\n\x0e\n\x07\x04\0\
-    \x04\0\x02\x02\x01\x12\x03\x06\x04\t\n\x0e\n\x07\x04\0\x04\0\x02\x02\x02\
-    \x12\x03\x06\x0c\r\n\r\n\x06\x04\0\x04\0\x02\x03\x12\x03\x07\x04\x13\n\
-    \x0e\n\x07\x04\0\x04\0\x02\x03\x01\x12\x03\x07\x04\x0e\n\x0e\n\x07\x04\0\
-    \x04\0\x02\x03\x02\x12\x03\x07\x11\x12\n\x0b\n\x04\x04\0\x02\0\x12\x03\t\
-    \x02\x1d\n\x0c\n\x05\x04\0\x02\0\x06\x12\x03\t\x02\x0c\n\x0c\n\x05\x04\0\
-    \x02\0\x01\x12\x03\t\r\x18\n\x0c\n\x05\x04\0\x02\0\x03\x12\x03\t\x1b\x1c\
-    \n\x0b\n\x04\x04\0\x02\x01\x12\x03\n\x02\x13\n\x0c\n\x05\x04\0\x02\x01\
-    \x05\x12\x03\n\x02\x08\n\x0c\n\x05\x04\0\x02\x01\x01\x12\x03\n\t\x0e\n\
-    \x0c\n\x05\x04\0\x02\x01\x03\x12\x03\n\x11\x12\n\x0b\n\x04\x04\0\x02\x02\
-    \x12\x03\x0b\x02\x11\n\x0c\n\x05\x04\0\x02\x02\x05\x12\x03\x0b\x02\x07\n\
-    \x0c\n\x05\x04\0\x02\x02\x01\x12\x03\x0b\x08\x0c\n\x0c\n\x05\x04\0\x02\
-    \x02\x03\x12\x03\x0b\x0f\x10b\x06proto3\
+    \x03\x20\x01(\x0cR\x04data\"\xcb\x01\n\nPacketType\x12\x0f\n\x0bRSA_PUB_\
+    KEY\x10\0\x12\x0b\n\x07AES_KEY\x10\x01\x12\t\n\x05MEDIA\x10\x02\x12\x0e\
+    \n\nCONNECTION\x10\x03\x12\x16\n\x12PARTICIPANT_JOINED\x10\x04\x12\x14\n\
+    \x10PARTICIPANT_LEFT\x10\x05\x12\x11\n\rROOM_SETTINGS\x10\x06\x12\x11\n\
+    \rJOIN_REJECTED\x10\x07\x12\x0b\n\x07CONTROL\x10\x08\x12\t\n\x05LOBBY\
+    \x10\t\x12\x08\n\x04CHAT\x10\n\x12\x0e\n\nRESUMPTION\x10\x0bb\x06pro\
+    to3\
 ";
 
 /// `FileDescriptorProto` object which was a source for this generated file
@@ -0,0 +1,239 @@
+// This file is generated by rust-protobuf 3.3.0. Do not edit
+// .proto file is parsed by protoc --rust-out=...
+// @generated
+
+// https://github.com/rust-lang/rust-clippy/issues/702
+#![allow(unknown_lints)]
+#![allow(clippy::all)]
+
+#![allow(unused_attributes)]
+#![cfg_attr(rustfmt, rustfmt::skip)]
+
+#![allow(box_pointers)]
+#![allow(dead_code)]
+#![allow(missing_docs)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(trivial_casts)]
+#![allow(unused_results)]
+#![allow(unused_mut)]
+
+//! Generated file from `types/room_settings.proto`
+
+/// Generated files are compatible only with the same version
+/// of protobuf runtime.
+const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_3_3_0;
+
+// @@protoc_insertion_point(message:RoomSettingsPacket)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct RoomSettingsPacket {
+    // message fields
+    // @@protoc_insertion_point(field:RoomSettingsPacket.e2ee_required)
+    pub e2ee_required: bool,
+    // @@protoc_insertion_point(field:RoomSettingsPacket.max_participants)
+    pub max_participants: u32,
+    // @@protoc_insertion_point(field:RoomSettingsPacket.publishers)
+    pub publishers: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:RoomSettingsPacket.recording_allowed)
+    pub recording_allowed: bool,
+    // special fields
+    // @@protoc_insertion_point(special_field:RoomSettingsPacket.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a RoomSettingsPacket {
+    fn default() -> &'a RoomSettingsPacket {
+        <RoomSettingsPacket as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl RoomSettingsPacket {
+    pub fn new() -> RoomSettingsPacket {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "e2ee_required",
+            |m: &RoomSettingsPacket| { &m.e2ee_required },
+            |m: &mut RoomSettingsPacket| { &mut m.e2ee_required },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "max_participants",
+            |m: &RoomSettingsPacket| { &m.max_participants },
+            |m: &mut RoomSettingsPacket| { &mut m.max_participants },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "publishers",
+            |m: &RoomSettingsPacket| { &m.publishers },
+            |m: &mut RoomSettingsPacket| { &mut m.publishers },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "recording_allowed",
+            |m: &RoomSettingsPacket| { &m.recording_allowed },
+            |m: &mut RoomSettingsPacket| { &mut m.recording_allowed },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<RoomSettingsPacket>(
+            "RoomSettingsPacket",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for RoomSettingsPacket {
+    const NAME: &'static str = "RoomSettingsPacket";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.e2ee_required = is.read_bool()?;
+                },
+                16 => {
+                    self.max_participants = is.read_uint32()?;
+                },
+                26 => {
+                    self.publishers.push(is.read_string()?);
+                },
+                32 => {
+                    self.recording_allowed = is.read_bool()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.e2ee_required != false {
+            my_size += 1 + 1;
+        }
+        if self.max_participants != 0 {
+            my_size += ::protobuf::rt::uint32_size(2, self.max_participants);
+        }
+        for value in &self.publishers {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        if self.recording_allowed != false {
+            my_size += 1 + 1;
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.e2ee_required != false {
+            os.write_bool(1, self.e2ee_required)?;
+        }
+        if self.max_participants != 0 {
+            os.write_uint32(2, self.max_participants)?;
+        }
+        for v in &self.publishers {
+            os.write_string(3, &v)?;
+        };
+        if self.recording_allowed != false {
+            os.write_bool(4, self.recording_allowed)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> RoomSettingsPacket {
+        RoomSettingsPacket::new()
+    }
+
+    fn clear(&mut self) {
+        self.e2ee_required = false;
+        self.max_participants = 0;
+        self.publishers.clear();
+        self.recording_allowed = false;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static RoomSettingsPacket {
+        static instance: RoomSettingsPacket = RoomSettingsPacket {
+            e2ee_required: false,
+            max_participants: 0,
+            publishers: ::std::vec::Vec::new(),
+            recording_allowed: false,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for RoomSettingsPacket {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("RoomSettingsPacket").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for RoomSettingsPacket {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for RoomSettingsPacket {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+static file_descriptor_proto_data: &'static [u8] = b"\
+    \n\x19types/room_settings.proto\"\xb1\x01\n\x12RoomSettingsPacket\x12#\n\
+    \re2ee_required\x18\x01\x20\x01(\x08R\x0ce2eeRequired\x12)\n\x10max_part\
+    icipants\x18\x02\x20\x01(\rR\x0fmaxParticipants\x12\x1e\n\npublishers\
+    \x18\x03\x20\x03(\tR\npublishers\x12+\n\x11recording_allowed\x18\x04\x20\
+    \x01(\x08R\x10recordingAllowedb\x06proto3\
+";
+
+/// `FileDescriptorProto` object which was a source for this generated file
+fn file_descriptor_proto() -> &'static ::protobuf::descriptor::FileDescriptorProto {
+    static file_descriptor_proto_lazy: ::protobuf::rt::Lazy<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::rt::Lazy::new();
+    file_descriptor_proto_lazy.get(|| {
+        ::protobuf::Message::parse_from_bytes(file_descriptor_proto_data).unwrap()
+    })
+}
+
+/// `FileDescriptor` object which allows dynamic access to files
+pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
+    static generated_file_descriptor_lazy: ::protobuf::rt::Lazy<::protobuf::reflect::GeneratedFileDescriptor> = ::protobuf::rt::Lazy::new();
+    static file_descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::FileDescriptor> = ::protobuf::rt::Lazy::new();
+    file_descriptor.get(|| {
+        let generated_file_descriptor = generated_file_descriptor_lazy.get(|| {
+            let mut deps = ::std::vec::Vec::with_capacity(0);
+            let mut messages = ::std::vec::Vec::with_capacity(1);
+            messages.push(RoomSettingsPacket::generated_message_descriptor_data());
+            let mut enums = ::std::vec::Vec::with_capacity(0);
+            ::protobuf::reflect::GeneratedFileDescriptor::new_generated(
+                file_descriptor_proto(),
+                deps,
+                messages,
+                enums,
+            )
+        });
+        ::protobuf::reflect::FileDescriptor::new_generated_2(generated_file_descriptor)
+    })
+}
@@ -1,3 +1,6 @@
 mod video_call_client;
 
-pub use video_call_client::{VideoCallClient, VideoCallClientOptions};
+pub use video_call_client::{
+    ChatMessage, HostCommand, JoinRejection, LobbyStatus, VideoCallClient,
+    VideoCallClientOptions,
+};
@@ -8,21 +8,147 @@ use protobuf::Message;
 use rsa::pkcs8::{DecodePublicKey, EncodePublicKey};
 use rsa::RsaPublicKey;
 use web_sys::MediaStream;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::{Rc, Weak};
 use types::protos::aes_packet::AesPacket;
+use types::protos::chat_packet::ChatPacket;
+use types::protos::control_packet::control_packet::Command;
+use types::protos::control_packet::ControlPacket;
+use types::protos::join_rejected::join_rejected_packet::Reason;
+use types::protos::join_rejected::JoinRejectedPacket;
+use types::protos::lobby_packet::lobby_packet::Status;
+use types::protos::lobby_packet::LobbyPacket;
 use types::protos::media_packet::media_packet::MediaType;
 use types::protos::packet_wrapper::packet_wrapper::PacketType;
 use types::protos::packet_wrapper::PacketWrapper;
+use types::protos::room_settings::RoomSettingsPacket;
 use types::protos::rsa_packet::RsaPacket;
+use js_sys::Reflect;
 use wasm_bindgen::JsValue;
 use yew::prelude::Callback;
 
+/// WebTransport close codes the server uses when it does not admit the client.
+const ROOM_FULL_CLOSE_CODE: u32 = 0x6;
+const SERVER_FULL_CLOSE_CODE: u32 = 0x7;
+const REMOVED_CLOSE_CODE: u32 = 0x8;
+const BANNED_CLOSE_CODE: u32 = 0x9;
+const DENIED_CLOSE_CODE: u32 = 0xA;
+const PASSCODE_REQUIRED_CLOSE_CODE: u32 = 0xB;
+const WRONG_PASSCODE_CLOSE_CODE: u32 = 0xC;
+const INVALID_INVITE_CLOSE_CODE: u32 = 0xD;
+
+/// Why the server refused to let the client into the room.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinRejection {
+    /// The room already holds its maximum of participants.
+    RoomFull,
+    /// The server already holds its maximum of sessions.
+    ServerFull,
+    /// The host removed this participant from the meeting a short while ago.
+    Banned,
+    /// The host did not let this participant in from the lobby.
+    Denied,
+    /// The meeting has a passcode and none was given.
+    PasscodeRequired,
+    /// The given passcode is wrong.
+    WrongPasscode,
+    /// The invite is invalid, expired or used up.
+    InvalidInvite,
+}
+
+impl JoinRejection {
+    fn from_packet(packet: &JoinRejectedPacket) -> Option<Self> {
+        match packet.reason.enum_value() {
+            Ok(Reason::ROOM_FULL) => Some(JoinRejection::RoomFull),
+            Ok(Reason::SERVER_FULL) => Some(JoinRejection::ServerFull),
+            Ok(Reason::BANNED) => Some(JoinRejection::Banned),
+            Ok(Reason::PASSCODE_REQUIRED) => Some(JoinRejection::PasscodeRequired),
+            Ok(Reason::WRONG_PASSCODE) => Some(JoinRejection::WrongPasscode),
+            Ok(Reason::INVALID_INVITE) => Some(JoinRejection::InvalidInvite),
+            _ => None,
+        }
+    }
+
+    fn from_close_info(info: &JsValue) -> Option<Self> {
+        match close_code(info)? {
+            ROOM_FULL_CLOSE_CODE => Some(JoinRejection::RoomFull),
+            SERVER_FULL_CLOSE_CODE => Some(JoinRejection::ServerFull),
+            BANNED_CLOSE_CODE => Some(JoinRejection::Banned),
+            DENIED_CLOSE_CODE => Some(JoinRejection::Denied),
+            PASSCODE_REQUIRED_CLOSE_CODE => Some(JoinRejection::PasscodeRequired),
+            WRONG_PASSCODE_CLOSE_CODE => Some(JoinRejection::WrongPasscode),
+            INVALID_INVITE_CLOSE_CODE => Some(JoinRejection::InvalidInvite),
+            _ => None,
+        }
+    }
+}
+
+/// Appends `name=value` to the query string of `url`.
+fn with_query_param(url: &str, name: &str, value: &str) -> String {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{url}{separator}{name}={value}")
+}
+
+/// Reads the `closeCode` of the `WebTransportCloseInfo` a closed session reports.
+fn close_code(info: &JsValue) -> Option<u32> {
+    let code = Reflect::get(info, &JsValue::from_str("closeCode"))
+        .ok()?
+        .as_f64()?;
+    Some(code as u32)
+}
+
+/// A request of the meeting's host that concerns this client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostCommand {
+    /// The host asks this participant to turn off their microphone.
+    Mute,
+    /// The host asks this participant to turn off their camera.
+    StopVideo,
+    /// The host removed this participant; the server closes the connection.
+    Removed,
+}
+
+/// Where the client is in the waiting room of a meeting that has one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LobbyStatus {
+    /// The client waits for the host to let it in.
+    Waiting,
+    /// The host let the client in, which then joins the room.
+    Admitted,
+}
+
+/// A chat message of the room, as assigned an id and timestamp by the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub id: String,
+    pub sender: String,
+    pub text: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: i64,
+    /// Id of the message this one replies to.
+    pub reply_to: Option<String>,
+}
+
+impl From<ChatPacket> for ChatMessage {
+    fn from(chat: ChatPacket) -> Self {
+        ChatMessage {
+            id: chat.id,
+            sender: chat.sender,
+            text: chat.text,
+            timestamp_ms: chat.timestamp_ms,
+            reply_to: Some(chat.reply_to).filter(|id| !id.is_empty()),
+        }
+    }
+}
+
 /// Options struct for constructing a client via [VideoCallClient::new(options)][VideoCallClient::new]
 #[derive(Clone, Debug, PartialEq)]
 pub struct VideoCallClientOptions {
-    /// `true` to use end-to-end encription; `false` to send data unencrypted
+    /// `true` to use end-to-end encription; `false` to send data unencrypted.
+    ///
+    /// This is only the mode used until the server sends the room settings, which decide it for
+    /// everyone in the room.
     pub enable_e2ee: bool,
 
     /// `true` to use webtransport, `false` to use websocket
@@ -31,6 +157,10 @@ pub struct VideoCallClientOptions {
     /// Callback will be called as `callback(peer_userid)` when a new peer is added
     pub on_peer_added: Callback<String>,
 
+    /// Callback will be called as `callback(peer_userid)` when a peer leaves the call or is
+    /// dropped for inactivity
+    pub on_peer_removed: Callback<String>,
+
     /// Callback will be called as `callback(peer_userid, media_type)` immediately after the first frame of a given peer & media type is decoded
     pub on_peer_first_frame: Callback<(String, MediaType)>,
 
@@ -57,6 +187,41 @@ pub struct VideoCallClientOptions {
 
     /// Callback will be called as `callback(())` if a connection gets dropped
     pub on_connection_lost: Callback<JsValue>,
+
+    /// Callback will be called as `callback(reason)` if the server refuses to let the client into
+    /// the room, in place of [`on_connection_lost`](Self::on_connection_lost)
+    pub on_join_rejected: Callback<JoinRejection>,
+
+    /// Callback will be called as `callback(settings)` after the server sent the room settings
+    /// and the client applied them
+    pub on_room_settings: Callback<RoomSettingsPacket>,
+
+    /// Callback will be called as `callback(command)` when the meeting's host mutes, stops the
+    /// video of or removes this client. After [`HostCommand::Removed`],
+    /// [`on_connection_lost`](Self::on_connection_lost) is not called.
+    pub on_host_command: Callback<HostCommand>,
+
+    /// Callback will be called as `callback(host_userid)` when the client learns who hosts the
+    /// meeting
+    pub on_host_changed: Callback<String>,
+
+    /// Callback will be called as `callback(status)` when the client waits in the lobby and when
+    /// the host lets it in. If the host denies it, [`on_join_rejected`](Self::on_join_rejected)
+    /// is called with [`JoinRejection::Denied`] instead.
+    pub on_lobby_status: Callback<LobbyStatus>,
+
+    /// Callback will be called as `callback(waiting_userids)` while this client is the host, when
+    /// someone starts or stops waiting in the lobby
+    pub on_waiting_changed: Callback<Vec<String>>,
+
+    /// Callback will be called as `callback(message)` for every chat message of the room: the
+    /// recent history the server replays after joining, messages of peers, and this client's own
+    /// messages once the server accepted them
+    pub on_chat_message: Callback<ChatMessage>,
+
+    /// Callback will be called as `callback(recording)` when the server starts or stops
+    /// recording the room
+    pub on_recording_changed: Callback<bool>,
 }
 
 #[derive(Debug)]
@@ -64,6 +229,14 @@ struct InnerOptions {
     enable_e2ee: bool,
     userid: String,
     on_peer_added: Callback<String>,
+    on_room_settings: Callback<RoomSettingsPacket>,
+    on_join_rejected: Callback<JoinRejection>,
+    on_host_command: Callback<HostCommand>,
+    on_host_changed: Callback<String>,
+    on_lobby_status: Callback<LobbyStatus>,
+    on_waiting_changed: Callback<Vec<String>>,
+    on_chat_message: Callback<ChatMessage>,
+    on_recording_changed: Callback<bool>,
 }
 
 #[derive(Debug)]
@@ -73,6 +246,16 @@ struct Inner {
     aes: Rc<Aes128State>,
     rsa: Rc<RsaWrapper>,
     peer_decode_manager: PeerDecodeManager,
+    room_settings: Option<RoomSettingsPacket>,
+    rejection: Rc<Cell<Option<JoinRejection>>>,
+    removed: Rc<Cell<bool>>,
+    host: Option<String>,
+    in_lobby: bool,
+    waiting: Vec<String>,
+    chat: Vec<ChatMessage>,
+    recording: bool,
+    /// Token the server handed out to resume this client's session after a dropped connection.
+    resume_token: Option<String>,
 }
 
 /// The client struct for a video call connection.
@@ -106,11 +289,28 @@ impl VideoCallClient {
                 enable_e2ee: options.enable_e2ee,
                 userid: options.userid.clone(),
                 on_peer_added: options.on_peer_added.clone(),
+                on_room_settings: options.on_room_settings.clone(),
+                on_join_rejected: options.on_join_rejected.clone(),
+                on_host_command: options.on_host_command.clone(),
+                on_host_changed: options.on_host_changed.clone(),
+                on_lobby_status: options.on_lobby_status.clone(),
+                on_waiting_changed: options.on_waiting_changed.clone(),
+                on_chat_message: options.on_chat_message.clone(),
+                on_recording_changed: options.on_recording_changed.clone(),
             },
             connection: None,
             aes: aes.clone(),
             rsa: Rc::new(RsaWrapper::new(options.enable_e2ee)),
             peer_decode_manager: Self::create_peer_decoder_manager(&options),
+            room_settings: None,
+            rejection: Rc::new(Cell::new(None)),
+            removed: Rc::new(Cell::new(false)),
+            host: None,
+            in_lobby: false,
+            waiting: Vec::new(),
+            chat: Vec::new(),
+            recording: false,
+            resume_token: None,
         }));
         Self {
             options,
@@ -130,14 +330,23 @@ impl VideoCallClient {
     /// connection.  The connection cannot actually be considered to have been succesful until the
     /// [`options.on_connected`](VideoCallClientOptions::on_connected) callback has been invoked.
     ///
+    /// When reconnecting over WebSocket, the client asks the server to resume its previous
+    /// session, so that peers do not see it leave and join again.
+    ///
     /// If the connection does not succeed, the
     /// [`options.on_connection_lost`](VideoCallClientOptions::on_connection_lost) callback will be
-    /// invoked.
+    /// invoked, or [`options.on_join_rejected`](VideoCallClientOptions::on_join_rejected) if the
+    /// server refused to admit the client.
     ///
     pub fn connect(&mut self) -> anyhow::Result<()> {
+        let resume_token = self.inner.try_borrow()?.resume_token.clone();
+        let websocket_url = match resume_token {
+            Some(token) => with_query_param(&self.options.websocket_url, "resume", &token),
+            None => self.options.websocket_url.clone(),
+        };
         let options = ConnectOptions {
             userid: self.options.userid.clone(),
-            websocket_url: self.options.websocket_url.clone(),
+            websocket_url,
             webtransport_url: self.options.webtransport_url.clone(),
             on_inbound_media: {
                 let inner = Rc::downgrade(&self.inner);
@@ -170,7 +379,32 @@ impl VideoCallClient {
                     callback.emit(());
                 })
             },
-            on_connection_lost: self.options.on_connection_lost.clone(),
+            on_connection_lost: {
+                let (rejection, removed) = {
+                    let inner = self.inner.try_borrow()?;
+                    (inner.rejection.clone(), inner.removed.clone())
+                };
+                let on_join_rejected = self.options.on_join_rejected.clone();
+                let on_host_command = self.options.on_host_command.clone();
+                let callback = self.options.on_connection_lost.clone();
+                Callback::from(move |reason: JsValue| {
+                    if rejection.get().is_some() || removed.get() {
+                        return;
+                    }
+                    if close_code(&reason) == Some(REMOVED_CLOSE_CODE) {
+                        removed.set(true);
+                        on_host_command.emit(HostCommand::Removed);
+                        return;
+                    }
+                    match JoinRejection::from_close_info(&reason) {
+                        Some(rejected) => {
+                            rejection.set(Some(rejected));
+                            on_join_rejected.emit(rejected);
+                        }
+                        None => callback.emit(reason),
+                    }
+                })
+            },
             peer_monitor: {
                 let inner = Rc::downgrade(&self.inner);
                 let on_connection_lost = self.options.on_connection_lost.clone();
@@ -200,6 +434,10 @@ impl VideoCallClient {
         );
 
         let mut borrowed = self.inner.try_borrow_mut()?;
+        borrowed.rejection.set(None);
+        borrowed.removed.set(false);
+        borrowed.in_lobby = false;
+        borrowed.waiting.clear();
         borrowed.connection.replace(Connection::connect(
             self.options.enable_webtransport,
             options,
@@ -214,12 +452,22 @@ impl VideoCallClient {
         peer_decode_manager.on_first_frame = opts.on_peer_first_frame.clone();
         peer_decode_manager.get_video_canvas_id = opts.get_peer_video_canvas_id.clone();
         peer_decode_manager.get_screen_canvas_id = opts.get_peer_screen_canvas_id.clone();
+        peer_decode_manager.on_peer_removed = opts.on_peer_removed.clone();
         peer_decode_manager
     }
 
+    /// Sends `media` to the room. Media packets are dropped while the room settings do not allow
+    /// this client to publish and while it waits in the lobby.
     pub fn send_packet(&self, media: PacketWrapper) {
         match self.inner.try_borrow() {
-            Ok(inner) => inner.send_packet(media),
+            Ok(inner) => {
+                if media.packet_type.enum_value() == Ok(PacketType::MEDIA)
+                    && (inner.in_lobby || !inner.can_publish())
+                {
+                    return;
+                }
+                inner.send_packet(media)
+            }
             Err(_) => {
                 error!("Unable to borrow inner -- dropping send packet {:?}", media)
             }
@@ -273,10 +521,155 @@ impl VideoCallClient {
         self.aes.clone()
     }
 
+    /// Returns `true` if media is currently end-to-end encrypted, which the room settings decide
+    /// once they have been received.
+    pub fn e2ee_enabled(&self) -> bool {
+        self.aes.enabled()
+    }
+
+    /// Returns the settings the server sent for the room, or `None` before they arrive.
+    pub fn room_settings(&self) -> Option<RoomSettingsPacket> {
+        match self.inner.try_borrow() {
+            Ok(inner) => inner.room_settings.clone(),
+            Err(_) => None,
+        }
+    }
+
+    /// Returns `true` if the room settings allow this client to publish media.
+    pub fn can_publish(&self) -> bool {
+        match self.inner.try_borrow() {
+            Ok(inner) => inner.can_publish(),
+            Err(_) => true,
+        }
+    }
+
     /// Returns a reference to a copy of [`options.userid`](VideoCallClientOptions::userid)
     pub fn userid(&self) -> &String {
         &self.options.userid
     }
+
+    /// Returns the userid of the meeting's host, or `None` for rooms without a host.
+    pub fn host(&self) -> Option<String> {
+        match self.inner.try_borrow() {
+            Ok(inner) => inner.host.clone(),
+            Err(_) => None,
+        }
+    }
+
+    /// Returns `true` if this client hosts the meeting and may use the host controls.
+    pub fn is_host(&self) -> bool {
+        self.host().as_ref() == Some(&self.options.userid)
+    }
+
+    /// Asks `userid` to turn off their microphone. Only the host may do this.
+    pub fn mute_participant(&self, userid: &str) {
+        self.send_command(Command::MUTE, userid);
+    }
+
+    /// Asks `userid` to turn off their camera. Only the host may do this.
+    pub fn stop_participant_video(&self, userid: &str) {
+        self.send_command(Command::STOP_VIDEO, userid);
+    }
+
+    /// Removes `userid` from the meeting, who cannot rejoin for a while. Only the host may do
+    /// this.
+    pub fn remove_participant(&self, userid: &str) {
+        self.send_command(Command::REMOVE, userid);
+    }
+
+    /// Makes `userid` the host of the meeting. Only the host may do this.
+    pub fn transfer_host(&self, userid: &str) {
+        self.send_command(Command::TRANSFER_HOST, userid);
+    }
+
+    /// Returns `true` while the client waits in the lobby for the host to let it in.
+    pub fn is_in_lobby(&self) -> bool {
+        match self.inner.try_borrow() {
+            Ok(inner) => inner.in_lobby,
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the userids waiting in the lobby, in the order they arrived. Only the host learns
+    /// about them.
+    pub fn waiting_participants(&self) -> Vec<String> {
+        match self.inner.try_borrow() {
+            Ok(inner) => inner.waiting.clone(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Lets `userid` in from the lobby. Only the host may do this.
+    pub fn admit_participant(&self, userid: &str) {
+        self.send_command(Command::ADMIT, userid);
+    }
+
+    /// Turns `userid` away from the lobby. Only the host may do this.
+    pub fn deny_participant(&self, userid: &str) {
+        self.send_command(Command::DENY, userid);
+    }
+
+    /// Returns `true` while the server records the room.
+    pub fn is_recording(&self) -> bool {
+        match self.inner.try_borrow() {
+            Ok(inner) => inner.recording,
+            Err(_) => false,
+        }
+    }
+
+    /// Starts recording the room on the server. Only the host of a meeting that allows
+    /// recording may do this.
+    pub fn start_recording(&self) {
+        self.send_command(Command::START_RECORDING, "");
+    }
+
+    /// Stops recording the room. Only the host may do this.
+    pub fn stop_recording(&self) {
+        self.send_command(Command::STOP_RECORDING, "");
+    }
+
+    /// Returns the chat messages received so far, oldest first.
+    pub fn chat_messages(&self) -> Vec<ChatMessage> {
+        match self.inner.try_borrow() {
+            Ok(inner) => inner.chat.clone(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Sends a chat message to the room, optionally replying to the message with id `reply_to`.
+    pub fn send_chat_message(&self, text: &str, reply_to: Option<&str>) {
+        let chat = ChatPacket {
+            text: text.to_string(),
+            reply_to: reply_to.unwrap_or_default().to_string(),
+            ..Default::default()
+        };
+        match chat.write_to_bytes() {
+            Ok(data) => self.send_packet(PacketWrapper {
+                packet_type: PacketType::CHAT.into(),
+                email: self.options.userid.clone(),
+                data,
+                ..Default::default()
+            }),
+            Err(e) => error!("Failed to serialize chat packet: {}", e.to_string()),
+        }
+    }
+
+    fn send_command(&self, command: Command, target: &str) {
+        let control = ControlPacket {
+            command: command.into(),
+            target: target.to_string(),
+            ..Default::default()
+        };
+        match control.write_to_bytes() {
+            Ok(data) => self.send_packet(PacketWrapper {
+                packet_type: PacketType::CONTROL.into(),
+                email: self.options.userid.clone(),
+                data,
+                ..Default::default()
+            }),
+            Err(e) => error!("Failed to serialize control packet: {}", e.to_string()),
+        }
+    }
 }
 
 impl Inner {
@@ -292,6 +685,62 @@ impl Inner {
             response.packet_type.enum_value(),
             response.email
         );
+        if response.packet_type.enum_value() == Ok(PacketType::ROOM_SETTINGS) {
+            match RoomSettingsPacket::parse_from_bytes(&response.data) {
+                Ok(settings) => self.apply_room_settings(settings),
+                Err(e) => error!("Failed to parse room settings: {}", e.to_string()),
+            }
+            return;
+        }
+        if response.packet_type.enum_value() == Ok(PacketType::RESUMPTION) {
+            match String::from_utf8(response.data) {
+                Ok(token) => self.resume_token = Some(token),
+                Err(e) => error!("Failed to parse resumption token: {}", e.to_string()),
+            }
+            return;
+        }
+        if response.packet_type.enum_value() == Ok(PacketType::JOIN_REJECTED) {
+            let rejected = JoinRejectedPacket::parse_from_bytes(&response.data)
+                .ok()
+                .and_then(|packet| JoinRejection::from_packet(&packet));
+            if let Some(rejected) = rejected {
+                info!("join rejected: {:?}", rejected);
+                self.rejection.set(Some(rejected));
+                self.options.on_join_rejected.emit(rejected);
+            }
+            return;
+        }
+        if response.packet_type.enum_value() == Ok(PacketType::CONTROL) {
+            match ControlPacket::parse_from_bytes(&response.data) {
+                Ok(control) => self.on_control(control),
+                Err(e) => error!("Failed to parse control packet: {}", e.to_string()),
+            }
+            return;
+        }
+        if response.packet_type.enum_value() == Ok(PacketType::LOBBY) {
+            match LobbyPacket::parse_from_bytes(&response.data) {
+                Ok(lobby) => self.on_lobby(response.email, lobby),
+                Err(e) => error!("Failed to parse lobby packet: {}", e.to_string()),
+            }
+            return;
+        }
+        if response.packet_type.enum_value() == Ok(PacketType::CHAT) {
+            match ChatPacket::parse_from_bytes(&response.data) {
+                Ok(chat) => self.on_chat(chat.into()),
+                Err(e) => error!("Failed to parse chat packet: {}", e.to_string()),
+            }
+            return;
+        }
+        if response.packet_type.enum_value() == Ok(PacketType::PARTICIPANT_LEFT) {
+            debug!("peer {} left", response.email);
+            self.peer_decode_manager.delete_peer(&response.email);
+            return;
+        }
+        if response.packet_type.enum_value() == Ok(PacketType::PARTICIPANT_JOINED)
+            && response.email == self.options.userid
+        {
+            return;
+        }
         let peer_status = self.peer_decode_manager.ensure_peer(&response.email);
         match response.packet_type.enum_value() {
             Ok(PacketType::AES_KEY) => {
@@ -358,6 +807,15 @@ impl Inner {
             Ok(PacketType::CONNECTION) => {
                 error!("Not implemented: CONNECTION packet type");
             }
+            Ok(PacketType::PARTICIPANT_JOINED) => {
+                debug!("peer {} joined", response.email);
+            }
+            Ok(PacketType::PARTICIPANT_LEFT)
+            | Ok(PacketType::ROOM_SETTINGS)
+            | Ok(PacketType::JOIN_REJECTED)
+            | Ok(PacketType::CONTROL)
+            | Ok(PacketType::LOBBY)
+            | Ok(PacketType::CHAT) => {}
             Err(_) => {}
         }
         if let PeerStatus::Added(peer_userid) = peer_status {
@@ -367,6 +825,122 @@ impl Inner {
         }
     }
 
+    fn on_control(&mut self, control: ControlPacket) {
+        let command = match control.command.enum_value() {
+            Ok(Command::HOST_CHANGED) => {
+                info!("host is {}", control.target);
+                if control.target != self.options.userid && !self.waiting.is_empty() {
+                    self.waiting.clear();
+                    self.options.on_waiting_changed.emit(Vec::new());
+                }
+                self.host = Some(control.target.clone());
+                self.options.on_host_changed.emit(control.target);
+                return;
+            }
+            Ok(Command::START_RECORDING) | Ok(Command::STOP_RECORDING) => {
+                self.recording = control.command.enum_value() == Ok(Command::START_RECORDING);
+                info!("recording: {}", self.recording);
+                self.options.on_recording_changed.emit(self.recording);
+                return;
+            }
+            _ if control.target != self.options.userid => return,
+            Ok(Command::MUTE) => HostCommand::Mute,
+            Ok(Command::STOP_VIDEO) => HostCommand::StopVideo,
+            Ok(Command::REMOVE) => {
+                self.removed.set(true);
+                HostCommand::Removed
+            }
+            _ => return,
+        };
+        info!("host command: {:?}", command);
+        self.options.on_host_command.emit(command);
+    }
+
+    fn on_lobby(&mut self, email: String, lobby: LobbyPacket) {
+        let Ok(status) = lobby.status.enum_value() else {
+            return;
+        };
+        if email == self.options.userid {
+            match status {
+                Status::WAITING => {
+                    self.in_lobby = true;
+                    self.options.on_lobby_status.emit(LobbyStatus::Waiting);
+                }
+                Status::ADMITTED => {
+                    self.in_lobby = false;
+                    self.options.on_lobby_status.emit(LobbyStatus::Admitted);
+                }
+                Status::DENIED => {
+                    self.rejection.set(Some(JoinRejection::Denied));
+                    self.options.on_join_rejected.emit(JoinRejection::Denied);
+                }
+                Status::LEFT | Status::UNSPECIFIED => {}
+            }
+            return;
+        }
+        let changed = match status {
+            Status::WAITING if !self.waiting.contains(&email) => {
+                self.waiting.push(email);
+                true
+            }
+            Status::ADMITTED | Status::DENIED | Status::LEFT => {
+                let before = self.waiting.len();
+                self.waiting.retain(|waiting| *waiting != email);
+                self.waiting.len() != before
+            }
+            _ => false,
+        };
+        if changed {
+            self.options.on_waiting_changed.emit(self.waiting.clone());
+        }
+    }
+
+    /// Keeps a chat message, ignoring the ones the client already has, e.g. when the server
+    /// replays the history again after a reconnect.
+    fn on_chat(&mut self, message: ChatMessage) {
+        if self.chat.iter().any(|known| known.id == message.id) {
+            return;
+        }
+        self.chat.push(message.clone());
+        self.options.on_chat_message.emit(message);
+    }
+
+    fn can_publish(&self) -> bool {
+        match &self.room_settings {
+            Some(settings) => {
+                settings.publishers.is_empty() || settings.publishers.contains(&self.options.userid)
+            }
+            None => true,
+        }
+    }
+
+    fn apply_room_settings(&mut self, settings: RoomSettingsPacket) {
+        if settings.e2ee_required != self.options.enable_e2ee {
+            self.set_e2ee(settings.e2ee_required);
+        }
+        self.room_settings = Some(settings.clone());
+        info!(
+            "room settings: e2ee required = {}, may publish = {}",
+            settings.e2ee_required,
+            self.can_publish()
+        );
+        self.options.on_room_settings.emit(settings);
+    }
+
+    /// Switches end-to-end encryption on or off for everything this client sends and receives.
+    fn set_e2ee(&mut self, enabled: bool) {
+        self.options.enable_e2ee = enabled;
+        self.aes.set_enabled(enabled);
+        if enabled {
+            if !self.rsa.enabled {
+                self.rsa = Rc::new(RsaWrapper::new(true));
+            }
+            self.send_public_key();
+        } else {
+            self.peer_decode_manager.clear_peer_aes();
+        }
+    }
+
     fn send_public_key(&self) {
         if !self.options.enable_e2ee {
             return;
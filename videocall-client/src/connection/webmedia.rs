@@ -37,3 +37,8 @@ pub(super) trait WebMedia<TASK> {
         }
     }
 }
+
+/// `url` without its query, which carries connect tokens, passcodes and invites, for logging.
+pub(super) fn without_query(url: &str) -> &str {
+    url.split('?').next().unwrap_or(url)
+}
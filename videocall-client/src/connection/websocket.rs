@@ -1,7 +1,7 @@
 //
 // This submodule implements our WebMedia trait for WebSocketTask.
 //
-use super::webmedia::{ConnectOptions, WebMedia};
+use super::webmedia::{without_query, ConnectOptions, WebMedia};
 use log::debug;
 use wasm_bindgen::JsValue;
 use yew::prelude::Callback;
@@ -18,7 +18,10 @@ impl WebMedia<WebSocketTask> for WebSocketTask {
                 .on_connection_lost
                 .emit(JsValue::from_str("WebSocket error")),
         });
-        debug!("WebSocket connecting to {}", &options.websocket_url);
+        debug!(
+            "WebSocket connecting to {}",
+            without_query(&options.websocket_url)
+        );
         let task = WebSocketService::connect(
             &options.websocket_url,
             options.on_inbound_media,
@@ -3,7 +3,7 @@
 // Sets up all the stream handling to support the callbacks on_connected, on_connection_lost, and
 // on_inbound_media
 //
-use super::webmedia::{ConnectOptions, WebMedia};
+use super::webmedia::{without_query, ConnectOptions, WebMedia};
 use js_sys::Boolean;
 use js_sys::JsString;
 use js_sys::Reflect;
@@ -62,7 +62,10 @@ impl WebMedia<WebTransportTask> for WebTransportTask {
                 WebTransportStatus::Error(error) => connection_lost_callback.emit(error),
             })
         };
-        info!("WebTransport connecting to {}", &options.webtransport_url);
+        info!(
+            "WebTransport connecting to {}",
+            without_query(&options.webtransport_url)
+        );
         let task = WebTransportService::connect(
             &options.webtransport_url,
             on_datagram,
@@ -2,57 +2,56 @@ use anyhow::anyhow;
 
 use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 use rand::RngCore;
+use std::cell::Cell;
 
 type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Aes128State {
-    pub enabled: bool,
+    enabled: Cell<bool>,
     pub key: [u8; 16],
     pub iv: [u8; 16],
 }
 
 impl Aes128State {
+    /// The key is random even when disabled, so encryption can be switched on later with
+    /// [set_enabled](Self::set_enabled) when the room requires it.
     pub fn new(enabled: bool) -> Self {
-        if enabled {
-            Self::new_random()
-        } else {
-            Self {
-                enabled,
-                key: [0u8; 16],
-                iv: [0u8; 16],
-            }
-        }
-    }
-
-    fn new_random() -> Self {
         let mut rng = rand::thread_rng();
         let mut key = [0u8; 16];
         let mut iv = [0u8; 16];
         rng.fill_bytes(&mut key);
         rng.fill_bytes(&mut iv);
         Self {
-            enabled: true,
+            enabled: Cell::new(enabled),
             key,
             iv,
         }
     }
 
+    pub fn enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+    }
+
     pub fn from_vecs(key: Vec<u8>, iv: Vec<u8>, enabled: bool) -> Self {
         let mut key_arr = [0u8; 16];
         let mut iv_arr = [0u8; 16];
         key_arr.copy_from_slice(&key);
         iv_arr.copy_from_slice(&iv);
         Self {
-            enabled,
+            enabled: Cell::new(enabled),
             key: key_arr,
             iv: iv_arr,
         }
     }
 
     pub fn encrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
-        if !self.enabled {
+        if !self.enabled() {
             // XXX: Don't make a new copy of data.
             return Ok(data.to_vec());
         }
@@ -62,7 +61,7 @@ impl Aes128State {
     }
 
     pub fn decrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
-        if !self.enabled {
+        if !self.enabled() {
             // XXX: Don't make a new copy of data.
             return Ok(data.to_vec());
         }
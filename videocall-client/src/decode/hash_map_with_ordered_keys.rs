@@ -40,6 +40,10 @@ impl<K: Ord + Hash + Clone, V> HashMapWithOrderedKeys<K, V> {
         self.map.get_mut(k)
     }
 
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.map.values_mut()
+    }
+
     pub fn contains_key<Q>(&self, k: &Q) -> bool
     where
         K: Borrow<Q>,
@@ -79,7 +83,7 @@ impl<K: Ord + Hash + Clone, V> HashMapWithOrderedKeys<K, V> {
         &self.map
     }
 
-    pub fn remove_if<F>(&mut self, predicate: F)
+    pub fn remove_if<F>(&mut self, predicate: F) -> Vec<K>
     where
         F: Fn(&mut V) -> bool,
     {
@@ -97,5 +101,6 @@ impl<K: Ord + Hash + Clone, V> HashMapWithOrderedKeys<K, V> {
             self.map.remove(key);
             self.keys.retain(|k| k != key);
         }
+        keys_to_remove
     }
 }
@@ -116,7 +116,7 @@ impl Peer {
             return Err(PeerDecodeError::IncorrectPacketType);
         }
 
-        let packet = match self.aes {
+        let packet = match &self.aes {
             Some(aes) => {
                 let data = aes
                     .decrypt(&packet.data)
@@ -190,6 +190,7 @@ pub struct PeerDecodeManager {
     pub on_first_frame: Callback<(String, MediaType)>,
     pub get_video_canvas_id: Callback<String, String>,
     pub get_screen_canvas_id: Callback<String, String>,
+    pub on_peer_removed: Callback<String>,
 }
 
 impl PeerDecodeManager {
@@ -199,6 +200,7 @@ impl PeerDecodeManager {
             on_first_frame: Callback::noop(),
             get_video_canvas_id: Callback::from(|key| format!("video-{}", &key)),
             get_screen_canvas_id: Callback::from(|key| format!("screen-{}", &key)),
+            on_peer_removed: Callback::noop(),
         }
     }
 
@@ -221,7 +223,9 @@ impl PeerDecodeManager {
 
     pub fn run_peer_monitor(&mut self) {
         let pred = |peer: &mut Peer| peer.check_heartbeat();
-        self.connected_peers.remove_if(pred);
+        for email in self.connected_peers.remove_if(pred) {
+            self.on_peer_removed.emit(email);
+        }
     }
 
     pub fn decode(&mut self, response: PacketWrapper) -> Result<(), PeerDecodeError> {
@@ -263,7 +267,9 @@ impl PeerDecodeManager {
     }
 
     pub fn delete_peer(&mut self, email: &String) {
-        self.connected_peers.remove(email);
+        if self.connected_peers.remove(email).is_some() {
+            self.on_peer_removed.emit(email.clone());
+        }
     }
 
     pub fn ensure_peer(&mut self, email: &String) -> PeerStatus {
@@ -275,6 +281,13 @@ impl PeerDecodeManager {
         }
     }
 
+    /// Forgets the keys of all peers, used when the room switches end-to-end encryption off.
+    pub fn clear_peer_aes(&mut self) {
+        for peer in self.connected_peers.values_mut() {
+            peer.aes = None;
+        }
+    }
+
     pub fn set_peer_aes(
         &mut self,
         email: &String,
@@ -79,6 +79,9 @@ mod wrappers;
 pub mod utils;
 pub mod errors;
 
-pub use client::{VideoCallClient, VideoCallClientOptions};
+pub use client::{
+    ChatMessage, HostCommand, JoinRejection, LobbyStatus, VideoCallClient,
+    VideoCallClientOptions,
+};
 pub use encode::{CameraEncoder, MicrophoneEncoder, ScreenEncoder};
 pub use media_devices::{MediaDeviceAccess, MediaDeviceList, SelectableDevices, request_permissions};
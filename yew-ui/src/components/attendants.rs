@@ -1,17 +1,19 @@
 use crate::constants::VIDEO_ELEMENT_ID;
+use crate::stores::app_store::AppStore;
 use crate::stores::media_store::{MediaMsg, MediaStore};
 use web_sys::*;
 use yew::prelude::*;
 use yew::{html, Html};
 use yewdux::use_store;
 use crate::components::{Devices, VideoButton};
+use videocall_client::{JoinRejection, VideoCallClient};
 
 #[function_component(AttendantsFunc)]
 pub fn attendats_func() -> Html {
 
+    let (state, _dispatch) = use_store::<AppStore>();
     let (media_state, media_dispatch) = use_store::<MediaStore>();
     let ws_client = media_state.get_client();
-    let user_name = ws_client.userid();
     let peers = ws_client.sorted_peer_keys();
     let rows = || {
         
@@ -41,20 +43,90 @@ pub fn attendats_func() -> Html {
                 </div>
                 <nav class="host">
                     <Devices />
-                    <VideoButton />
-                    <h4 class="floating-name">{(*user_name).clone()}</h4>
+                    {if ws_client.can_publish() {
+                        html! {<VideoButton />}
+                    } else {
+                        html! {}
+                    }}
+                    <h4 class="floating-name">{state.name.clone()}</h4>
 
-                    {if !media_state.is_connected() {
+                    {if let Some(rejection) = media_state.join_rejection() {
+                        html! {<h4>{match rejection {
+                            JoinRejection::RoomFull => "This meeting is full",
+                            JoinRejection::ServerFull => "The server is full, try again later",
+                            JoinRejection::Banned => "You were removed from this meeting, try again later",
+                            JoinRejection::Denied => "The host did not let you in",
+                            JoinRejection::PasscodeRequired | JoinRejection::WrongPasscode => "This meeting needs a passcode",
+                            JoinRejection::InvalidInvite => "This invite link is invalid or has expired",
+                        }}</h4>}
+                    } else if media_state.is_removed() {
+                        html! {<h4>{"You were removed from the meeting"}</h4>}
+                    } else if media_state.is_waiting() {
+                        html! {<h4>{"Waiting for the host to let you in"}</h4>}
+                    } else if !media_state.is_connected() {
                         html! {<h4>{"Connecting"}</h4>}
                     } else {
                         html! {<h4>{"Connected"}</h4>}
                     }}
 
-                    // {if ctx.props().e2ee_enabled {
-                    //     html! {<h4>{"End to End Encryption Enabled"}</h4>}
-                    // } else {
-                    //     html! {<h4>{"End to End Encryption Disabled"}</h4>}
-                    // }}
+                    {if ws_client.is_host() {
+                        ws_client.waiting_participants().into_iter().map(|waiting| {
+                            let admit = {
+                                let ws_client = ws_client.clone();
+                                let waiting = waiting.clone();
+                                Callback::from(move |_: MouseEvent| ws_client.admit_participant(&waiting))
+                            };
+                            let deny = {
+                                let ws_client = ws_client.clone();
+                                let waiting = waiting.clone();
+                                Callback::from(move |_: MouseEvent| ws_client.deny_participant(&waiting))
+                            };
+                            html! {
+                                <div class="lobby">
+                                    <h4>{format!("{} is waiting", waiting)}</h4>
+                                    <button onclick={admit}>{"Admit"}</button>
+                                    <button onclick={deny}>{"Deny"}</button>
+                                </div>
+                            }
+                        }).collect::<Html>()
+                    } else {
+                        html! {}
+                    }}
+
+                    {if ws_client.is_recording() {
+                        html! {<h4 class="recording">{"This meeting is being recorded"}</h4>}
+                    } else {
+                        html! {}
+                    }}
+
+                    {if ws_client.is_host()
+                        && ws_client.room_settings().is_some_and(|settings| settings.recording_allowed)
+                    {
+                        let recording = ws_client.is_recording();
+                        let toggle = {
+                            let ws_client = ws_client.clone();
+                            Callback::from(move |_: MouseEvent| {
+                                if recording {
+                                    ws_client.stop_recording();
+                                } else {
+                                    ws_client.start_recording();
+                                }
+                            })
+                        };
+                        html! {
+                            <button onclick={toggle}>
+                                {if recording { "Stop recording" } else { "Start recording" }}
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }}
+
+                    {if ws_client.e2ee_enabled() {
+                        html! {<h4>{"End to End Encryption Enabled"}</h4>}
+                    } else {
+                        html! {<h4>{"End to End Encryption Disabled"}</h4>}
+                    }}
                 </nav>
             </div>
     }
@@ -71,6 +143,7 @@ pub fn video_component(props: &ItemPorps) -> Html {
     let key = &props.key_id;
     
     let (media_state, _media_dispatch) = use_store::<MediaStore>();
+    let ws_client = media_state.get_client().clone();
     let video_ref = use_node_ref();
     let screen_ref = use_node_ref();
     use_effect({
@@ -95,6 +168,25 @@ pub fn video_component(props: &ItemPorps) -> Html {
     html! {
         <div class="bg-gray-700 shadow-2xl rounded-xl p-4 flex flex-col items-center">
             <p>{ key.clone() }</p>
+            {
+                if ws_client.is_host() {
+                    let command = |send: fn(&VideoCallClient, &str)| {
+                        let ws_client = ws_client.clone();
+                        let key = key.clone();
+                        Callback::from(move |_: MouseEvent| send(&ws_client, &key))
+                    };
+                    html! {
+                        <div class="host-controls">
+                            <button onclick={command(VideoCallClient::mute_participant)}>{"Mute"}</button>
+                            <button onclick={command(VideoCallClient::stop_participant_video)}>{"Stop video"}</button>
+                            <button onclick={command(VideoCallClient::remove_participant)}>{"Remove"}</button>
+                            <button onclick={command(VideoCallClient::transfer_host)}>{"Make host"}</button>
+                        </div>
+                    }
+                } else {
+                    html!(<></>)
+                }
+            }
             <video class="rounded-lg w-32 h-32 mb-2" ref={video_ref} autoplay=true />
             {
                 if media_state.is_screen_share() {
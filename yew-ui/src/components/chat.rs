@@ -0,0 +1,102 @@
+use crate::stores::media_store::MediaStore;
+use wasm_bindgen::JsValue;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yew::{html, Html};
+use yewdux::use_store;
+use videocall_client::ChatMessage;
+
+/// `HH:MM` of a chat timestamp in the local time zone.
+fn format_time(timestamp_ms: i64) -> String {
+    let date = js_sys::Date::new(&JsValue::from_f64(timestamp_ms as f64));
+    format!("{:02}:{:02}", date.get_hours(), date.get_minutes())
+}
+
+fn quote(messages: &[ChatMessage], reply_to: &str) -> Html {
+    match messages.iter().find(|message| message.id == reply_to) {
+        Some(original) => html! {
+            <p class="text-xs text-gray-400 border-l-2 border-gray-500 pl-2 truncate">
+                { format!("{}: {}", original.sender, original.text) }
+            </p>
+        },
+        None => html! {
+            <p class="text-xs text-gray-400 border-l-2 border-gray-500 pl-2">{ "Earlier message" }</p>
+        },
+    }
+}
+
+#[function_component(ChatPanel)]
+pub fn chat_panel() -> Html {
+    let (media_state, _) = use_store::<MediaStore>();
+    let client = media_state.get_client();
+    let messages = client.chat_messages();
+    let input_ref = use_node_ref();
+    let reply_to = use_state(|| None::<ChatMessage>);
+
+    let onsubmit = {
+        let client = client.clone();
+        let input_ref = input_ref.clone();
+        let reply_to = reply_to.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let input = input_ref.cast::<HtmlInputElement>().unwrap();
+            let text = input.value();
+            if text.trim().is_empty() {
+                return;
+            }
+            client.send_chat_message(&text, reply_to.as_ref().map(|message| message.id.as_str()));
+            input.set_value("");
+            reply_to.set(None);
+        })
+    };
+
+    let cancel_reply = {
+        let reply_to = reply_to.clone();
+        Callback::from(move |_| reply_to.set(None))
+    };
+
+    let rows = messages.iter().map(|message| {
+        let onclick = {
+            let reply_to = reply_to.clone();
+            let message = message.clone();
+            Callback::from(move |_| reply_to.set(Some(message.clone())))
+        };
+        html! {
+            <li key={ message.id.clone() } class="mb-2">
+                if let Some(id) = &message.reply_to {
+                    { quote(&messages, id) }
+                }
+                <p class="text-xs text-gray-400">
+                    <b>{ &message.sender }</b>{ " " }{ format_time(message.timestamp_ms) }
+                    <button class="ml-2 underline" {onclick}>{ "Reply" }</button>
+                </p>
+                <p class="break-words">{ &message.text }</p>
+            </li>
+        }
+    }).collect::<Html>();
+
+    html! {
+        <div id="chat-container" class="flex flex-col w-80 h-full bg-gray-800 text-white p-2">
+            <p><b>{ "Chat" }</b></p>
+            <ul class="flex-1 overflow-y-auto">
+                { rows }
+            </ul>
+            if let Some(message) = &*reply_to {
+                <div class="flex items-center text-xs text-gray-400">
+                    <span class="truncate flex-1">{ format!("Replying to {}", message.sender) }</span>
+                    <button class="ml-2 underline" onclick={cancel_reply}>{ "Cancel" }</button>
+                </div>
+            }
+            <form class="flex" {onsubmit}>
+                <input
+                    ref={input_ref}
+                    class="flex-1 rounded px-2 text-black"
+                    type="text"
+                    placeholder="Send a message"
+                    maxlength="4000"
+                />
+                <button class="ml-2 px-2 rounded bg-blue-600" type="submit">{ "Send" }</button>
+            </form>
+        </div>
+    }
+}
@@ -1,16 +1,19 @@
 use yew::{ html, function_component, Html};
 use yewdux::prelude::use_store;
 use crate::stores::app_store::AppStore;
+use crate::stores::media_store::MediaStore;
 use crate::AttendantsFunc;
+use crate::components::ChatPanel;
 use crate::TopBar;
 use crate::Home;
 
 #[function_component(Middleware)]
 pub fn middleware() -> Html {
     let (state, _dispatch) = use_store::<AppStore>();
+    let (media_state, _media_dispatch) = use_store::<MediaStore>();
 
     html! {
-        {if state.name.is_empty() {
+        {if state.name.is_empty() || media_state.needs_passcode() {
             html! {
                 <Home/>
             }
@@ -18,7 +21,10 @@ pub fn middleware() -> Html {
             html! {
                 <>
                     <TopBar room_id={state.id.clone()}/>
-                    <AttendantsFunc />
+                    <div class="flex">
+                        <AttendantsFunc />
+                        <ChatPanel />
+                    </div>
                 </>
 
             }
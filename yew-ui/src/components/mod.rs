@@ -1,4 +1,5 @@
 pub mod attendants;
+pub mod chat;
 pub mod device_selector;
 pub mod host;
 pub mod icons;
@@ -10,5 +11,6 @@ mod peer_list_item;
 mod buttons;
 pub use buttons::VideoButton;
 pub use attendants::AttendantsFunc;
+pub use chat::ChatPanel;
 pub use device_selector::Devices;
 pub use device_selector::PermissionsDevices;
\ No newline at end of file
@@ -1,45 +1,64 @@
+use videocall_client::JoinRejection;
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 use yew_router::prelude::*;
 use yewdux::prelude::*;
 
 use crate::components::PermissionsDevices;
+use crate::constants::ENABLE_OAUTH;
 use crate::stores::app_store::AppMsg;
 use crate::stores::app_store::AppStore;
 use crate::stores::media_store::MediaMsg;
 use crate::stores::media_store::MediaStore;
-use crate::utils::dom::get_url_pathname;
+use crate::stores::media_store::RoomAccess;
+use crate::utils::api::{create_meeting, fetch_session_email};
+use crate::utils::dom::{get_query_param, get_url_pathname, query_param};
 use crate::Route;
 
 const TEXT_INPUT_CLASSES: &str = "rounded-md mx-2 p-2 text-black required:ring-2 required:ring-red-500 required:valid:ring-2 required:valid:ring-green-500";
 
+/// Reads the meeting id and invite token from an invite link like `https://host/m/{id}?invite=...`.
+fn parse_invite_link(link: &str) -> Option<(String, String)> {
+    let (path, query) = link.trim().split_once('?')?;
+    let meeting_id = path.split("/m/").nth(1)?.trim_end_matches('/');
+    let invite = query_param(query.split('#').next()?, "invite")?;
+    (!meeting_id.is_empty()).then(|| (meeting_id.to_string(), invite))
+}
+
 #[function_component(Home)]
 pub fn home() -> Html {
-    let (_state, dispatch) = use_store::<AppStore>();
-    let (_media_state, media_dispatch) = use_store::<MediaStore>();
+    let (state, dispatch) = use_store::<AppStore>();
+    let (media_state, media_dispatch) = use_store::<MediaStore>();
     let navigator = use_navigator().unwrap();
 
     let username_ref = use_node_ref();
+    let title_ref = use_node_ref();
+    let invite_link_ref = use_node_ref();
+    let passcode_ref = use_node_ref();
+    let invite = use_state(|| get_query_param("invite"));
+    use_effect_with((), {
+        let dispatch = dispatch.clone();
+        move |_| {
+            if *ENABLE_OAUTH {
+                wasm_bindgen_futures::spawn_local(async move {
+                    match fetch_session_email().await {
+                        Ok(email) => dispatch.apply(AppMsg::SetEmail(email)),
+                        Err(e) => log::error!("{}", e),
+                    }
+                });
+            }
+        }
+    });
     let session_id = use_state(|| {
         let url = get_url_pathname();
+        // `None` means a new meeting, which is registered with the server when OAuth is on.
         let session_id = match url {
             Ok(url) => {
-                let id = if url == "/" || url.is_empty() {
-                    let generated_session_id = uuid::Uuid::new_v4().to_string(); 
-                    generated_session_id
+                if url == "/" || url.is_empty() {
+                    None
                 } else {
-                    let id = match url.split("/m/").last() {
-                        Some(session_id) => {
-                            session_id.to_string()
-                        },
-                        _ => {
-                            let generated_session_id = uuid::Uuid::new_v4().to_string(); 
-                            generated_session_id
-                        },
-                    };
-                    id
-                };
-                id
+                    url.split("/m/").last().map(|session_id| session_id.to_string())
+                }
             },
             Err(_) => todo!(),
         };
@@ -48,18 +67,76 @@ pub fn home() -> Html {
 
     let onsubmit = {
         let username_ref = username_ref.clone();
+        let title_ref = title_ref.clone();
+        let invite_link_ref = invite_link_ref.clone();
+        let passcode_ref = passcode_ref.clone();
+        let session_id = session_id.clone();
+        let invite = invite.clone();
         let dispatch = dispatch.clone();
         let media_dispatch = media_dispatch.clone();
+        let email = state.email.clone();
         Callback::from(move |e: SubmitEvent| {
             e.prevent_default();
             let username = username_ref.cast::<HtmlInputElement>().unwrap().value();
-            let meeting_id = session_id.to_string();
-            media_dispatch.apply(MediaMsg::ClientInit(username.clone(), meeting_id.clone()));
-            dispatch.apply(AppMsg::SetName(username));
-            dispatch.apply(AppMsg::SetId(meeting_id.clone()));
-            navigator.push(&Route::Middleware {
-                id: meeting_id,
-            })
+            // Connect tokens are issued for the login email, the typed name is only displayed.
+            let user_id = match &email {
+                Some(email) => email.clone(),
+                None if *ENABLE_OAUTH => {
+                    navigator.push(&Route::Login);
+                    return;
+                }
+                None => username.clone(),
+            };
+            let title = title_ref
+                .cast::<HtmlInputElement>()
+                .map(|input| input.value())
+                .unwrap_or_default();
+            let invite_link = invite_link_ref
+                .cast::<HtmlInputElement>()
+                .map(|input| input.value())
+                .unwrap_or_default();
+            let (session_id, invite) = match parse_invite_link(&invite_link) {
+                Some((meeting_id, invite)) => (Some(meeting_id), Some(invite)),
+                None => {
+                    if !invite_link.trim().is_empty() {
+                        log::warn!("ignoring invalid invite link {}", invite_link);
+                    }
+                    ((*session_id).clone(), (*invite).clone())
+                }
+            };
+            let access = RoomAccess {
+                passcode: passcode_ref
+                    .cast::<HtmlInputElement>()
+                    .map(|input| input.value())
+                    .filter(|passcode| !passcode.is_empty()),
+                invite,
+            };
+            let join = {
+                let username = username.clone();
+                let dispatch = dispatch.clone();
+                let media_dispatch = media_dispatch.clone();
+                let navigator = navigator.clone();
+                move |meeting_id: String| {
+                    media_dispatch.apply(MediaMsg::ClientInit(user_id, meeting_id.clone(), access));
+                    dispatch.apply(AppMsg::SetName(username));
+                    dispatch.apply(AppMsg::SetId(meeting_id.clone()));
+                    navigator.push(&Route::Middleware {
+                        id: meeting_id,
+                    })
+                }
+            };
+            match session_id {
+                Some(meeting_id) => join(meeting_id),
+                None if *ENABLE_OAUTH => {
+                    wasm_bindgen_futures::spawn_local(async move {
+                        match create_meeting(&title).await {
+                            Ok(meeting_id) => join(meeting_id),
+                            Err(e) => log::error!("{}", e),
+                        }
+                    });
+                }
+                None => join(uuid::Uuid::new_v4().to_string()),
+            }
         })
     };
     html! {
@@ -67,7 +144,11 @@ pub fn home() -> Html {
             <div class="flex items-center flex-col">
                 <h1 class="text-xl">{ "Neuromeet" }</h1>
                 <p class="text-xs">{ "Создайте комнату видеоконференции, указав логин" }</p>
-                <p class="text-xs">{ "Допускаются символы: a-z, A-Z, 0-9, и _" }</p>
+                {if *ENABLE_OAUTH {
+                    html! {}
+                } else {
+                    html! {<p class="text-xs">{ "Допускаются символы: a-z, A-Z, 0-9, и _" }</p>}
+                }}
             </div>
             <form {onsubmit}>
                 <div class="py-4">
@@ -78,10 +159,64 @@ pub fn home() -> Html {
                         placeholder="Username"
                         ref={username_ref}
                         required={true}
-                        pattern="^[a-zA-Z0-9_]*$"
-                        value="User"
+                        pattern={(!*ENABLE_OAUTH).then_some("^[a-zA-Z0-9_]*$")}
+                        value={if state.name.is_empty() { "User".to_string() } else { state.name.clone() }}
                     />
                 </div>
+                {if session_id.is_none() && *ENABLE_OAUTH {
+                    html! {
+                        <div class="py-4">
+                            <input
+                                class={TEXT_INPUT_CLASSES}
+                                label="title"
+                                type="text"
+                                placeholder="Meeting title"
+                                ref={title_ref}
+                                maxlength="200"
+                            />
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }}
+                {if session_id.is_none() {
+                    html! {
+                        <div class="py-4">
+                            <input
+                                class={TEXT_INPUT_CLASSES}
+                                label="invite"
+                                type="text"
+                                placeholder="Invite link (optional)"
+                                ref={invite_link_ref}
+                            />
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }}
+                {if media_state.needs_passcode() {
+                    html! {
+                        <div class="py-4">
+                            {if media_state.join_rejection() == Some(JoinRejection::WrongPasscode) {
+                                html! {<p class="text-xs">{"Wrong passcode, try again"}</p>}
+                            } else if media_state.join_rejection() == Some(JoinRejection::InvalidInvite) {
+                                html! {<p class="text-xs">{"The invite link is invalid or has expired, enter the passcode instead"}</p>}
+                            } else {
+                                html! {<p class="text-xs">{"This meeting needs a passcode"}</p>}
+                            }}
+                            <input
+                                class={TEXT_INPUT_CLASSES}
+                                label="passcode"
+                                type="password"
+                                placeholder="Passcode"
+                                ref={passcode_ref}
+                                required={true}
+                            />
+                        </div>
+                    }
+                } else {
+                    html! {}
+                }}
                 <input type="submit" value="Подключиться" class="py-2 px-4 pointer bg-yew-blue rounded-md w-full cursor-pointer" />
             </form>
             <PermissionsDevices /> 
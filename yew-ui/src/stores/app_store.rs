@@ -4,8 +4,11 @@ use std::rc::Rc;
 
 #[derive(Clone, PartialEq, Store)]
 pub struct AppStore {
+    /// Display name typed on the home page.
     pub name: String,
     pub id: String,
+    /// Email of the logged in user, which connections use as their user id.
+    pub email: Option<String>,
 }
 
 
@@ -14,6 +17,7 @@ impl Default for AppStore {
         Self { 
             name: Default::default(),
             id: Default::default(),
+            email: None,
         }
     }
 }
@@ -22,6 +26,7 @@ impl Default for AppStore {
 pub enum AppMsg {
     SetName(String),
     SetId(String),
+    SetEmail(Option<String>),
 }
 
 impl Reducer<AppStore> for AppMsg {
@@ -34,7 +39,10 @@ impl Reducer<AppStore> for AppMsg {
             }
             AppMsg::SetId(id) => {
                 state.id = id;
-            } 
+            }
+            AppMsg::SetEmail(email) => {
+                state.email = email;
+            }
         }
         store
     } 
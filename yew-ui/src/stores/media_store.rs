@@ -1,15 +1,23 @@
 use std::rc::Rc;
 use gloo_timers::callback::Timeout;
 use types::protos::packet_wrapper::PacketWrapper;
-use videocall_client::{CameraEncoder, MediaDeviceAccess, MicrophoneEncoder, ScreenEncoder, VideoCallClient, VideoCallClientOptions};
+use videocall_client::{CameraEncoder, HostCommand, JoinRejection, LobbyStatus, MediaDeviceAccess, MicrophoneEncoder, ScreenEncoder, VideoCallClient, VideoCallClientOptions};
 // use yewdux::{Dispatch, Reducer, Store};
 use yew::prelude::*;
 use yewdux::prelude::*;
 
-use crate::constants::{ACTIX_WEBSOCKET, WEBTRANSPORT_HOST};
+use crate::constants::{ACTIX_WEBSOCKET, CONNECT_TOKEN_COOKIE, E2EE_ENABLED, WEBTRANSPORT_HOST};
+use crate::utils::dom::get_cookie;
 
 const VIDEO_ELEMENT_ID: &str = "webcam";
 
+/// What the user gave to get into a meeting that has a passcode.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoomAccess {
+    pub passcode: Option<String>,
+    pub invite: Option<String>,
+}
+
 #[derive(Clone, PartialEq, Store)]
 pub struct MediaStore {
     rerender: bool,
@@ -21,6 +29,9 @@ pub struct MediaStore {
     is_device_access: bool,
     pub is_connected: bool,
     is_screen_share: bool,
+    join_rejection: Option<JoinRejection>,
+    is_removed: bool,
+    lobby_status: Option<LobbyStatus>,
 }
 
 impl Default for MediaStore {
@@ -35,6 +46,9 @@ impl Default for MediaStore {
             is_device_access: false,
             is_connected: false,
             is_screen_share: Default::default(),
+            join_rejection: None,
+            is_removed: false,
+            lobby_status: None,
         }
     }
 }
@@ -82,12 +96,49 @@ impl MediaStore {
         self.is_screen_share
     }
 
-    fn create_video_call_client(&mut self, user_name: String, meeting_id: String, dispatch: Dispatch<MediaStore>) -> VideoCallClient {
+    pub fn join_rejection(&self) -> Option<JoinRejection> {
+        self.join_rejection
+    }
+
+    /// Whether the server wants a (different) passcode before it lets the user in.
+    pub fn needs_passcode(&self) -> bool {
+        matches!(
+            self.join_rejection,
+            Some(JoinRejection::PasscodeRequired | JoinRejection::WrongPasscode | JoinRejection::InvalidInvite)
+        )
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.is_removed
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.lobby_status == Some(LobbyStatus::Waiting)
+    }
+
+    /// `user_id` is the logged in email when OAuth is on, as connect tokens are issued for it.
+    fn create_video_call_client(&mut self, user_id: String, meeting_id: String, access: RoomAccess, dispatch: Dispatch<MediaStore>) -> VideoCallClient {
+        let mut params = Vec::new();
+        if let Some(token) = get_cookie(CONNECT_TOKEN_COOKIE) {
+            params.push(format!("token={token}"));
+        }
+        if let Some(passcode) = &access.passcode {
+            params.push(format!("passcode={}", urlencoding::encode(passcode)));
+        }
+        if let Some(invite) = &access.invite {
+            params.push(format!("invite={}", urlencoding::encode(invite)));
+        }
+        let query = if params.is_empty() {
+            String::new()
+        } else {
+            format!("?{}", params.join("&"))
+        };
+        let path_user = urlencoding::encode(&user_id).into_owned();
         let opts = VideoCallClientOptions {
-            userid: user_name.clone(),
-            websocket_url: format!("{ACTIX_WEBSOCKET}/{user_name}/{meeting_id}"),
-            webtransport_url: format!("{WEBTRANSPORT_HOST}/{user_name}/{meeting_id}"),
-            enable_e2ee: false,
+            userid: user_id.clone(),
+            websocket_url: format!("{ACTIX_WEBSOCKET}/{path_user}/{meeting_id}{query}"),
+            webtransport_url: format!("{WEBTRANSPORT_HOST}/{path_user}/{meeting_id}{query}"),
+            enable_e2ee: *E2EE_ENABLED,
             enable_webtransport: true,
             on_connected: {
                 let dispatch = dispatch.clone();
@@ -101,12 +152,66 @@ impl MediaStore {
                     dispatch.apply(MediaMsg::Connect);
                 })
             },
+            on_join_rejected: {
+                let dispatch = dispatch.clone();
+                Callback::from(move |rejection| {
+                    dispatch.apply(MediaMsg::JoinRejected(rejection));
+                })
+            },
+            on_host_command: {
+                let dispatch = dispatch.clone();
+                Callback::from(move |command| {
+                    dispatch.apply(MediaMsg::HostCommand(command));
+                })
+            },
+            on_host_changed: {
+                let dispatch = dispatch.clone();
+                Callback::from(move |_| {
+                    dispatch.apply(MediaMsg::Rerender);
+                })
+            },
+            on_lobby_status: {
+                let dispatch = dispatch.clone();
+                Callback::from(move |status| {
+                    dispatch.apply(MediaMsg::LobbyStatus(status));
+                })
+            },
+            on_waiting_changed: {
+                let dispatch = dispatch.clone();
+                Callback::from(move |_| {
+                    dispatch.apply(MediaMsg::Rerender);
+                })
+            },
             on_peer_added: {
                 let dispatch = dispatch.clone();
                 Callback::from(move |_| {
                     dispatch.apply(MediaMsg::Rerender);
                 })
             },
+            on_peer_removed: {
+                let dispatch = dispatch.clone();
+                Callback::from(move |_| {
+                    dispatch.apply(MediaMsg::Rerender);
+                })
+            },
+            on_room_settings: {
+                let dispatch = dispatch.clone();
+                Callback::from(move |_| {
+                    dispatch.apply(MediaMsg::Rerender);
+                })
+            },
+            on_chat_message: {
+                let dispatch = dispatch.clone();
+                Callback::from(move |_| {
+                    dispatch.apply(MediaMsg::Rerender);
+                })
+            },
+            on_recording_changed: {
+                let dispatch = dispatch.clone();
+                Callback::from(move |_| {
+                    dispatch.apply(MediaMsg::Rerender);
+                })
+            },
             on_peer_first_frame: {
                 Callback::from(move |(_email, _media_type)| {
 
@@ -139,7 +244,10 @@ pub enum MediaMsg {
     Rerender,
     Connect,
     SetConnected(bool),
-    ClientInit(String, String),
+    JoinRejected(JoinRejection),
+    HostCommand(HostCommand),
+    LobbyStatus(LobbyStatus),
+    ClientInit(String, String, RoomAccess),
     AudioDeviceChanged(String),
     EnableMicrophone(bool),
     SwitchMic(bool),
@@ -160,7 +268,7 @@ impl Reducer<MediaStore> for MediaMsg {
                 state.rerender();
             },
             MediaMsg::Connect => {
-                if !state.get_mut_client().is_connected() {
+                if state.join_rejection.is_none() && !state.is_removed && !state.get_mut_client().is_connected() {
                     match state.get_mut_client().connect() {
                         Ok(_) => {
 
@@ -175,8 +283,34 @@ impl Reducer<MediaStore> for MediaMsg {
             MediaMsg::SetConnected(is_connected) => {
                 state.set_connected(is_connected);
             }
-            MediaMsg::ClientInit(user_name, meeting_id) => {
-                state.client = Some(state.create_video_call_client(user_name, meeting_id, dispatch));
+            MediaMsg::JoinRejected(rejection) => {
+                log::warn!("join rejected: {:?}", rejection);
+                state.join_rejection = Some(rejection);
+                state.set_connected(false);
+            }
+            MediaMsg::HostCommand(command) => {
+                log::info!("host command: {:?}", command);
+                match command {
+                    HostCommand::Mute => {
+                        state.get_mut_mic().set_enabled(false);
+                    }
+                    HostCommand::StopVideo => {
+                        state.get_mut_camera().set_enabled(false);
+                    }
+                    HostCommand::Removed => {
+                        state.is_removed = true;
+                        state.set_connected(false);
+                    }
+                }
+            }
+            MediaMsg::LobbyStatus(status) => {
+                log::info!("lobby status: {:?}", status);
+                state.lobby_status = Some(status);
+            }
+            MediaMsg::ClientInit(user_id, meeting_id, access) => {
+                // A new client, e.g. with the passcode the server asked for, gets a new chance.
+                state.join_rejection = None;
+                state.client = Some(state.create_video_call_client(user_id, meeting_id, access, dispatch));
             },
             MediaMsg::AudioDeviceChanged(audio) => {
                 if state.microphone.select(audio) {
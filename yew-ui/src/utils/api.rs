@@ -0,0 +1,53 @@
+use gloo::net::http::{Request, RequestCredentials};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::constants::{MEETINGS_URL, SESSION_URL};
+
+#[derive(Serialize)]
+struct NewMeeting<'a> {
+    title: &'a str,
+}
+
+#[derive(Deserialize)]
+struct Meeting {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct SessionUser {
+    email: String,
+}
+
+/// Returns the email of the logged in user, `None` when nobody is logged in. Connect tokens are
+/// issued for this email, so connections must use it as their user id.
+pub async fn fetch_session_email() -> Result<Option<String>, String> {
+    let response = Request::get(SESSION_URL)
+        .credentials(RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if response.status() == 401 {
+        return Ok(None);
+    }
+    if !response.ok() {
+        return Err(format!("failed to fetch the session: {}", response.status()));
+    }
+    let user: SessionUser = response.json().await.map_err(|e| e.to_string())?;
+    Ok(Some(user.email))
+}
+
+/// Registers a meeting owned by the logged in user and returns its id.
+pub async fn create_meeting(title: &str) -> Result<String, String> {
+    let response = Request::post(MEETINGS_URL)
+        .credentials(RequestCredentials::Include)
+        .json(&NewMeeting { title })
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.ok() {
+        return Err(format!("failed to create meeting: {}", response.status()));
+    }
+    let meeting: Meeting = response.json().await.map_err(|e| e.to_string())?;
+    Ok(meeting.id)
+}
@@ -1,5 +1,5 @@
-use wasm_bindgen::JsValue;
-use web_sys::Window;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{HtmlDocument, Window};
 
 pub fn global_window() -> Window {
     web_sys::window().expect("there was no window global object!")
@@ -8,4 +8,27 @@ pub fn global_window() -> Window {
 
 pub fn get_url_pathname() -> Result<String, JsValue> {
     global_window().location().pathname()
-}
\ No newline at end of file
+}
+
+/// Reads the decoded value of `name` from the query string of the current url.
+pub fn get_query_param(name: &str) -> Option<String> {
+    let search = global_window().location().search().ok()?;
+    query_param(&search, name)
+}
+
+/// Reads the decoded value of `name` from a query string like `?a=1&b=2`.
+pub fn query_param(query: &str, name: &str) -> Option<String> {
+    query.trim_start_matches('?').split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| urlencoding::decode(value).ok().map(|v| v.into_owned()))?
+    })
+}
+
+pub fn get_cookie(name: &str) -> Option<String> {
+    let document = global_window().document()?.dyn_into::<HtmlDocument>().ok()?;
+    let cookies = document.cookie().ok()?;
+    cookies.split(';').find_map(|cookie| {
+        let (key, value) = cookie.trim().split_once('=')?;
+        (key == name).then(|| value.to_string())
+    })
+}
@@ -1 +1,2 @@
-pub mod dom;
\ No newline at end of file
+pub mod api;
+pub mod dom;